@@ -55,6 +55,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         prompt: "What is 2 + 2? Reply with just the number.".to_string(),
         task: Some("chat".to_string()),
         params: None,
+        ..Default::default()
     };
 
     let responses = manager.generate_sequentially(vec![request]).await;
@@ -66,10 +66,13 @@ async fn main() {
     println!("llm_request_duration_seconds - Request latency histogram");
     println!("llm_tokens_prompt_total      - Prompt tokens consumed");
     println!("llm_tokens_completion_total  - Completion tokens generated");
+    println!("llm_tokens_prompt            - Prompt tokens per request (histogram)");
+    println!("llm_tokens_completion        - Completion tokens per request (histogram)");
     println!("llm_errors_total             - Errors by type");
     println!("llm_provider_healthy         - Provider health status");
     println!("llm_retries_total            - Retry attempts");
     println!("llm_rate_limits_total        - Rate limit events");
+    println!("llm_inflight_requests        - Requests currently outstanding");
 
     // Make sample requests to generate metrics
     if api_key != "sk-placeholder" {
@@ -81,6 +84,7 @@ async fn main() {
                 prompt: "Say hello in one word.".to_string(),
                 task: Some("chat".to_string()),
                 params: None,
+                ..Default::default()
             };
 
             match manager.generate_sequentially(vec![request]).await.pop() {
@@ -120,6 +124,7 @@ async fn main() {
                 prompt: "Say hi.".to_string(),
                 task: Some("chat".to_string()),
                 params: None,
+                ..Default::default()
             };
 
             match manager.generate_sequentially(vec![request]).await.pop() {
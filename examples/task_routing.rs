@@ -289,68 +289,28 @@ async fn print_available_models(
     // Create a map to store models by provider
     let mut models_by_provider: HashMap<ProviderType, Vec<ModelInfo>> = HashMap::new();
 
-    // Build futures only for providers with API keys
-    let mut futures: Vec<(ProviderType, _)> = Vec::new();
-
-    if let Some(key) = anthropic_api_key {
-        let key = key.to_string();
-        futures.push((
-            ProviderType::Anthropic,
-            tokio::spawn(async move { ModelDiscovery::list_anthropic_models(&key).await })
-        ));
-    }
-
-    if let Some(key) = openai_api_key {
-        let key = key.to_string();
-        futures.push((
-            ProviderType::OpenAI,
-            tokio::spawn(async move { ModelDiscovery::list_openai_models(&key).await })
-        ));
-    }
-
-    if let Some(key) = mistral_api_key {
-        let key = key.to_string();
-        futures.push((
-            ProviderType::Mistral,
-            tokio::spawn(async move { ModelDiscovery::list_mistral_models(&key).await })
-        ));
-    }
-
-    if let Some(key) = google_api_key {
-        let key = key.to_string();
-        futures.push((
-            ProviderType::Google,
-            tokio::spawn(async move { ModelDiscovery::list_google_models(&key).await })
-        ));
+    // Query providers with API keys, plus local providers that don't need one
+    let mut creds: Vec<(ProviderType, String, Option<String>)> = Vec::new();
+    let keyed_providers = [
+        (ProviderType::Anthropic, anthropic_api_key),
+        (ProviderType::OpenAI, openai_api_key),
+        (ProviderType::Mistral, mistral_api_key),
+        (ProviderType::Google, google_api_key),
+    ];
+    for (provider, key) in keyed_providers {
+        if let Some(key) = key {
+            creds.push((provider, key.to_string(), None));
+        }
     }
-
-    // Always try local providers (no API key required)
-    futures.push((
-        ProviderType::Ollama,
-        tokio::spawn(async { ModelDiscovery::list_ollama_models(None).await })
-    ));
-    futures.push((
-        ProviderType::LMStudio,
-        tokio::spawn(async { ModelDiscovery::list_lmstudio_models(None).await })
-    ));
+    creds.push((ProviderType::Ollama, String::new(), None));
+    creds.push((ProviderType::LMStudio, String::new(), None));
     // Perplexity has a static model list
-    futures.push((
-        ProviderType::Perplexity,
-        tokio::spawn(async { ModelDiscovery::list_perplexity_models().await })
-    ));
-
-    // Process results
-    for (provider, future) in futures {
-        match future.await {
-            Ok(Ok(models)) => {
-                models_by_provider.insert(provider, models);
-            },
-            Ok(Err(_)) => {
-                // Silently skip errors for optional providers
-            },
-            Err(e) => {
-                println!("Task error fetching {} models: {}", provider, e);
-            }
+    creds.push((ProviderType::Perplexity, String::new(), None));
+
+    // Silently skip errors for optional providers
+    for (provider, result) in ModelDiscovery::list_all_models(&creds).await {
+        if let Ok(models) = result {
+            models_by_provider.insert(provider, models);
         }
     }
 
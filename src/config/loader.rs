@@ -6,7 +6,9 @@ use std::path::Path;
 use regex::Regex;
 
 use crate::errors::{LlmError, LlmResult};
-use super::types::{Config, ProviderConfig};
+use super::types::Config;
+use crate::providers::instances::build_proxy;
+use crate::providers::ProviderType;
 
 /// Load and parse a TOML configuration file.
 ///
@@ -23,16 +25,7 @@ use super::types::{Config, ProviderConfig};
 /// let config = load_config("flyllm.toml").unwrap();
 /// ```
 pub fn load_config<P: AsRef<Path>>(path: P) -> LlmResult<Config> {
-    let path = path.as_ref();
-
-    let content = fs::read_to_string(path).map_err(|e| {
-        LlmError::ConfigError(format!(
-            "Failed to read config file '{}': {}",
-            path.display(),
-            e
-        ))
-    })?;
-
+    let content = read_config_file(path.as_ref())?;
     parse_config(&content)
 }
 
@@ -44,10 +37,88 @@ pub fn load_config<P: AsRef<Path>>(path: P) -> LlmResult<Config> {
 /// # Returns
 /// * `LlmResult<Config>` - Parsed configuration with environment variables resolved
 pub fn parse_config(content: &str) -> LlmResult<Config> {
-    let mut config: Config = toml::from_str(content).map_err(|e| {
+    let config: Config = toml::from_str(content).map_err(|e| {
         LlmError::ConfigError(format!("Failed to parse TOML: {}", e))
     })?;
 
+    finish_config(config)
+}
+
+/// Load and parse a YAML configuration file.
+///
+/// The YAML document has the same structure as the TOML format. Requires the `yaml` feature.
+///
+/// # Arguments
+/// * `path` - Path to the YAML configuration file
+///
+/// # Returns
+/// * `LlmResult<Config>` - Parsed configuration with environment variables resolved
+#[cfg(feature = "yaml")]
+pub fn load_config_yaml<P: AsRef<Path>>(path: P) -> LlmResult<Config> {
+    let content = read_config_file(path.as_ref())?;
+    parse_config_yaml(&content)
+}
+
+/// Parse a YAML configuration string.
+///
+/// Requires the `yaml` feature.
+///
+/// # Arguments
+/// * `content` - YAML configuration string
+///
+/// # Returns
+/// * `LlmResult<Config>` - Parsed configuration with environment variables resolved
+#[cfg(feature = "yaml")]
+pub fn parse_config_yaml(content: &str) -> LlmResult<Config> {
+    let config: Config = serde_yaml::from_str(content).map_err(|e| {
+        LlmError::ConfigError(format!("Failed to parse YAML: {}", e))
+    })?;
+
+    finish_config(config)
+}
+
+/// Load a configuration file, choosing the format from its extension.
+///
+/// `.yaml` and `.yml` files are parsed as YAML (requires the `yaml` feature);
+/// anything else is parsed as TOML.
+///
+/// # Arguments
+/// * `path` - Path to the configuration file
+///
+/// # Returns
+/// * `LlmResult<Config>` - Parsed configuration with environment variables resolved
+pub fn load_config_auto<P: AsRef<Path>>(path: P) -> LlmResult<Config> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+
+    match extension.as_deref() {
+        #[cfg(feature = "yaml")]
+        Some("yaml") | Some("yml") => load_config_yaml(path),
+        #[cfg(not(feature = "yaml"))]
+        Some("yaml") | Some("yml") => Err(LlmError::ConfigError(format!(
+            "Cannot load '{}': YAML configuration requires the `yaml` feature",
+            path.display()
+        ))),
+        _ => load_config(path),
+    }
+}
+
+/// Read a configuration file into a string.
+fn read_config_file(path: &Path) -> LlmResult<String> {
+    fs::read_to_string(path).map_err(|e| {
+        LlmError::ConfigError(format!(
+            "Failed to read config file '{}': {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Resolve environment variables and validate a freshly parsed configuration.
+fn finish_config(mut config: Config) -> LlmResult<Config> {
     resolve_env_vars(&mut config)?;
     validate_config(&config)?;
 
@@ -92,6 +163,13 @@ fn resolve_env_vars(config: &mut Config) -> LlmResult<()> {
         }
     }
 
+    // Resolve proxy if it uses env vars (e.g. credentials)
+    if let Some(ref proxy) = config.settings.proxy {
+        if let Some(resolved) = resolve_env_var_string(proxy, &env_var_pattern)? {
+            config.settings.proxy = Some(resolved);
+        }
+    }
+
     Ok(())
 }
 
@@ -127,15 +205,14 @@ fn resolve_env_var_string(s: &str, pattern: &Regex) -> LlmResult<Option<String>>
 
 /// Validate the configuration for consistency.
 fn validate_config(config: &Config) -> LlmResult<()> {
-    // Check for valid provider types
-    let valid_providers = [
-        "anthropic", "openai", "mistral", "google", "ollama",
-        "lmstudio", "groq", "cohere", "togetherai", "perplexity"
-    ];
+    let valid_providers: Vec<String> = ProviderType::ALL
+        .iter()
+        .map(|p| p.to_string().to_lowercase())
+        .collect();
 
     for (idx, provider) in config.providers.iter().enumerate() {
-        let provider_type = provider.provider_type.to_lowercase();
-        if !valid_providers.contains(&provider_type.as_str()) {
+        // Check for valid provider types
+        if provider.provider_type.parse::<ProviderType>().is_err() {
             return Err(LlmError::ConfigError(format!(
                 "Unknown provider type '{}' in providers[{}]\n  \
                  → Valid types: {}",
@@ -145,11 +222,79 @@ fn validate_config(config: &Config) -> LlmResult<()> {
             )));
         }
 
+        if provider.provider_type.parse::<ProviderType>().ok() == Some(ProviderType::OpenAICompatible)
+            && provider.endpoint.is_none()
+        {
+            return Err(LlmError::ConfigError(format!(
+                "Provider '{}' of type '{}' has no endpoint\n  \
+                 → Set endpoint to the server's API base URL (e.g. \"https://api.deepinfra.com/v1/openai\")",
+                provider.display_name(),
+                provider.provider_type
+            )));
+        }
+
+        if provider.provider_type.parse::<ProviderType>().ok() == Some(ProviderType::VertexAI)
+            && provider.project_id.as_deref().is_none_or(str::is_empty)
+        {
+            return Err(LlmError::ConfigError(format!(
+                "Provider '{}' of type '{}' has no project_id\n  \
+                 → Set project_id to the Google Cloud project to bill (and optionally location)",
+                provider.display_name(),
+                provider.provider_type
+            )));
+        }
+
+        if provider.provider_type.parse::<ProviderType>().ok() == Some(ProviderType::Replicate)
+            && !is_replicate_model(&provider.model)
+        {
+            return Err(LlmError::ConfigError(format!(
+                "Invalid model '{}' for provider '{}'\n  \
+                 → Replicate models are 'owner/name' (e.g. \"meta/meta-llama-3-8b-instruct\"), optionally with ':version'",
+                provider.model,
+                provider.display_name()
+            )));
+        }
+
+        if provider.provider_type.parse::<ProviderType>().ok() == Some(ProviderType::Fireworks)
+            && !is_fireworks_model(&provider.model)
+        {
+            return Err(LlmError::ConfigError(format!(
+                "Invalid model '{}' for provider '{}'\n  \
+                 → Fireworks models are full paths (e.g. \"accounts/fireworks/models/llama-v3p1-70b-instruct\")",
+                provider.model,
+                provider.display_name()
+            )));
+        }
+
+        if provider.timeout_ms == Some(0) || provider.connect_timeout_ms == Some(0) {
+            return Err(LlmError::ConfigError(format!(
+                "Invalid timeout for provider '{}'\n  \
+                 → timeout_ms and connect_timeout_ms must be greater than 0",
+                provider.display_name()
+            )));
+        }
+
+        if provider.default_max_tokens == Some(0) {
+            return Err(LlmError::ConfigError(format!(
+                "Invalid default_max_tokens for provider '{}'\n  \
+                 → Must be greater than 0",
+                provider.display_name()
+            )));
+        }
+
+        if provider.weight.is_some_and(|w| !w.is_finite() || w < 0.0) {
+            return Err(LlmError::ConfigError(format!(
+                "Invalid weight for provider '{}'\n  \
+                 → Must be a non-negative number",
+                provider.display_name()
+            )));
+        }
+
         // Check that referenced tasks exist
         let defined_tasks: Vec<&str> = config.tasks.iter().map(|t| t.name.as_str()).collect();
         for task in &provider.tasks {
             if !defined_tasks.contains(&task.as_str()) {
-                let provider_name = get_provider_display_name(provider);
+                let provider_name = provider.display_name();
                 return Err(LlmError::ConfigError(format!(
                     "Task '{}' referenced by provider '{}' is not defined\n  \
                      → Define it in [[tasks]] section or remove from provider's tasks list",
@@ -159,8 +304,58 @@ fn validate_config(config: &Config) -> LlmResult<()> {
         }
     }
 
+    // Check pricing entries
+    for (provider_type, models) in &config.pricing {
+        if provider_type.parse::<ProviderType>().is_err() {
+            return Err(LlmError::ConfigError(format!(
+                "Unknown provider type '{}' in [pricing]\n  \
+                 → Valid types: {}",
+                provider_type,
+                valid_providers.join(", ")
+            )));
+        }
+        for (model, pricing) in models {
+            if pricing.input_per_1k < 0.0 || pricing.output_per_1k < 0.0 {
+                return Err(LlmError::ConfigError(format!(
+                    "Negative price for '{}/{}' in [pricing]",
+                    provider_type, model
+                )));
+            }
+        }
+    }
+
+    // Check that the proxy URL can be used
+    if let Some(ref proxy) = config.settings.proxy {
+        build_proxy(proxy)?;
+    }
+
+    // Check retry settings
+    if let Some(ref retry) = config.settings.retry {
+        if retry.multiplier.is_some_and(|m| !m.is_finite() || m < 0.0) {
+            return Err(LlmError::ConfigError(
+                "Invalid retry multiplier in [settings.retry]\n  \
+                 → Must be a non-negative number".to_string()
+            ));
+        }
+    }
+
+    // Check that every task can be served, if required
+    if config.settings.require_all_tasks_served {
+        let unserved: Vec<&str> = config.tasks.iter()
+            .map(|t| t.name.as_str())
+            .filter(|name| !config.providers.iter().any(|p| p.enabled && p.tasks.iter().any(|t| t == name)))
+            .collect();
+        if !unserved.is_empty() {
+            return Err(LlmError::ConfigError(format!(
+                "No enabled provider supports task(s): {}\n  \
+                 → Add them to a provider's tasks list or set require_all_tasks_served = false",
+                unserved.join(", ")
+            )));
+        }
+    }
+
     // Check for valid strategy
-    let valid_strategies = ["lru", "lowest_latency", "random"];
+    let valid_strategies = ["lru", "lowest_latency", "random", "p2c", "weighted", "priority"];
     let strategy = config.settings.strategy.to_lowercase();
     if !valid_strategies.contains(&strategy.as_str()) {
         return Err(LlmError::ConfigError(format!(
@@ -170,19 +365,43 @@ fn validate_config(config: &Config) -> LlmResult<()> {
             valid_strategies.join(", ")
         )));
     }
+    for task in &config.tasks {
+        let Some(task_strategy) = &task.strategy else { continue };
+        if !valid_strategies.contains(&task_strategy.to_lowercase().as_str()) {
+            return Err(LlmError::ConfigError(format!(
+                "Unknown strategy '{}' for task '{}'\n  \
+                 → Valid strategies: {}",
+                task_strategy,
+                task.name,
+                valid_strategies.join(", ")
+            )));
+        }
+    }
 
     Ok(())
 }
 
-/// Get a display name for a provider configuration.
-fn get_provider_display_name(provider: &ProviderConfig) -> String {
-    if let Some(ref name) = provider.name {
-        name.clone()
-    } else {
-        format!("{}/{}", provider.provider_type, provider.model)
+/// Whether a Replicate model is `owner/name`, `owner/name:version` or a bare version ID.
+fn is_replicate_model(model: &str) -> bool {
+    let (name, version) = match model.split_once(':') {
+        Some((name, version)) => (name, Some(version)),
+        None => (model, None),
+    };
+    if version.is_some_and(str::is_empty) {
+        return false;
+    }
+    match name.split_once('/') {
+        Some((owner, name)) => !owner.is_empty() && !name.is_empty() && !name.contains('/'),
+        None => version.is_none() && model.len() == 64 && model.chars().all(|c| c.is_ascii_hexdigit()),
     }
 }
 
+/// Whether a Fireworks model is a full `accounts/<account>/<kind>/<name>` path.
+fn is_fireworks_model(model: &str) -> bool {
+    let segments: Vec<&str> = model.split('/').collect();
+    segments.len() >= 4 && segments[0] == "accounts" && segments.iter().all(|s| !s.is_empty())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,4 +491,234 @@ api_key = "key"
         assert_eq!(config.settings.max_retries, 5);
         assert!(config.settings.debug_folder.is_none());
     }
+
+    #[test]
+    fn test_pricing_table() {
+        let toml = r#"
+[pricing.openai]
+"gpt-4o" = { input_per_1k = 0.0025, output_per_1k = 0.01 }
+"#;
+
+        let config = parse_config(toml).unwrap();
+        let pricing = &config.pricing["openai"]["gpt-4o"];
+        assert_eq!(pricing.input_per_1k, 0.0025);
+        assert_eq!(pricing.output_per_1k, 0.01);
+    }
+
+    #[test]
+    fn test_invalid_pricing_provider() {
+        let toml = r#"
+[pricing.unknown]
+"model" = { input_per_1k = 0.001, output_per_1k = 0.002 }
+"#;
+
+        let err = parse_config(toml).unwrap_err().to_string();
+        assert!(err.contains("Unknown provider type"));
+    }
+
+    #[test]
+    fn test_proxy_setting() {
+        let toml = r#"
+[settings]
+proxy = "http://proxy.internal:8080"
+"#;
+
+        let config = parse_config(toml).unwrap();
+        assert_eq!(config.settings.proxy.as_deref(), Some("http://proxy.internal:8080"));
+
+        let invalid = r#"
+[settings]
+proxy = "not a url"
+"#;
+        let err = parse_config(invalid).unwrap_err().to_string();
+        assert!(err.contains("Invalid proxy URL"));
+    }
+
+    #[test]
+    fn test_user_agent_setting() {
+        let toml = r#"
+[settings]
+user_agent = "my-app/1.0"
+"#;
+
+        let config = parse_config(toml).unwrap();
+        assert_eq!(config.settings.user_agent.as_deref(), Some("my-app/1.0"));
+        assert_eq!(parse_config("").unwrap().settings.user_agent, None);
+    }
+
+    #[test]
+    fn test_retry_settings() {
+        let toml = r#"
+[settings.retry]
+base_delay_ms = 250
+jitter = true
+"#;
+
+        let config = parse_config(toml).unwrap();
+        let policy = config.settings.retry.unwrap().to_policy();
+        assert_eq!(policy.base_delay, std::time::Duration::from_millis(250));
+        assert!(policy.jitter);
+        // Unset fields keep the defaults
+        assert_eq!(policy.multiplier, 2.0);
+        assert!(!policy.backoff_on_errors);
+    }
+}
+
+#[cfg(all(test, feature = "yaml"))]
+mod yaml_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_minimal_config() {
+        let yaml = r#"
+tasks:
+  - name: chat
+providers:
+  - type: openai
+    model: gpt-4
+    api_key: test-key
+    tasks: [chat]
+"#;
+
+        let config = parse_config_yaml(yaml).unwrap();
+        assert_eq!(config.tasks.len(), 1);
+        assert_eq!(config.providers.len(), 1);
+        assert_eq!(config.providers[0].api_key, "test-key");
+    }
+
+    #[test]
+    fn test_env_var_resolution() {
+        env::set_var("TEST_YAML_API_KEY", "resolved-key");
+
+        let yaml = r#"
+tasks:
+  - name: chat
+providers:
+  - type: openai
+    model: gpt-4
+    api_key: "${TEST_YAML_API_KEY}"
+    tasks: [chat]
+"#;
+
+        let config = parse_config_yaml(yaml).unwrap();
+        assert_eq!(config.providers[0].api_key, "resolved-key");
+
+        env::remove_var("TEST_YAML_API_KEY");
+    }
+
+    #[test]
+    fn test_invalid_provider_type() {
+        let yaml = r#"
+providers:
+  - type: invalid_provider
+    model: test
+    api_key: key
+"#;
+
+        let err = parse_config_yaml(yaml).unwrap_err().to_string();
+        assert!(err.contains("Unknown provider type"));
+    }
+
+    #[test]
+    fn test_undefined_task_reference() {
+        let yaml = r#"
+providers:
+  - type: openai
+    model: gpt-4
+    api_key: key
+    tasks: [undefined_task]
+"#;
+
+        let err = parse_config_yaml(yaml).unwrap_err().to_string();
+        assert!(err.contains("not defined"));
+    }
+
+    #[test]
+    fn test_default_settings() {
+        let yaml = r#"
+providers:
+  - type: openai
+    model: gpt-4
+    api_key: key
+"#;
+
+        let config = parse_config_yaml(yaml).unwrap();
+        assert_eq!(config.settings.strategy, "lru");
+        assert_eq!(config.settings.max_retries, 5);
+        assert!(config.settings.debug_folder.is_none());
+    }
+
+    #[test]
+    fn test_pricing_table() {
+        let yaml = r#"
+pricing:
+  openai:
+    gpt-4o: { input_per_1k: 0.0025, output_per_1k: 0.01 }
+"#;
+
+        let config = parse_config_yaml(yaml).unwrap();
+        let pricing = &config.pricing["openai"]["gpt-4o"];
+        assert_eq!(pricing.input_per_1k, 0.0025);
+        assert_eq!(pricing.output_per_1k, 0.01);
+    }
+
+    #[test]
+    fn test_invalid_pricing_provider() {
+        let yaml = r#"
+pricing:
+  unknown:
+    model: { input_per_1k: 0.001, output_per_1k: 0.002 }
+"#;
+
+        let err = parse_config_yaml(yaml).unwrap_err().to_string();
+        assert!(err.contains("Unknown provider type"));
+    }
+
+    #[test]
+    fn test_proxy_setting() {
+        let yaml = r#"
+settings:
+  proxy: "http://proxy.internal:8080"
+"#;
+
+        let config = parse_config_yaml(yaml).unwrap();
+        assert_eq!(config.settings.proxy.as_deref(), Some("http://proxy.internal:8080"));
+
+        let invalid = r#"
+settings:
+  proxy: "not a url"
+"#;
+        let err = parse_config_yaml(invalid).unwrap_err().to_string();
+        assert!(err.contains("Invalid proxy URL"));
+    }
+
+    #[test]
+    fn test_retry_settings() {
+        let yaml = r#"
+settings:
+  retry:
+    base_delay_ms: 250
+    jitter: true
+"#;
+
+        let config = parse_config_yaml(yaml).unwrap();
+        let policy = config.settings.retry.unwrap().to_policy();
+        assert_eq!(policy.base_delay, std::time::Duration::from_millis(250));
+        assert!(policy.jitter);
+        assert_eq!(policy.multiplier, 2.0);
+        assert!(!policy.backoff_on_errors);
+    }
+
+    #[test]
+    fn test_load_config_auto_detects_format() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let yaml_path = dir.path().join("flyllm.yml");
+        fs::write(&yaml_path, "settings:\n  strategy: random\n").unwrap();
+        assert_eq!(load_config_auto(&yaml_path).unwrap().settings.strategy, "random");
+
+        let toml_path = dir.path().join("flyllm.toml");
+        fs::write(&toml_path, "[settings]\nstrategy = \"p2c\"\n").unwrap();
+        assert_eq!(load_config_auto(&toml_path).unwrap().settings.strategy, "p2c");
+    }
 }
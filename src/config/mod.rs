@@ -22,6 +22,9 @@
 //! tasks = ["summary"]
 //! ```
 //!
+//! With the `yaml` feature the same structure can be written in YAML and loaded with
+//! `load_config_yaml`, or with `load_config_auto`, which picks the format from the file extension.
+//!
 //! # Environment Variables
 //!
 //! API keys and other sensitive values can reference environment variables
@@ -30,5 +33,7 @@
 mod types;
 mod loader;
 
-pub use types::{Config, Settings, TaskConfig, ProviderConfig};
-pub use loader::{load_config, parse_config};
+pub use types::{Config, Settings, TaskConfig, ProviderConfig, RetryConfig, ConfigReport, ProviderReport};
+pub use loader::{load_config, load_config_auto, parse_config};
+#[cfg(feature = "yaml")]
+pub use loader::{load_config_yaml, parse_config_yaml};
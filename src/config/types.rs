@@ -2,7 +2,10 @@
 //!
 //! These types map directly to the TOML configuration file structure.
 
-use serde::Deserialize;
+use crate::load_balancer::{Pricing, RetryPolicy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
 /// Root configuration structure.
 #[derive(Debug, Deserialize, Default)]
@@ -18,12 +21,16 @@ pub struct Config {
     /// Provider instance configurations.
     #[serde(default)]
     pub providers: Vec<ProviderConfig>,
+
+    /// Model prices for cost estimation, keyed by provider type and then model.
+    #[serde(default)]
+    pub pricing: HashMap<String, HashMap<String, Pricing>>,
 }
 
 /// Global settings for the LlmManager.
 #[derive(Debug, Deserialize)]
 pub struct Settings {
-    /// Load balancing strategy: "lru", "lowest_latency", or "random".
+    /// Load balancing strategy: "lru", "lowest_latency", "random", "p2c", "weighted", or "priority".
     #[serde(default = "default_strategy")]
     pub strategy: String,
 
@@ -33,6 +40,27 @@ pub struct Settings {
 
     /// Optional folder path for debug logging.
     pub debug_folder: Option<String>,
+
+    /// Optional proxy URL for all provider traffic (falls back to `HTTPS_PROXY`/`NO_PROXY`).
+    pub proxy: Option<String>,
+
+    /// Optional `User-Agent` for all provider traffic (defaults to `flyllm/<version>`).
+    pub user_agent: Option<String>,
+
+    /// Optional retry backoff settings (`[settings.retry]`).
+    pub retry: Option<RetryConfig>,
+
+    /// Refuse requests whose estimated size exceeds the provider's `context_window`.
+    #[serde(default)]
+    pub reject_oversized_prompts: bool,
+
+    /// Fail loading if a task in `[[tasks]]` is not supported by any enabled provider.
+    #[serde(default)]
+    pub require_all_tasks_served: bool,
+
+    /// Refuse requests with parameters the selected provider doesn't recognize instead of warning.
+    #[serde(default)]
+    pub strict_params: bool,
 }
 
 impl Default for Settings {
@@ -41,6 +69,12 @@ impl Default for Settings {
             strategy: default_strategy(),
             max_retries: default_max_retries(),
             debug_folder: None,
+            proxy: None,
+            user_agent: None,
+            retry: None,
+            reject_oversized_prompts: false,
+            require_all_tasks_served: false,
+            strict_params: false,
         }
     }
 }
@@ -53,6 +87,39 @@ fn default_max_retries() -> usize {
     5
 }
 
+/// Retry backoff configuration. Unset fields keep the `RetryPolicy` defaults.
+#[derive(Debug, Deserialize, Default)]
+pub struct RetryConfig {
+    /// Delay before the first retry, in milliseconds.
+    pub base_delay_ms: Option<u64>,
+
+    /// Factor applied to the delay after each attempt.
+    pub multiplier: Option<f64>,
+
+    /// Upper bound for any single delay, in milliseconds.
+    pub max_delay_ms: Option<u64>,
+
+    /// Randomize each delay.
+    pub jitter: Option<bool>,
+
+    /// Also back off after non-rate-limit errors.
+    pub backoff_on_errors: Option<bool>,
+}
+
+impl RetryConfig {
+    /// Build the retry policy, filling unset fields with defaults.
+    pub fn to_policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        RetryPolicy {
+            base_delay: self.base_delay_ms.map(Duration::from_millis).unwrap_or(defaults.base_delay),
+            multiplier: self.multiplier.unwrap_or(defaults.multiplier),
+            max_delay: self.max_delay_ms.map(Duration::from_millis).unwrap_or(defaults.max_delay),
+            jitter: self.jitter.unwrap_or(defaults.jitter),
+            backoff_on_errors: self.backoff_on_errors.unwrap_or(defaults.backoff_on_errors),
+        }
+    }
+}
+
 /// Task definition configuration.
 #[derive(Debug, Deserialize)]
 pub struct TaskConfig {
@@ -64,6 +131,9 @@ pub struct TaskConfig {
 
     /// Temperature setting for this task.
     pub temperature: Option<f32>,
+
+    /// Load balancing strategy for this task's requests, replacing `settings.strategy`.
+    pub strategy: Option<String>,
 }
 
 /// Provider instance configuration.
@@ -88,13 +158,110 @@ pub struct ProviderConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
 
-    /// Custom endpoint URL (for Ollama, LM Studio, or self-hosted providers).
+    /// Custom endpoint URL (for Ollama, LM Studio, or OpenAI-compatible providers behind a proxy).
     pub endpoint: Option<String>,
 
+    /// Extra HTTP headers sent with every request to this provider.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// Overall timeout of each request to this provider, in milliseconds (defaults to 120000).
+    pub timeout_ms: Option<u64>,
+
+    /// Timeout for connecting to this provider, in milliseconds (defaults to none).
+    pub connect_timeout_ms: Option<u64>,
+
+    /// Header carrying the raw API key instead of `Authorization: Bearer` (`openai_compatible` only).
+    pub auth_header: Option<String>,
+
+    /// Path of the model list under the endpoint, for health checks (`openai_compatible` only, defaults to `/models`).
+    pub models_path: Option<String>,
+
+    /// Google Cloud project ID (`vertexai` only, required).
+    pub project_id: Option<String>,
+
+    /// Google Cloud region such as `us-central1` or `global` (`vertexai` only, defaults to `us-central1`).
+    pub location: Option<String>,
+
+    /// Beta features sent in the `anthropic-beta` header (`anthropic` only).
+    pub anthropic_beta: Option<Vec<String>>,
+
+    /// Output token limit for requests that don't set `max_tokens` (defaults to the provider's own,
+    /// or 1024 for `anthropic`, which requires one).
+    pub default_max_tokens: Option<u32>,
+
+    /// Retries against this provider before moving on to another one (overrides the default of none).
+    pub max_retries: Option<usize>,
+
+    /// Relative share of traffic with `strategy = "weighted"` (defaults to 1.0).
+    pub weight: Option<f64>,
+
+    /// Preference order for priority-based strategies; lower values are tried first.
+    pub priority: Option<u32>,
+
+    /// Context window of the model in tokens, used by `reject_oversized_prompts`.
+    /// Overrides the window known for well-known hosted models.
+    pub context_window: Option<u32>,
+
     /// Optional name identifier (useful when having multiple instances of the same provider).
     pub name: Option<String>,
 }
 
+impl ProviderConfig {
+    /// Name used to refer to this provider in messages: its `name`, or `type/model`.
+    pub fn display_name(&self) -> String {
+        match self.name {
+            Some(ref name) => name.clone(),
+            None => format!("{}/{}", self.provider_type, self.model),
+        }
+    }
+
+    /// Key identifying this provider across configuration reloads: type, model and name.
+    pub fn instance_key(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.provider_type.to_lowercase(),
+            self.model,
+            self.name.as_deref().unwrap_or("")
+        )
+    }
+}
+
 fn default_true() -> bool {
     true
 }
+
+/// Summary of what was built from a configuration, for validating configs in CI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigReport {
+    /// Providers added to the manager, in configuration order.
+    pub providers: Vec<ProviderReport>,
+
+    /// Names of the defined tasks.
+    pub tasks: Vec<String>,
+
+    /// Non-fatal issues found in the configuration.
+    pub warnings: Vec<String>,
+}
+
+/// A provider instance added from the configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderReport {
+    /// ID assigned to the instance by the manager.
+    pub instance_id: usize,
+
+    /// Display name (the configured `name`, or `type/model`).
+    pub name: String,
+
+    /// Provider type as written in the configuration.
+    pub provider_type: String,
+
+    /// Model identifier.
+    pub model: String,
+
+    /// Tasks the provider supports.
+    pub tasks: Vec<String>,
+
+    /// Whether the provider is enabled.
+    pub enabled: bool,
+}
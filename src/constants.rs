@@ -3,9 +3,14 @@
 // General
 pub const DEFAULT_MAX_TOKENS: u32 = 1024;
 pub const DEFAULT_MAX_TRIES: usize = 5;
+pub const DEFAULT_LATENCY_SMOOTHING: f64 = 0.3;
+pub const DEFAULT_FAILURE_WINDOW: usize = 20;
+pub const DEFAULT_USER_AGENT: &str = concat!("flyllm/", env!("CARGO_PKG_VERSION"));
 
 // OpenAI
 pub const OPENAI_API_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions"; 
+pub const OPENAI_EMBEDDINGS_ENDPOINT: &str = "https://api.openai.com/v1/embeddings";
+pub const OPENAI_MODELS_ENDPOINT: &str = "https://api.openai.com/v1/models";
 
 // Anthropic
 pub const ANTHROPIC_API_ENDPOINT: &str = "https://api.anthropic.com/v1/messages";
@@ -13,10 +18,14 @@ pub const ANTHROPIC_API_VERSION: &str = "2023-06-01";
 
 // Mistral
 pub const MISTRAL_API_ENDPOINT: &str = "https://api.mistral.ai/v1/chat/completions";
+pub const MISTRAL_EMBEDDINGS_ENDPOINT: &str = "https://api.mistral.ai/v1/embeddings";
 
 // Google
 pub const GOOGLE_API_ENDPOINT_PREFIX: &str = "https://generativelanguage.googleapis.com";
 
+// Vertex AI (Google Cloud)
+pub const VERTEX_DEFAULT_LOCATION: &str = "us-central1";
+
 // Ollama
 pub const OLLAMA_API_ENDPOINT: &str = "http://localhost:11434/api/chat";
 
@@ -25,9 +34,24 @@ pub const LMSTUDIO_API_ENDPOINT: &str = "http://localhost:1234/v1/chat/completio
 
 // Groq
 pub const GROQ_API_ENDPOINT: &str = "https://api.groq.com/openai/v1/chat/completions";
+pub const GROQ_MODELS_ENDPOINT: &str = "https://api.groq.com/openai/v1/models";
+
+// xAI
+pub const XAI_API_ENDPOINT: &str = "https://api.x.ai/v1/chat/completions";
+pub const XAI_MODELS_ENDPOINT: &str = "https://api.x.ai/v1/models";
+
+// AI21
+pub const AI21_API_ENDPOINT: &str = "https://api.ai21.com/studio/v1/chat/completions";
+pub const AI21_MODELS_ENDPOINT: &str = "https://api.ai21.com/studio/v1/models";
+
+// Fireworks AI
+pub const FIREWORKS_API_BASE: &str = "https://api.fireworks.ai/inference/v1";
+pub const FIREWORKS_API_ENDPOINT: &str = "https://api.fireworks.ai/inference/v1/chat/completions";
+pub const FIREWORKS_MODELS_ENDPOINT: &str = "https://api.fireworks.ai/inference/v1/models";
 
 // Cohere (v2 API)
 pub const COHERE_API_ENDPOINT: &str = "https://api.cohere.com/v2/chat";
+pub const COHERE_EMBED_ENDPOINT: &str = "https://api.cohere.com/v2/embed";
 
 // Together AI
 pub const TOGETHERAI_API_ENDPOINT: &str = "https://api.together.xyz/v1/chat/completions";
@@ -35,6 +59,17 @@ pub const TOGETHERAI_API_ENDPOINT: &str = "https://api.together.xyz/v1/chat/comp
 // Perplexity
 pub const PERPLEXITY_API_ENDPOINT: &str = "https://api.perplexity.ai/chat/completions";
 
+// Replicate
+pub const REPLICATE_API_BASE: &str = "https://api.replicate.com/v1";
+pub const REPLICATE_POLL_INTERVAL_MS: u64 = 500;
+pub const REPLICATE_MAX_WAIT_SECS: u64 = 600;
+
+// HTTP clients
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120;
+
+// Health checks
+pub const HEALTH_CHECK_TIMEOUT_SECS: u64 = 10;
+
 // Rate limiting
 pub const DEFAULT_RATE_LIMIT_WAIT_SECS: u64 = 2;
 pub const MAX_RATE_LIMIT_WAIT_SECS: u64 = 60;
\ No newline at end of file
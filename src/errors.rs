@@ -1,6 +1,7 @@
 use std::error::Error;
 use std::fmt;
 use serde_json;
+use std::time::{Duration, SystemTime};
 
 /// Custom error types for LLM operations
 #[derive(Debug)]
@@ -9,14 +10,41 @@ pub enum LlmError {
     RequestError(reqwest::Error),
     /// Error from the API provider
     ApiError(String),
-    /// Rate limiting error
-    RateLimit(String),
+    /// Error response from a provider's API, parsed from its JSON error envelope when possible
+    Api {
+        /// HTTP status code
+        status: u16,
+        /// Provider error code or type (e.g. `invalid_api_key`, `not_found_error`, `INVALID_ARGUMENT`)
+        code: Option<String>,
+        /// Error message from the envelope, or the raw body if it couldn't be parsed
+        message: String,
+        /// Provider display name (e.g. "OpenAI")
+        provider: String,
+    },
+    /// Rate limiting error, with how long the provider asked us to wait (from `Retry-After`)
+    RateLimit {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The model is still being loaded (e.g. a serverless cold start), with the provider's estimate of how long it takes
+    ModelLoading {
+        message: String,
+        retry_after: Option<Duration>,
+    },
     /// Parsing error
     ParseError(String),
+    /// JSON (de)serialization error, kept as the error's `source()`
+    Json(serde_json::Error),
+    /// I/O error, kept as the error's `source()`
+    Io(std::io::Error),
     /// Provider is disabled
     ProviderDisabled(String),
     /// Configuration error
     ConfigError(String),
+    /// Spending budget has been exhausted
+    BudgetExceeded(String),
+    /// Request was cancelled by the caller
+    Cancelled,
 }
 
 impl fmt::Display for LlmError {
@@ -24,10 +52,18 @@ impl fmt::Display for LlmError {
         match self {
             LlmError::RequestError(err) => write!(f, "Request error: {}", err),
             LlmError::ApiError(msg) => write!(f, "API error: {}", msg),
-            LlmError::RateLimit(msg) => write!(f, "Rate limit error: {}", msg),
+            LlmError::Api { provider, message, .. } => {
+                write!(f, "API error: {} API error: {}", provider, message)
+            }
+            LlmError::RateLimit { message, .. } => write!(f, "Rate limit error: {}", message),
+            LlmError::ModelLoading { message, .. } => write!(f, "Model loading: {}", message),
             LlmError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            LlmError::Json(err) => write!(f, "Parse error: {}", err),
+            LlmError::Io(err) => write!(f, "I/O error: {}", err),
             LlmError::ProviderDisabled(provider) => write!(f, "Provider disabled: {}", provider),
             LlmError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            LlmError::BudgetExceeded(msg) => write!(f, "Budget exceeded: {}", msg),
+            LlmError::Cancelled => write!(f, "Request cancelled"),
         }
     }
 }
@@ -36,6 +72,8 @@ impl Error for LlmError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             LlmError::RequestError(err) => Some(err),
+            LlmError::Json(err) => Some(err),
+            LlmError::Io(err) => Some(err),
             _ => None,
         }
     }
@@ -51,14 +89,14 @@ impl From<reqwest::Error> for LlmError {
 /// Convert serde_json errors to LlmError
 impl From<serde_json::Error> for LlmError {
     fn from(err: serde_json::Error) -> Self {
-        LlmError::ParseError(err.to_string())
+        LlmError::Json(err)
     }
 }
 
 /// Convert std::io::Error to LlmError
 impl From<std::io::Error> for LlmError {
     fn from(err: std::io::Error) -> Self {
-        LlmError::ConfigError(err.to_string())
+        LlmError::Io(err)
     }
 }
 
@@ -73,10 +111,62 @@ impl From<toml::de::Error> for LlmError {
 pub type LlmResult<T> = Result<T, LlmError>;
 
 impl LlmError {
+    /// Create a RateLimit error, reading the wait time from the response's `Retry-After` header
+    pub fn rate_limit(message: impl Into<String>, headers: &reqwest::header::HeaderMap) -> Self {
+        let retry_after = headers
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        LlmError::RateLimit { message: message.into(), retry_after }
+    }
+
+    /// Create an Api error from a failed response, extracting the message and code from the body
+    ///
+    /// A 503 whose body says the model is loading (Hugging Face's `{"error": "Model ... is
+    /// currently loading", "estimated_time": 20.0}`) becomes `ModelLoading` instead.
+    ///
+    /// # Parameters
+    /// * `provider` - Provider display name used in the error message
+    /// * `status` - HTTP status of the response
+    /// * `body` - Raw response body
+    pub fn api(provider: impl Into<String>, status: reqwest::StatusCode, body: &str) -> Self {
+        let (message, code) = parse_error_envelope(body).unwrap_or_else(|| (body.to_string(), None));
+        if status == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+            if let Some(retry_after) = parse_model_loading(body, &message) {
+                return LlmError::ModelLoading {
+                    message: format!("{} API error: {}", provider.into(), message),
+                    retry_after,
+                };
+            }
+        }
+        LlmError::Api {
+            status: status.as_u16(),
+            code,
+            message,
+            provider: provider.into(),
+        }
+    }
+
+    /// Whether the same instance is expected to succeed if the request is retried after a wait
+    ///
+    /// True for rate limits and models that are still loading; the manager waits for their
+    /// `retry_after` instead of falling back to another instance right away.
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, LlmError::RateLimit { .. } | LlmError::ModelLoading { .. })
+    }
+
+    /// How long the provider asked to wait before retrying, if it said
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LlmError::RateLimit { retry_after, .. } | LlmError::ModelLoading { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
     /// Returns RateLimit error for 429 status or rate limit keywords
     pub fn from_api_response(status: reqwest::StatusCode, error_message: String) -> Self {
         if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
-            return LlmError::RateLimit(error_message);
+            return LlmError::RateLimit { message: error_message, retry_after: None };
         }
         
         // Check error message for rate limit indicators
@@ -86,9 +176,221 @@ impl LlmError {
             || msg_lower.contains("quota exceeded")
             || msg_lower.contains("overloaded")
             || msg_lower.contains("throttle") {
-            return LlmError::RateLimit(error_message);
+            return LlmError::RateLimit { message: error_message, retry_after: None };
         }
         
         LlmError::ApiError(error_message)
     }
-}
\ No newline at end of file
+}
+
+/// Extract `(message, code)` from a provider's JSON error body
+///
+/// Handles the OpenAI-style `{"error": {"message", "type", "code"}}` envelope, which
+/// Anthropic (`error.type`) and Google (`error.status`) also follow, as well as bare
+/// `{"error": "..."}` and top-level `{"message", "type"}` bodies.
+fn parse_error_envelope(body: &str) -> Option<(String, Option<String>)> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    let error = match json.get("error") {
+        Some(serde_json::Value::String(message)) => return Some((message.clone(), None)),
+        Some(error) => error,
+        None => &json,
+    };
+
+    let message = error.get("message")?.as_str()?.to_string();
+    let code = ["code", "status", "type"]
+        .iter()
+        .find_map(|key| error.get(*key).and_then(|v| v.as_str()))
+        .map(|code| code.to_string());
+    Some((message, code))
+}
+
+/// Detect a "model is loading" body, returning the estimated load time if one was given
+///
+/// Returns `None` when the body doesn't describe a loading model.
+fn parse_model_loading(body: &str, message: &str) -> Option<Option<Duration>> {
+    let estimated_time = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| json.get("estimated_time").and_then(|v| v.as_f64()));
+    let message = message.to_lowercase();
+    if estimated_time.is_none() && !message.contains("loading") {
+        return None;
+    }
+    Some(estimated_time.filter(|secs| secs.is_finite() && *secs >= 0.0).map(Duration::from_secs_f64))
+}
+
+/// Parse a `Retry-After` header value, given either as delay seconds or an HTTP-date
+///
+/// Dates in the past yield a zero delay. Returns `None` for unparseable values.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after_http_date() {
+        let in_a_minute = SystemTime::now() + Duration::from_secs(60);
+        let delay = parse_retry_after(&httpdate::fmt_http_date(in_a_minute)).unwrap();
+        // HTTP-dates have one-second resolution
+        assert!(delay > Duration::from_secs(58) && delay <= Duration::from_secs(60));
+
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_api_error_parses_openai_envelope() {
+        let body = r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "param": null, "code": "invalid_api_key"}}"#;
+
+        let error = LlmError::api("OpenAI", reqwest::StatusCode::UNAUTHORIZED, body);
+        assert_eq!(error.to_string(), "API error: OpenAI API error: Incorrect API key provided");
+        match error {
+            LlmError::Api { status, code, message, provider } => {
+                assert_eq!(status, 401);
+                assert_eq!(code.as_deref(), Some("invalid_api_key"));
+                assert_eq!(message, "Incorrect API key provided");
+                assert_eq!(provider, "OpenAI");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+
+        // A null code falls back to the error type
+        let body = r#"{"error": {"message": "Bad model", "type": "invalid_request_error", "code": null}}"#;
+        match LlmError::api("OpenAI", reqwest::StatusCode::BAD_REQUEST, body) {
+            LlmError::Api { code, .. } => assert_eq!(code.as_deref(), Some("invalid_request_error")),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_api_error_parses_anthropic_envelope() {
+        let body = r#"{"type": "error", "error": {"type": "not_found_error", "message": "model: claude-9"}}"#;
+
+        match LlmError::api("Anthropic", reqwest::StatusCode::NOT_FOUND, body) {
+            LlmError::Api { status, code, message, .. } => {
+                assert_eq!(status, 404);
+                assert_eq!(code.as_deref(), Some("not_found_error"));
+                assert_eq!(message, "model: claude-9");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_api_error_parses_google_envelope() {
+        let body = r#"{"error": {"code": 400, "message": "API key not valid", "status": "INVALID_ARGUMENT"}}"#;
+
+        match LlmError::api("Google", reqwest::StatusCode::BAD_REQUEST, body) {
+            LlmError::Api { code, message, .. } => {
+                assert_eq!(code.as_deref(), Some("INVALID_ARGUMENT"));
+                assert_eq!(message, "API key not valid");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_api_error_keeps_unparseable_body() {
+        let error = LlmError::api("Ollama", reqwest::StatusCode::INTERNAL_SERVER_ERROR, "upstream crashed");
+        assert_eq!(error.to_string(), "API error: Ollama API error: upstream crashed");
+        match error {
+            LlmError::Api { code, message, .. } => {
+                assert_eq!(code, None);
+                assert_eq!(message, "upstream crashed");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_reads_retry_after_header() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+
+        match LlmError::rate_limit("slow down", &headers) {
+            LlmError::RateLimit { message, retry_after } => {
+                assert_eq!(message, "slow down");
+                assert_eq!(retry_after, Some(Duration::from_secs(7)));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_api_error_detects_loading_model() {
+        let body = r#"{"error": "Model bigscience/bloom is currently loading", "estimated_time": 20.5}"#;
+
+        let error = LlmError::api("Hugging Face", reqwest::StatusCode::SERVICE_UNAVAILABLE, body);
+        assert!(error.is_retriable());
+        match error {
+            LlmError::ModelLoading { message, retry_after } => {
+                assert_eq!(message, "Hugging Face API error: Model bigscience/bloom is currently loading");
+                assert_eq!(retry_after, Some(Duration::from_secs_f64(20.5)));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+
+        let error = LlmError::api("Ollama", reqwest::StatusCode::SERVICE_UNAVAILABLE, r#"{"error": "loading model"}"#);
+        assert!(matches!(error, LlmError::ModelLoading { retry_after: None, .. }));
+
+        // Other 503s and loading messages with other statuses stay API errors
+        let error = LlmError::api("OpenAI", reqwest::StatusCode::SERVICE_UNAVAILABLE, r#"{"error": "server busy"}"#);
+        assert!(matches!(error, LlmError::Api { status: 503, .. }));
+        assert!(!error.is_retriable());
+        let error = LlmError::api("OpenAI", reqwest::StatusCode::BAD_REQUEST, r#"{"error": "loading failed"}"#);
+        assert!(matches!(error, LlmError::Api { status: 400, .. }));
+    }
+
+    fn parse_json(body: &str) -> LlmResult<serde_json::Value> {
+        Ok(serde_json::from_str(body)?)
+    }
+
+    fn read_file(path: &str) -> LlmResult<String> {
+        Ok(std::fs::read_to_string(path)?)
+    }
+
+    fn build_request(url: &str) -> LlmResult<reqwest::Request> {
+        Ok(reqwest::Client::new().get(url).build()?)
+    }
+
+    #[test]
+    fn test_question_mark_converts_and_keeps_source() {
+        let error = parse_json("{not json").unwrap_err();
+        assert!(matches!(error, LlmError::Json(_)));
+        assert!(error.to_string().starts_with("Parse error: "));
+        assert!(error.source().unwrap().is::<serde_json::Error>());
+
+        let error = read_file("/nonexistent/flyllm.toml").unwrap_err();
+        assert!(matches!(&error, LlmError::Io(e) if e.kind() == std::io::ErrorKind::NotFound));
+        assert!(error.source().unwrap().is::<std::io::Error>());
+
+        let error = build_request("not a url").unwrap_err();
+        assert!(matches!(error, LlmError::RequestError(_)));
+        assert!(error.source().unwrap().is::<reqwest::Error>());
+
+        assert!(LlmError::ConfigError("bad".to_string()).source().is_none());
+    }
+
+    #[test]
+    fn test_converts_into_boxed_errors() {
+        fn boxed() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+            parse_json("[")?;
+            Ok(())
+        }
+        let error = boxed().unwrap_err();
+        assert!(error.downcast_ref::<LlmError>().is_some());
+    }
+}
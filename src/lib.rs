@@ -6,7 +6,7 @@
 //! # Features
 //!
 //! - **Multi-provider support**: Integrate with OpenAI, Anthropic, Google, Mistral, Ollama,
-//!   LM Studio, Groq, Cohere, Together AI, and Perplexity
+//!   LM Studio, Groq, xAI, Cohere, Together AI, Perplexity, Replicate, AI21, and Fireworks
 //! - **Load balancing**: Distribute requests across multiple providers
 //! - **Automatic retries**: Handle provider failures with configurable retry policies
 //! - **Task routing**: Route specific tasks to the most suitable providers
@@ -32,6 +32,7 @@
 //!         prompt: "Explain Rust in one paragraph".to_string(),
 //!         task: None,
 //!         params: None,
+//!         ..Default::default()
 //!     };
 //!
 //!     let responses = manager.generate_sequentially(vec![request]).await;
@@ -52,19 +53,35 @@ pub use providers::{
     ProviderType,
     LlmRequest,
     LlmResponse,
+    Message,
     LlmInstance,
+    EmbeddingInstance,
+    EmbeddingResponse,
+    InstanceSettings,
     create_instance,
     AnthropicInstance,
     OpenAIInstance,
     ModelInfo,
+    ProviderCapabilities,
     ModelDiscovery,
     StreamChunk,
-    LlmStream
+    LlmStream,
+    collect_response,
+    ToolCall,
+    ToolDefinition,
+    ResponseFormat,
+    ContentPart
 };
 
+pub use providers::tokens::{context_window_for_model, estimate_tokens, trim_messages};
+#[cfg(feature = "tokenizer")]
+pub use providers::tokens::count_tokens_exact;
 pub use errors::{LlmError, LlmResult};
+#[cfg(feature = "test-util")]
+pub use providers::MockInstance;
+pub use tokio_util::sync::CancellationToken;
 
-pub use load_balancer::{LlmManager, GenerationRequest, LlmManagerResponse, TaskDefinition};
+pub use load_balancer::{LlmManager, GenerationRequest, LlmManagerResponse, TaskDefinition, UsageSnapshot, Pricing, RetryPolicy, InstanceStats, DebugFormat, RetryEvent, RequestCompleteEvent};
 
 #[cfg(feature = "metrics")]
 pub use metrics::describe_metrics;
@@ -1,11 +1,20 @@
 use crate::errors::{LlmResult, LlmError};
 use crate::load_balancer::strategies::{LoadBalancingStrategy, LeastRecentlyUsedStrategy};
 use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::InstanceSettings;
 use crate::{ProviderType, constants}; 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use log::debug;
 use super::LlmManager; 
+use super::cache::ResponseCache;
+use super::hooks::{RequestCompleteHook, RetryHook};
+use super::pricing::Pricing;
+use super::retry::RetryPolicy;
+use super::types::UsageSnapshot;
+use super::utils::DebugFormat;
 
 /// Internal helper struct for Builder
 #[derive(Clone)] 
@@ -16,6 +25,19 @@ struct ProviderConfig {
     supported_task_names: Vec<String>,
     enabled: bool,
     custom_endpoint: Option<String>,
+    custom_headers: HashMap<String, String>,
+    max_retries: Option<usize>,
+    weight: Option<f64>,
+    priority: Option<u32>,
+    context_window: Option<u32>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    auth_header: Option<String>,
+    models_path: Option<String>,
+    project_id: Option<String>,
+    location: Option<String>,
+    anthropic_beta: Option<Vec<String>>,
+    default_max_tokens: Option<u32>,
 }
 
 /// LlmManager Builder
@@ -23,8 +45,31 @@ pub struct LlmManagerBuilder {
     defined_tasks: HashMap<String, TaskDefinition>,
     providers_to_build: Vec<ProviderConfig>,
     strategy: Box<dyn LoadBalancingStrategy + Send + Sync>,
+    task_strategies: HashMap<String, Box<dyn LoadBalancingStrategy + Send + Sync>>,
     max_retries: usize,
+    retry_policy: RetryPolicy,
+    max_total_retry_duration: Option<Duration>,
+    latency_smoothing: f64,
+    max_failures_before_disable: Option<(usize, usize)>,
     debug_folder: Option<PathBuf>,
+    debug_format: DebugFormat,
+    max_debug_file_bytes: Option<u64>,
+    redact_secrets: bool,
+    cache_config: Option<(usize, Duration)>,
+    usage_snapshot: Option<UsageSnapshot>,
+    pricing: HashMap<(String, String), Pricing>,
+    budget_usd: Option<f64>,
+    proxy: Option<String>,
+    user_agent: Option<String>,
+    shared_http_client: Option<Arc<reqwest::Client>>,
+    max_concurrency: Option<usize>,
+    reject_oversized_prompts: bool,
+    strict_params: bool,
+    default_params: HashMap<String, serde_json::Value>,
+    require_all_tasks_served: bool,
+    health_check_interval: Option<Duration>,
+    on_retry: Option<RetryHook>,
+    on_request_complete: Option<RequestCompleteHook>,
     #[cfg(feature = "metrics-server")]
     dashboard_config: Option<crate::metrics::dashboard::DashboardServerConfig>,
 }
@@ -36,8 +81,31 @@ impl LlmManagerBuilder {
             defined_tasks: HashMap::new(),
             providers_to_build: Vec::new(),
             strategy: Box::new(LeastRecentlyUsedStrategy::new()), // Default strategy
+            task_strategies: HashMap::new(),
             max_retries: constants::DEFAULT_MAX_TRIES, // Default retries
+            retry_policy: RetryPolicy::default(),
+            max_total_retry_duration: None,
+            latency_smoothing: constants::DEFAULT_LATENCY_SMOOTHING,
+            max_failures_before_disable: None,
             debug_folder: None,
+            debug_format: DebugFormat::default(),
+            max_debug_file_bytes: None,
+            redact_secrets: true,
+            cache_config: None,
+            usage_snapshot: None,
+            pricing: HashMap::new(),
+            budget_usd: None,
+            proxy: None,
+            user_agent: None,
+            shared_http_client: None,
+            max_concurrency: None,
+            reject_oversized_prompts: false,
+            strict_params: false,
+            default_params: HashMap::new(),
+            require_all_tasks_served: false,
+            health_check_interval: None,
+            on_retry: None,
+            on_request_complete: None,
             #[cfg(feature = "metrics-server")]
             dashboard_config: None,
         }
@@ -55,12 +123,50 @@ impl LlmManagerBuilder {
         self
     }
 
+    /// Sets the load balancing strategy for requests of one task, replacing the manager's
+    /// strategy for them. Fails at build time if the task was not defined using define_task().
+    pub fn task_strategy(mut self, task: impl Into<String>, strategy: Box<dyn LoadBalancingStrategy + Send + Sync>) -> Self {
+        self.task_strategies.insert(task.into(), strategy);
+        self
+    }
+
     /// Sets the maximum number of retries for failed requests.
     pub fn max_retries(mut self, retries: usize) -> Self {
         self.max_retries = retries;
         self
     }
 
+    /// Sets the backoff applied between retries.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Caps the total time one request may spend across all its attempts (unlimited by default).
+    /// Once the next attempt could not start within `limit`, including any backoff wait,
+    /// the request fails with the last error. Enable `RetryPolicy::jitter` so many requests
+    /// failing together don't retry in lockstep.
+    pub fn max_total_retry_duration(mut self, limit: Duration) -> Self {
+        self.max_total_retry_duration = Some(limit);
+        self
+    }
+
+    /// Sets how strongly each instance's latency average follows its newest request (default 0.3).
+    /// `alpha` is the weight of the newest sample in the exponential moving average used by
+    /// `LowestLatencyStrategy`; values outside (0, 1] fall back to the default.
+    pub fn latency_smoothing(mut self, alpha: f64) -> Self {
+        self.latency_smoothing = alpha;
+        self
+    }
+
+    /// Disables an instance once `failures` of its last `window` requests have failed (off by default).
+    /// Unlike retries, this pulls instances that fail intermittently, such as a slowly degrading key.
+    /// Disabled instances stay out of rotation until `LlmManager::reenable_instance` is called.
+    pub fn max_failures_before_disable(mut self, failures: usize, window: usize) -> Self {
+        self.max_failures_before_disable = Some((failures, window.max(failures)));
+        self
+    }
+
     /// Begins configuring a new provider instance.
     /// Subsequent calls like `.supports()`, `.enabled()`, `.custom_endpoint()` will apply to this provider.
     pub fn add_instance(
@@ -76,6 +182,19 @@ impl LlmManagerBuilder {
             supported_task_names: Vec::new(),
             enabled: true, // Default to enabled
             custom_endpoint: None,
+            custom_headers: HashMap::new(),
+            max_retries: None,
+            weight: None,
+            priority: None,
+            context_window: None,
+            timeout: None,
+            connect_timeout: None,
+            auth_header: None,
+            models_path: None,
+            project_id: None,
+            location: None,
+            anthropic_beta: None,
+            default_max_tokens: None,
         };
         self.providers_to_build.push(config);
         self // Return self to allow chaining provider configurations
@@ -138,6 +257,144 @@ impl LlmManagerBuilder {
         self
     }
 
+    /// Sets the layout of the debug files (JSON array by default).
+    /// `DebugFormat::JsonLines` appends one line per request instead of rewriting the file.
+    pub fn debug_format(mut self, format: DebugFormat) -> Self {
+        self.debug_format = format;
+        self
+    }
+
+    /// Starts a new numbered debug file (`debug.1.json`, ...) once the current one reaches `bytes`.
+    pub fn max_debug_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_debug_file_bytes = Some(bytes);
+        self
+    }
+
+    /// Masks API keys, bearer tokens and credential fields in debug files and logged errors (on by default).
+    pub fn redact_secrets(mut self, enabled: bool) -> Self {
+        self.redact_secrets = enabled;
+        self
+    }
+
+    /// Enables an in-memory LRU cache for deterministic (temperature 0) responses.
+    /// Cached responses are served without calling the provider until `ttl` elapses.
+    pub fn enable_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache_config = Some((capacity, ttl));
+        self
+    }
+
+    /// Seeds token usage from a snapshot exported with `LlmManager::export_usage`.
+    /// Entries are matched to the built instances by provider name and model.
+    pub fn restore_usage(mut self, snapshot: UsageSnapshot) -> Self {
+        self.usage_snapshot = Some(snapshot);
+        self
+    }
+
+    /// Registers the price of a provider/model pair used for cost estimation.
+    /// Overrides the built-in price table for that model.
+    pub fn pricing(
+        mut self,
+        provider_type: ProviderType,
+        model: impl Into<String>,
+        pricing: Pricing,
+    ) -> Self {
+        self.pricing.insert(
+            (provider_type.to_string().to_lowercase(), model.into()),
+            pricing,
+        );
+        self
+    }
+
+    /// Caps the estimated spend in USD; requests fail with `BudgetExceeded` once it is reached.
+    /// Requires pricing for the models in use (see `pricing()`).
+    pub fn budget_usd(mut self, limit: f64) -> Self {
+        self.budget_usd = Some(limit);
+        self
+    }
+
+    /// Limits how many requests `batch_generate` sends at once (unbounded by default).
+    /// A limit of 0 is treated as 1.
+    pub fn max_concurrency(mut self, limit: usize) -> Self {
+        self.max_concurrency = Some(limit);
+        self
+    }
+
+    /// Skips providers whose `context_window` a request is estimated (prompt plus `max_tokens`)
+    /// not to fit, and refuses the request with a `ConfigError` if it fits none of them.
+    /// Well-known hosted models have a built-in context window; other providers are only
+    /// checked when given one with `context_window`.
+    pub fn reject_oversized_prompts(mut self, enabled: bool) -> Self {
+        self.reject_oversized_prompts = enabled;
+        self
+    }
+
+    /// Refuses requests whose task or request parameters the selected provider doesn't
+    /// recognize with a `ConfigError`, instead of only logging a warning (off by default).
+    /// Instances of custom `LlmInstance` types are not checked.
+    pub fn strict_params(mut self, enabled: bool) -> Self {
+        self.strict_params = enabled;
+        self
+    }
+
+    /// Sets parameters, such as `temperature` or `max_tokens`, applied to every request.
+    /// Task parameters override them, and request parameters override both.
+    /// Can be called multiple times; later values replace earlier ones for the same key.
+    pub fn default_params(mut self, params: HashMap<String, serde_json::Value>) -> Self {
+        self.default_params.extend(params);
+        self
+    }
+
+    /// Makes `build()` fail with a `ConfigError` if a task defined with `define_task()`
+    /// is not supported by any enabled provider (off by default).
+    pub fn require_all_tasks_served(mut self, enabled: bool) -> Self {
+        self.require_all_tasks_served = enabled;
+        self
+    }
+
+    /// Probes every enabled provider in the background every `interval` (off by default).
+    /// Providers failing their probe are skipped until a later probe succeeds.
+    pub fn enable_health_checks(mut self, interval: Duration) -> Self {
+        self.health_check_interval = Some(interval);
+        self
+    }
+
+    /// Calls `hook` whenever an attempt fails and the request is about to be retried,
+    /// on the same instance or another one. Runs inline, so it should return quickly.
+    pub fn on_retry(mut self, hook: RetryHook) -> Self {
+        self.on_retry = Some(hook);
+        self
+    }
+
+    /// Calls `hook` with the outcome of every non-streaming request once it has
+    /// succeeded or run out of attempts. Requests cancelled mid-flight are not reported.
+    pub fn on_request_complete(mut self, hook: RequestCompleteHook) -> Self {
+        self.on_request_complete = Some(hook);
+        self
+    }
+
+    /// Routes the traffic of all providers through the given proxy (e.g. `http://proxy:8080`).
+    /// Without it, the `HTTPS_PROXY`/`NO_PROXY` environment variables are used.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Sets the `User-Agent` header all providers send (defaults to `flyllm/<version>`).
+    /// Like `.proxy()`, it gives every provider a dedicated client, even with `.shared_http_client()`.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Shares one HTTP client, and so one connection pool, across all providers instead of
+    /// building a client per instance. Providers with their own timeouts or custom headers,
+    /// or any provider when `.proxy()` or `.user_agent()` is set, still get a dedicated client.
+    /// The shared client's own `User-Agent` is used as is.
+    pub fn shared_http_client(mut self, client: Arc<reqwest::Client>) -> Self {
+        self.shared_http_client = Some(client);
+        self
+    }
+
     /// Configure the dashboard HTTP server with custom settings.
     /// Requires the `metrics-server` feature.
     ///
@@ -191,14 +448,251 @@ impl LlmManagerBuilder {
         self
     }
 
+    /// Adds a header sent with every request of the *last added* provider.
+    /// Panics if `add_instance` was not called before this.
+    pub fn custom_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.custom_headers.insert(key.into(), value.into());
+            }
+            None => {
+                panic!("'.custom_header()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the overall timeout of each request to the *last added* provider (defaults to 120s).
+    /// Panics if `add_instance` was not called before this.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.timeout = Some(timeout);
+            }
+            None => {
+                panic!("'.timeout()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the timeout for connecting to the *last added* provider (defaults to none).
+    /// Panics if `add_instance` was not called before this.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.connect_timeout = Some(timeout);
+            }
+            None => {
+                panic!("'.connect_timeout()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sends the *last added* provider's API key as-is in this header instead of
+    /// `Authorization: Bearer <key>` (e.g. `api-key`). Only used by `ProviderType::OpenAICompatible`.
+    /// Panics if `add_instance` was not called before this.
+    pub fn auth_header(mut self, name: &str) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.auth_header = Some(name.to_string());
+            }
+            None => {
+                panic!("'.auth_header()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the path of the *last added* provider's model list under its endpoint, used for
+    /// health checks (defaults to `/models`). Only used by `ProviderType::OpenAICompatible`.
+    /// Panics if `add_instance` was not called before this.
+    pub fn models_path(mut self, path: &str) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.models_path = Some(path.to_string());
+            }
+            None => {
+                panic!("'.models_path()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the Google Cloud project of the *last added* provider. Required by `ProviderType::VertexAI`.
+    /// Panics if `add_instance` was not called before this.
+    pub fn project_id(mut self, project_id: &str) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.project_id = Some(project_id.to_string());
+            }
+            None => {
+                panic!("'.project_id()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the Google Cloud region of the *last added* provider (defaults to `us-central1`).
+    /// Only used by `ProviderType::VertexAI`.
+    /// Panics if `add_instance` was not called before this.
+    pub fn location(mut self, location: &str) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.location = Some(location.to_string());
+            }
+            None => {
+                panic!("'.location()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Enables an Anthropic beta feature (e.g. `prompt-caching-2024-07-31`) on the *last added*
+    /// provider; call it once per feature. Only used by `ProviderType::Anthropic`.
+    /// Panics if `add_instance` was not called before this.
+    pub fn anthropic_beta(mut self, feature: &str) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider
+                    .anthropic_beta
+                    .get_or_insert_with(Vec::new)
+                    .push(feature.to_string());
+            }
+            None => {
+                panic!("'.anthropic_beta()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the output token limit the *last added* provider uses for requests without
+    /// `max_tokens`. Anthropic requires a limit and otherwise uses `constants::DEFAULT_MAX_TOKENS`;
+    /// other providers otherwise leave it to the API.
+    /// Panics if `add_instance` was not called before this.
+    pub fn default_max_tokens(mut self, tokens: u32) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.default_max_tokens = Some(tokens);
+            }
+            None => {
+                panic!("'.default_max_tokens()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets how many times the *last added* provider is retried before moving on to another instance.
+    /// These consecutive retries don't count towards the manager's `max_retries`.
+    /// Panics if `add_instance` was not called before this.
+    pub fn instance_max_retries(mut self, retries: usize) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.max_retries = Some(retries);
+            }
+            None => {
+                panic!("'.instance_max_retries()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the share of traffic the *last added* provider gets under `WeightedStrategy` (default 1.0).
+    /// Panics if `add_instance` was not called before this.
+    pub fn weight(mut self, weight: f64) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.weight = Some(weight);
+            }
+            None => {
+                panic!("'.weight()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the priority of the *last added* provider for priority-based strategies (lower is preferred).
+    /// Panics if `add_instance` was not called before this.
+    pub fn priority(mut self, priority: u32) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.priority = Some(priority);
+            }
+            None => {
+                panic!("'.priority()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
+
+    /// Sets the context window (in tokens) of the *last added* provider's model.
+    /// Used by `reject_oversized_prompts`; overrides the window known for well-known hosted
+    /// models. Panics if `add_instance` was not called before this.
+    pub fn context_window(mut self, tokens: u32) -> Self {
+        match self.providers_to_build.last_mut() {
+            Some(last_provider) => {
+                last_provider.context_window = Some(tokens);
+            }
+            None => {
+                panic!("'.context_window()' called before '.add_instance()'");
+            }
+        }
+        self
+    }
 
     /// Consumes the builder and constructs the `LlmManager`.
-    /// Returns an error if a referenced task was not defined.
+    /// Returns an error if a referenced task was not defined or an instance's HTTP client
+    /// could not be built (e.g. an invalid proxy URL or custom header).
     pub async fn build(self) -> LlmResult<LlmManager> {
         let mut manager = LlmManager::new_with_strategy_and_retries(self.strategy, self.max_retries);
+        manager.retry_policy = self.retry_policy;
+        manager.max_total_retry_duration = self.max_total_retry_duration;
+        manager.latency_smoothing = self.latency_smoothing;
+        if let Some((failures, window)) = self.max_failures_before_disable {
+            manager.max_failures_before_disable = Some(failures);
+            manager.failure_window = window;
+        }
         
         // Set debug folder if specified
         manager.debug_folder = self.debug_folder;
+        manager.debug_format = self.debug_format;
+        manager.max_debug_file_bytes = self.max_debug_file_bytes;
+        manager.redact_secrets = self.redact_secrets;
+        manager.cache = self.cache_config.map(|(capacity, ttl)| ResponseCache::new(capacity, ttl));
+        manager.pricing = self.pricing;
+        *manager.budget_usd.get_mut() = self.budget_usd;
+        manager.max_concurrency = self.max_concurrency;
+        manager.reject_oversized_prompts = self.reject_oversized_prompts;
+        manager.strict_params = self.strict_params;
+        manager.default_params = self.default_params;
+        manager.on_retry = self.on_retry;
+        manager.on_request_complete = self.on_request_complete;
+
+        if let Some(task_name) = self.task_strategies.keys().find(|name| !self.defined_tasks.contains_key(*name)) {
+            return Err(LlmError::ConfigError(format!(
+                "Build failed: Strategy set for task '{}' which was not defined using define_task()",
+                task_name
+            )));
+        }
+        *manager.task_strategies.get_mut() = self.task_strategies;
+        manager.known_tasks.get_mut().extend(self.defined_tasks.keys().cloned());
+
+        if self.require_all_tasks_served {
+            let mut unserved: Vec<&str> = self.defined_tasks.keys()
+                .map(String::as_str)
+                .filter(|name| !self.providers_to_build.iter().any(|p| {
+                    p.enabled && p.supported_task_names.iter().any(|t| t == name)
+                }))
+                .collect();
+            if !unserved.is_empty() {
+                unserved.sort_unstable();
+                return Err(LlmError::ConfigError(format!(
+                    "Build failed: No enabled provider supports task(s): {}",
+                    unserved.join(", ")
+                )));
+            }
+        }
 
         for provider_config in self.providers_to_build {
             // Resolve TaskDefinition structs from names
@@ -213,14 +707,34 @@ impl LlmManagerBuilder {
                 }
             }
 
-            manager.add_instance( 
+            let instance_id = manager.add_instance(
                 provider_config.provider_type,
                 provider_config.api_key,
                 provider_config.model.clone(),
                 provider_tasks,
                 provider_config.enabled,
                 provider_config.custom_endpoint,
-            ).await;
+                InstanceSettings {
+                    custom_headers: provider_config.custom_headers,
+                    proxy: self.proxy.clone(),
+                    timeout: provider_config.timeout,
+                    connect_timeout: provider_config.connect_timeout,
+                    shared_client: self.shared_http_client.clone(),
+                    auth_header: provider_config.auth_header,
+                    models_path: provider_config.models_path,
+                    project_id: provider_config.project_id,
+                    location: provider_config.location,
+                    anthropic_beta: provider_config.anthropic_beta,
+                    default_max_tokens: provider_config.default_max_tokens,
+                    user_agent: self.user_agent.clone(),
+                },
+            ).await?;
+            if let Some(tracker) = manager.trackers.lock().await.get_mut(&instance_id) {
+                tracker.max_retries = provider_config.max_retries;
+                tracker.weight = provider_config.weight.unwrap_or(1.0);
+                tracker.priority = provider_config.priority;
+                tracker.context_window = provider_config.context_window;
+            }
             debug!("Built and added provider: {} ({})", provider_config.provider_type, provider_config.model);
         }
 
@@ -233,14 +747,23 @@ impl LlmManagerBuilder {
             log::warn!("LlmManager built with no provider instances.");
         }
 
-        // Start dashboard server if configured
+        if let Some(snapshot) = &self.usage_snapshot {
+            manager.import_usage(snapshot).await;
+        }
+
+        if let Some(interval) = self.health_check_interval {
+            manager.spawn_health_checks(interval).await;
+        }
+
+        // Start dashboard server if configured; `shutdown` stops it
         #[cfg(feature = "metrics-server")]
-        if let Some(config) = self.dashboard_config {
-            tokio::spawn(async move {
+        if let Some(mut config) = self.dashboard_config {
+            config.stats.get_or_insert_with(|| manager.stats_handle());
+            manager.spawn_background(async move {
                 if let Err(e) = crate::metrics::dashboard::start_dashboard_server(config).await {
                     log::error!("Dashboard server failed: {}", e);
                 }
-            });
+            }).await;
         }
 
         Ok(manager)
@@ -0,0 +1,156 @@
+use crate::providers::{LlmRequest, LlmResponse};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// In-memory LRU cache of successful responses
+///
+/// Only deterministic requests (temperature 0) are cached. Entries expire after
+/// the configured TTL and the least recently used entry is evicted once the
+/// cache is full.
+pub struct ResponseCache {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+/// Cached entries plus their recency order (front = least recently used)
+struct CacheState {
+    entries: HashMap<u64, CacheEntry>,
+    order: VecDeque<u64>,
+}
+
+/// A cached response and when it was stored
+struct CacheEntry {
+    response: LlmResponse,
+    inserted_at: Instant,
+}
+
+impl ResponseCache {
+    /// Create a new cache
+    ///
+    /// # Parameters
+    /// * `capacity` - Maximum number of responses to keep
+    /// * `ttl` - How long a response stays valid after being cached
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Whether a request is deterministic enough to be cached
+    ///
+    /// Requests without an explicit temperature use the provider default, which is
+    /// usually non-zero, so they are not cached.
+    pub fn is_cacheable(request: &LlmRequest) -> bool {
+        request.temperature == Some(0.0)
+    }
+
+    /// Compute the cache key for a request sent to a given model
+    ///
+    /// # Parameters
+    /// * `model` - Model the request is sent to
+    /// * `task` - Optional task the request is routed for
+    /// * `params` - Merged task and request parameters
+    /// * `request` - The provider request, including message content
+    pub fn key(
+        model: &str,
+        task: Option<&str>,
+        params: &HashMap<String, Value>,
+        request: &LlmRequest,
+    ) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        model.hash(&mut hasher);
+        task.hash(&mut hasher);
+
+        // Sort params so the key doesn't depend on HashMap iteration order
+        let sorted_params: BTreeMap<&String, &Value> = params.iter().collect();
+        serde_json::to_string(&sorted_params)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        serde_json::to_string(request)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+
+        // Content parts aren't part of the serialized request
+        for message in &request.messages {
+            format!("{:?}", message.content_parts).hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// Look up a cached response, dropping it if it has expired
+    pub async fn get(&self, key: u64) -> Option<LlmResponse> {
+        let mut state = self.state.lock().await;
+
+        let expired = match state.entries.get(&key) {
+            Some(entry) => entry.inserted_at.elapsed() > self.ttl,
+            None => return None,
+        };
+
+        if expired {
+            state.entries.remove(&key);
+            state.order.retain(|k| *k != key);
+            return None;
+        }
+
+        // Mark as most recently used
+        state.order.retain(|k| *k != key);
+        state.order.push_back(key);
+        state.entries.get(&key).map(|entry| entry.response.clone())
+    }
+
+    /// Store a response, evicting the least recently used entry if full
+    pub async fn insert(&self, key: u64, response: LlmResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().await;
+        state.order.retain(|k| *k != key);
+
+        while state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            match state.order.pop_front() {
+                Some(oldest) => {
+                    state.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        state.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                inserted_at: Instant::now(),
+            },
+        );
+        state.order.push_back(key);
+    }
+
+    /// Remove all cached responses
+    pub async fn clear(&self) {
+        let mut state = self.state.lock().await;
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    /// Number of responses currently cached (including expired ones not yet evicted)
+    pub async fn len(&self) -> usize {
+        self.state.lock().await.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+}
@@ -0,0 +1,46 @@
+use crate::errors::LlmError;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Callback run before the manager retries a failed attempt, set with `LlmManagerBuilder::on_retry`
+pub type RetryHook = Arc<dyn Fn(RetryEvent<'_>) + Send + Sync>;
+
+/// Callback run once a request has finished, set with `LlmManagerBuilder::on_request_complete`
+pub type RequestCompleteHook = Arc<dyn Fn(RequestCompleteEvent<'_>) + Send + Sync>;
+
+/// A failed attempt that is about to be retried, on the same or another instance
+#[derive(Debug, Clone, Copy)]
+pub struct RetryEvent<'a> {
+    /// ID of the request (see `LlmManagerResponse::request_id`)
+    pub request_id: u64,
+    /// Instance the attempt failed on, `None` if it failed before one was selected
+    pub instance_id: Option<usize>,
+    /// Error of the failed attempt
+    pub error: &'a LlmError,
+    /// Number of the failed attempt, starting at 1
+    pub attempt: usize,
+    pub task: Option<&'a str>,
+}
+
+/// Final outcome of a non-streaming request
+#[derive(Debug, Clone, Copy)]
+pub struct RequestCompleteEvent<'a> {
+    /// ID of the request (see `LlmManagerResponse::request_id`)
+    pub request_id: u64,
+    pub task: Option<&'a str>,
+    /// Instance that served the request, `None` if it failed
+    pub instance_id: Option<usize>,
+    /// Number of attempts made, including the successful one
+    pub attempts: usize,
+    /// Time from the first attempt until the outcome, including waits between retries
+    pub duration: Duration,
+    /// Error of the last attempt, `None` on success
+    pub error: Option<&'a LlmError>,
+}
+
+impl RequestCompleteEvent<'_> {
+    /// Whether the request succeeded
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
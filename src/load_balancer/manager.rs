@@ -1,22 +1,47 @@
-use crate::config::{self, Config};
+use crate::config::{self, Config, ConfigReport, ProviderConfig, ProviderReport};
 use crate::errors::{LlmError, LlmResult};
 use crate::load_balancer::builder::LlmManagerBuilder;
-use crate::load_balancer::types::{GenerationRequest, LlmManagerResponse, LlmManagerRequest};
-use crate::load_balancer::strategies::{self, LoadBalancingStrategy, LeastRecentlyUsedStrategy, LowestLatencyStrategy, RandomStrategy};
+use crate::load_balancer::cache::ResponseCache;
+use crate::load_balancer::hooks::{RequestCompleteEvent, RequestCompleteHook, RetryEvent, RetryHook};
+use crate::load_balancer::pricing::{self, Pricing};
+use crate::load_balancer::recording::{RecordingStream, StreamRecord};
+use crate::load_balancer::retry::RetryPolicy;
+use crate::load_balancer::stats::{InstanceStats, StatsHandle};
+use crate::load_balancer::types::{GenerationRequest, LlmManagerResponse, LlmManagerRequest, UsageSnapshot, UsageSnapshotEntry};
+use crate::load_balancer::strategies::{self, LoadBalancingStrategy, LeastRecentlyUsedStrategy, LowestLatencyStrategy, PowerOfTwoStrategy, PriorityStrategy, RandomStrategy, WeightedStrategy};
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::load_balancer::tracker::InstanceTracker;
-use crate::load_balancer::utils::{get_debug_path, write_to_debug_file};
-use crate::providers::{LlmInstance, LlmRequest, LlmStream, Message, TokenUsage};
+use crate::load_balancer::tracker::{InFlightGuard, InstanceTracker};
+use crate::load_balancer::utils::{self as debug_utils, append_to_debug_file, get_debug_path, rotated_debug_path, write_to_debug_file, DebugFormat};
+use crate::providers::streaming::{CancellableStream, StopSequenceStream};
+use crate::providers::tokens;
+use crate::providers::{ContentPart, InstanceSettings, LlmInstance, LlmRequest, LlmResponse, LlmStream, Message, ResponseFormat, TokenUsage, validate_messages};
 use crate::{constants, create_instance, ProviderType};
 use futures::future::join_all;
+use futures::stream::{FuturesUnordered, Stream};
 use log::{debug, info, warn};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "tracing")]
+use tracing::field::Empty;
+
+/// Record fields on the current `tracing` span; expands to nothing without the `tracing` feature
+macro_rules! record_span {
+    ($($field:literal = $value:expr),+ $(,)?) => {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            $(span.record($field, $value);)+
+        }
+    };
+}
 
 /// Main manager for LLM providers that handles load balancing and retries
 ///
@@ -29,12 +54,37 @@ use tokio::sync::Mutex;
 pub struct LlmManager {
     pub trackers: Arc<Mutex<HashMap<usize, InstanceTracker>>>, // Current instance trackers in the manager (contains the instances themselves)
     pub strategy: Arc<Mutex<Box<dyn strategies::LoadBalancingStrategy + Send + Sync>>>, // Current strategy for load balancing being used
+    pub task_strategies: Mutex<HashMap<String, Box<dyn strategies::LoadBalancingStrategy + Send + Sync>>>, // Strategies replacing `strategy` for requests of specific tasks
     pub tasks_to_instances: Arc<Mutex<HashMap<String, Vec<usize>>>>, // Map of which instances handle which tasks
+    pub known_tasks: Mutex<BTreeSet<String>>, // Every task defined or served so far, kept when its last instance is removed
     pub instance_counter: Mutex<usize>, // Used for giving unique IDs to each instance in this manager
     pub max_retries: usize, // Controls how many times a failed request will be tried before giving up
-    pub total_usage: Mutex<HashMap<usize, TokenUsage>>, // Token usage of each instance
+    pub retry_policy: RetryPolicy, // Controls the delay between retry attempts
+    pub max_total_retry_duration: Option<Duration>, // Optional wall-clock limit across all attempts of one request
+    pub latency_smoothing: f64, // Smoothing factor given to the latency EMA of new instances
+    pub max_failures_before_disable: Option<usize>, // Failures within `failure_window` requests that auto-disable a new instance
+    pub failure_window: usize, // How many recent requests of each instance count towards `max_failures_before_disable`
+    pub total_usage: Arc<Mutex<HashMap<usize, TokenUsage>>>, // Token usage of each instance
     pub debug_folder: Option<PathBuf>, // Path where JSONs with debug inputs/outputs of each model will be stored
-    pub creation_time: SystemTime
+    pub debug_format: DebugFormat, // Layout of the debug files (JSON array or JSON Lines)
+    pub max_debug_file_bytes: Option<u64>, // Size after which debug output rotates to a new numbered file
+    pub redact_secrets: bool, // Mask API keys and tokens in debug files and logged errors
+    debug_write_lock: Mutex<()>, // Serializes read-modify-write of debug files across concurrent requests
+    request_counter: AtomicU64, // Source of the IDs given to each request
+    pub creation_time: SystemTime,
+    pub cache: Option<ResponseCache>, // Optional cache of deterministic responses
+    pub pricing: HashMap<(String, String), Pricing>, // Price overrides keyed by (provider name, model)
+    pub total_cost: Arc<Mutex<HashMap<usize, f64>>>, // Estimated cost in USD of each instance
+    retired_cost: Mutex<f64>, // Cost of removed instances, still counted towards the total and the budget
+    pub budget_usd: Mutex<Option<f64>>, // Optional spending cap in USD, checked before each request
+    pub max_concurrency: Option<usize>, // Optional cap on how many requests batch_generate runs at once
+    pub reject_oversized_prompts: bool, // Refuse requests estimated to exceed the instance's context window
+    pub strict_params: bool, // Refuse requests with parameters the selected provider doesn't recognize instead of warning
+    pub default_params: HashMap<String, serde_json::Value>, // Parameters applied to every request, overridden by task and request parameters
+    pub on_retry: Option<RetryHook>, // Called before each retry of a failed attempt
+    pub on_request_complete: Option<RequestCompleteHook>, // Called with the outcome of each non-streaming request
+    shutdown_token: CancellationToken, // Cancelled by `shutdown` to stop background tasks
+    background_tasks: Mutex<Vec<JoinHandle<()>>>, // Health-check and dashboard tasks, awaited by `shutdown`
 }
 
 impl LlmManager {
@@ -45,12 +95,37 @@ impl LlmManager {
             strategy: Arc::new(Mutex::new(Box::new(
                 strategies::LeastRecentlyUsedStrategy::new(),
             ))),
+            task_strategies: Mutex::new(HashMap::new()),
             tasks_to_instances: Arc::new(Mutex::new(HashMap::new())),
+            known_tasks: Mutex::new(BTreeSet::new()),
             instance_counter: Mutex::new(0),
             max_retries: constants::DEFAULT_MAX_TRIES,
-            total_usage: Mutex::new(HashMap::new()),
+            retry_policy: RetryPolicy::default(),
+            max_total_retry_duration: None,
+            latency_smoothing: constants::DEFAULT_LATENCY_SMOOTHING,
+            max_failures_before_disable: None,
+            failure_window: constants::DEFAULT_FAILURE_WINDOW,
+            total_usage: Arc::new(Mutex::new(HashMap::new())),
             debug_folder: None,
-            creation_time: SystemTime::now()
+            debug_format: DebugFormat::default(),
+            max_debug_file_bytes: None,
+            redact_secrets: true,
+            debug_write_lock: Mutex::new(()),
+            request_counter: AtomicU64::new(0),
+            creation_time: SystemTime::now(),
+            cache: None,
+            pricing: HashMap::new(),
+            total_cost: Arc::new(Mutex::new(HashMap::new())),
+            retired_cost: Mutex::new(0.0),
+            budget_usd: Mutex::new(None),
+            max_concurrency: None,
+            reject_oversized_prompts: false,
+            strict_params: false,
+            default_params: HashMap::new(),
+            on_retry: None,
+            on_request_complete: None,
+            shutdown_token: CancellationToken::new(),
+            background_tasks: Mutex::new(Vec::new()),
         }
     }
 
@@ -64,8 +139,10 @@ impl LlmManager {
     /// This provides a declarative way to configure the manager without using the builder pattern.
     /// API keys can use environment variable syntax: `api_key = "${OPENAI_API_KEY}"`
     ///
+    /// `.yaml`/`.yml` files are read as YAML when the `yaml` feature is enabled; other files are read as TOML.
+    ///
     /// # Parameters
-    /// * `path` - Path to the TOML configuration file
+    /// * `path` - Path to the TOML (or YAML) configuration file
     ///
     /// # Returns
     /// * Result with the configured LlmManager or a configuration error
@@ -81,7 +158,7 @@ impl LlmManager {
     /// }
     /// ```
     pub async fn from_config_file<P: AsRef<Path>>(path: P) -> LlmResult<Self> {
-        let config = config::load_config(path)?;
+        let config = config::load_config_auto(path)?;
         Self::from_config(config).await
     }
 
@@ -99,60 +176,104 @@ impl LlmManager {
         Self::from_config(config).await
     }
 
+    /// Create an LlmManager from a configuration file, also returning a report of what was built.
+    ///
+    /// # Parameters
+    /// * `path` - Path to the TOML (or YAML) configuration file
+    ///
+    /// # Returns
+    /// * Result with the configured LlmManager and its `ConfigReport`, or a configuration error
+    pub async fn from_config_file_verbose<P: AsRef<Path>>(path: P) -> LlmResult<(Self, ConfigReport)> {
+        let config = config::load_config_auto(path)?;
+        Self::from_config_with_report(config).await
+    }
+
+    /// Create an LlmManager from a TOML configuration string, also returning a report of what was built.
+    ///
+    /// The report lists the added providers and defined tasks plus warnings such as providers
+    /// without tasks, and serializes to JSON for CI checks.
+    ///
+    /// # Parameters
+    /// * `toml_content` - TOML configuration as a string
+    ///
+    /// # Returns
+    /// * Result with the configured LlmManager and its `ConfigReport`, or a configuration error
+    pub async fn from_config_str_verbose(toml_content: &str) -> LlmResult<(Self, ConfigReport)> {
+        let config = config::parse_config(toml_content)?;
+        Self::from_config_with_report(config).await
+    }
+
     /// Internal method to build an LlmManager from a parsed Config.
     async fn from_config(config: Config) -> LlmResult<Self> {
+        let (manager, _report) = Self::from_config_with_report(config).await?;
+        Ok(manager)
+    }
+
+    async fn from_config_with_report(config: Config) -> LlmResult<(Self, ConfigReport)> {
+        let mut report = ConfigReport {
+            tasks: config.tasks.iter().map(|t| t.name.clone()).collect(),
+            ..Default::default()
+        };
+
         // Create strategy based on config
-        let strategy: Box<dyn LoadBalancingStrategy + Send + Sync> =
-            match config.settings.strategy.to_lowercase().as_str() {
-                "lru" | "least_recently_used" => Box::new(LeastRecentlyUsedStrategy::new()),
-                "lowest_latency" | "latency" => Box::new(LowestLatencyStrategy::new()),
-                "random" => Box::new(RandomStrategy::new()),
-                _ => Box::new(LeastRecentlyUsedStrategy::new()), // Default fallback
-            };
+        let strategy_name = config.settings.strategy.to_lowercase();
+        let strategy = Self::strategy_from_name(&strategy_name);
 
         let mut manager = Self::new_with_strategy_and_retries(strategy, config.settings.max_retries);
+        manager.known_tasks.get_mut().extend(config.tasks.iter().map(|task| task.name.clone()));
+
+        for task in &config.tasks {
+            if let Some(task_strategy) = &task.strategy {
+                manager
+                    .task_strategies
+                    .get_mut()
+                    .insert(task.name.clone(), Self::strategy_from_name(task_strategy));
+            }
+        }
+
+        manager.reject_oversized_prompts = config.settings.reject_oversized_prompts;
+        manager.strict_params = config.settings.strict_params;
+
+        if let Some(retry) = &config.settings.retry {
+            manager.retry_policy = retry.to_policy();
+        }
 
         // Set debug folder if specified
-        if let Some(debug_folder) = config.settings.debug_folder {
+        if let Some(debug_folder) = &config.settings.debug_folder {
             manager.debug_folder = Some(PathBuf::from(debug_folder));
         }
 
-        // Build task definitions map for lookup
-        let mut task_defs: HashMap<String, TaskDefinition> = HashMap::new();
-        for task_config in &config.tasks {
-            let mut task_def = TaskDefinition::new(&task_config.name);
-            if let Some(max_tokens) = task_config.max_tokens {
-                task_def = task_def.with_max_tokens(max_tokens);
-            }
-            if let Some(temperature) = task_config.temperature {
-                task_def = task_def.with_temperature(temperature);
+        // Register model prices for cost estimation
+        for (provider_type, models) in &config.pricing {
+            for (model, model_pricing) in models {
+                manager.pricing.insert(
+                    (provider_type.to_lowercase(), model.clone()),
+                    *model_pricing,
+                );
             }
-            task_defs.insert(task_config.name.clone(), task_def);
         }
 
+        let task_defs = Self::config_task_definitions(&config);
+
         // Add provider instances
         for provider_config in &config.providers {
             // Parse provider type
             let provider_type: ProviderType = provider_config.provider_type.as_str().into();
-
-            // Collect task definitions for this provider
-            let mut provider_tasks: Vec<TaskDefinition> = Vec::new();
-            for task_name in &provider_config.tasks {
-                if let Some(task_def) = task_defs.get(task_name) {
-                    provider_tasks.push(task_def.clone());
-                }
-                // Note: validation already happened in config::loader, so task should exist
-            }
+            let provider_tasks = Self::config_provider_tasks(provider_config, &task_defs);
 
             // Add the instance
-            manager.add_instance(
+            let instance_id = manager.add_instance(
                 provider_type,
                 provider_config.api_key.clone(),
                 provider_config.model.clone(),
                 provider_tasks,
                 provider_config.enabled,
                 provider_config.endpoint.clone(),
-            ).await;
+                Self::config_instance_settings(provider_config, &config),
+            ).await?;
+            if let Some(tracker) = manager.trackers.lock().await.get_mut(&instance_id) {
+                Self::apply_provider_config(tracker, provider_config);
+            }
 
             let provider_name = provider_config.name.as_deref()
                 .unwrap_or(&provider_config.model);
@@ -162,17 +283,246 @@ impl LlmManager {
                 provider_name,
                 provider_config.tasks
             );
+
+            let display_name = provider_config.display_name();
+            if provider_config.tasks.is_empty() {
+                report.warnings.push(format!(
+                    "Provider '{}' references no tasks; it only serves requests without a task",
+                    display_name
+                ));
+            }
+            if !provider_config.enabled {
+                report.warnings.push(format!("Provider '{}' is disabled", display_name));
+            }
+            if provider_config.weight.is_some() && strategy_name != "weighted" {
+                report.warnings.push(format!(
+                    "Provider '{}' sets a weight, which strategy '{}' ignores",
+                    display_name, config.settings.strategy
+                ));
+            }
+            if provider_config.priority.is_some() && strategy_name != "priority" {
+                report.warnings.push(format!(
+                    "Provider '{}' sets a priority, which strategy '{}' ignores",
+                    display_name, config.settings.strategy
+                ));
+            }
+            report.providers.push(ProviderReport {
+                instance_id,
+                name: display_name,
+                provider_type: provider_config.provider_type.clone(),
+                model: provider_config.model.clone(),
+                tasks: provider_config.tasks.clone(),
+                enabled: provider_config.enabled,
+            });
+        }
+
+        for task in &config.tasks {
+            if !config.providers.iter().any(|p| p.tasks.contains(&task.name)) {
+                report.warnings.push(format!("Task '{}' is not supported by any provider", task.name));
+            }
         }
 
         // Warn if no providers were configured
         let provider_count = manager.get_provider_count().await;
         if provider_count == 0 {
             warn!("LlmManager loaded from config with no provider instances.");
+            report.warnings.push("No providers configured".to_string());
         } else {
             info!("LlmManager loaded from config with {} provider(s)", provider_count);
         }
 
-        Ok(manager)
+        Ok((manager, report))
+    }
+
+    /// Build the strategy a configuration names, falling back to least-recently-used
+    fn strategy_from_name(name: &str) -> Box<dyn LoadBalancingStrategy + Send + Sync> {
+        match name.to_lowercase().as_str() {
+            "lru" | "least_recently_used" => Box::new(LeastRecentlyUsedStrategy::new()),
+            "lowest_latency" | "latency" => Box::new(LowestLatencyStrategy::new()),
+            "random" => Box::new(RandomStrategy::new()),
+            "p2c" => Box::new(PowerOfTwoStrategy::new()),
+            "weighted" => Box::new(WeightedStrategy::new()),
+            "priority" => Box::new(PriorityStrategy::new()),
+            _ => Box::new(LeastRecentlyUsedStrategy::new()), // Default fallback
+        }
+    }
+
+    /// Task definitions declared in a configuration, keyed by name
+    fn config_task_definitions(config: &Config) -> HashMap<String, TaskDefinition> {
+        let mut task_defs: HashMap<String, TaskDefinition> = HashMap::new();
+        for task_config in &config.tasks {
+            let mut task_def = TaskDefinition::new(&task_config.name);
+            if let Some(max_tokens) = task_config.max_tokens {
+                task_def = task_def.with_max_tokens(max_tokens);
+            }
+            if let Some(temperature) = task_config.temperature {
+                task_def = task_def.with_temperature(temperature);
+            }
+            task_defs.insert(task_config.name.clone(), task_def);
+        }
+        task_defs
+    }
+
+    /// Task definitions referenced by a configured provider
+    fn config_provider_tasks(
+        provider_config: &ProviderConfig,
+        task_defs: &HashMap<String, TaskDefinition>,
+    ) -> Vec<TaskDefinition> {
+        // Validation already happened in config::loader, so every task should exist
+        provider_config
+            .tasks
+            .iter()
+            .filter_map(|task_name| task_defs.get(task_name).cloned())
+            .collect()
+    }
+
+    /// HTTP settings of a configured provider's client
+    fn config_instance_settings(provider_config: &ProviderConfig, config: &Config) -> InstanceSettings {
+        InstanceSettings {
+            custom_headers: provider_config.headers.clone(),
+            proxy: config.settings.proxy.clone(),
+            timeout: provider_config.timeout_ms.map(Duration::from_millis),
+            connect_timeout: provider_config.connect_timeout_ms.map(Duration::from_millis),
+            shared_client: None,
+            auth_header: provider_config.auth_header.clone(),
+            models_path: provider_config.models_path.clone(),
+            project_id: provider_config.project_id.clone(),
+            location: provider_config.location.clone(),
+            anthropic_beta: provider_config.anthropic_beta.clone(),
+            default_max_tokens: provider_config.default_max_tokens,
+            user_agent: config.settings.user_agent.clone(),
+        }
+    }
+
+    /// Copy the per-instance settings of a configured provider onto its tracker
+    fn apply_provider_config(tracker: &mut InstanceTracker, provider_config: &ProviderConfig) {
+        tracker.max_retries = provider_config.max_retries;
+        tracker.weight = provider_config.weight.unwrap_or(1.0);
+        tracker.priority = provider_config.priority;
+        tracker.context_window = provider_config.context_window;
+        tracker.config_key = Some(provider_config.instance_key());
+    }
+
+    /// Apply a new TOML configuration to a running manager
+    ///
+    /// Providers are matched to the current instances by type, model and `name`:
+    /// - New providers are added
+    /// - Instances whose provider is no longer configured are removed
+    /// - Surviving instances keep their ID, token usage and cost, and pick up the new
+    ///   API key, endpoint, headers, tasks, enabled flag, `max_retries`, weight and priority
+    ///
+    /// Only instances that were loaded from a configuration are matched; instances added
+    /// with the builder or `add_instance_dynamic` are left alone. `[settings]` and `[pricing]`
+    /// are not reloaded. Requests already running finish on the instance they started on.
+    ///
+    /// # Parameters
+    /// * `toml_content` - The new TOML configuration
+    ///
+    /// # Returns
+    /// * `ConfigError` if the configuration is invalid or an instance could not be built,
+    ///   in which case the manager is left untouched
+    pub async fn reload_from_config_str(&self, toml_content: &str) -> LlmResult<()> {
+        let config = config::parse_config(toml_content)?;
+        let task_defs = Self::config_task_definitions(&config);
+
+        // Build every instance up front so a failure leaves the manager untouched
+        let mut new_instances = Vec::with_capacity(config.providers.len());
+        for provider_config in &config.providers {
+            let instance = create_instance(
+                provider_config.provider_type.as_str().into(),
+                provider_config.api_key.clone(),
+                provider_config.model.clone(),
+                Self::config_provider_tasks(provider_config, &task_defs),
+                provider_config.enabled,
+                provider_config.endpoint.clone(),
+                Self::config_instance_settings(provider_config, &config),
+            )?;
+            new_instances.push((provider_config, instance));
+        }
+
+        // Current configured instances by key, oldest first so duplicates match in order
+        let mut current: HashMap<String, Vec<usize>> = HashMap::new();
+        {
+            let trackers = self.trackers.lock().await;
+            let mut ids: Vec<&usize> = trackers.keys().collect();
+            ids.sort();
+            for id in ids {
+                if let Some(key) = &trackers[id].config_key {
+                    current.entry(key.clone()).or_default().push(*id);
+                }
+            }
+        }
+        for ids in current.values_mut() {
+            ids.reverse();
+        }
+
+        let (mut added, mut updated) = (0, 0);
+        for (provider_config, instance) in new_instances {
+            let key = provider_config.instance_key();
+            let existing_id = current.get_mut(&key).and_then(|ids| ids.pop());
+
+            let id = match existing_id {
+                Some(id) if self.replace_instance(id, instance.clone(), provider_config.enabled).await => {
+                    updated += 1;
+                    id
+                }
+                // New provider, or its instance was removed concurrently
+                _ => {
+                    added += 1;
+                    self.add_instance_dynamic(instance).await
+                }
+            };
+
+            if let Some(tracker) = self.trackers.lock().await.get_mut(&id) {
+                Self::apply_provider_config(tracker, provider_config);
+            }
+        }
+
+        let mut removed = 0;
+        for id in current.into_values().flatten() {
+            if self.remove_instance(id).await {
+                removed += 1;
+            }
+        }
+
+        info!(
+            "Reloaded configuration: {} added, {} updated, {} removed",
+            added, updated, removed
+        );
+        Ok(())
+    }
+
+    /// Swap the provider behind an instance, keeping its ID and statistics
+    ///
+    /// # Returns
+    /// * Whether an instance with that ID was found
+    async fn replace_instance(
+        &self,
+        instance_id: usize,
+        instance: Arc<dyn LlmInstance + Send + Sync>,
+        enabled: bool,
+    ) -> bool {
+        {
+            let mut trackers = self.trackers.lock().await;
+            let Some(tracker) = trackers.get_mut(&instance_id) else {
+                return false;
+            };
+            tracker.instance = instance.clone();
+            tracker.set_enabled(enabled);
+            tracker.healthy = true;
+        }
+
+        self.known_tasks.lock().await.extend(instance.get_supported_tasks().keys().cloned());
+
+        let mut task_map = self.tasks_to_instances.lock().await;
+        for ids in task_map.values_mut() {
+            ids.retain(|id| *id != instance_id);
+        }
+        for task_name in instance.get_supported_tasks().keys() {
+            task_map.entry(task_name.clone()).or_default().push(instance_id);
+        }
+        task_map.retain(|_, ids| !ids.is_empty());
+        true
     }
 
     /// Create a new LlmManager with a custom load balancing strategy
@@ -185,12 +535,37 @@ impl LlmManager {
         Self {
             trackers: Arc::new(Mutex::new(HashMap::new())),
             strategy: Arc::new(Mutex::new(strategy)),
+            task_strategies: Mutex::new(HashMap::new()),
             tasks_to_instances: Arc::new(Mutex::new(HashMap::new())),
+            known_tasks: Mutex::new(BTreeSet::new()),
             instance_counter: Mutex::new(0),
             max_retries: constants::DEFAULT_MAX_TRIES,
-            total_usage: Mutex::new(HashMap::new()),
+            retry_policy: RetryPolicy::default(),
+            max_total_retry_duration: None,
+            latency_smoothing: constants::DEFAULT_LATENCY_SMOOTHING,
+            max_failures_before_disable: None,
+            failure_window: constants::DEFAULT_FAILURE_WINDOW,
+            total_usage: Arc::new(Mutex::new(HashMap::new())),
             debug_folder: None,
-            creation_time: SystemTime::now()
+            debug_format: DebugFormat::default(),
+            max_debug_file_bytes: None,
+            redact_secrets: true,
+            debug_write_lock: Mutex::new(()),
+            request_counter: AtomicU64::new(0),
+            creation_time: SystemTime::now(),
+            cache: None,
+            pricing: HashMap::new(),
+            total_cost: Arc::new(Mutex::new(HashMap::new())),
+            retired_cost: Mutex::new(0.0),
+            budget_usd: Mutex::new(None),
+            max_concurrency: None,
+            reject_oversized_prompts: false,
+            strict_params: false,
+            default_params: HashMap::new(),
+            on_retry: None,
+            on_request_complete: None,
+            shutdown_token: CancellationToken::new(),
+            background_tasks: Mutex::new(Vec::new()),
         }
     }
 
@@ -202,12 +577,37 @@ impl LlmManager {
         Self {
             trackers: Arc::new(Mutex::new(HashMap::new())),
             strategy: Arc::new(Mutex::new(strategy)),
+            task_strategies: Mutex::new(HashMap::new()),
             tasks_to_instances: Arc::new(Mutex::new(HashMap::new())),
+            known_tasks: Mutex::new(BTreeSet::new()),
             instance_counter: Mutex::new(0),
             max_retries, // Use passed value
-            total_usage: Mutex::new(HashMap::new()),
+            retry_policy: RetryPolicy::default(),
+            max_total_retry_duration: None,
+            latency_smoothing: constants::DEFAULT_LATENCY_SMOOTHING,
+            max_failures_before_disable: None,
+            failure_window: constants::DEFAULT_FAILURE_WINDOW,
+            total_usage: Arc::new(Mutex::new(HashMap::new())),
             debug_folder: None,
-            creation_time: SystemTime::now()
+            debug_format: DebugFormat::default(),
+            max_debug_file_bytes: None,
+            redact_secrets: true,
+            debug_write_lock: Mutex::new(()),
+            request_counter: AtomicU64::new(0),
+            creation_time: SystemTime::now(),
+            cache: None,
+            pricing: HashMap::new(),
+            total_cost: Arc::new(Mutex::new(HashMap::new())),
+            retired_cost: Mutex::new(0.0),
+            budget_usd: Mutex::new(None),
+            max_concurrency: None,
+            reject_oversized_prompts: false,
+            strict_params: false,
+            default_params: HashMap::new(),
+            on_retry: None,
+            on_request_complete: None,
+            shutdown_token: CancellationToken::new(),
+            background_tasks: Mutex::new(Vec::new()),
         }
     }
 
@@ -220,6 +620,12 @@ impl LlmManager {
     /// * `tasks` - List of tasks this provider supports
     /// * `enabled` - Whether this provider should be enabled
     /// * `custom_endpont` - Optional specification on where the requests for this instance should go
+    /// * `settings` - Extra HTTP settings such as custom headers and proxy
+    ///
+    /// # Returns
+    /// * ID assigned to the new instance
+    /// * `ConfigError` if the instance's HTTP client could not be built
+    #[allow(clippy::too_many_arguments)]
     pub async fn add_instance(
         &mut self,
         provider_type: ProviderType,
@@ -228,7 +634,8 @@ impl LlmManager {
         tasks: Vec<TaskDefinition>,
         enabled: bool,
         custom_endpoint: Option<String>,
-    ) {
+        settings: InstanceSettings,
+    ) -> LlmResult<usize> {
         debug!("Creating provider with model {}", model);
         let instance = create_instance(
             provider_type,
@@ -237,14 +644,16 @@ impl LlmManager {
             tasks.clone(),
             enabled,
             custom_endpoint,
-        );
-        self.add_instance_to_manager(instance).await;
+            settings,
+        )?;
+        let id = self.add_instance_dynamic(instance).await;
         info!(
             "Added Provider Instance ({}) - Model: {} - Supports Tasks: {:?}",
             provider_type,
             model,
             tasks.iter().map(|t| t.name.as_str()).collect::<Vec<&str>>()
         );
+        Ok(id)
     }
 
     /// Add a pre-created provider instance
@@ -252,6 +661,20 @@ impl LlmManager {
     /// # Parameters
     /// * `provider` - The provider instance to add
     pub async fn add_instance_to_manager(&mut self, instance: Arc<dyn LlmInstance + Send + Sync>) {
+        self.add_instance_dynamic(instance).await;
+    }
+
+    /// Add a pre-created provider instance to a running manager
+    ///
+    /// Unlike `add_instance_to_manager`, this only needs a shared reference, so it can be
+    /// called while requests are being served.
+    ///
+    /// # Parameters
+    /// * `instance` - The provider instance to add
+    ///
+    /// # Returns
+    /// * ID assigned to the new instance
+    pub async fn add_instance_dynamic(&self, instance: Arc<dyn LlmInstance + Send + Sync>) -> usize {
         let id = {
             let mut counter = self.instance_counter.lock().await;
             let current_id = *counter;
@@ -259,11 +682,15 @@ impl LlmManager {
             current_id
         };
 
-        let tracker = InstanceTracker::new(instance.clone());
+        let mut tracker = InstanceTracker::new(instance.clone());
+        tracker.latency_smoothing = self.latency_smoothing;
+        tracker.max_failures_before_disable = self.max_failures_before_disable;
+        tracker.failure_window = self.failure_window;
         debug!("Adding instance {} ({})", id, instance.get_name());
 
         let supported_tasks_names: Vec<String> =
             instance.get_supported_tasks().keys().cloned().collect();
+        self.known_tasks.lock().await.extend(supported_tasks_names.iter().cloned());
 
         {
             let mut task_map = self.tasks_to_instances.lock().await;
@@ -285,6 +712,63 @@ impl LlmManager {
             let mut usage_map = self.total_usage.lock().await;
             usage_map.insert(id, TokenUsage::default()); // TODO - Implement default
         }
+
+        {
+            let mut cost_map = self.total_cost.lock().await;
+            cost_map.insert(id, 0.0);
+        }
+
+        id
+    }
+
+    /// Remove a provider instance from the manager
+    ///
+    /// Requests already sent to the instance finish normally, but it will not be selected again.
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to remove
+    ///
+    /// # Returns
+    /// * Whether an instance with that ID was found and removed
+    pub async fn remove_instance(&self, instance_id: usize) -> bool {
+        let removed = {
+            let mut trackers = self.trackers.lock().await;
+            trackers.remove(&instance_id)
+        };
+
+        let Some(tracker) = removed else {
+            warn!("Cannot remove instance {}: not found", instance_id);
+            return false;
+        };
+
+        {
+            let mut task_map = self.tasks_to_instances.lock().await;
+            for ids in task_map.values_mut() {
+                ids.retain(|id| *id != instance_id);
+            }
+            task_map.retain(|_, ids| !ids.is_empty());
+        }
+
+        {
+            let mut usage_map = self.total_usage.lock().await;
+            usage_map.remove(&instance_id);
+        }
+
+        {
+            let mut cost_map = self.total_cost.lock().await;
+            if let Some(cost) = cost_map.remove(&instance_id) {
+                // Keep the spend so removing an instance doesn't refund the budget
+                *self.retired_cost.lock().await += cost;
+            }
+        }
+
+        info!(
+            "Removed Provider Instance {} ({}) - Model: {}",
+            instance_id,
+            tracker.instance.get_name(),
+            tracker.instance.get_model()
+        );
+        true
     }
 
     /// Set a new load balancing strategy
@@ -299,6 +783,220 @@ impl LlmManager {
         *current_strategy = strategy;
     }
 
+    /// Set or clear the load balancing strategy used for requests of one task
+    ///
+    /// Requests for tasks without their own strategy, and requests without a task,
+    /// use the manager's strategy.
+    ///
+    /// # Parameters
+    /// * `task` - Name of the task
+    /// * `strategy` - Strategy for the task, or `None` to fall back to the manager's strategy
+    pub async fn set_task_strategy(
+        &self,
+        task: impl Into<String>,
+        strategy: Option<Box<dyn strategies::LoadBalancingStrategy + Send + Sync>>,
+    ) {
+        let task = task.into();
+        let mut task_strategies = self.task_strategies.lock().await;
+        match strategy {
+            Some(strategy) => {
+                info!("Task '{}' now uses its own load balancing strategy", task);
+                task_strategies.insert(task, strategy);
+            }
+            None => {
+                task_strategies.remove(&task);
+            }
+        }
+    }
+
+    /// The strategy that selects instances for `task`: its own, else the manager's
+    fn strategy_for<'a>(
+        task: Option<&str>,
+        default: &'a mut Box<dyn strategies::LoadBalancingStrategy + Send + Sync>,
+        task_strategies: &'a mut HashMap<String, Box<dyn strategies::LoadBalancingStrategy + Send + Sync>>,
+    ) -> &'a mut Box<dyn strategies::LoadBalancingStrategy + Send + Sync> {
+        match task.and_then(|task| task_strategies.get_mut(task)) {
+            Some(strategy) => strategy,
+            None => default,
+        }
+    }
+
+    /// Convert a user-facing request to internal format, giving it the next request ID
+    ///
+    /// IDs start at 1 and are unique within this manager.
+    fn internal_request(&self, request: GenerationRequest) -> LlmManagerRequest {
+        LlmManagerRequest {
+            request_id: self.request_counter.fetch_add(1, Ordering::Relaxed) + 1,
+            ..LlmManagerRequest::from_generation_request(request)
+        }
+    }
+
+    /// Process a single request
+    ///
+    /// Shares the retry and fallback logic of `generate_sequentially` and `batch_generate`,
+    /// but failures are returned as `Err` instead of being stored in
+    /// `LlmManagerResponse.error`, so `success` is always `true` on `Ok`.
+    /// For incremental output use `generate_stream`.
+    ///
+    /// # Parameters
+    /// * `request` - The generation request to process
+    ///
+    /// # Returns
+    /// * Result with either the response or the error of the last attempt
+    pub async fn generate(&self, request: GenerationRequest) -> LlmResult<LlmManagerResponse> {
+        let internal_request = self.internal_request(request);
+        let request_id = internal_request.request_id;
+        match self.generate_response(internal_request, None).await {
+            Ok((response, instance_id, attempts)) => {
+                Ok(self.success_response(response, instance_id, attempts, request_id).await)
+            }
+            Err((error, _)) => Err(error),
+        }
+    }
+
+    /// Send a request straight to one instance, without strategy, retries or fallback
+    ///
+    /// The request is passed to the instance as-is, so manager and task parameters are
+    /// not applied. Latency, usage, cost and metrics are recorded like for any request.
+    /// Useful for diagnostics and for comparing instances.
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to send the request to
+    /// * `request` - The provider request to send
+    ///
+    /// # Returns
+    /// * The instance's full response, or an error if the instance is unknown, disabled or fails
+    pub async fn generate_with_instance(&self, instance_id: usize, request: LlmRequest) -> LlmResult<LlmResponse> {
+        self.check_budget().await?;
+        let (instance, in_flight_guard) = {
+            let trackers = self.trackers.lock().await;
+            let tracker = trackers
+                .get(&instance_id)
+                .ok_or_else(|| LlmError::ConfigError(format!("Instance {} not found", instance_id)))?;
+            if !tracker.is_enabled() {
+                return Err(LlmError::ProviderDisabled(format!(
+                    "instance {} ({})",
+                    instance_id,
+                    tracker.instance.get_name()
+                )));
+            }
+            Self::check_messages(&request.messages, tracker.instance.as_ref())?;
+            (tracker.instance.clone(), tracker.start_request())
+        };
+
+        let start_time = Instant::now();
+        let result = instance.generate(&request).await;
+        let duration = start_time.elapsed();
+        drop(in_flight_guard);
+        debug!(
+            "Instance {} ({}) answered a direct request in {:?}",
+            instance_id,
+            instance.get_name(),
+            duration
+        );
+
+        let tracker_present = match self.trackers.lock().await.get_mut(&instance_id) {
+            Some(tracker) => {
+                tracker.record_result(duration, &result);
+                true
+            }
+            None => false,
+        };
+
+        #[cfg(feature = "metrics")]
+        match &result {
+            Ok(response) => crate::metrics::record_request_success(
+                instance.get_name(),
+                instance.get_model(),
+                None,
+                instance_id,
+                duration,
+                response.usage.as_ref(),
+            ),
+            Err(e) => crate::metrics::record_request_failure(
+                instance.get_name(),
+                instance.get_model(),
+                None,
+                instance_id,
+                e,
+                duration,
+            ),
+        }
+
+        let response = result?;
+        // Skip usage for instances removed mid-request so their entry isn't recreated
+        if let (Some(usage), true) = (&response.usage, tracker_present) {
+            self.update_instance_usage(instance_id, usage).await;
+            self.update_instance_cost(instance_id, instance.get_name(), instance.get_model(), usage).await;
+        }
+        Ok(response)
+    }
+
+    /// Send one request to several instances at once, for A/B comparisons
+    ///
+    /// Ignores the load-balancing strategy: each targeted instance gets the request once,
+    /// through `generate_with_instance`, with parameters merged as for `generate`. Unlike
+    /// `batch_generate`, which spreads many requests across instances, this runs the same
+    /// request on many instances.
+    ///
+    /// # Parameters
+    /// * `request` - The generation request to send
+    /// * `instance_ids` - Instances to query, or `None` for every enabled instance
+    ///   supporting the request's task
+    ///
+    /// # Returns
+    /// * `(instance ID, result, latency)` of each instance, in the order of `instance_ids`
+    ///   (by ID when `None`)
+    pub async fn compare(
+        &self,
+        request: GenerationRequest,
+        instance_ids: Option<Vec<usize>>,
+    ) -> Vec<(usize, LlmResult<LlmResponse>, Duration)> {
+        let manager_request = self.internal_request(request);
+        let task = manager_request.task.as_deref();
+        let targets: Vec<(usize, Option<Arc<dyn LlmInstance + Send + Sync>>)> = {
+            let trackers = self.trackers.lock().await;
+            let ids = instance_ids.unwrap_or_else(|| {
+                let mut ids: Vec<usize> = trackers
+                    .iter()
+                    .filter(|(_, tracker)| {
+                        tracker.is_enabled()
+                            && task.is_none_or(|task| tracker.instance.get_supported_tasks().contains_key(task))
+                    })
+                    .map(|(id, _)| *id)
+                    .collect();
+                ids.sort_unstable();
+                ids
+            });
+            ids.into_iter()
+                .map(|id| (id, trackers.get(&id).map(|tracker| tracker.instance.clone())))
+                .collect()
+        };
+        info!(
+            "[request {}] Comparing {} instances for task: {:?}",
+            manager_request.request_id,
+            targets.len(),
+            task
+        );
+
+        let manager_request = &manager_request;
+        join_all(targets.into_iter().map(|(instance_id, instance)| async move {
+            let start_time = Instant::now();
+            let result = match instance {
+                Some(instance) => {
+                    let task_def = task.and_then(|task| instance.get_supported_tasks().get(task).cloned());
+                    match self.provider_request(manager_request, task_def.as_ref(), instance.as_ref()) {
+                        Ok(request) => self.generate_with_instance(instance_id, request).await,
+                        Err(error) => Err(error),
+                    }
+                }
+                None => Err(LlmError::ConfigError(format!("Instance {} not found", instance_id))),
+            };
+            (instance_id, result, start_time.elapsed())
+        }))
+        .await
+    }
+
     /// Process multiple requests sequentially
     ///
     /// # Parameters
@@ -317,8 +1015,9 @@ impl LlmManager {
         );
 
         for (index, request) in requests.into_iter().enumerate() {
-            info!("Starting sequential request index: {}", index);
-            let internal_request = LlmManagerRequest::from_generation_request(request);
+            let internal_request = self.internal_request(request);
+            let request_id = internal_request.request_id;
+            info!("[request {}] Starting sequential request index: {}", request_id, index);
 
             let response_result = self.generate_response(internal_request, None).await;
             info!(
@@ -327,21 +1026,13 @@ impl LlmManager {
             );
 
             let response = match response_result {
-                Ok(content) => {
+                Ok((response, instance_id, attempts)) => {
                     info!("Sequential request index {} succeeded.", index);
-                    LlmManagerResponse {
-                        content,
-                        success: true,
-                        error: None,
-                    }
+                    self.success_response(response, instance_id, attempts, request_id).await
                 }
-                Err(e) => {
-                    warn!("Sequential request index {} failed: {}", index, e);
-                    LlmManagerResponse {
-                        content: String::new(),
-                        success: false,
-                        error: Some(e.to_string()),
-                    }
+                Err((e, attempts)) => {
+                    warn!("Sequential request index {} failed: {}", index, self.loggable_error(&e));
+                    Self::failure_response(&e, attempts, request_id)
                 }
             };
 
@@ -356,6 +1047,9 @@ impl LlmManager {
 
     /// Process multiple requests in parallel
     ///
+    /// At most `max_concurrency` requests are in flight at once when it is set;
+    /// otherwise all requests are started together.
+    ///
     /// # Parameters
     /// * `requests` - List of generation requests to process
     ///
@@ -365,36 +1059,82 @@ impl LlmManager {
         &self,
         requests: Vec<GenerationRequest>,
     ) -> Vec<LlmManagerResponse> {
-        info!("Entering batch_generate with {} requests", requests.len());
-        let internal_requests = requests
-            .into_iter()
-            .map(|request| LlmManagerRequest::from_generation_request(request))
-            .collect::<Vec<_>>();
+        self.run_batch(requests, None).await
+    }
 
-        let futures = internal_requests
+    /// Process multiple requests in parallel until the token is cancelled
+    ///
+    /// Requests still running when the token fires are dropped, which aborts their
+    /// provider HTTP requests, and come back as failed responses with a
+    /// `Request cancelled` error. Responses finished before that are kept.
+    ///
+    /// # Parameters
+    /// * `requests` - List of generation requests to process
+    /// * `token` - Token that cancels the requests that haven't finished
+    ///
+    /// # Returns
+    /// * List of responses in the same order as the requests
+    pub async fn batch_generate_with_cancel(
+        &self,
+        requests: Vec<GenerationRequest>,
+        token: CancellationToken,
+    ) -> Vec<LlmManagerResponse> {
+        self.run_batch(requests, Some(&token)).await
+    }
+
+    /// Process multiple requests in parallel, yielding each response as it completes
+    ///
+    /// Unlike `batch_generate`, fast responses can be handled while slow ones are
+    /// still pending. At most `max_concurrency` requests are in flight at once when
+    /// it is set. Requests only run while the stream is polled, and dropping the
+    /// stream drops the requests that haven't finished.
+    ///
+    /// # Parameters
+    /// * `requests` - List of generation requests to process
+    ///
+    /// # Returns
+    /// * Stream of `(index, response)` pairs in completion order, where `index` is the
+    ///   request's position in `requests`
+    pub fn stream_batch(
+        &self,
+        requests: Vec<GenerationRequest>,
+    ) -> impl Stream<Item = (usize, LlmManagerResponse)> + '_ {
+        info!("Entering stream_batch with {} requests", requests.len());
+        let semaphore = self.max_concurrency.map(|limit| Arc::new(Semaphore::new(limit.max(1))));
+
+        requests
             .into_iter()
             .enumerate()
-            .map(|(index, request)| async move {
-                info!("Starting parallel request index: {}", index);
-                match self.generate_response(request, None).await {
-                    Ok(content) => {
-                        info!("Parallel request index {} succeeded.", index);
-                        LlmManagerResponse {
-                            content,
-                            success: true,
-                            error: None,
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Parallel request index {} failed: {}", index, e);
-                        LlmManagerResponse {
-                            content: String::new(),
-                            success: false,
-                            error: Some(e.to_string()),
-                        }
-                    }
+            .map(|(index, request)| {
+                let semaphore = semaphore.clone();
+                async move {
+                    let request = self.internal_request(request);
+                    let response = self.run_batch_request(index, request, semaphore.as_deref(), None).await;
+                    (index, response)
                 }
             })
+            .collect::<FuturesUnordered<_>>()
+    }
+
+    /// Shared implementation of `batch_generate` and `batch_generate_with_cancel`
+    async fn run_batch(
+        &self,
+        requests: Vec<GenerationRequest>,
+        token: Option<&CancellationToken>,
+    ) -> Vec<LlmManagerResponse> {
+        info!("Entering batch_generate with {} requests", requests.len());
+        let internal_requests = requests
+            .into_iter()
+            .map(|request| self.internal_request(request))
+            .collect::<Vec<_>>();
+
+        let semaphore = self.max_concurrency.map(|limit| Semaphore::new(limit.max(1)));
+        let semaphore = semaphore.as_ref();
+
+        let futures = internal_requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, request)| self.run_batch_request(index, request, semaphore, token))
             .collect::<Vec<_>>();
 
         let results = join_all(futures).await;
@@ -402,6 +1142,113 @@ impl LlmManager {
         results
     }
 
+    /// Run one request of a batch, waiting for a permit when concurrency is limited
+    async fn run_batch_request(
+        &self,
+        index: usize,
+        request: LlmManagerRequest,
+        semaphore: Option<&Semaphore>,
+        token: Option<&CancellationToken>,
+    ) -> LlmManagerResponse {
+        let request_id = request.request_id;
+        let run = async {
+            // Held until the request completes; the semaphore is never closed
+            let _permit = match semaphore {
+                Some(semaphore) => Some(semaphore.acquire().await.expect("batch semaphore closed")),
+                None => None,
+            };
+            info!("[request {}] Starting parallel request index: {}", request_id, index);
+            self.generate_response(request, None).await
+        };
+
+        let result = match token {
+            Some(token) => tokio::select! {
+                biased;
+                _ = token.cancelled() => Err((LlmError::Cancelled, 0)),
+                result = run => result,
+            },
+            None => run.await,
+        };
+
+        match result {
+            Ok((response, instance_id, attempts)) => {
+                info!("Parallel request index {} succeeded.", index);
+                self.success_response(response, instance_id, attempts, request_id).await
+            }
+            Err((e, attempts)) => {
+                warn!("Parallel request index {} failed: {}", index, self.loggable_error(&e));
+                Self::failure_response(&e, attempts, request_id)
+            }
+        }
+    }
+
+    /// Build the user-facing response for a request served by an instance
+    async fn success_response(
+        &self,
+        response: LlmResponse,
+        instance_id: usize,
+        attempts: usize,
+        request_id: u64,
+    ) -> LlmManagerResponse {
+        let (provider, configured_model) = match self.trackers.lock().await.get(&instance_id) {
+            Some(tracker) => (
+                Some(tracker.instance.get_name().to_string()),
+                Some(tracker.instance.get_model().to_string()),
+            ),
+            None => (None, None),
+        };
+        // Prefer the model the provider reported, which may be a more specific snapshot
+        let model = if response.model.is_empty() {
+            configured_model
+        } else {
+            Some(response.model)
+        };
+        // Providers only fill `choices` for several completions
+        let choices = if response.choices.is_empty() {
+            vec![response.content.clone()]
+        } else {
+            response.choices
+        };
+        LlmManagerResponse {
+            content: response.content,
+            success: true,
+            error: None,
+            tool_calls: response.tool_calls,
+            provider,
+            model,
+            instance_id: Some(instance_id),
+            attempts,
+            usage: response.usage,
+            citations: response.citations,
+            finish_reason: response.finish_reason,
+            request_id,
+            choices,
+            logprobs: response.logprobs,
+            metadata: response.metadata,
+        }
+    }
+
+    /// Build the user-facing response for a request that failed on every attempt
+    fn failure_response(error: &LlmError, attempts: usize, request_id: u64) -> LlmManagerResponse {
+        LlmManagerResponse {
+            content: String::new(),
+            success: false,
+            error: Some(error.to_string()),
+            tool_calls: Vec::new(),
+            provider: None,
+            model: None,
+            instance_id: None,
+            attempts,
+            usage: None,
+            citations: Vec::new(),
+            finish_reason: None,
+            request_id,
+            choices: Vec::new(),
+            logprobs: None,
+            metadata: HashMap::new(),
+        }
+    }
+
     /// Generate a streaming response for a single request
     ///
     /// This method selects an appropriate provider instance and returns a stream
@@ -414,59 +1261,103 @@ impl LlmManager {
     /// # Returns
     /// * Result with either a stream of chunks or an error
     pub async fn generate_stream(&self, request: GenerationRequest) -> LlmResult<LlmStream> {
-        info!("generate_stream called for task: {:?}", request.task);
+        let internal_request = self.internal_request(request);
+        let request_id = internal_request.request_id;
+        info!("[request {}] generate_stream called for task: {:?}", request_id, internal_request.task);
 
-        let internal_request = LlmManagerRequest::from_generation_request(request);
-        let task = internal_request.task.as_deref();
-        let request_params = internal_request.params.clone();
+        self.check_budget().await?;
 
-        // Select an instance (similar logic to instance_selection but simplified for streaming)
-        let (selected_instance, selected_id, task_def) = self.select_streaming_instance(task).await?;
-
-        // Merge parameters
-        let mut final_params = HashMap::new();
-        if let Some(task_def) = task_def {
-            final_params.extend(task_def.parameters.clone());
-        }
-        if let Some(req_params) = request_params {
-            final_params.extend(req_params);
-        }
-
-        let max_tokens = final_params
-            .get("max_tokens")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
+        let excluded = if self.reject_oversized_prompts {
+            self.oversized_instances(&internal_request, &[]).await.inspect_err(|error| {
+                warn!("[request {}] Request refused for task {:?}: {}", request_id, internal_request.task, error);
+            })?
+        } else {
+            Vec::new()
+        };
 
-        let temperature = final_params
-            .get("temperature")
-            .and_then(|v| v.as_f64())
-            .map(|v| v as f32);
+        // Select an instance (similar logic to instance_selection but simplified for streaming)
+        let (selected_instance, selected_id, task_def) =
+            self.select_streaming_instance(&internal_request, &excluded).await?;
 
-        let llm_request = LlmRequest {
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: internal_request.prompt,
-            }],
-            model: None,
-            max_tokens,
-            temperature,
-        };
+        let llm_request = self.provider_request(&internal_request, task_def.as_ref(), selected_instance.as_ref())?;
+        Self::check_streamable(&llm_request)?;
+        let stop_sequences = llm_request.stop.clone().unwrap_or_default();
 
-        debug!("Instance {} starting streaming request", selected_id);
+        debug!("[request {}] Instance {} starting streaming request", request_id, selected_id);
 
         // Check if the selected instance supports streaming
         if !selected_instance.supports_streaming() {
-            warn!("Instance {} does not support native streaming, falling back to non-streaming", selected_id);
+            warn!("[request {}] Instance {} does not support native streaming, falling back to non-streaming", request_id, selected_id);
+        }
+
+        let in_flight = self.trackers.lock().await.get(&selected_id).map(InstanceTracker::start_request);
+        let record = StreamRecord::start(
+            selected_id,
+            self.trackers.clone(),
+            self.total_usage.clone(),
+            in_flight,
+            selected_instance.get_name(),
+            selected_instance.get_model(),
+            internal_request.task.as_deref(),
+        )
+        .with_cost(
+            self.total_cost.clone(),
+            self.pricing_for(selected_instance.get_name(), selected_instance.get_model()),
+        );
+
+        match selected_instance.generate_stream(&llm_request).await {
+            Ok(stream) => {
+                // Enforce stop sequences even when the provider streams past them
+                let stream: LlmStream = if stop_sequences.is_empty() {
+                    stream
+                } else {
+                    let model = llm_request.model.as_deref().unwrap_or(selected_instance.get_model());
+                    let prompt_tokens = tokens::count_tokens(&internal_request.prompt, model);
+                    Box::pin(StopSequenceStream::new(stream, stop_sequences).with_usage_estimate(prompt_tokens, model))
+                };
+                Ok(Box::pin(RecordingStream::new(stream, record)))
+            }
+            Err(e) => {
+                record.record(false, None, Some(&e)).await;
+                Err(e)
+            }
         }
+    }
 
-        selected_instance.generate_stream(&llm_request).await
+    /// Generate a streaming response that stops when the token is cancelled
+    ///
+    /// Cancelling before the stream starts returns `LlmError::Cancelled`. Cancelling
+    /// afterwards ends the stream: the provider stream is dropped, which aborts its
+    /// HTTP request (reqwest cancels requests on drop).
+    ///
+    /// # Parameters
+    /// * `request` - The generation request to process
+    /// * `token` - Token that cancels the request
+    ///
+    /// # Returns
+    /// * Result with either a stream of chunks or an error
+    pub async fn generate_stream_with_cancel(
+        &self,
+        request: GenerationRequest,
+        token: CancellationToken,
+    ) -> LlmResult<LlmStream> {
+        let stream = tokio::select! {
+            biased;
+            _ = token.cancelled() => return Err(LlmError::Cancelled),
+            stream = self.generate_stream(request) => stream?,
+        };
+        Ok(Box::pin(CancellableStream::new(stream, token)))
     }
 
     /// Select an instance for streaming (simpler than regular selection, no retries)
+    ///
+    /// Instances in `excluded` are never selected.
     async fn select_streaming_instance(
         &self,
-        task: Option<&str>,
+        request: &LlmManagerRequest,
+        excluded: &[usize],
     ) -> LlmResult<(Arc<dyn LlmInstance + Send + Sync>, usize, Option<TaskDefinition>)> {
+        let task = request.task.as_deref();
         // Get candidate instance IDs based on task
         let candidate_ids: Option<Vec<usize>> = match task {
             Some(task_name) => {
@@ -484,7 +1375,7 @@ impl LlmManager {
         }
 
         // Get eligible instances
-        let eligible_instances_data: Vec<(usize, Arc<dyn LlmInstance + Send + Sync>, Option<TaskDefinition>)>;
+        let mut eligible_instances_data: Vec<(usize, Arc<dyn LlmInstance + Send + Sync>, Option<TaskDefinition>)>;
         let eligible_instance_ids: Vec<usize>;
 
         {
@@ -499,7 +1390,10 @@ impl LlmManager {
                     eligible_instances_data = trackers_guard
                         .iter()
                         .filter(|(id, tracker)| {
-                            ids.contains(id) && tracker.is_enabled()
+                            ids.contains(id)
+                                && !excluded.contains(id)
+                                && tracker.is_available()
+                                && request.matches_pin(**id, tracker.instance.get_name())
                         })
                         .map(|(id, tracker)| {
                             let task_def = task
@@ -511,7 +1405,11 @@ impl LlmManager {
                 None => {
                     eligible_instances_data = trackers_guard
                         .iter()
-                        .filter(|(_, tracker)| tracker.is_enabled())
+                        .filter(|(id, tracker)| {
+                            !excluded.contains(id)
+                                && tracker.is_available()
+                                && request.matches_pin(**id, tracker.instance.get_name())
+                        })
                         .map(|(id, tracker)| {
                             let task_def = task
                                 .and_then(|t| tracker.instance.get_supported_tasks().get(t).cloned());
@@ -522,26 +1420,40 @@ impl LlmManager {
             }
 
             if eligible_instances_data.is_empty() {
+                if let Some(error) = request.pin_error() {
+                    return Err(error);
+                }
                 return Err(LlmError::ConfigError(format!(
                     "No enabled providers available{}",
                     task.map_or_else(|| "".to_string(), |t| format!(" for task: '{}'", t))
                 )));
             }
 
+            // Trackers iterate in hash order; sort so strategies see a reproducible order
+            eligible_instances_data.sort_unstable_by_key(|(id, _, _)| *id);
             eligible_instance_ids = eligible_instances_data.iter().map(|(id, _, _)| *id).collect();
         }
 
         // Select using strategy
         let selected_id = {
             let trackers_guard = self.trackers.lock().await;
-            let mut strategy = self.strategy.lock().await;
+            let mut default_strategy = self.strategy.lock().await;
+            let mut task_strategies = self.task_strategies.lock().await;
+            let strategy = Self::strategy_for(task, &mut default_strategy, &mut task_strategies);
 
             let eligible_trackers: Vec<(usize, &InstanceTracker)> = eligible_instance_ids
                 .iter()
                 .filter_map(|id| trackers_guard.get(id).map(|tracker| (*id, tracker)))
                 .collect();
 
-            let selected_index = strategy.select_instance(&eligible_trackers);
+            // Instances may have been removed since the eligibility check
+            if eligible_trackers.is_empty() {
+                return Err(LlmError::ConfigError(
+                    "No enabled providers available (instances removed during selection)".to_string(),
+                ));
+            }
+
+            let selected_index = strategy.select_instance_for_session(&eligible_trackers, request.session_key.as_deref());
             eligible_trackers[selected_index].0
         };
 
@@ -554,6 +1466,173 @@ impl LlmManager {
         Ok((selected.1, selected.0, selected.2))
     }
 
+    /// Generate embeddings for the given inputs
+    ///
+    /// Routes through the same strategy as text generation, but only considers instances
+    /// whose provider supports embeddings. Instances that fail are excluded from later attempts.
+    ///
+    /// # Parameters
+    /// * `task` - Optional task name used to filter instances
+    /// * `inputs` - Texts to embed
+    ///
+    /// # Returns
+    /// * One embedding vector per input, in input order
+    pub async fn embed(&self, task: Option<&str>, inputs: Vec<String>) -> LlmResult<Vec<Vec<f32>>> {
+        let mut failed_instances: Vec<usize> = Vec::new();
+        let mut instance_failures: HashMap<usize, usize> = HashMap::new();
+        let mut pinned_instance: Option<usize> = None;
+        let mut last_error: Option<LlmError> = None;
+        let mut attempts = 0;
+
+        while attempts <= self.max_retries {
+            let excluded = match pinned_instance {
+                Some(instance_id) => self.exclude_all_but(instance_id).await,
+                None => failed_instances.clone(),
+            };
+            let (instance, instance_id, in_flight_guard) =
+                match self.select_embedding_instance(task, &excluded).await {
+                    Ok(selected) => selected,
+                    Err(e) => return Err(last_error.unwrap_or(e)),
+                };
+            let embedder = instance
+                .as_embedding()
+                .expect("Selected embedding instance does not support embeddings - LOGIC ERROR!");
+
+            debug!(
+                "Instance {} ({}) embedding {} inputs",
+                instance_id,
+                instance.get_name(),
+                inputs.len()
+            );
+            let start_time = Instant::now();
+            let result = embedder.embed_with_usage(&inputs).await;
+            let duration = start_time.elapsed();
+            drop(in_flight_guard);
+
+            let tracker_present = {
+                let mut trackers_guard = self.trackers.lock().await;
+                match trackers_guard.get_mut(&instance_id) {
+                    Some(tracker) => {
+                        tracker.record_outcome(duration, result.is_ok());
+                        true
+                    }
+                    None => false,
+                }
+            };
+
+            #[cfg(feature = "metrics")]
+            match &result {
+                Ok(response) => crate::metrics::record_request_success(
+                    instance.get_name(),
+                    instance.get_model(),
+                    task,
+                    instance_id,
+                    duration,
+                    response.usage.as_ref(),
+                ),
+                Err(e) => crate::metrics::record_request_failure(
+                    instance.get_name(),
+                    instance.get_model(),
+                    task,
+                    instance_id,
+                    e,
+                    duration,
+                ),
+            }
+
+            match result {
+                Ok(response) => {
+                    if let (Some(usage), true) = (&response.usage, tracker_present) {
+                        self.update_instance_usage(instance_id, usage).await;
+                    }
+                    info!(
+                        "Embedded {} inputs with instance {} in {:?}",
+                        inputs.len(),
+                        instance_id,
+                        duration
+                    );
+                    return Ok(response.embeddings);
+                }
+                Err(e) => {
+                    warn!(
+                        "Embedding attempt {} failed with instance {}: {}",
+                        attempts + 1,
+                        instance_id,
+                        self.loggable_error(&e)
+                    );
+                    if e.is_retriable() {
+                        let wait_time = self.retriable_delay(&e, attempts);
+                        tokio::time::sleep(wait_time).await;
+                    } else if self.retry_in_place(instance_id, &e, &mut instance_failures).await {
+                        pinned_instance = Some(instance_id);
+                        last_error = Some(e);
+                        continue;
+                    } else {
+                        pinned_instance = None;
+                        failed_instances.push(instance_id);
+                    }
+                    last_error = Some(e);
+                    attempts += 1;
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            LlmError::ConfigError("No embedding-capable providers available".to_string())
+        }))
+    }
+
+    /// Select an embedding-capable instance using the current strategy
+    async fn select_embedding_instance(
+        &self,
+        task: Option<&str>,
+        failed_instances: &[usize],
+    ) -> LlmResult<(Arc<dyn LlmInstance + Send + Sync>, usize, InFlightGuard)> {
+        let candidate_ids: Option<Vec<usize>> = match task {
+            Some(task_name) => {
+                let task_map = self.tasks_to_instances.lock().await;
+                match task_map.get(task_name) {
+                    Some(ids) => Some(ids.clone()),
+                    None => {
+                        return Err(LlmError::ConfigError(format!(
+                            "No providers available for task: {}",
+                            task_name
+                        )))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let trackers_guard = self.trackers.lock().await;
+        let mut default_strategy = self.strategy.lock().await;
+        let mut task_strategies = self.task_strategies.lock().await;
+        let strategy = Self::strategy_for(task, &mut default_strategy, &mut task_strategies);
+
+        let mut eligible_trackers: Vec<(usize, &InstanceTracker)> = trackers_guard
+            .iter()
+            .filter(|(id, tracker)| {
+                candidate_ids.as_ref().is_none_or(|ids| ids.contains(id))
+                    && tracker.is_available()
+                    && tracker.instance.as_embedding().is_some()
+                    && !failed_instances.contains(id)
+            })
+            .map(|(id, tracker)| (*id, tracker))
+            .collect();
+        eligible_trackers.sort_unstable_by_key(|(id, _)| *id);
+
+        if eligible_trackers.is_empty() {
+            return Err(LlmError::ConfigError(format!(
+                "No embedding-capable providers available{}",
+                task.map_or_else(|| "".to_string(), |t| format!(" for task: '{}'", t))
+            )));
+        }
+
+        let selected_index = strategy.select_instance(&eligible_trackers);
+        let (selected_id, selected_tracker) = eligible_trackers[selected_index];
+        Ok((selected_tracker.instance.clone(), selected_id, selected_tracker.start_request()))
+    }
+
     /// Core function to generate a response with retries
     ///
     /// # Parameters
@@ -561,74 +1640,155 @@ impl LlmManager {
     /// * `max_attempts` - Optional override for maximum retry attempts
     ///
     /// # Returns
-    /// * Result with either the generated content or an error
+    /// * Success: (provider response, ID of the instance that served it, attempts made)
+    /// * Error: (error of the last attempt, attempts made)
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "generate_response",
+        skip_all,
+        fields(request_id = request.request_id, task = request.task.as_deref(), attempts = Empty, instance_id = Empty, latency_ms = Empty),
+    ))]
     async fn generate_response(
         &self,
         request: LlmManagerRequest,
         max_attempts: Option<usize>,
-    ) -> LlmResult<String> {
+    ) -> Result<(LlmResponse, usize, usize), (LlmError, usize)> {
         let start_time = Instant::now();
+        let request_id = request.request_id;
+        let task = request.task.clone();
+        let result = self.run_attempts(request, max_attempts, start_time).await;
+
+        if let Some(hook) = &self.on_request_complete {
+            let (instance_id, attempts, error) = match &result {
+                Ok((_, instance_id, attempts)) => (Some(*instance_id), *attempts, None),
+                Err((error, attempts)) => (None, *attempts, Some(error)),
+            };
+            hook(RequestCompleteEvent {
+                request_id,
+                task: task.as_deref(),
+                instance_id,
+                attempts,
+                duration: start_time.elapsed(),
+                error,
+            });
+        }
+        result
+    }
+
+    /// Retry loop of `generate_response`, trying instances until one succeeds or attempts run out
+    async fn run_attempts(
+        &self,
+        request: LlmManagerRequest,
+        max_attempts: Option<usize>,
+        start_time: Instant,
+    ) -> Result<(LlmResponse, usize, usize), (LlmError, usize)> {
         let mut attempts = request.attempts;
         let mut failed_instances = request.failed_instances.clone();
+        let mut instance_failures: HashMap<usize, usize> = HashMap::new();
+        let mut pinned_instance: Option<usize> = None;
+        let mut tries = 0;
         let prompt_preview = request.prompt.chars().take(50).collect::<String>();
         let task = request.task.as_deref();
-        let request_params = request.params.clone();
         let max_retries = max_attempts.unwrap_or(self.max_retries);
+        let request_id = request.request_id;
 
         info!(
-            "generate_response called for task: {:?}, prompt: '{}...'",
+            "[request {}] generate_response called for task: {:?}, prompt: '{}...'", request_id,
             task, prompt_preview
         );
 
         while attempts <= max_retries {
+            tries += 1;
+            record_span!("attempts" = tries);
             debug!(
-                "Attempt {} of {} for request (task: {:?})",
+                "[request {}] Attempt {} of {} for request (task: {:?})", request_id,
                 attempts + 1,
                 max_retries + 1,
                 task
             );
 
+            // While retrying an instance in place, exclude every other instance
+            let mut excluded = match pinned_instance {
+                Some(instance_id) => self.exclude_all_but(instance_id).await,
+                None => failed_instances.clone(),
+            };
+            if self.reject_oversized_prompts {
+                match self.oversized_instances(&request, &excluded).await {
+                    Ok(oversized) => excluded.extend(oversized),
+                    Err(error) => {
+                        warn!("[request {}] Request refused for task {:?}: {}", request_id, task, error);
+                        return Err((error, tries));
+                    }
+                }
+            }
             let attempt_result = self
-                .instance_selection(
-                    &request.prompt,
-                    task,
-                    request_params.clone(),
-                    &failed_instances,
-                )
+                .instance_selection(&request, &excluded, tries)
                 .await;
 
             match attempt_result {
-                Ok((content, instance_id)) => {
+                Ok((response, instance_id)) => {
                     let duration = start_time.elapsed();
+                    record_span!(
+                        "instance_id" = instance_id,
+                        "latency_ms" = duration.as_secs_f64() * 1000.0,
+                    );
                     info!(
-                        "Request successful on attempt {} with instance {} after {:?}",
+                        "[request {}] Request successful on attempt {} with instance {} after {:?}", request_id,
                         attempts + 1,
                         instance_id,
                         duration
                     );
-                    return Ok(content);
+                    return Ok((response, instance_id, tries));
                 }
-                Err((error, instance_id)) => {
-                    warn!(
-                        "Attempt {} failed with instance {}: {}",
-                        attempts + 1,
-                        instance_id,
-                        error
-                    );
+                Err((error, _)) if matches!(error, LlmError::BudgetExceeded(_)) => {
+                    warn!("[request {}] Request refused for task {:?}: {}", request_id, task, self.loggable_error(&error));
+                    return Err((error, tries));
+                }
+                Err((error, selected)) => {
+                    match selected {
+                        Some(instance_id) => warn!(
+                            "[request {}] Attempt {} failed with instance {}: {}", request_id,
+                            attempts + 1,
+                            instance_id,
+                            self.loggable_error(&error)
+                        ),
+                        None => warn!(
+                            "[request {}] Attempt {} failed before selecting an instance: {}", request_id,
+                            attempts + 1,
+                            self.loggable_error(&error)
+                        ),
+                    }
 
-                    // Check if this is a rate limit error
-                    if matches!(error, LlmError::RateLimit(_)) {
-                        warn!(
-                            "Rate limit detected for instance {}. Waiting before retry...",
-                            instance_id
-                        );
+                    let retry_here = match selected {
+                        Some(instance_id) if !error.is_retriable() => {
+                            self.retry_in_place(instance_id, &error, &mut instance_failures).await
+                        }
+                        _ => false,
+                    };
+
+                    // Check if this is a rate limit or a model that is still loading
+                    if let (Some(instance_id), true) = (selected, error.is_retriable()) {
+                        if matches!(error, LlmError::ModelLoading { .. }) {
+                            warn!(
+                                "[request {}] Model still loading on instance {}. Waiting before retrying it...", request_id,
+                                instance_id
+                            );
+                            // The model will be ready on this instance, so don't fall back to another one
+                            pinned_instance = Some(instance_id);
+                        } else {
+                            warn!(
+                                "[request {}] Rate limit detected for instance {}. Waiting before retry...", request_id,
+                                instance_id
+                            );
+                        }
 
-                        // Wait before retrying (exponential backoff)
-                        let wait_time =
-                            std::time::Duration::from_secs(2_u64.pow(attempts as u32).min(60));
+                        // Wait as long as the provider asked, or back off per the retry policy
+                        let wait_time = self.retriable_delay(&error, attempts);
+                        if self.exceeds_retry_duration(start_time, wait_time) {
+                            return Err((error, tries));
+                        }
                         tokio::time::sleep(wait_time).await;
 
-                        // Don't mark this instance as failed for rate limits
+                        // Don't mark this instance as failed for rate limits or loading models
                         // Just increment attempts and try again
                         attempts += 1;
 
@@ -640,16 +1800,55 @@ impl LlmManager {
                                 crate::metrics::record_retry(tracker.instance.get_name());
                             }
                         }
+                    } else if let (Some(instance_id), true) = (selected, retry_here) {
+                        // The instance has its own max_retries left: try it again before moving on
+                        pinned_instance = Some(instance_id);
+                        let wait_time = if self.retry_policy.backoff_on_errors {
+                            self.retry_policy.delay_for(instance_failures[&instance_id] - 1)
+                        } else {
+                            Duration::ZERO
+                        };
+                        if self.exceeds_retry_duration(start_time, wait_time) {
+                            return Err((error, tries));
+                        }
+                        if !wait_time.is_zero() {
+                            debug!("[request {}] Waiting {:?} before retrying instance {}", request_id, wait_time, instance_id);
+                            tokio::time::sleep(wait_time).await;
+                        }
+
+                        // Record retry metric
+                        #[cfg(feature = "metrics")]
+                        {
+                            let trackers_guard = self.trackers.lock().await;
+                            if let Some(tracker) = trackers_guard.get(&instance_id) {
+                                crate::metrics::record_retry(tracker.instance.get_name());
+                            }
+                        }
+                        self.notify_retry(request_id, selected, &error, tries, task);
+                        continue;
                     } else {
                         // For non-rate-limit errors, mark instance as failed
-                        failed_instances.push(instance_id);
+                        pinned_instance = None;
+                        failed_instances.extend(selected);
+                        let wait_time = if self.retry_policy.backoff_on_errors && attempts < max_retries {
+                            self.retry_policy.delay_for(attempts)
+                        } else {
+                            Duration::ZERO
+                        };
+                        if self.exceeds_retry_duration(start_time, wait_time) {
+                            return Err((error, tries));
+                        }
+                        if !wait_time.is_zero() {
+                            debug!("[request {}] Waiting {:?} before retrying after error", request_id, wait_time);
+                            tokio::time::sleep(wait_time).await;
+                        }
                         attempts += 1;
 
                         // Record retry metric
                         #[cfg(feature = "metrics")]
                         {
                             let trackers_guard = self.trackers.lock().await;
-                            if let Some(tracker) = trackers_guard.get(&instance_id) {
+                            if let Some(tracker) = selected.and_then(|id| trackers_guard.get(&id)) {
                                 crate::metrics::record_retry(tracker.instance.get_name());
                             }
                         }
@@ -657,24 +1856,26 @@ impl LlmManager {
 
                     if attempts > max_retries {
                         warn!(
-                            "Max retries ({}) reached for task: {:?}. Returning last error.",
+                            "[request {}] Max retries ({}) reached for task: {:?}. Returning last error.", request_id,
                             max_retries + 1,
                             task
                         );
-                        return Err(error);
+                        return Err((error, tries));
                     }
+                    self.notify_retry(request_id, selected, &error, tries, task);
 
                     debug!(
-                        "Retrying with next eligible instance for task: {:?}...",
+                        "[request {}] Retrying with next eligible instance for task: {:?}...", request_id,
                         task
                     );
                 }
             }
         }
 
-        warn!("Exited retry loop unexpectedly for task: {:?}", task);
-        Err(LlmError::ConfigError(
-            "No available providers after all retry attempts".to_string(),
+        warn!("[request {}] Exited retry loop unexpectedly for task: {:?}", request_id, task);
+        Err((
+            LlmError::ConfigError("No available providers after all retry attempts".to_string()),
+            tries,
         ))
     }
 
@@ -689,26 +1890,37 @@ impl LlmManager {
     /// 6. Updates metrics based on the result
     ///
     /// # Parameters
-    /// * `prompt` - The prompt text to send
-    /// * `task` - Optional task identifier
-    /// * `request_params` - Optional request parameters
+    /// * `manager_request` - The request being processed (prompt, task, params, tools, images)
     /// * `failed_instances` - List of instance IDs that have failed
+    /// * `attempt` - Number of this attempt within the request, starting at 1
     ///
     /// # Returns
-    /// * Success: (generated content, instance ID)
-    /// * Error: (error, instance ID that failed)
+    /// * Success: (provider response, instance ID)
+    /// * Error: (error, ID of the instance that failed, or `None` if none was selected)
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "instance_selection",
+        skip_all,
+        fields(task = manager_request.task.as_deref(), attempt = attempt, instance_id = Empty, provider = Empty, latency_ms = Empty),
+    ))]
     async fn instance_selection(
         &self,
-        prompt: &str,
-        task: Option<&str>,
-        request_params: Option<HashMap<String, serde_json::Value>>,
+        manager_request: &LlmManagerRequest,
         failed_instances: &[usize],
-    ) -> Result<(String, usize), (LlmError, usize)> {
+        attempt: usize,
+    ) -> Result<(LlmResponse, usize), (LlmError, Option<usize>)> {
+        let task = manager_request.task.as_deref();
+        let request_id = manager_request.request_id;
+
         debug!(
-            "instance_selection: Starting selection for task: {:?}",
-            task
+            "[request {}] instance_selection: Starting selection for task: {:?} (attempt {})", request_id,
+            task, attempt
         );
 
+        // 0. Refuse to send anything once the budget is spent
+        if let Err(e) = self.check_budget().await {
+            return Err((e, None));
+        }
+
         // 1. Get candidate instance IDs based on task (if any)
         let candidate_ids: Option<Vec<usize>> = match task {
             Some(task_name) => {
@@ -719,19 +1931,19 @@ impl LlmManager {
         };
 
         if task.is_some() && candidate_ids.is_none() {
-            warn!("No instances found supporting task: '{}'", task.unwrap());
-            debug!("instance_selection returning Err (no task support)");
+            warn!("[request {}] No instances found supporting task: '{}'", request_id, task.unwrap());
+            debug!("[request {}] instance_selection returning Err (no task support)", request_id);
             return Err((
                 LlmError::ConfigError(format!(
                     "No providers available for task: {}",
                     task.unwrap()
                 )),
-                0,
+                None,
             ));
         }
 
         // 2. Filter candidates by availability and collect all needed data in one go
-        let eligible_instances_data: Vec<(
+        let mut eligible_instances_data: Vec<(
             usize,
             String,
             Arc<dyn LlmInstance + Send + Sync>,
@@ -744,13 +1956,13 @@ impl LlmManager {
         // Scope the lock to ensure it's dropped before strategy selection
         {
             let trackers_guard = self.trackers.lock().await;
-            debug!("instance_selection: Acquired trackers lock (1st time)");
+            debug!("[request {}] instance_selection: Acquired trackers lock (1st time)", request_id);
 
             if trackers_guard.is_empty() {
-                warn!("No LLM providers configured.");
+                warn!("[request {}] No LLM providers configured.", request_id);
                 return Err((
                     LlmError::ConfigError("No LLM providers available".to_string()),
-                    0,
+                    None,
                 ));
             }
 
@@ -758,14 +1970,17 @@ impl LlmManager {
             match candidate_ids {
                 Some(ids) => {
                     debug!(
-                        "Filtering instances for task '{}' using IDs: {:?}",
+                        "[request {}] Filtering instances for task '{}' using IDs: {:?}", request_id,
                         task.unwrap(),
                         ids
                     );
                     eligible_instances_data = trackers_guard
                         .iter()
                         .filter(|(id, tracker)| {
-                            ids.contains(id) && tracker.is_enabled() && !failed_instances.contains(id)
+                            ids.contains(id)
+                                && tracker.is_available()
+                                && !failed_instances.contains(id)
+                                && manager_request.matches_pin(**id, tracker.instance.get_name())
                         })
                         .map(|(id, tracker)| {
                             let task_def = task
@@ -779,16 +1994,20 @@ impl LlmManager {
                         })
                         .collect();
                     debug!(
-                        "Found {} eligible instances for task '{}'",
+                        "[request {}] Found {} eligible instances for task '{}'", request_id,
                         eligible_instances_data.len(),
                         task.unwrap()
                     );
                 }
                 None => {
-                    debug!("No specific task. Filtering all enabled instances.");
+                    debug!("[request {}] No specific task. Filtering all enabled instances.", request_id);
                     eligible_instances_data = trackers_guard
                         .iter()
-                        .filter(|(id, tracker)| tracker.is_enabled() && !failed_instances.contains(id))
+                        .filter(|(id, tracker)| {
+                            tracker.is_available()
+                                && !failed_instances.contains(id)
+                                && manager_request.matches_pin(**id, tracker.instance.get_name())
+                        })
                         .map(|(id, tracker)| {
                             let task_def = task
                                 .and_then(|t| tracker.instance.get_supported_tasks().get(t).cloned());
@@ -801,12 +2020,15 @@ impl LlmManager {
                         })
                         .collect();
                     debug!(
-                        "Found {} eligible instances (no task)",
+                        "[request {}] Found {} eligible instances (no task)", request_id,
                         eligible_instances_data.len()
                     );
                 }
             }
 
+            // Trackers iterate in hash order; sort so strategies see a reproducible order
+            eligible_instances_data.sort_unstable_by_key(|(id, _, _, _)| *id);
+
             // Extract just the IDs for strategy selection
             eligible_instance_ids = eligible_instances_data
                 .iter()
@@ -815,6 +2037,10 @@ impl LlmManager {
 
             // No eligible instances check
             if eligible_instances_data.is_empty() {
+                if let Some(error) = manager_request.pin_error() {
+                    warn!("[request {}] {}", request_id, error);
+                    return Err((error, None));
+                }
                 let error_msg = format!(
                     "No enabled providers available{}{}",
                     task.map_or_else(|| "".to_string(), |t| format!(" for task: '{}'", t)),
@@ -824,16 +2050,18 @@ impl LlmManager {
                         "".to_string()
                     }
                 );
-                warn!("{}", error_msg);
-                return Err((LlmError::ConfigError(error_msg), 0));
+                warn!("[request {}] {}", request_id, error_msg);
+                return Err((LlmError::ConfigError(error_msg), None));
             }
         } 
 
         // 5. Select instance using strategy (need to re-acquire lock for metrics)
-        let selected_instance_id = {
+        let (selected_instance_id, in_flight_guard) = {
             let trackers_guard = self.trackers.lock().await;
-            let mut strategy = self.strategy.lock().await;
-            debug!("instance_selection: Acquired strategy and trackers locks");
+            let mut default_strategy = self.strategy.lock().await;
+            let mut task_strategies = self.task_strategies.lock().await;
+            let strategy = Self::strategy_for(manager_request.task.as_deref(), &mut default_strategy, &mut task_strategies);
+            debug!("[request {}] instance_selection: Acquired strategy and trackers locks", request_id);
             
             // Build the trackers slice for the strategy
             let eligible_trackers: Vec<(usize, &InstanceTracker)> = eligible_instance_ids
@@ -843,11 +2071,27 @@ impl LlmManager {
                 })
                 .collect();
 
-            let selected_metric_index = strategy.select_instance(&eligible_trackers);
-            let selected_id = eligible_trackers[selected_metric_index].0;
+            // Instances may have been removed since the eligibility check
+            if eligible_trackers.is_empty() {
+                warn!("[request {}] All eligible instances were removed during selection", request_id);
+                return Err((
+                    LlmError::ConfigError(
+                        "No enabled providers available (instances removed during selection)".to_string(),
+                    ),
+                    None,
+                ));
+            }
+
+            let selected_metric_index =
+                strategy.select_instance_for_session(&eligible_trackers, manager_request.session_key.as_deref());
+            let (selected_id, selected_tracker) = eligible_trackers[selected_metric_index];
+
+            // Count the request as in-flight while still holding the lock so concurrent
+            // selections see the updated load
+            let guard = selected_tracker.start_request();
             
-            debug!("instance_selection: Released strategy lock");
-            selected_id
+            debug!("[request {}] instance_selection: Released strategy lock", request_id);
+            (selected_id, guard)
         }; 
 
         // Find the corresponding instance in our extracted data
@@ -865,88 +2109,81 @@ impl LlmManager {
         );
 
         debug!(
-            "Selected instance {} ({}) for the request.",
+            "[request {}] Selected instance {} ({}) for the request.", request_id,
             selected_id, selected_name
         );
+        record_span!("instance_id" = selected_id, "provider" = selected_name.as_str());
+
+        // 6. Merge parameters and build the provider request
+        let request = match self.provider_request(manager_request, task_def.as_ref(), selected_provider_arc.as_ref()) {
+            Ok(request) => request,
+            Err(error) => {
+                warn!("[request {}] Instance {} ({}) refused: {}", request_id, selected_id, selected_name, error);
+                return Err((error, Some(selected_id)));
+            }
+        };
+        let final_params = &request.params;
 
-        // 6. Merge parameters
-        let mut final_params = HashMap::new();
-        if let Some(task_def) = task_def {
-            final_params.extend(task_def.parameters.clone());
-            debug!("Applied parameters from task for instance {}", selected_id);
-        }
+        debug!(
+            "[request {}] Instance {} ({}) sending request to provider...", request_id,
+            selected_id, selected_name
+        );
+        // Serve deterministic requests from the cache when it is enabled
+        let cache_key = match &self.cache {
+            Some(cache) if ResponseCache::is_cacheable(&request) => {
+                let key = ResponseCache::key(selected_provider_arc.get_model(), task, final_params, &request);
+                if let Some(cached) = cache.get(key).await {
+                    drop(in_flight_guard);
+                    info!("[request {}] Cache hit for instance {} ({})", request_id, selected_id, selected_name);
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::record_cache_hit(selected_name, selected_provider_arc.get_model());
+                    return Ok((cached, selected_id));
+                }
+                Some(key)
+            }
+            _ => None,
+        };
 
-        if let Some(req_params) = request_params {
-            final_params.extend(req_params);
-            debug!(
-                "Applied request-specific parameters for instance {}",
-                selected_id
-            );
-        }
-
-        // Create and execute the request
-        let max_tokens = final_params
-            .get("max_tokens")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
-
-        let temperature = final_params
-            .get("temperature")
-            .and_then(|v| v.as_f64())
-            .map(|v| v as f32);
-
-        let request = LlmRequest {
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            }],
-            model: None, // Let provider use its configured model
-            max_tokens,
-            temperature,
-        };
-
-        debug!(
-            "Instance {} ({}) sending request to provider...",
-            selected_id, selected_name
-        );
         let start_time = Instant::now();
         let result = selected_provider_arc.generate(&request).await;
         let duration = start_time.elapsed();
+        drop(in_flight_guard);
+        record_span!("latency_ms" = duration.as_secs_f64() * 1000.0);
         info!(
-            "Instance {} ({}) received result in {:?}",
+            "[request {}] Instance {} ({}) received result in {:?}", request_id,
             selected_id, selected_name, duration
         );
 
         // Update metrics regardless of success or failure
-        {
-            debug!("instance_selection: Attempting to acquire trackers lock (2nd time) for metrics update");
+        let tracker_present = {
+            debug!("[request {}] instance_selection: Attempting to acquire trackers lock (2nd time) for metrics update", request_id);
             let mut trackers_guard = self.trackers.lock().await;
-            debug!("instance_selection: Acquired trackers lock (2nd time)");
+            debug!("[request {}] instance_selection: Acquired trackers lock (2nd time)", request_id);
             if let Some((_id, instance_tracker)) = trackers_guard
                 .iter_mut()
                 .find(|(id, _tracker)| **id == selected_id)
             {
-                debug!("Recording result for instance {}", selected_id);
+                debug!("[request {}] Recording result for instance {}", request_id, selected_id);
                 instance_tracker.record_result(duration, &result);
-                debug!("Finished recording result for instance {}", selected_id);
+                debug!("[request {}] Finished recording result for instance {}", request_id, selected_id);
+                true
             } else {
                 warn!(
-                    "Instance {} not found for metric update after request completion.",
+                    "[request {}] Instance {} not found for metric update after request completion.", request_id,
                     selected_id
                 );
+                false
             }
-            debug!("instance_selection: Releasing trackers lock (2nd time) after metrics update");
             // Lock released when trackers_guard goes out of scope here
-        }
+        };
+        debug!("[request {}] instance_selection: Released trackers lock (2nd time) after metrics update", request_id);
 
         // Write debug information if debug folder is configured
         self.write_debug_info(
+            manager_request,
             selected_id,
-            selected_name,
-            &selected_provider_arc.get_model(),
-            prompt,
-            task,
-            &final_params,
+            selected_provider_arc.as_ref(),
+            &request,
             &result,
             duration,
         ).await;
@@ -961,6 +2198,7 @@ impl LlmManager {
                         selected_name,
                         &model,
                         task,
+                        selected_instance_id,
                         duration,
                         response.usage.as_ref(),
                     );
@@ -970,6 +2208,7 @@ impl LlmManager {
                         selected_name,
                         &model,
                         task,
+                        selected_instance_id,
                         e,
                         duration,
                     );
@@ -980,68 +2219,331 @@ impl LlmManager {
         // Return either content or error with the instance ID
         match result {
             Ok(response) => {
-                if let Some(usage) = &response.usage {
+                // Skip usage for instances removed mid-request so their entry isn't recreated
+                if let (Some(usage), true) = (&response.usage, tracker_present) {
                     self.update_instance_usage(selected_id, usage).await;
                     debug!(
-                        "Updated token usage for instance {}: {:?}",
+                        "[request {}] Updated token usage for instance {}: {:?}", request_id,
                         selected_id, usage
                     );
+                    self.update_instance_cost(
+                        selected_id,
+                        selected_name,
+                        selected_provider_arc.get_model(),
+                        usage,
+                    ).await;
+                }
+                if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+                    cache.insert(key, response.clone()).await;
                 }
                 debug!(
-                    "instance_selection returning Ok for instance {}",
+                    "[request {}] instance_selection returning Ok for instance {}", request_id,
                     selected_id
                 );
-                Ok((response.content, selected_id))
+                Ok((response, selected_id))
             }
             Err(e) => {
                 debug!(
-                    "instance_selection returning Err for instance {}: {}",
+                    "[request {}] instance_selection returning Err for instance {}: {}", request_id,
                     selected_id, e
                 );
-                Err((e, selected_id))
+                Err((e, Some(selected_id)))
             }
         }
     }
 
+    /// Build the request sent to an instance for a manager request
+    ///
+    /// Parameters are merged from the manager defaults, then the task's, then the request's,
+    /// and kept in `LlmRequest::params`.
+    ///
+    /// # Parameters
+    /// * `manager_request` - The request being processed
+    /// * `task_def` - The instance's definition of the request's task, if any
+    /// * `instance` - The instance the request is for
+    ///
+    /// # Returns
+    /// * The provider request, or an error if `check_params` refuses the parameters
+    fn provider_request(
+        &self,
+        manager_request: &LlmManagerRequest,
+        task_def: Option<&TaskDefinition>,
+        instance: &(dyn LlmInstance + Send + Sync),
+    ) -> LlmResult<LlmRequest> {
+        let request_id = manager_request.request_id;
+        let mut final_params = self.default_params.clone();
+        if let Some(task_def) = task_def {
+            final_params.extend(task_def.parameters.clone());
+            debug!("[request {}] Applied parameters from task for instance {}", request_id, instance.get_name());
+        }
+
+        if let Some(req_params) = &manager_request.params {
+            final_params.extend(req_params.clone());
+            debug!(
+                "[request {}] Applied request-specific parameters for instance {}", request_id,
+                instance.get_name()
+            );
+        }
+
+        self.check_params(&final_params, instance.get_name())?;
+
+        let max_tokens = final_params
+            .get("max_tokens")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+
+        let temperature = final_params
+            .get("temperature")
+            .and_then(|v| v.as_f64())
+            .map(|v| v as f32);
+
+        let seed = final_params.get("seed").and_then(|v| v.as_u64());
+
+        let n = final_params
+            .get("n")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+
+        let logprobs = final_params.get("logprobs").and_then(|v| v.as_bool());
+
+        let top_logprobs = final_params
+            .get("top_logprobs")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+
+        let (top_p, frequency_penalty, presence_penalty) = Self::sampling_params(&final_params);
+        let stop = Some(Self::stop_sequences(&final_params)).filter(|stop| !stop.is_empty());
+
+        let response_format = Self::resolve_response_format(&final_params, instance);
+        Self::check_model_override(manager_request, instance);
+
+        let documents = final_params
+            .get("documents")
+            .and_then(|v| v.as_array())
+            .cloned();
+
+        let request = LlmRequest {
+            messages: vec![Self::build_user_message(&manager_request.prompt, manager_request.images.as_deref())],
+            model: manager_request.model.clone(), // None lets the provider use its configured model
+            max_tokens,
+            temperature,
+            tools: manager_request.tools.clone(),
+            tool_choice: manager_request.tool_choice.clone(),
+            response_format,
+            seed,
+            n,
+            logprobs,
+            top_logprobs,
+            top_p,
+            frequency_penalty,
+            presence_penalty,
+            stop,
+            documents,
+            params: final_params,
+        };
+        Self::check_messages(&request.messages, instance)?;
+        Ok(request)
+    }
+
+    /// Refuse features a stream can't carry, instead of silently dropping them
+    ///
+    /// Stream chunks only hold text, so tool calls, extra choices and log probabilities
+    /// are only available from `generate`.
+    fn check_streamable(request: &LlmRequest) -> LlmResult<()> {
+        let unsupported = if request.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+            Some("tools")
+        } else if request.n.is_some_and(|n| n > 1) {
+            Some("n")
+        } else if request.logprobs == Some(true) {
+            Some("logprobs")
+        } else {
+            None
+        };
+        match unsupported {
+            Some(feature) => Err(LlmError::ConfigError(format!(
+                "'{}' can't be streamed; use generate instead",
+                feature
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// The `top_p`, `frequency_penalty` and `presence_penalty` parameters, in that order
+    fn sampling_params(params: &HashMap<String, Value>) -> (Option<f32>, Option<f32>, Option<f32>) {
+        let get = |key: &str| params.get(key).and_then(|v| v.as_f64()).map(|v| v as f32);
+        (get("top_p"), get("frequency_penalty"), get("presence_penalty"))
+    }
+
+    /// Stop sequences from the `stop` parameter, given as one string or a list of strings
+    fn stop_sequences(params: &HashMap<String, Value>) -> Vec<String> {
+        match params.get("stop") {
+            Some(Value::String(stop)) => vec![stop.clone()],
+            Some(Value::Array(stops)) => stops.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Refuse conversations the instance's provider would reject or rewrite (see `validate_messages`)
+    ///
+    /// Instances whose name isn't a built-in provider type only need a message.
+    fn check_messages(messages: &[Message], instance: &(dyn LlmInstance + Send + Sync)) -> LlmResult<()> {
+        let provider = instance.get_name().parse().unwrap_or(ProviderType::OpenAICompatible);
+        validate_messages(messages, provider)
+    }
+
+    /// Warn when a request's model override doesn't look like a model of the instance's provider
+    ///
+    /// The request is still sent, since providers add models faster than FlyLLM learns their names.
+    fn check_model_override(request: &LlmManagerRequest, instance: &(dyn LlmInstance + Send + Sync)) {
+        let Some(model) = &request.model else {
+            return;
+        };
+        let Ok(provider) = instance.get_name().parse::<ProviderType>() else {
+            return;
+        };
+        if !provider.recognizes_model(model) {
+            warn!(
+                "[request {}] Model override '{}' doesn't look like a {} model (instance is configured for '{}')",
+                request.request_id, model, provider, instance.get_model()
+            );
+        }
+    }
+
+    /// Warn about parameters the selected provider doesn't recognize, or refuse them with `strict_params`
+    ///
+    /// Such parameters (often typos like `temprature`) would otherwise be dropped silently.
+    /// Instances whose name isn't a built-in provider type are not checked.
+    fn check_params(&self, params: &HashMap<String, Value>, provider_name: &str) -> LlmResult<()> {
+        let Ok(provider) = provider_name.parse::<ProviderType>() else {
+            return Ok(());
+        };
+        let mut unknown: Vec<&str> = params
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !provider.accepts_param(key))
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        unknown.sort_unstable();
+
+        let message = format!("Unknown parameter(s) for {}: {}", provider, unknown.join(", "));
+        if self.strict_params {
+            return Err(LlmError::ConfigError(message));
+        }
+        warn!("{} (ignored)", message);
+        Ok(())
+    }
+
+    /// Whether waiting `wait` more before the next attempt would overrun `max_total_retry_duration`
+    ///
+    /// Logs a warning when it would, since the caller gives up with the last error.
+    fn exceeds_retry_duration(&self, start_time: Instant, wait: Duration) -> bool {
+        let Some(limit) = self.max_total_retry_duration else {
+            return false;
+        };
+        let elapsed = start_time.elapsed();
+        if elapsed + wait < limit {
+            return false;
+        }
+        warn!(
+            "Retry time limit of {:?} reached after {:?}. Returning last error.",
+            limit, elapsed
+        );
+        true
+    }
+
+    /// How long to wait before retrying after a rate-limit or model-loading error
+    ///
+    /// Uses the provider's `retry_after` hint when present, otherwise the retry policy's backoff.
+    fn retriable_delay(&self, error: &LlmError, attempts: usize) -> std::time::Duration {
+        error.retry_after().unwrap_or_else(|| self.retry_policy.delay_for(attempts))
+    }
+
+    /// Whether a failed instance should be retried again before it is excluded
+    ///
+    /// Only instances with their own `max_retries` are retried in place; configuration
+    /// errors never are. Increments the instance's entry in `instance_failures`.
+    async fn retry_in_place(
+        &self,
+        instance_id: usize,
+        error: &LlmError,
+        instance_failures: &mut HashMap<usize, usize>,
+    ) -> bool {
+        if matches!(error, LlmError::ConfigError(_)) {
+            return false;
+        }
+        let limit = match self.trackers.lock().await.get(&instance_id) {
+            Some(tracker) => tracker.max_retries,
+            None => None,
+        };
+        let failures = instance_failures.entry(instance_id).or_insert(0);
+        *failures += 1;
+        limit.is_some_and(|limit| *failures <= limit)
+    }
+
+    /// Run the `on_retry` hook, if any, for an attempt about to be retried
+    fn notify_retry(&self, request_id: u64, instance_id: Option<usize>, error: &LlmError, attempt: usize, task: Option<&str>) {
+        if let Some(hook) = &self.on_retry {
+            hook(RetryEvent { request_id, instance_id, error, attempt, task });
+        }
+    }
+
+    /// IDs of every instance except the given one, used to pin a retry to that instance
+    async fn exclude_all_but(&self, instance_id: usize) -> Vec<usize> {
+        let trackers = self.trackers.lock().await;
+        trackers.keys().copied().filter(|id| *id != instance_id).collect()
+    }
+
     /// Write debug information for a request/response to the debug folder
+    ///
+    /// # Parameters
+    /// * `manager_request` - The request being processed (ID, prompt and task)
+    /// * `instance_id` - ID of the instance that served the attempt
+    /// * `instance` - The instance that served the attempt
+    /// * `request` - The provider request, carrying the merged parameters
+    /// * `result` - The provider's response or error
+    /// * `duration` - How long the provider took
     async fn write_debug_info(
         &self,
+        manager_request: &LlmManagerRequest,
         instance_id: usize,
-        instance_name: &str,
-        instance_model: &str,
-        prompt: &str,
-        task: Option<&str>,
-        final_params: &HashMap<String, serde_json::Value>,
+        instance: &(dyn LlmInstance + Send + Sync),
+        request: &LlmRequest,
         result: &Result<crate::providers::LlmResponse, LlmError>,
         duration: std::time::Duration,
     ) {
         if let Some(debug_folder) = &self.debug_folder {
+            let instance_name = instance.get_name();
+            let instance_model = instance.get_model();
             let timestamp = self.creation_time
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs();
             
-            let debug_path = get_debug_path(
+            let base_path = get_debug_path(
                 debug_folder,
                 timestamp,
                 instance_id,
                 instance_name,
                 instance_model
-            );
+            )
+            .with_extension(self.debug_format.extension());
             
             // Create the new generation entry
             let generation_entry = json!({
                 "metadata": {
                     "timestamp": SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+                    "request_id": manager_request.request_id,
                     "instance_id": instance_id,
                     "instance_name": instance_name,
                     "instance_model": instance_model,
                     "duration_ms": duration.as_millis()
                 },
                 "input": {
-                    "prompt": prompt,
-                    "task": task,
-                    "parameters": final_params
+                    "prompt": manager_request.prompt,
+                    "task": manager_request.task,
+                    "parameters": request.params
                 },
                 "output": match result {
                     Ok(response) => json!({
@@ -1056,6 +2558,28 @@ impl LlmManager {
                 }
             });
             
+            let generation_entry = if self.redact_secrets {
+                debug_utils::redact_json(&generation_entry)
+            } else {
+                generation_entry
+            };
+
+            // Hold the lock across read and write so concurrent requests don't drop entries
+            let _guard = self.debug_write_lock.lock().await;
+            let debug_path = rotated_debug_path(&base_path, self.max_debug_file_bytes);
+
+            if self.debug_format == DebugFormat::JsonLines {
+                match serde_json::to_string(&generation_entry) {
+                    Ok(line) => {
+                        if let Err(e) = append_to_debug_file(&debug_path, &line) {
+                            warn!("Failed to write debug file: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Failed to serialize debug data: {}", e),
+                }
+                return;
+            }
+
             // Read existing file or create new array
             let mut generations: Vec<Value> = if debug_path.exists() {
                 match fs::read_to_string(&debug_path) {
@@ -1095,6 +2619,111 @@ impl LlmManager {
         }
     }
 
+    /// Find the candidate instances a request is estimated not to fit
+    ///
+    /// # Parameters
+    /// * `request` - The request about to be sent
+    /// * `excluded` - Instance IDs that are already out of the running
+    ///
+    /// # Returns
+    /// * Success: IDs of the instances to skip for this attempt
+    /// * Error: ConfigError describing the last candidate checked, if the request fits none of them
+    async fn oversized_instances(
+        &self,
+        request: &LlmManagerRequest,
+        excluded: &[usize],
+    ) -> LlmResult<Vec<usize>> {
+        let task = request.task.as_deref();
+        let task_instances = match task {
+            Some(task_name) => self.tasks_to_instances.lock().await.get(task_name).cloned(),
+            None => None,
+        };
+
+        let trackers_guard = self.trackers.lock().await;
+        let mut candidates = 0;
+        let mut oversized = Vec::new();
+        let mut last_error = None;
+        for (id, tracker) in trackers_guard.iter() {
+            let supports_task = task_instances.as_ref().is_none_or(|ids| ids.contains(id));
+            if !supports_task || !tracker.is_available() || excluded.contains(id) {
+                continue;
+            }
+            candidates += 1;
+
+            let task_def = task.and_then(|t| tracker.instance.get_supported_tasks().get(t));
+            let max_tokens = Self::requested_max_tokens(task_def, request.params.as_ref());
+            let prompt_tokens = tokens::count_tokens(&request.prompt, tracker.instance.get_model());
+            if let Err(e) = Self::check_context_window(
+                *id,
+                tracker.instance.get_name(),
+                tracker.context_window(),
+                prompt_tokens,
+                max_tokens,
+            ) {
+                debug!("Skipping instance {}: {}", id, e);
+                oversized.push(*id);
+                last_error = Some(e);
+            }
+        }
+
+        match last_error {
+            Some(e) if oversized.len() == candidates => Err(e),
+            _ => Ok(oversized),
+        }
+    }
+
+    /// Check that a request is estimated to fit an instance's context window
+    ///
+    /// Always passes when the instance's context window is unknown.
+    ///
+    /// # Parameters
+    /// * `instance_id` / `instance_name` - The instance, for the error message
+    /// * `context_window` - The instance's context window in tokens, if known
+    /// * `prompt_tokens` - Estimated prompt tokens
+    /// * `max_tokens` - Output tokens the request reserves
+    fn check_context_window(
+        instance_id: usize,
+        instance_name: &str,
+        context_window: Option<u32>,
+        prompt_tokens: u32,
+        max_tokens: Option<u32>,
+    ) -> LlmResult<()> {
+        let Some(context_window) = context_window else {
+            return Ok(());
+        };
+
+        let reserved = max_tokens.unwrap_or(0);
+        let estimated = prompt_tokens.saturating_add(reserved);
+        if estimated > context_window {
+            return Err(LlmError::ConfigError(format!(
+                "Request needs an estimated {} tokens (including {} reserved for output), but instance {} ({}) has a context window of {} tokens",
+                estimated, reserved, instance_id, instance_name, context_window
+            )));
+        }
+        Ok(())
+    }
+
+    /// The `max_tokens` a request will ask for, with request parameters overriding the task's
+    fn requested_max_tokens(
+        task_def: Option<&TaskDefinition>,
+        request_params: Option<&HashMap<String, Value>>,
+    ) -> Option<u32> {
+        request_params
+            .and_then(|params| params.get("max_tokens"))
+            .or_else(|| task_def.and_then(|task| task.parameters.get("max_tokens")))
+            .and_then(|v| v.as_u64())
+            .map(|v| u32::try_from(v).unwrap_or(u32::MAX))
+    }
+
+    /// Format an error for logging, masking secrets unless redaction is disabled
+    fn loggable_error(&self, error: &LlmError) -> String {
+        if self.redact_secrets {
+            debug_utils::redact_text(&error.to_string())
+        } else {
+            error.to_string()
+        }
+    }
+
     /// Update token usage for a specific instance
     ///
     /// # Parameters
@@ -1103,15 +2732,8 @@ impl LlmManager {
     async fn update_instance_usage(&self, instance_id: usize, usage: &TokenUsage) {
         let mut usage_map = self.total_usage.lock().await;
 
-        let instance_usage = usage_map.entry(instance_id).or_insert(TokenUsage {
-            prompt_tokens: 0,
-            completion_tokens: 0,
-            total_tokens: 0,
-        });
-
-        instance_usage.prompt_tokens += usage.prompt_tokens;
-        instance_usage.completion_tokens += usage.completion_tokens;
-        instance_usage.total_tokens += usage.total_tokens;
+        let instance_usage = usage_map.entry(instance_id).or_default();
+        instance_usage.accumulate(usage);
 
         debug!(
             "Updated usage for instance {}: current total is {} tokens",
@@ -1119,6 +2741,95 @@ impl LlmManager {
         );
     }
 
+    /// Add the estimated cost of a response to an instance's running total
+    ///
+    /// Instances without a known price are left untouched.
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to update
+    /// * `provider` - Provider name of the instance
+    /// * `model` - Model of the instance
+    /// * `usage` - Token usage of the response
+    async fn update_instance_cost(
+        &self,
+        instance_id: usize,
+        provider: &str,
+        model: &str,
+        usage: &TokenUsage,
+    ) {
+        let Some(pricing) = self.pricing_for(provider, model) else {
+            return;
+        };
+        let cost = pricing.cost(usage);
+
+        {
+            let mut cost_map = self.total_cost.lock().await;
+            *cost_map.entry(instance_id).or_insert(0.0) += cost;
+        }
+        debug!("Added ${:.6} to cost of instance {}", cost, instance_id);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_cost(provider, model, cost);
+    }
+
+    /// Resolve the price of a provider/model pair
+    ///
+    /// Registered pricing takes precedence over the built-in price table.
+    ///
+    /// # Parameters
+    /// * `provider` - Provider name as returned by `LlmInstance::get_name`
+    /// * `model` - Model identifier
+    pub fn pricing_for(&self, provider: &str, model: &str) -> Option<Pricing> {
+        self.pricing
+            .get(&(provider.to_string(), model.to_string()))
+            .copied()
+            .or_else(|| pricing::default_pricing(provider, model))
+    }
+
+    /// Build the user message for a prompt, attaching any images as content parts
+    fn build_user_message(prompt: &str, images: Option<&[ContentPart]>) -> Message {
+        let content_parts = images.filter(|images| !images.is_empty()).map(|images| {
+            let mut parts = Vec::with_capacity(images.len() + 1);
+            parts.push(ContentPart::Text(prompt.to_string()));
+            parts.extend_from_slice(images);
+            parts
+        });
+
+        Message {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+            content_parts,
+            ..Default::default()
+        }
+    }
+
+    /// Read the `response_format` parameter, warning if it can't be honoured
+    ///
+    /// Invalid values are ignored. Instances without structured output still receive
+    /// the format (they ignore it and return plain text), but a warning is logged.
+    fn resolve_response_format(
+        params: &HashMap<String, Value>,
+        instance: &(dyn LlmInstance + Send + Sync),
+    ) -> Option<ResponseFormat> {
+        let value = params.get("response_format")?;
+        let format: ResponseFormat = match serde_json::from_value(value.clone()) {
+            Ok(format) => format,
+            Err(e) => {
+                warn!("Ignoring invalid response_format {}: {}", value, e);
+                return None;
+            }
+        };
+
+        if format.is_structured() && !instance.supports_structured_output() {
+            warn!(
+                "Provider {} does not support structured output; falling back to plain text",
+                instance.get_name()
+            );
+        }
+
+        Some(format)
+    }
+
     /// Get token usage for a specific instance
     ///
     /// # Parameters
@@ -1138,19 +2849,187 @@ impl LlmManager {
     pub async fn get_total_usage(&self) -> TokenUsage {
         let usage_map = self.total_usage.lock().await;
 
-        usage_map.values().fold(
-            TokenUsage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-                total_tokens: 0,
-            },
-            |mut acc, usage| {
-                acc.prompt_tokens += usage.prompt_tokens;
-                acc.completion_tokens += usage.completion_tokens;
-                acc.total_tokens += usage.total_tokens;
-                acc
-            },
-        )
+        usage_map.values().fold(TokenUsage::default(), |mut acc, usage| {
+            acc.accumulate(usage);
+            acc
+        })
+    }
+
+    /// Return the token usage of every instance and reset it to zero
+    ///
+    /// The swap happens under the usage lock, so tokens of concurrently finishing requests
+    /// are counted either in the returned map or in the next window, never lost or doubled.
+    /// Estimated costs (and so the budget) are not reset.
+    ///
+    /// # Returns
+    /// * Usage of each instance since it was added or since the last reset, keyed by instance ID
+    pub async fn take_usage(&self) -> HashMap<usize, TokenUsage> {
+        let mut usage_map = self.total_usage.lock().await;
+        let taken = usage_map
+            .iter_mut()
+            .map(|(id, usage)| (*id, std::mem::take(usage)))
+            .collect();
+        debug!("Reset token usage of {} instance(s)", usage_map.len());
+        taken
+    }
+
+    /// Reset the token usage of every instance to zero
+    ///
+    /// Like `take_usage`, without returning the previous values.
+    pub async fn reset_usage(&self) {
+        self.take_usage().await;
+    }
+
+    /// Get the estimated cost in USD of a specific instance
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to query
+    ///
+    /// # Returns
+    /// * Accumulated cost for the specified instance, if found
+    pub async fn get_instance_cost(&self, instance_id: usize) -> Option<f64> {
+        let cost_map = self.total_cost.lock().await;
+        cost_map.get(&instance_id).copied()
+    }
+
+    /// Get the estimated cost in USD across all instances
+    ///
+    /// Only responses from instances with known pricing contribute to the total.
+    /// Instances that have since been removed still count.
+    pub async fn get_total_cost(&self) -> f64 {
+        let active: f64 = self.total_cost.lock().await.values().sum();
+        active + *self.retired_cost.lock().await
+    }
+
+    /// Set or clear the spending cap in USD
+    ///
+    /// Once the estimated total cost reaches the cap, requests fail with
+    /// `LlmError::BudgetExceeded` without reaching any provider.
+    ///
+    /// # Parameters
+    /// * `budget_usd` - New cap, or `None` for no limit
+    pub async fn set_budget(&self, budget_usd: Option<f64>) {
+        *self.budget_usd.lock().await = budget_usd;
+        info!("Budget set to {:?} USD", budget_usd);
+    }
+
+    /// Get how much of the budget is left in USD
+    ///
+    /// # Returns
+    /// * Remaining budget (never negative), or `None` when no budget is set
+    pub async fn remaining_budget(&self) -> Option<f64> {
+        let budget = (*self.budget_usd.lock().await)?;
+        Some((budget - self.get_total_cost().await).max(0.0))
+    }
+
+    /// Fail with `BudgetExceeded` if the estimated cost has reached the budget
+    async fn check_budget(&self) -> LlmResult<()> {
+        let Some(budget) = *self.budget_usd.lock().await else {
+            return Ok(());
+        };
+        let spent = self.get_total_cost().await;
+        if spent >= budget {
+            return Err(LlmError::BudgetExceeded(format!(
+                "spent ${:.4} of ${:.4} budget",
+                spent, budget
+            )));
+        }
+        Ok(())
+    }
+
+    /// Export accumulated token usage so it can be persisted across restarts
+    ///
+    /// Usage of instances sharing the same provider and model is summed into one entry.
+    ///
+    /// # Returns
+    /// * Snapshot keyed by provider name and model, sorted by both
+    pub async fn export_usage(&self) -> UsageSnapshot {
+        let trackers = self.trackers.lock().await;
+        let usage_map = self.total_usage.lock().await;
+
+        let mut aggregated: BTreeMap<(String, String), TokenUsage> = BTreeMap::new();
+        for (instance_id, tracker) in trackers.iter() {
+            let Some(usage) = usage_map.get(instance_id) else {
+                continue;
+            };
+            let entry = aggregated
+                .entry((
+                    tracker.instance.get_name().to_string(),
+                    tracker.instance.get_model().to_string(),
+                ))
+                .or_default();
+            entry.accumulate(usage);
+        }
+
+        UsageSnapshot {
+            entries: aggregated
+                .into_iter()
+                .map(|((provider, model), usage)| UsageSnapshotEntry { provider, model, usage })
+                .collect(),
+        }
+    }
+
+    /// Get a cloneable handle for reading instance statistics
+    ///
+    /// The handle shares the manager's trackers and usage, so it stays current while
+    /// the manager keeps serving requests.
+    pub fn stats_handle(&self) -> StatsHandle {
+        StatsHandle {
+            trackers: self.trackers.clone(),
+            usage: self.total_usage.clone(),
+        }
+    }
+
+    /// Collect request counts, latency, errors, token usage and health of every instance
+    ///
+    /// Available without the `metrics` feature; the result serializes with serde.
+    pub async fn get_instance_stats(&self) -> Vec<InstanceStats> {
+        self.stats_handle().instance_stats().await
+    }
+
+    /// Seed token usage from a previously exported snapshot
+    ///
+    /// Each entry is assigned to the lowest-ID instance with the same provider and model,
+    /// replacing its current usage. Entries with no matching instance are dropped, and
+    /// instances without an entry keep their current usage.
+    ///
+    /// # Parameters
+    /// * `snapshot` - Snapshot produced by `export_usage`
+    pub async fn import_usage(&self, snapshot: &UsageSnapshot) {
+        let trackers = self.trackers.lock().await;
+        let mut usage_map = self.total_usage.lock().await;
+
+        let mut ids: Vec<&usize> = trackers.keys().collect();
+        ids.sort();
+
+        for entry in &snapshot.entries {
+            let matching_id = ids.iter().find(|id| {
+                let instance = &trackers[**id].instance;
+                instance.get_name() == entry.provider && instance.get_model() == entry.model
+            });
+
+            match matching_id {
+                Some(id) => {
+                    usage_map.insert(**id, entry.usage.clone());
+                    debug!(
+                        "Restored usage for instance {} ({} / {}): {} tokens",
+                        id, entry.provider, entry.model, entry.usage.total_tokens
+                    );
+                }
+                None => warn!(
+                    "Dropping usage snapshot for {} / {}: no matching instance",
+                    entry.provider, entry.model
+                ),
+            }
+        }
+    }
+
+    /// Remove all cached responses (no-op when caching is disabled)
+    pub async fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear().await;
+            info!("Response cache cleared");
+        }
     }
 
     /// Get the number of configured provider instances
@@ -1162,6 +3041,325 @@ impl LlmManager {
         trackers.len()
     }
 
+    /// Enable or disable a provider instance at runtime
+    ///
+    /// Disabled instances are skipped by instance selection until re-enabled.
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to update
+    /// * `enabled` - Whether the instance should receive requests
+    ///
+    /// # Returns
+    /// * Whether an instance with that ID was found
+    pub async fn set_instance_enabled(&self, instance_id: usize, enabled: bool) -> bool {
+        let mut trackers = self.trackers.lock().await;
+        match trackers.get_mut(&instance_id) {
+            Some(tracker) => {
+                tracker.set_enabled(enabled);
+                info!(
+                    "Instance {} ({}) {}",
+                    instance_id,
+                    tracker.instance.get_name(),
+                    if enabled { "enabled" } else { "disabled" }
+                );
+                true
+            }
+            None => {
+                warn!("Cannot change enabled status: instance {} not found", instance_id);
+                false
+            }
+        }
+    }
+
+    /// Bring back an instance disabled for failing too often
+    ///
+    /// Clears the instance's recent failures, so it needs `max_failures_before_disable`
+    /// new failures to be disabled again. Also re-enables manually disabled instances.
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to re-enable
+    ///
+    /// # Returns
+    /// * Whether an instance with that ID was found
+    pub async fn reenable_instance(&self, instance_id: usize) -> bool {
+        let mut trackers = self.trackers.lock().await;
+        match trackers.get_mut(&instance_id) {
+            Some(tracker) => {
+                tracker.reenable();
+                info!("Instance {} ({}) re-enabled", instance_id, tracker.instance.get_name());
+                true
+            }
+            None => {
+                warn!("Cannot re-enable: instance {} not found", instance_id);
+                false
+            }
+        }
+    }
+
+    /// Probe every enabled instance once and update its health flag
+    ///
+    /// Each instance is checked with `LlmInstance::health_check`. Instances whose probe
+    /// fails (or takes longer than `constants::HEALTH_CHECK_TIMEOUT_SECS`) are skipped by
+    /// instance selection until a later probe succeeds. Disabled instances are not probed.
+    ///
+    /// # Returns
+    /// * Probe result of each enabled instance, keyed by instance ID
+    pub async fn check_all_health(&self) -> HashMap<usize, LlmResult<()>> {
+        Self::probe_instances(&self.trackers).await
+    }
+
+    /// Pre-connect to every enabled instance and validate its credentials
+    ///
+    /// Runs each instance's `LlmInstance::health_check` (a models-endpoint call or a
+    /// one-token request), so connections are open and bad keys surface at startup
+    /// instead of on the first real request. Like `check_all_health`, failing instances
+    /// are marked unhealthy until a later probe succeeds.
+    ///
+    /// # Returns
+    /// * Probe result of each enabled instance, ordered by instance ID
+    pub async fn warm_up(&self) -> Vec<(usize, LlmResult<()>)> {
+        let mut results: Vec<(usize, LlmResult<()>)> = self.check_all_health().await.into_iter().collect();
+        results.sort_by_key(|(id, _)| *id);
+
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+        if failed == 0 {
+            info!("Warm-up: all {} enabled instance(s) reachable", results.len());
+        } else {
+            for (id, result) in &results {
+                if let Err(e) = result {
+                    warn!("Warm-up: instance {} failed: {}", id, self.loggable_error(e));
+                }
+            }
+        }
+        results
+    }
+
+    /// Start probing instance health in the background every `interval`
+    ///
+    /// The task holds only a weak reference to the trackers and stops once the
+    /// manager is dropped or shut down.
+    pub(crate) async fn spawn_health_checks(&self, interval: Duration) {
+        let trackers = Arc::downgrade(&self.trackers);
+        self.spawn_background(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(trackers) = trackers.upgrade() else {
+                    debug!("Manager dropped, stopping health checks");
+                    break;
+                };
+                Self::probe_instances(&trackers).await;
+            }
+        }).await;
+    }
+
+    /// Spawn a task that runs until it finishes or `shutdown` is called
+    pub(crate) async fn spawn_background<F>(&self, task: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let token = self.shutdown_token.clone();
+        let handle = tokio::spawn(async move {
+            tokio::select! {
+                _ = token.cancelled() => {}
+                _ = task => {}
+            }
+        });
+        self.background_tasks.lock().await.push(handle);
+    }
+
+    /// Stop background tasks and wait for pending debug writes
+    ///
+    /// Signals the health-check and dashboard tasks started by the builder to stop and
+    /// waits until they have, then waits for any debug entry being written. The manager
+    /// still serves requests afterwards, but instances are no longer probed.
+    pub async fn shutdown(&self) {
+        self.shutdown_token.cancel();
+        let tasks: Vec<JoinHandle<()>> = self.background_tasks.lock().await.drain(..).collect();
+        for task in tasks {
+            if let Err(e) = task.await {
+                warn!("Background task ended abnormally during shutdown: {}", e);
+            }
+        }
+
+        // Debug entries are written while holding this lock
+        drop(self.debug_write_lock.lock().await);
+        info!("LlmManager shut down");
+    }
+
+    /// Whether `shutdown` has been called
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown_token.is_cancelled()
+    }
+
+    /// Run one health check round against the given trackers
+    async fn probe_instances(
+        trackers: &Mutex<HashMap<usize, InstanceTracker>>,
+    ) -> HashMap<usize, LlmResult<()>> {
+        // Probe without holding the lock so requests keep flowing meanwhile
+        let instances: Vec<(usize, Arc<dyn LlmInstance + Send + Sync>)> = trackers
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, tracker)| tracker.is_enabled())
+            .map(|(id, tracker)| (*id, tracker.instance.clone()))
+            .collect();
+
+        let timeout = Duration::from_secs(constants::HEALTH_CHECK_TIMEOUT_SECS);
+        let results: HashMap<usize, LlmResult<()>> = join_all(instances.iter().map(|(id, instance)| async move {
+            let result = match tokio::time::timeout(timeout, instance.health_check()).await {
+                Ok(result) => result,
+                Err(_) => Err(LlmError::ApiError(format!(
+                    "Health check timed out after {}s",
+                    constants::HEALTH_CHECK_TIMEOUT_SECS
+                ))),
+            };
+            if let Err(e) = &result {
+                debug!("Health check failed for instance {} ({}): {}", id, instance.get_name(), e);
+            }
+            (*id, result)
+        }))
+        .await
+        .into_iter()
+        .collect();
+
+        let mut trackers = trackers.lock().await;
+        for (id, result) in &results {
+            // The instance may have been removed or replaced while it was probed
+            let Some(tracker) = trackers.get_mut(id) else { continue };
+            let healthy = result.is_ok();
+            if tracker.healthy != healthy {
+                if healthy {
+                    info!("Instance {} ({}) recovered", id, tracker.instance.get_name());
+                } else {
+                    warn!("Instance {} ({}) is unhealthy", id, tracker.instance.get_name());
+                }
+            }
+            tracker.healthy = healthy;
+
+            #[cfg(feature = "metrics")]
+            crate::metrics::set_provider_health(tracker.instance.get_name(), healthy);
+        }
+        results
+    }
+
+    /// Override how many times an instance is retried before the manager moves on
+    ///
+    /// These retries are consecutive attempts against the same instance and don't count
+    /// towards the manager's `max_retries`. Once they are used up, the instance is excluded
+    /// for the rest of the request and the next instance is tried as usual. Rate-limit
+    /// retries are unaffected.
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance to update
+    /// * `max_retries` - Retries against this instance, or `None` to move on after the first failure
+    ///
+    /// # Returns
+    /// * Whether an instance with that ID was found
+    pub async fn set_instance_max_retries(&self, instance_id: usize, max_retries: Option<usize>) -> bool {
+        let mut trackers = self.trackers.lock().await;
+        match trackers.get_mut(&instance_id) {
+            Some(tracker) => {
+                tracker.max_retries = max_retries;
+                true
+            }
+            None => {
+                warn!("Cannot set max retries: instance {} not found", instance_id);
+                false
+            }
+        }
+    }
+
+    /// List all provider instances in the manager
+    ///
+    /// # Returns
+    /// * List of (instance ID, provider name, model, enabled) sorted by ID
+    pub async fn list_instances(&self) -> Vec<(usize, String, String, bool)> {
+        let trackers = self.trackers.lock().await;
+        let mut instances: Vec<(usize, String, String, bool)> = trackers
+            .iter()
+            .map(|(id, tracker)| {
+                (
+                    *id,
+                    tracker.instance.get_name().to_string(),
+                    tracker.instance.get_model().to_string(),
+                    tracker.is_enabled(),
+                )
+            })
+            .collect();
+        instances.sort_by_key(|(id, _, _, _)| *id);
+        instances
+    }
+
+    /// Map each task to the instances that serve it
+    ///
+    /// Lists every instance registered for the task, including disabled and unhealthy
+    /// ones; see `unrouted_tasks` for tasks none of them can currently serve. Defined
+    /// tasks without any instance map to an empty list.
+    ///
+    /// # Returns
+    /// * Map of task name to (instance ID, provider name, model) entries sorted by ID
+    pub async fn routing_table(&self) -> HashMap<String, Vec<(usize, String, String)>> {
+        let known_tasks = self.known_tasks.lock().await.clone();
+        let task_map = self.tasks_to_instances.lock().await.clone();
+        let trackers = self.trackers.lock().await;
+        known_tasks
+            .into_iter()
+            .map(|task| {
+                let mut instances: Vec<(usize, String, String)> = task_map
+                    .get(&task)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|id| {
+                        trackers.get(id).map(|tracker| {
+                            (
+                                *id,
+                                tracker.instance.get_name().to_string(),
+                                tracker.instance.get_model().to_string(),
+                            )
+                        })
+                    })
+                    .collect();
+                instances.sort_by_key(|(id, _, _)| *id);
+                (task, instances)
+            })
+            .collect()
+    }
+
+    /// List the tasks whose requests currently fail with "No enabled providers available"
+    ///
+    /// These are defined tasks with no instance registered (never supported, or all
+    /// removed) and tasks whose instances are all disabled or unhealthy.
+    ///
+    /// # Returns
+    /// * Names of the tasks without an available instance, sorted
+    pub async fn unrouted_tasks(&self) -> Vec<String> {
+        let known_tasks = self.known_tasks.lock().await.clone();
+        let task_map = self.tasks_to_instances.lock().await.clone();
+        let trackers = self.trackers.lock().await;
+        known_tasks
+            .into_iter()
+            .filter(|task| {
+                !task_map.get(task).into_iter().flatten().any(|id| {
+                    trackers.get(id).is_some_and(|tracker| tracker.is_available())
+                })
+            })
+            .collect()
+    }
+
+    /// Get the number of requests currently outstanding per instance
+    ///
+    /// # Returns
+    /// * Map of instance ID to its in-flight request count
+    pub async fn get_inflight_counts(&self) -> HashMap<usize, usize> {
+        let trackers = self.trackers.lock().await;
+        trackers
+            .iter()
+            .map(|(id, tracker)| (*id, tracker.in_flight_count()))
+            .collect()
+    }
+
     /// Print token usage statistics to console
     pub async fn print_token_usage(&self) {
         println!("\n--- Token Usage Statistics ---");
@@ -14,7 +14,18 @@ pub mod tasks;
 pub mod builder;
 pub mod utils;
 pub mod types;
+pub mod cache;
+pub mod pricing;
+pub mod retry;
+pub mod stats;
+pub mod hooks;
+mod recording;
 
-pub use types::{GenerationRequest, LlmManagerResponse};
+pub use types::{GenerationRequest, LlmManagerResponse, UsageSnapshot, UsageSnapshotEntry};
 pub use manager::{LlmManager};
-pub use tasks::TaskDefinition;
\ No newline at end of file
+pub use tasks::TaskDefinition;
+pub use pricing::Pricing;
+pub use retry::RetryPolicy;
+pub use stats::{InstanceStats, StatsHandle};
+pub use utils::DebugFormat;
+pub use hooks::{RetryEvent, RetryHook, RequestCompleteEvent, RequestCompleteHook};
\ No newline at end of file
@@ -0,0 +1,68 @@
+use crate::providers::TokenUsage;
+use serde::{Deserialize, Serialize};
+
+/// Price of a model in USD per 1K tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+}
+
+impl Pricing {
+    /// Create a new price entry
+    ///
+    /// # Parameters
+    /// * `input_per_1k` - USD per 1K prompt tokens
+    /// * `output_per_1k` - USD per 1K completion tokens
+    pub fn new(input_per_1k: f64, output_per_1k: f64) -> Self {
+        Self {
+            input_per_1k,
+            output_per_1k,
+        }
+    }
+
+    /// Estimated cost in USD of the given token usage
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 / 1000.0) * self.input_per_1k
+            + (usage.completion_tokens as f64 / 1000.0) * self.output_per_1k
+    }
+}
+
+/// Built-in list prices as (provider, model prefix, input per 1K, output per 1K)
+///
+/// These are a convenience for common models and go stale; register your own
+/// pricing to override them.
+const DEFAULT_PRICES: &[(&str, &str, f64, f64)] = &[
+    ("openai", "gpt-4o-mini", 0.00015, 0.0006),
+    ("openai", "gpt-4o", 0.0025, 0.01),
+    ("openai", "gpt-4.1-nano", 0.0001, 0.0004),
+    ("openai", "gpt-4.1-mini", 0.0004, 0.0016),
+    ("openai", "gpt-4.1", 0.002, 0.008),
+    ("openai", "gpt-3.5-turbo", 0.0005, 0.0015),
+    ("anthropic", "claude-3-5-haiku", 0.0008, 0.004),
+    ("anthropic", "claude-3-5-sonnet", 0.003, 0.015),
+    ("anthropic", "claude-3-7-sonnet", 0.003, 0.015),
+    ("anthropic", "claude-3-haiku", 0.00025, 0.00125),
+    ("anthropic", "claude-3-opus", 0.015, 0.075),
+    ("mistral", "mistral-large", 0.002, 0.006),
+    ("mistral", "mistral-small", 0.0002, 0.0006),
+    ("google", "gemini-1.5-flash", 0.000075, 0.0003),
+    ("google", "gemini-1.5-pro", 0.00125, 0.005),
+    ("google", "gemini-2.0-flash", 0.0001, 0.0004),
+];
+
+/// Look up the built-in price for a provider and model
+///
+/// Models match by the longest listed prefix, so dated snapshots such as
+/// `claude-3-5-sonnet-20241022` resolve to their base model.
+///
+/// # Parameters
+/// * `provider` - Provider name as returned by `LlmInstance::get_name`
+/// * `model` - Model identifier
+pub fn default_pricing(provider: &str, model: &str) -> Option<Pricing> {
+    DEFAULT_PRICES
+        .iter()
+        .filter(|(p, prefix, _, _)| *p == provider && model.starts_with(prefix))
+        .max_by_key(|(_, prefix, _, _)| prefix.len())
+        .map(|(_, _, input, output)| Pricing::new(*input, *output))
+}
@@ -0,0 +1,217 @@
+use crate::errors::LlmError;
+use crate::load_balancer::pricing::Pricing;
+use crate::load_balancer::tracker::{InFlightGuard, InstanceTracker};
+use crate::providers::{LlmStream, StreamChunk, TokenUsage};
+use futures::Stream;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+type StreamItem = Option<Result<StreamChunk, LlmError>>;
+type CostMap = Arc<Mutex<HashMap<usize, f64>>>;
+
+/// Bookkeeping for one streaming request, recorded once when it ends
+pub(crate) struct StreamRecord {
+    instance_id: usize,
+    trackers: Arc<Mutex<HashMap<usize, InstanceTracker>>>,
+    usage: Arc<Mutex<HashMap<usize, TokenUsage>>>,
+    // The manager's per-instance cost and the instance's price, when cost is tracked
+    cost: Option<(CostMap, Pricing)>,
+    started: Instant,
+    // Held until the request is recorded so the instance counts as busy while streaming
+    _in_flight: Option<InFlightGuard>,
+    #[cfg(feature = "metrics")]
+    provider: String,
+    #[cfg(feature = "metrics")]
+    model: String,
+    #[cfg(feature = "metrics")]
+    task: Option<String>,
+}
+
+impl StreamRecord {
+    /// Start timing a streaming request
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance serving the stream
+    /// * `trackers` - The manager's trackers, updated with the outcome
+    /// * `usage` - The manager's per-instance token usage, updated from the final chunk
+    /// * `in_flight` - Guard released once the stream is recorded
+    pub(crate) fn start(
+        instance_id: usize,
+        trackers: Arc<Mutex<HashMap<usize, InstanceTracker>>>,
+        usage: Arc<Mutex<HashMap<usize, TokenUsage>>>,
+        in_flight: Option<InFlightGuard>,
+        _provider: &str,
+        _model: &str,
+        _task: Option<&str>,
+    ) -> Self {
+        Self {
+            instance_id,
+            trackers,
+            usage,
+            cost: None,
+            started: Instant::now(),
+            _in_flight: in_flight,
+            #[cfg(feature = "metrics")]
+            provider: _provider.to_string(),
+            #[cfg(feature = "metrics")]
+            model: _model.to_string(),
+            #[cfg(feature = "metrics")]
+            task: _task.map(str::to_string),
+        }
+    }
+
+    /// Also add the estimated cost of the final chunk's usage to the instance's running total
+    ///
+    /// # Parameters
+    /// * `cost` - The manager's per-instance cost in USD
+    /// * `pricing` - Price of the instance's model; `None` leaves cost untouched
+    pub(crate) fn with_cost(mut self, cost: CostMap, pricing: Option<Pricing>) -> Self {
+        self.cost = pricing.map(|pricing| (cost, pricing));
+        self
+    }
+
+    /// Record the outcome of the request
+    ///
+    /// Usage is skipped for instances removed mid-stream so their entry isn't recreated.
+    pub(crate) async fn record(self, success: bool, usage: Option<&TokenUsage>, _error: Option<&LlmError>) {
+        let duration = self.started.elapsed();
+
+        let tracker_present = match self.trackers.lock().await.get_mut(&self.instance_id) {
+            Some(tracker) => {
+                tracker.record_outcome(duration, success);
+                true
+            }
+            None => false,
+        };
+
+        if let (Some(usage), true) = (usage, tracker_present) {
+            let mut usage_map = self.usage.lock().await;
+            usage_map.entry(self.instance_id).or_default().accumulate(usage);
+            drop(usage_map);
+
+            if let Some((cost_map, pricing)) = &self.cost {
+                let cost = pricing.cost(usage);
+                *cost_map.lock().await.entry(self.instance_id).or_insert(0.0) += cost;
+
+                #[cfg(feature = "metrics")]
+                crate::metrics::record_cost(&self.provider, &self.model, cost);
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        self.emit_metrics(duration, usage, _error);
+    }
+
+    #[cfg(feature = "metrics")]
+    fn emit_metrics(&self, duration: Duration, usage: Option<&TokenUsage>, error: Option<&LlmError>) {
+        match error {
+            Some(e) => crate::metrics::record_request_failure(
+                &self.provider,
+                &self.model,
+                self.task.as_deref(),
+                self.instance_id,
+                e,
+                duration,
+            ),
+            None => crate::metrics::record_request_success(
+                &self.provider,
+                &self.model,
+                self.task.as_deref(),
+                self.instance_id,
+                duration,
+                usage,
+            ),
+        }
+    }
+
+    /// Record the item that ended the stream, then hand it back
+    async fn finish(self, item: StreamItem) -> StreamItem {
+        match &item {
+            Some(Ok(chunk)) => self.record(true, chunk.usage.as_ref(), None).await,
+            Some(Err(e)) => self.record(false, None, Some(e)).await,
+            None => self.record(true, None, None).await,
+        }
+        item
+    }
+}
+
+/// Stream wrapper that records a streaming request with the manager when it ends
+///
+/// The request is recorded when a final chunk carrying token usage passes through, when
+/// the provider returns an error, or when the stream ends. A final chunk without usage
+/// doesn't end the request, since some providers (OpenAI) send usage in a chunk of its
+/// own after the finish reason. A stream dropped early is still counted, with the latency
+/// up to the drop and no usage; abandoning a stream isn't held against the instance.
+pub(crate) struct RecordingStream {
+    inner: LlmStream,
+    record: Option<StreamRecord>,
+    pending: Option<Pin<Box<dyn Future<Output = StreamItem> + Send>>>,
+}
+
+impl RecordingStream {
+    pub(crate) fn new(inner: LlmStream, record: StreamRecord) -> Self {
+        Self {
+            inner,
+            record: Some(record),
+            pending: None,
+        }
+    }
+}
+
+impl Stream for RecordingStream {
+    type Item = Result<StreamChunk, LlmError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // Hold the ending item back until it has been recorded, so callers see
+        // up-to-date usage as soon as they have the final chunk
+        if let Some(pending) = this.pending.as_mut() {
+            let item = ready!(pending.as_mut().poll(cx));
+            this.pending = None;
+            return Poll::Ready(item);
+        }
+
+        let item = ready!(this.inner.as_mut().poll_next(cx));
+        let ends_request = match &item {
+            Some(Ok(chunk)) => chunk.is_final && chunk.usage.is_some(),
+            Some(Err(_)) | None => true,
+        };
+
+        match this.record.take() {
+            Some(record) if ends_request => {
+                let mut pending = Box::pin(record.finish(item));
+                let polled = pending.as_mut().poll(cx);
+                match polled {
+                    Poll::Ready(item) => Poll::Ready(item),
+                    Poll::Pending => {
+                        this.pending = Some(pending);
+                        Poll::Pending
+                    }
+                }
+            }
+            record => {
+                this.record = record;
+                Poll::Ready(item)
+            }
+        }
+    }
+}
+
+impl Drop for RecordingStream {
+    fn drop(&mut self) {
+        if let Some(record) = self.record.take() {
+            // Recording needs the async locks; outside a runtime the request goes unrecorded
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(record.record(true, None, None));
+            }
+        }
+    }
+}
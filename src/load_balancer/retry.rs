@@ -0,0 +1,51 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Controls how long the manager waits between retry attempts
+///
+/// The delay for attempt `n` (starting at 0) is `base_delay * multiplier^n`, capped at
+/// `max_delay`. The default policy waits 1s, 2s, 4s, ... up to 60s, and only after
+/// rate-limit errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Factor applied to the delay after each attempt
+    pub multiplier: f64,
+    /// Upper bound for any single delay
+    pub max_delay: Duration,
+    /// Randomize each delay to between half and all of its computed value
+    pub jitter: bool,
+    /// Also wait before retrying after non-rate-limit errors
+    pub backoff_on_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(60),
+            jitter: false,
+            backoff_on_errors: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Compute the delay before retrying after the given (0-based) attempt
+    pub fn delay_for(&self, attempt: usize) -> Duration {
+        let exponent = i32::try_from(attempt).unwrap_or(i32::MAX);
+        let max_secs = self.max_delay.as_secs_f64();
+        let secs = (self.base_delay.as_secs_f64() * self.multiplier.powi(exponent)).min(max_secs);
+        // NaN (e.g. 0 * inf) or negative values fall back to no delay
+        let secs = if secs.is_finite() && secs > 0.0 { secs } else { 0.0 };
+
+        let secs = if self.jitter {
+            secs * rand::rng().random_range(0.5..=1.0)
+        } else {
+            secs
+        };
+        Duration::from_secs_f64(secs)
+    }
+}
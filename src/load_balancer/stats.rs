@@ -0,0 +1,76 @@
+use crate::load_balancer::tracker::InstanceTracker;
+use crate::providers::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Point-in-time statistics of one manager instance
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InstanceStats {
+    pub instance_id: usize,
+    pub provider: String,
+    pub model: String,
+    pub enabled: bool,
+    pub healthy: bool,
+    pub request_count: usize,
+    pub success_count: usize,
+    pub error_count: usize,
+    /// Average latency of the most recent successful requests, in milliseconds
+    pub avg_latency_ms: f64,
+    /// Exponential moving average of successful request latency, in milliseconds
+    pub ema_latency_ms: f64,
+    /// Latency of the most recent successful request, in milliseconds
+    pub last_latency_ms: Option<f64>,
+    pub in_flight: usize,
+    pub usage: TokenUsage,
+    /// Maximum prompt plus output tokens the model accepts, if known
+    pub context_window: Option<u32>,
+}
+
+/// Cloneable handle for reading instance statistics without borrowing the manager
+///
+/// Obtained with `LlmManager::stats_handle`; the dashboard server uses it to serve
+/// `/api/stats` while the manager itself stays owned by the application.
+#[derive(Clone)]
+pub struct StatsHandle {
+    pub(crate) trackers: Arc<Mutex<HashMap<usize, InstanceTracker>>>,
+    pub(crate) usage: Arc<Mutex<HashMap<usize, TokenUsage>>>,
+}
+
+impl fmt::Debug for StatsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatsHandle").finish_non_exhaustive()
+    }
+}
+
+impl StatsHandle {
+    /// Collect the statistics of every instance, ordered by instance ID
+    pub async fn instance_stats(&self) -> Vec<InstanceStats> {
+        let trackers = self.trackers.lock().await;
+        let usage = self.usage.lock().await;
+
+        let mut stats: Vec<InstanceStats> = trackers
+            .iter()
+            .map(|(id, tracker)| InstanceStats {
+                instance_id: *id,
+                provider: tracker.instance.get_name().to_string(),
+                model: tracker.instance.get_model().to_string(),
+                enabled: tracker.is_enabled(),
+                healthy: tracker.healthy,
+                request_count: tracker.request_count,
+                success_count: tracker.request_count.saturating_sub(tracker.error_count),
+                error_count: tracker.error_count,
+                avg_latency_ms: tracker.avg_response_time().as_secs_f64() * 1000.0,
+                ema_latency_ms: tracker.ema_response_time().as_secs_f64() * 1000.0,
+                last_latency_ms: tracker.response_times.last().map(|d| d.as_secs_f64() * 1000.0),
+                in_flight: tracker.in_flight_count(),
+                usage: usage.get(id).cloned().unwrap_or_default(),
+                context_window: tracker.context_window(),
+            })
+            .collect();
+        stats.sort_by_key(|s| s.instance_id);
+        stats
+    }
+}
@@ -1,5 +1,9 @@
 use log::debug;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 use crate::load_balancer::tracker::InstanceTracker;
 
@@ -16,6 +20,25 @@ pub trait LoadBalancingStrategy {
     /// # Returns
     /// * Index into the trackers array of the selected instance
     fn select_instance(&mut self, trackers: &[(usize, &InstanceTracker)]) -> usize;
+
+    /// Select an instance for a request that may belong to a session
+    ///
+    /// Strategies that don't route by session ignore the key and call `select_instance`.
+    ///
+    /// # Parameters
+    /// * `trackers` - Array of (id, tracker) tuples for available instances
+    /// * `session_key` - Session the request belongs to, if any
+    ///
+    /// # Returns
+    /// * Index into the trackers array of the selected instance
+    fn select_instance_for_session(
+        &mut self,
+        trackers: &[(usize, &InstanceTracker)],
+        session_key: Option<&str>,
+    ) -> usize {
+        let _ = session_key;
+        self.select_instance(trackers)
+    }
 }
 
 /// Strategy that selects the instance that was used least recently
@@ -79,7 +102,7 @@ impl LowestLatencyStrategy {
 }
 
 impl LoadBalancingStrategy for LowestLatencyStrategy {
-    /// Select the instance with the minimum `avg_response_time`.
+    /// Select the instance with the minimum `ema_response_time`.
     ///
     /// # Parameters
     /// * `trackers` - Array of (id, tracker) tuples for available instances.
@@ -95,18 +118,18 @@ impl LoadBalancingStrategy for LowestLatencyStrategy {
         }
 
         let mut best_index = 0;
-        let mut lowest_time = trackers[0].1.avg_response_time();
+        let mut lowest_time = trackers[0].1.ema_response_time();
 
         for (i, (_id, tracker)) in trackers.iter().enumerate().skip(1) {
-            let avg_time = tracker.avg_response_time();
-            if avg_time < lowest_time {
+            let ema_time = tracker.ema_response_time();
+            if ema_time < lowest_time {
                 best_index = i;
-                lowest_time = avg_time;
+                lowest_time = ema_time;
             }
         }
 
         debug!(
-            "LowestLatencyStrategy: Selected index {} (ID: {}) from {} eligible trackers with avg_response_time: {:?}",
+            "LowestLatencyStrategy: Selected index {} (ID: {}) from {} eligible trackers with ema_response_time: {:?}",
             best_index, trackers[best_index].0, trackers.len(), lowest_time
         );
 
@@ -116,12 +139,21 @@ impl LoadBalancingStrategy for LowestLatencyStrategy {
 
 /// Strategy that selects a random instance from the available pool.
 #[derive(Debug, Default)]
-pub struct RandomStrategy;
+pub struct RandomStrategy {
+    rng: Option<StdRng>, // Seeded generator for reproducible picks; the thread RNG when `None`
+}
 
 impl RandomStrategy {
      /// Creates a new RandomStrategy
      pub fn new() -> Self {
-         Self {}
+         Self { rng: None }
+     }
+
+     /// Creates a RandomStrategy whose picks are reproducible for a given seed
+     ///
+     /// Useful in tests: the same seed and eligible instances yield the same sequence of picks.
+     pub fn with_seed(seed: u64) -> Self {
+         Self { rng: Some(StdRng::seed_from_u64(seed)) }
      }
 }
 
@@ -141,7 +173,10 @@ impl LoadBalancingStrategy for RandomStrategy {
             panic!("RandomStrategy::select_instance called with empty trackers slice");
         }
 
-        let index = rand::rng().random_range(0..trackers.len());
+        let index = match &mut self.rng {
+            Some(rng) => rng.random_range(0..trackers.len()),
+            None => rand::rng().random_range(0..trackers.len()),
+        };
 
         debug!(
             "RandomStrategy: Selected random index {} (ID: {}) from {} eligible trackers",
@@ -150,4 +185,279 @@ impl LoadBalancingStrategy for RandomStrategy {
 
         index
     }
-}
\ No newline at end of file
+}
+
+/// "Power of two choices" strategy.
+///
+/// Samples two eligible instances at random and picks the one with fewer
+/// in-flight requests, breaking ties by lower average response time. This keeps
+/// load close to balanced without herding every request onto whichever instance
+/// currently looks fastest.
+#[derive(Debug, Default)]
+pub struct PowerOfTwoStrategy;
+
+impl PowerOfTwoStrategy {
+     /// Creates a new PowerOfTwoStrategy
+     pub fn new() -> Self {
+         Self {}
+     }
+}
+
+impl LoadBalancingStrategy for PowerOfTwoStrategy {
+    /// Select the less loaded of two randomly sampled instances.
+    ///
+    /// # Parameters
+    /// * `trackers` - Array of (id, tracker) tuples for available instances.
+    ///
+    /// # Returns
+    /// * Index into the trackers array of the chosen instance.
+    ///
+    /// # Panics
+    /// * Panics if `trackers` is empty.
+    fn select_instance(&mut self, trackers: &[(usize, &InstanceTracker)]) -> usize {
+        if trackers.is_empty() {
+            panic!("PowerOfTwoStrategy::select_instance called with empty trackers slice");
+        }
+        if trackers.len() == 1 {
+            return 0;
+        }
+
+        let mut rng = rand::rng();
+        let first = rng.random_range(0..trackers.len());
+        let mut second = rng.random_range(0..trackers.len() - 1);
+        if second >= first {
+            second += 1;
+        }
+
+        let load = |i: usize| {
+            let tracker = trackers[i].1;
+            (tracker.in_flight_count(), tracker.avg_response_time())
+        };
+        let index = if load(second) < load(first) { second } else { first };
+
+        debug!(
+            "PowerOfTwoStrategy: Sampled indices {} and {}, selected index {} (ID: {}) with {} in-flight requests",
+            first, second, index, trackers[index].0, trackers[index].1.in_flight_count()
+        );
+
+        index
+    }
+}
+
+/// Strategy that picks instances at random in proportion to their weight.
+///
+/// Weights come from `InstanceTracker::weight` (1.0 unless configured). Instances with a
+/// weight of 0 are only chosen when every eligible instance has a weight of 0, in which
+/// case the choice is uniform.
+#[derive(Debug, Default)]
+pub struct WeightedStrategy;
+
+impl WeightedStrategy {
+     /// Creates a new WeightedStrategy
+     pub fn new() -> Self {
+         Self {}
+     }
+}
+
+impl LoadBalancingStrategy for WeightedStrategy {
+    /// Select an instance with probability proportional to its weight.
+    ///
+    /// # Parameters
+    /// * `trackers` - Array of (id, tracker) tuples for available instances.
+    ///
+    /// # Returns
+    /// * Index into the trackers array of the chosen instance.
+    ///
+    /// # Panics
+    /// * Panics if `trackers` is empty.
+    fn select_instance(&mut self, trackers: &[(usize, &InstanceTracker)]) -> usize {
+        if trackers.is_empty() {
+            panic!("WeightedStrategy::select_instance called with empty trackers slice");
+        }
+
+        let weight = |tracker: &InstanceTracker| {
+            if tracker.weight.is_finite() && tracker.weight > 0.0 { tracker.weight } else { 0.0 }
+        };
+        let total: f64 = trackers.iter().map(|(_, tracker)| weight(tracker)).sum();
+
+        let mut rng = rand::rng();
+        let index = if total <= 0.0 {
+            rng.random_range(0..trackers.len())
+        } else {
+            let mut point = rng.random_range(0.0..total);
+            trackers
+                .iter()
+                .position(|(_, tracker)| {
+                    let w = weight(tracker);
+                    if w > 0.0 && point < w {
+                        true
+                    } else {
+                        point -= w;
+                        false
+                    }
+                })
+                // Floating point rounding can leave `point` just past the last weight
+                .unwrap_or_else(|| trackers.iter().rposition(|(_, t)| weight(t) > 0.0).unwrap_or(0))
+        };
+
+        debug!(
+            "WeightedStrategy: Selected index {} (ID: {}) with weight {} of total {}",
+            index, trackers[index].0, trackers[index].1.weight, total
+        );
+
+        index
+    }
+}
+
+/// Strategy that always prefers the instance with the highest priority.
+///
+/// Priorities come from `InstanceTracker::priority`, where lower values are tried first.
+/// Instances without a priority come after all prioritized ones, and ties go to the
+/// lowest instance ID. Because failed instances are excluded on retry, a failing
+/// instance hands over to the next one in the chain.
+#[derive(Debug, Default)]
+pub struct PriorityStrategy;
+
+impl PriorityStrategy {
+     /// Creates a new PriorityStrategy
+     pub fn new() -> Self {
+         Self {}
+     }
+}
+
+impl LoadBalancingStrategy for PriorityStrategy {
+    /// Select the eligible instance with the lowest priority value.
+    ///
+    /// # Parameters
+    /// * `trackers` - Array of (id, tracker) tuples for available instances.
+    ///
+    /// # Returns
+    /// * Index into the trackers array of the chosen instance.
+    ///
+    /// # Panics
+    /// * Panics if `trackers` is empty.
+    fn select_instance(&mut self, trackers: &[(usize, &InstanceTracker)]) -> usize {
+        if trackers.is_empty() {
+            panic!("PriorityStrategy::select_instance called with empty trackers slice");
+        }
+
+        let index = trackers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (id, tracker))| (tracker.priority.unwrap_or(u32::MAX), *id))
+            .map(|(index, _)| index)
+            .unwrap_or(0);
+
+        debug!(
+            "PriorityStrategy: Selected index {} (ID: {}) with priority {:?}",
+            index, trackers[index].0, trackers[index].1.priority
+        );
+
+        index
+    }
+}
+
+/// Strategy that keeps every request of a session on the same instance.
+///
+/// Requests with a session key are pinned to the instance that wins a rendezvous hash
+/// of the key over the eligible instances the first time the session is seen, so the
+/// same key maps to the same instance while the set of instances stays the same. If
+/// the pinned instance is not eligible (failed, disabled, removed or rate limited), the
+/// inner strategy picks one and the session is re-pinned to it, so the rest of the
+/// session stays together. Requests without a session key always go to the inner strategy.
+pub struct StickyStrategy<S> {
+    inner: S,
+    pins: HashMap<String, usize>,
+    max_sessions: usize,
+}
+
+impl<S: LoadBalancingStrategy> StickyStrategy<S> {
+    /// Default number of sessions remembered before the pins are reset
+    pub const DEFAULT_MAX_SESSIONS: usize = 10_000;
+
+    /// Creates a new StickyStrategy wrapping `inner`
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            pins: HashMap::new(),
+            max_sessions: Self::DEFAULT_MAX_SESSIONS,
+        }
+    }
+
+    /// Sets how many sessions are remembered
+    ///
+    /// Once the limit is reached all pins are dropped. Sessions then re-pin by hash,
+    /// which picks the same instance again unless the eligible instances changed.
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions;
+        self
+    }
+
+    /// Index of the eligible instance with the highest hash score for the key
+    fn rendezvous_index(trackers: &[(usize, &InstanceTracker)], session_key: &str) -> usize {
+        trackers
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (id, _))| {
+                let mut hasher = DefaultHasher::new();
+                session_key.hash(&mut hasher);
+                id.hash(&mut hasher);
+                hasher.finish()
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(0)
+    }
+}
+
+impl<S: LoadBalancingStrategy> LoadBalancingStrategy for StickyStrategy<S> {
+    /// Delegate to the inner strategy (the request has no session key).
+    fn select_instance(&mut self, trackers: &[(usize, &InstanceTracker)]) -> usize {
+        self.inner.select_instance(trackers)
+    }
+
+    /// Select the session's pinned instance, pinning one by hash on first use.
+    ///
+    /// Re-pins the session to the inner strategy's choice when its instance is not eligible.
+    ///
+    /// # Panics
+    /// * Panics if `trackers` is empty.
+    fn select_instance_for_session(
+        &mut self,
+        trackers: &[(usize, &InstanceTracker)],
+        session_key: Option<&str>,
+    ) -> usize {
+        if trackers.is_empty() {
+            panic!("StickyStrategy::select_instance called with empty trackers slice");
+        }
+        let Some(session_key) = session_key else {
+            return self.inner.select_instance(trackers);
+        };
+
+        match self.pins.get(session_key) {
+            Some(pinned_id) => match trackers.iter().position(|(id, _)| id == pinned_id) {
+                Some(index) => {
+                    debug!("StickyStrategy: Session '{}' stays on instance {}", session_key, pinned_id);
+                    index
+                }
+                None => {
+                    let index = self.inner.select_instance(trackers);
+                    debug!(
+                        "StickyStrategy: Instance {} pinned for session '{}' is unavailable, re-pinned to instance {}",
+                        pinned_id, session_key, trackers[index].0
+                    );
+                    self.pins.insert(session_key.to_string(), trackers[index].0);
+                    index
+                }
+            },
+            None => {
+                if self.pins.len() >= self.max_sessions {
+                    self.pins.clear();
+                }
+                let index = Self::rendezvous_index(trackers, session_key);
+                self.pins.insert(session_key.to_string(), trackers[index].0);
+                debug!("StickyStrategy: Pinned session '{}' to instance {}", session_key, trackers[index].0);
+                index
+            }
+        }
+    }
+}
@@ -7,6 +7,11 @@ use serde_json::{json, Value};
 /// Tasks represent specialized capabilities or configurations that
 /// certain providers might be better suited for. Each task can have
 /// associated parameters that affect how the request is processed.
+///
+/// Not every provider accepts every parameter: `top_p`, `frequency_penalty`,
+/// `presence_penalty` and `stop` are only sent to providers whose
+/// `ProviderType::accepts_param` lists them, and `stop` is also enforced on
+/// streamed responses from the others.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskDefinition {
     pub name: String,
@@ -38,4 +43,35 @@ impl TaskDefinition {
     pub fn with_temperature(self, temp: f32) -> Self {
         self.with_param("temperature", json!(temp))
     }
+
+    /// Sets the `top_p` (nucleus sampling) parameter to a given value.
+    pub fn with_top_p(self, top_p: f32) -> Self {
+        self.with_param("top_p", json!(top_p))
+    }
+
+    /// Sets the `frequency_penalty` parameter to a given value.
+    pub fn with_frequency_penalty(self, penalty: f32) -> Self {
+        self.with_param("frequency_penalty", json!(penalty))
+    }
+
+    /// Sets the `presence_penalty` parameter to a given value.
+    pub fn with_presence_penalty(self, penalty: f32) -> Self {
+        self.with_param("presence_penalty", json!(penalty))
+    }
+
+    /// Sets the `stop` parameter to the sequences that end generation.
+    pub fn with_stop(self, sequences: Vec<String>) -> Self {
+        self.with_param("stop", json!(sequences))
+    }
+
+    /// Sets the `seed` parameter used for reproducible sampling.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.with_param("seed", json!(seed))
+    }
+
+    /// Requests JSON output matching the given schema for this task.
+    /// Providers without structured output fall back to plain text.
+    pub fn with_json_schema(self, schema: Value) -> Self {
+        self.with_param("response_format", json!({ "type": "json_schema", "schema": schema }))
+    }
 }
\ No newline at end of file
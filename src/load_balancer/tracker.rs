@@ -1,15 +1,53 @@
+use crate::constants;
 use crate::providers::LlmInstance;
 use crate::{LlmResponse, LlmResult};
+use log::warn;
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use std::sync::Arc; 
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// An LLM provider instance with associated metrics
 pub struct InstanceTracker {
     pub instance: Arc<dyn LlmInstance + Send + Sync>,
     pub last_used: Instant,
     pub response_times: Vec<Duration>,
+    pub latency_ema: Option<Duration>, // Exponential moving average of successful request durations
+    pub latency_smoothing: f64, // Weight of the newest sample in `latency_ema`, in (0, 1]
     pub request_count: usize,
     pub error_count: usize,
+    pub in_flight: Arc<AtomicUsize>,
+    pub enabled: bool,
+    pub max_retries: Option<usize>, // Retries against this instance before moving on; None uses the manager's behavior
+    pub config_key: Option<String>, // Identity of the configured provider this instance was loaded from, used by config reloads
+    pub weight: f64, // Relative share of traffic under WeightedStrategy
+    pub priority: Option<u32>, // Position in priority-based strategies (lower is preferred)
+    pub context_window: Option<u32>, // Maximum prompt plus output tokens the model accepts, if known
+    pub healthy: bool, // Result of the latest health check; unhealthy instances are skipped until a probe succeeds
+    pub recent_outcomes: VecDeque<bool>, // Whether each of the last `failure_window` requests failed, oldest first
+    pub failure_window: usize, // How many recent requests `recent_outcomes` keeps
+    pub max_failures_before_disable: Option<usize>, // Failures within the window that disable the instance; None never does
+}
+
+/// Guard that keeps an instance's in-flight counter raised while a request is outstanding
+///
+/// The counter is decremented when the guard is dropped, so every exit path
+/// (success, error or a dropped future) releases it.
+pub struct InFlightGuard {
+    counter: Arc<AtomicUsize>,
+    #[cfg(feature = "metrics")]
+    provider: String,
+    #[cfg(feature = "metrics")]
+    model: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let _remaining = self.counter.fetch_sub(1, Ordering::SeqCst) - 1;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::set_inflight_requests(&self.provider, &self.model, _remaining);
+    }
 }
 
 impl InstanceTracker {
@@ -19,34 +57,133 @@ impl InstanceTracker {
     /// * `id` - Unique identifier for this instance
     /// * `provider` - Reference to the provider implementation
     pub fn new(instance: Arc<dyn LlmInstance + Send + Sync>) -> Self {
+        let enabled = instance.is_enabled();
         Self {
             instance: instance,
             last_used: Instant::now(),
             response_times: Vec::new(),
+            latency_ema: None,
+            latency_smoothing: constants::DEFAULT_LATENCY_SMOOTHING,
             request_count: 0,
             error_count: 0,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            enabled,
+            max_retries: None,
+            config_key: None,
+            weight: 1.0,
+            priority: None,
+            context_window: None,
+            healthy: true,
+            recent_outcomes: VecDeque::new(),
+            failure_window: constants::DEFAULT_FAILURE_WINDOW,
+            max_failures_before_disable: None,
+        }
+    }
+
+    /// Mark a request as started against this instance
+    ///
+    /// # Returns
+    /// * Guard that marks the request as finished when dropped
+    pub fn start_request(&self) -> InFlightGuard {
+        let _current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::set_inflight_requests(self.instance.get_name(), self.instance.get_model(), _current);
+
+        InFlightGuard {
+            counter: self.in_flight.clone(),
+            #[cfg(feature = "metrics")]
+            provider: self.instance.get_name().to_string(),
+            #[cfg(feature = "metrics")]
+            model: self.instance.get_model().to_string(),
         }
     }
 
+    /// Get the number of requests currently outstanding against this instance
+    ///
+    /// # Returns
+    /// * Number of in-flight requests
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
     /// Record the result of a request for metrics tracking
     ///
     /// # Parameters
     /// * `duration` - How long the request took
     /// * `result` - The result of the request (success or error)
     pub fn record_result(&mut self, duration: Duration, result: &LlmResult<LlmResponse>) {
+        self.record_outcome(duration, result.is_ok());
+    }
+
+    /// Record the outcome of a request that doesn't produce an `LlmResponse`
+    ///
+    /// # Parameters
+    /// * `duration` - How long the request took
+    /// * `success` - Whether the request succeeded
+    pub fn record_outcome(&mut self, duration: Duration, success: bool) {
         self.last_used = Instant::now();
         self.request_count += 1;
 
-        match result {
-            Ok(_) => {
-                self.response_times.push(duration);
-                if self.response_times.len() > 10 {
-                    self.response_times.remove(0);
-                }            }
-            Err(e) => {
-                self.error_count += 1;
+        if success {
+            self.response_times.push(duration);
+            if self.response_times.len() > 10 {
+                self.response_times.remove(0);
             }
+            self.update_latency_ema(duration);
+        } else {
+            self.error_count += 1;
         }
+
+        self.recent_outcomes.push_back(!success);
+        while self.recent_outcomes.len() > self.failure_window.max(1) {
+            self.recent_outcomes.pop_front();
+        }
+        self.quarantine_if_failing();
+    }
+
+    /// Count the failures among the last `failure_window` requests
+    ///
+    /// # Returns
+    /// * Number of recent failed requests
+    pub fn recent_failures(&self) -> usize {
+        self.recent_outcomes.iter().filter(|failed| **failed).count()
+    }
+
+    /// Disable the instance once its recent failures reach `max_failures_before_disable`
+    ///
+    /// Unlike retries, which only look at the request at hand, this catches an instance that
+    /// keeps failing intermittently. It stays disabled until `reenable` is called.
+    fn quarantine_if_failing(&mut self) {
+        let Some(max_failures) = self.max_failures_before_disable else {
+            return;
+        };
+        if !self.enabled || self.recent_failures() < max_failures {
+            return;
+        }
+
+        warn!(
+            "Disabling {} ({}): {} of its last {} requests failed",
+            self.instance.get_name(),
+            self.instance.get_model(),
+            self.recent_failures(),
+            self.recent_outcomes.len()
+        );
+        self.set_enabled(false);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::set_provider_health(self.instance.get_name(), false);
+    }
+
+    /// Enable the instance again with a clean failure history
+    ///
+    /// Used to bring back an instance disabled by `max_failures_before_disable`.
+    pub fn reenable(&mut self) {
+        self.recent_outcomes.clear();
+        self.set_enabled(true);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::set_provider_health(self.instance.get_name(), self.healthy);
     }
 
     /// Calculate the average response time from recent requests
@@ -61,6 +198,34 @@ impl InstanceTracker {
         total / self.response_times.len().max(1) as u32 // Avoid division by zero
     }
 
+    /// Fold a successful request's duration into the latency EMA
+    ///
+    /// A smoothing factor outside (0, 1] falls back to the default.
+    fn update_latency_ema(&mut self, duration: Duration) {
+        let alpha = if self.latency_smoothing > 0.0 && self.latency_smoothing <= 1.0 {
+            self.latency_smoothing
+        } else {
+            constants::DEFAULT_LATENCY_SMOOTHING
+        };
+        let sample = duration.as_secs_f64();
+        let ema = match self.latency_ema {
+            Some(previous) => alpha * sample + (1.0 - alpha) * previous.as_secs_f64(),
+            None => sample,
+        };
+        self.latency_ema = Some(Duration::from_secs_f64(ema));
+    }
+
+    /// Get the exponential moving average of successful request durations
+    ///
+    /// Recent requests weigh more than in `avg_response_time`, so a single slow
+    /// request fades out after a few fast ones.
+    ///
+    /// # Returns
+    /// * Smoothed duration, or zero if no requests recorded
+    pub fn ema_response_time(&self) -> Duration {
+        self.latency_ema.unwrap_or_default()
+    }
+
     /// Calculate the error rate as a percentage
     ///
     /// # Returns
@@ -74,11 +239,38 @@ impl InstanceTracker {
     }
 
     /// Check if this instance is currently enabled
+    ///
+    /// Starts from the instance's configured value and can be changed at runtime
+    /// with `set_enabled`.
     /// 
     /// # Returns
     /// * Whether this instance is enabled or not
     pub fn is_enabled(&self) -> bool {
-        self.instance.is_enabled()
+        self.enabled
+    }
+
+    /// The instance's context window: the configured one, else the one its provider knows
+    ///
+    /// # Returns
+    /// * Maximum prompt plus output tokens, or `None` if unknown
+    pub fn context_window(&self) -> Option<u32> {
+        self.context_window.or_else(|| self.instance.context_window())
+    }
+
+    /// Check if this instance can be selected for requests
+    ///
+    /// # Returns
+    /// * Whether this instance is both enabled and healthy
+    pub fn is_available(&self) -> bool {
+        self.enabled && self.healthy
+    }
+
+    /// Enable or disable this instance at runtime
+    ///
+    /// # Parameters
+    /// * `enabled` - Whether the instance should receive requests
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
     }
 
     /// Check if this instance supports a specific task
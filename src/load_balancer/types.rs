@@ -2,6 +2,8 @@
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use serde_json::{json, Value};
+use crate::errors::LlmError;
+use crate::providers::types::{ContentPart, ProviderType, TokenUsage, ToolCall, ToolDefinition};
 
 /// User-facing request for LLM generation
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -9,6 +11,13 @@ pub struct GenerationRequest {
     pub prompt: String,                                     // Prompt for the LLM
     pub task: Option<String>,                               // Task to route for
     pub params: Option<HashMap<String, serde_json::Value>>, // Extra parameters
+    pub tools: Option<Vec<ToolDefinition>>,                 // Tools the model may call
+    pub tool_choice: Option<String>,                        // "auto", "none", "required" or a tool name
+    pub images: Option<Vec<ContentPart>>,                   // Images sent alongside the prompt
+    pub session_key: Option<String>,                        // Conversation key for sticky routing
+    pub provider: Option<ProviderType>,                     // Only use instances of this provider
+    pub instance_id: Option<usize>,                         // Only use this instance
+    pub model: Option<String>,                              // Model to use instead of the instance's own
 }
 
 impl Default for GenerationRequest {
@@ -17,6 +26,13 @@ impl Default for GenerationRequest {
             prompt: String::new(),
             task: None,
             params: None,
+            tools: None,
+            tool_choice: None,
+            images: None,
+            session_key: None,
+            provider: None,
+            instance_id: None,
+            model: None,
         }
     }
 }
@@ -54,6 +70,98 @@ impl GenerationRequest {
         self.param("max_tokens", json!(tokens))
     }
 
+    /// Sets a sampling seed for reproducible output on providers that support it
+    pub fn seed(self, seed: u64) -> Self {
+        self.param("seed", json!(seed))
+    }
+
+    /// Asks for `n` completions, returned in `LlmManagerResponse::choices`
+    ///
+    /// Only OpenAI and OpenAI-compatible providers honour it; others return one completion.
+    pub fn choices(self, n: u32) -> Self {
+        self.param("n", json!(n))
+    }
+
+    /// Asks for token log probabilities, returned in `LlmManagerResponse::logprobs`
+    ///
+    /// Only OpenAI, OpenAI-compatible endpoints, Groq, Together AI, xAI and Fireworks honour it; others return none.
+    pub fn logprobs(self, enabled: bool) -> Self {
+        self.param("logprobs", json!(enabled))
+    }
+
+    /// Asks for the `n` most likely alternatives to each token; implies `logprobs(true)`
+    pub fn top_logprobs(self, n: u32) -> Self {
+        self.logprobs(true).param("top_logprobs", json!(n))
+    }
+
+    /// Grounds the answer in these documents on providers that support it (currently Cohere),
+    /// which return their citations in `LlmManagerResponse::metadata["citations"]`
+    pub fn documents(self, documents: Vec<Value>) -> Self {
+        self.param("documents", Value::Array(documents))
+    }
+
+    /// Requests a JSON object response from providers that support structured output
+    pub fn json_mode(self) -> Self {
+        self.param("response_format", json!({ "type": "json_object" }))
+    }
+
+    /// Sets the tools the model may call for this request
+    pub fn tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Sets how the model should choose tools ("auto", "none", "required" or a tool name)
+    pub fn tool_choice(mut self, choice: impl Into<String>) -> Self {
+        self.tool_choice = Some(choice.into());
+        self
+    }
+
+    /// Attaches an image by URL (requires a vision-capable provider)
+    pub fn image_url(mut self, url: impl Into<String>) -> Self {
+        self.images.get_or_insert_with(Vec::new).push(ContentPart::ImageUrl {
+            url: url.into(),
+            detail: None,
+        });
+        self
+    }
+
+    /// Attaches an inline base64-encoded image (requires a vision-capable provider)
+    pub fn image_base64(mut self, media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        self.images.get_or_insert_with(Vec::new).push(ContentPart::ImageBase64 {
+            media_type: media_type.into(),
+            data: data.into(),
+        });
+        self
+    }
+
+    /// Tags the request with a session, so `StickyStrategy` routes it like the session's other requests
+    pub fn session_key(mut self, key: impl Into<String>) -> Self {
+        self.session_key = Some(key.into());
+        self
+    }
+
+    /// Sends the request only to instances of this provider, bypassing the strategy's
+    /// choice between providers. Combined with a task, only instances serving both are used.
+    pub fn provider(mut self, provider: ProviderType) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Sends the request only to the instance with this ID (as returned by `add_instance`).
+    /// Fails with a `ConfigError` if that instance is disabled, unhealthy or has failed.
+    pub fn instance_id(mut self, id: usize) -> Self {
+        self.instance_id = Some(id);
+        self
+    }
+
+    /// Sends the request to this model instead of the serving instance's configured one.
+    /// Best combined with `provider` or `instance_id`, since the model name is provider-specific.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
     /// Finalizes the GenerationRequest
     pub fn build(self) -> Self {
         self
@@ -66,8 +174,16 @@ pub struct LlmManagerRequest {
     pub prompt: String,
     pub task: Option<String>,
     pub params: Option<HashMap<String, serde_json::Value>>,
+    pub tools: Option<Vec<ToolDefinition>>,
+    pub tool_choice: Option<String>,
+    pub images: Option<Vec<ContentPart>>,
+    pub session_key: Option<String>,
+    pub provider: Option<ProviderType>,
+    pub instance_id: Option<usize>,
+    pub model: Option<String>,
     pub attempts: usize,
     pub failed_instances: Vec<usize>,
+    pub request_id: u64, // Correlates the request's log lines, debug entry and response
 }
 
 impl LlmManagerRequest {
@@ -77,10 +193,43 @@ impl LlmManagerRequest {
             prompt: request.prompt,
             task: request.task,
             params: request.params,
+            tools: request.tools,
+            tool_choice: request.tool_choice,
+            images: request.images,
+            session_key: request.session_key,
+            provider: request.provider,
+            instance_id: request.instance_id,
+            model: request.model,
             attempts: 0,
             failed_instances: Vec::new(),
+            request_id: 0,
         }
     }
+
+    /// Whether an instance satisfies the request's provider and instance pins
+    ///
+    /// # Parameters
+    /// * `instance_id` - ID of the instance
+    /// * `provider_name` - Name the instance reports via `LlmInstance::get_name`
+    pub fn matches_pin(&self, instance_id: usize, provider_name: &str) -> bool {
+        self.instance_id.is_none_or(|pinned| pinned == instance_id)
+            && self.provider.is_none_or(|pinned| provider_name.parse::<ProviderType>().ok() == Some(pinned))
+    }
+
+    /// Error for a pinned request that no available instance satisfies, or None if it isn't pinned
+    pub fn pin_error(&self) -> Option<LlmError> {
+        let pin = match (self.instance_id, self.provider) {
+            (Some(id), Some(provider)) => format!("instance {} of provider {}", id, provider),
+            (Some(id), None) => format!("instance {}", id),
+            (None, Some(provider)) => format!("provider {}", provider),
+            (None, None) => return None,
+        };
+        Some(LlmError::ConfigError(format!(
+            "Pinned {} is not available{}: it is unknown, disabled, unhealthy, doesn't serve the task, or already failed",
+            pin,
+            self.task.as_ref().map_or_else(String::new, |t| format!(" for task '{}'", t))
+        )))
+    }
 }
 
 /// Response structure returned to users
@@ -89,4 +238,45 @@ pub struct LlmManagerResponse {
     pub content: String,
     pub success: bool,
     pub error: Option<String>,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>, // Tool calls requested by the model
+    #[serde(default)]
+    pub provider: Option<String>, // Name of the provider that served the request
+    #[serde(default)]
+    pub model: Option<String>, // Model reported by the provider
+    #[serde(default)]
+    pub instance_id: Option<usize>, // ID of the instance that served the request
+    #[serde(default)]
+    pub attempts: usize, // Number of attempts made, including the successful one
+    #[serde(default)]
+    pub usage: Option<TokenUsage>, // Token usage of this request
+    #[serde(default)]
+    pub citations: Vec<String>, // Sources cited by search-augmented providers
+    #[serde(default)]
+    pub finish_reason: Option<String>, // Why generation stopped, e.g. "stop" or "length"
+    #[serde(default)]
+    pub request_id: u64, // ID of the request, also found in its log lines and debug entry
+    #[serde(default)]
+    pub choices: Vec<String>, // Every completion, the first being `content`; several when `n` was requested
+    #[serde(default)]
+    pub logprobs: Option<Value>, // Raw token log probabilities of `content`, when requested and supported
+    #[serde(default)]
+    pub metadata: HashMap<String, Value>, // Provider-specific extras, e.g. Cohere's document citations
+}
+
+/// Serializable snapshot of accumulated token usage
+///
+/// Entries are keyed by provider name and model rather than instance ID, since
+/// IDs are reassigned every time a manager is built.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UsageSnapshot {
+    pub entries: Vec<UsageSnapshotEntry>,
+}
+
+/// Token usage accumulated by one provider/model pair
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UsageSnapshotEntry {
+    pub provider: String,
+    pub model: String,
+    pub usage: TokenUsage,
+}
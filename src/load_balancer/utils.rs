@@ -1,7 +1,106 @@
-use std::fs::{File, create_dir_all};
+use std::fs::{File, OpenOptions, create_dir_all};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use crate::errors::LlmError;
+use regex::Regex;
+use serde_json::Value;
+
+/// Replacement for redacted secrets
+const REDACTED: &str = "[REDACTED]";
+
+/// Patterns of secrets that can appear in free text, with the replacement for each match
+static SECRET_PATTERNS: LazyLock<Vec<(Regex, String)>> = LazyLock::new(|| {
+    [
+        // OpenAI / Anthropic style keys (sk-..., sk-proj-..., sk-ant-...)
+        (r"sk-[A-Za-z0-9_\-]{16,}", REDACTED.to_string()),
+        // Groq keys
+        (r"gsk_[A-Za-z0-9]{16,}", REDACTED.to_string()),
+        // xAI keys
+        (r"xai-[A-Za-z0-9]{16,}", REDACTED.to_string()),
+        // Replicate tokens
+        (r"r8_[A-Za-z0-9]{16,}", REDACTED.to_string()),
+        // Google API keys
+        (r"AIza[0-9A-Za-z_\-]{30,}", REDACTED.to_string()),
+        // Bearer tokens in headers or messages
+        (r"(?i)(bearer\s+)[A-Za-z0-9._~+/=\-]+", format!("${{1}}{}", REDACTED)),
+        // Keys passed as URL query parameters
+        (r#"(?i)([?&](?:api_?)?key=)[^&\s"']+"#, format!("${{1}}{}", REDACTED)),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect()
+});
+
+/// Whether a JSON key or header name holds a credential
+fn is_secret_key(key: &str) -> bool {
+    let normalized: String = key
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    matches!(
+        normalized.as_str(),
+        "authorization" | "proxyauthorization" | "token" | "accesstoken" | "authtoken" | "bearer"
+    ) || normalized.ends_with("apikey")
+        || normalized.ends_with("secret")
+        || normalized.ends_with("password")
+}
+
+/// Mask API keys and bearer tokens in free text such as error bodies
+pub fn redact_text(text: &str) -> String {
+    let mut redacted = text.to_string();
+    for (pattern, replacement) in SECRET_PATTERNS.iter() {
+        if pattern.is_match(&redacted) {
+            redacted = pattern.replace_all(&redacted, replacement.as_str()).into_owned();
+        }
+    }
+    redacted
+}
+
+/// Mask secrets in a JSON value
+///
+/// Values under credential-like keys (`api_key`, `authorization`, `x-api-key`, ...) are
+/// replaced entirely, and every other string goes through `redact_text`.
+pub fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret_key(key) && !value.is_null() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact_json(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_json).collect()),
+        Value::String(text) => Value::String(redact_text(text)),
+        other => other.clone(),
+    }
+}
+
+/// Layout of the per-instance debug files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugFormat {
+    /// One pretty-printed JSON array per file (`debug.json`), rewritten on every request
+    #[default]
+    JsonArray,
+    /// One JSON object per line (`debug.jsonl`), appended without re-reading the file
+    JsonLines,
+}
+
+impl DebugFormat {
+    /// File extension used for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DebugFormat::JsonArray => "json",
+            DebugFormat::JsonLines => "jsonl",
+        }
+    }
+}
 
 pub fn get_debug_path(debug_folder: &PathBuf, timestamp: u64, instance_id: usize, instance_provider: &str, instance_model: &str) -> PathBuf {
     let timestamp_folder = debug_folder.join(timestamp.to_string());
@@ -23,4 +122,62 @@ pub fn write_to_debug_file(file_path: &PathBuf, contents: &str) -> Result<(), Ll
         .map_err(|e| LlmError::ConfigError(format!("Failed to write to debug file: {}", e)))?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Append a single line to a debug file, creating it (and its parents) if needed
+pub fn append_to_debug_file(file_path: &Path, line: &str) -> Result<(), LlmError> {
+    if let Some(parent) = file_path.parent() {
+        create_dir_all(parent)
+            .map_err(|e| LlmError::ConfigError(format!("Failed to create debug directories: {}", e)))?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_path)
+        .map_err(|e| LlmError::ConfigError(format!("Failed to open debug file: {}", e)))?;
+
+    file.write_all(format!("{}\n", line).as_bytes())
+        .map_err(|e| LlmError::ConfigError(format!("Failed to write to debug file: {}", e)))?;
+
+    Ok(())
+}
+
+/// Resolve the debug file new entries should go to, rotating when it is full
+///
+/// Rotated files are numbered after the base name (`debug.json`, `debug.1.json`,
+/// `debug.2.json`, ...). The newest existing file is used until it reaches
+/// `max_bytes`, after which the next number is started.
+///
+/// # Parameters
+/// * `base_path` - Path of the first debug file
+/// * `max_bytes` - Size at which a file is considered full; `None` disables rotation
+pub fn rotated_debug_path(base_path: &Path, max_bytes: Option<u64>) -> PathBuf {
+    let Some(max_bytes) = max_bytes else {
+        return base_path.to_path_buf();
+    };
+
+    let numbered = |index: usize| -> PathBuf {
+        if index == 0 {
+            return base_path.to_path_buf();
+        }
+        let stem = base_path.file_stem().and_then(|s| s.to_str()).unwrap_or("debug");
+        match base_path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => base_path.with_file_name(format!("{}.{}.{}", stem, index, ext)),
+            None => base_path.with_file_name(format!("{}.{}", stem, index)),
+        }
+    };
+
+    let mut index = 0;
+    while numbered(index + 1).exists() {
+        index += 1;
+    }
+
+    let current = numbered(index);
+    let full = std::fs::metadata(&current).map(|m| m.len() >= max_bytes).unwrap_or(false);
+    if full {
+        numbered(index + 1)
+    } else {
+        current
+    }
+}
@@ -1,8 +1,10 @@
 //! HTTP server for serving Grafana dashboard JSON
 //!
 //! This module provides an optional HTTP endpoint that serves a pre-built
-//! Grafana dashboard JSON for monitoring LLM operations.
+//! Grafana dashboard JSON for monitoring LLM operations, plus a JSON snapshot of
+//! per-instance statistics.
 
+use crate::load_balancer::StatsHandle;
 use axum::{routing::get, Json, Router};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
@@ -15,12 +17,16 @@ const DASHBOARD_JSON: &str = include_str!("../assets/grafana_dashboard.json");
 pub struct DashboardServerConfig {
     /// Address to bind the HTTP server to
     pub bind_address: SocketAddr,
+    /// Source of the statistics served at `/api/stats`; the route is only added when set.
+    /// `LlmManagerBuilder` fills it in with the manager being built.
+    pub stats: Option<StatsHandle>,
 }
 
 impl Default for DashboardServerConfig {
     fn default() -> Self {
         Self {
             bind_address: ([127, 0, 0, 1], 9898).into(),
+            stats: None,
         }
     }
 }
@@ -30,6 +36,7 @@ impl DashboardServerConfig {
     pub fn new(addr: impl Into<SocketAddr>) -> Self {
         Self {
             bind_address: addr.into(),
+            stats: None,
         }
     }
 
@@ -37,8 +44,15 @@ impl DashboardServerConfig {
     pub fn with_port(port: u16) -> Self {
         Self {
             bind_address: ([0, 0, 0, 0], port).into(),
+            stats: None,
         }
     }
+
+    /// Serve statistics from the given handle at `/api/stats`
+    pub fn with_stats(mut self, stats: StatsHandle) -> Self {
+        self.stats = Some(stats);
+        self
+    }
 }
 
 /// Start the dashboard HTTP server
@@ -46,6 +60,7 @@ impl DashboardServerConfig {
 /// This function starts an HTTP server that serves:
 /// - `GET /dashboard` - Returns the Grafana dashboard JSON
 /// - `GET /health` - Returns "OK" for health checks
+/// - `GET /api/stats` - Returns per-instance statistics as JSON (when `stats` is set)
 ///
 /// # Example
 ///
@@ -59,10 +74,19 @@ impl DashboardServerConfig {
 /// }
 /// ```
 pub async fn start_dashboard_server(config: DashboardServerConfig) -> std::io::Result<()> {
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/dashboard", get(serve_dashboard))
         .route("/health", get(health_check));
 
+    if let Some(stats) = config.stats {
+        app = app.route(
+            "/api/stats",
+            get(move || async move {
+                Json(serde_json::json!({ "instances": stats.instance_stats().await }))
+            }),
+        );
+    }
+
     let listener = TcpListener::bind(config.bind_address).await?;
     log::info!(
         "FlyLLM dashboard server listening on http://{}",
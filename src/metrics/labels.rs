@@ -2,6 +2,7 @@
 
 use crate::errors::LlmError;
 use crate::ProviderType;
+use metrics::Label;
 
 /// Standard label keys
 pub mod keys {
@@ -11,10 +12,31 @@ pub mod keys {
     pub const MODEL: &str = "model";
     /// Task name label key
     pub const TASK: &str = "task";
+    /// Manager instance ID label key
+    pub const INSTANCE_ID: &str = "instance_id";
     /// Error type label key
     pub const ERROR_TYPE: &str = "error_type";
 }
 
+/// Task label value used for requests sent without a task
+pub const NO_TASK: &str = "none";
+
+/// Standard labels attached to per-request metrics
+///
+/// # Parameters
+/// * `provider` - Provider name
+/// * `model` - Model name
+/// * `task` - Task the request was routed for, if any (recorded as `NO_TASK` otherwise)
+/// * `instance_id` - ID of the manager instance that served the request
+pub fn request_labels(provider: &str, model: &str, task: Option<&str>, instance_id: usize) -> Vec<Label> {
+    vec![
+        Label::new(keys::PROVIDER, provider.to_string()),
+        Label::new(keys::MODEL, model.to_string()),
+        Label::new(keys::TASK, task.unwrap_or(NO_TASK).to_string()),
+        Label::new(keys::INSTANCE_ID, instance_id.to_string()),
+    ]
+}
+
 /// Convert ProviderType to label value string
 pub fn provider_label(provider: ProviderType) -> &'static str {
     match provider {
@@ -28,6 +50,12 @@ pub fn provider_label(provider: ProviderType) -> &'static str {
         ProviderType::Cohere => "cohere",
         ProviderType::TogetherAI => "togetherai",
         ProviderType::Perplexity => "perplexity",
+        ProviderType::OpenAICompatible => "openai_compatible",
+        ProviderType::VertexAI => "vertexai",
+        ProviderType::Xai => "xai",
+        ProviderType::Replicate => "replicate",
+        ProviderType::Ai21 => "ai21",
+        ProviderType::Fireworks => "fireworks",
     }
 }
 
@@ -35,10 +63,14 @@ pub fn provider_label(provider: ProviderType) -> &'static str {
 pub fn error_type_label(error: &LlmError) -> &'static str {
     match error {
         LlmError::RequestError(_) => "request_error",
-        LlmError::ApiError(_) => "api_error",
-        LlmError::RateLimit(_) => "rate_limit",
-        LlmError::ParseError(_) => "parse_error",
+        LlmError::ApiError(_) | LlmError::Api { .. } => "api_error",
+        LlmError::RateLimit { .. } => "rate_limit",
+        LlmError::ModelLoading { .. } => "model_loading",
+        LlmError::ParseError(_) | LlmError::Json(_) => "parse_error",
+        LlmError::Io(_) => "io_error",
         LlmError::ProviderDisabled(_) => "provider_disabled",
         LlmError::ConfigError(_) => "config_error",
+        LlmError::BudgetExceeded(_) => "budget_exceeded",
+        LlmError::Cancelled => "cancelled",
     }
 }
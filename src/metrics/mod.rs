@@ -19,6 +19,15 @@
 //! // Describe metrics (optional, improves Prometheus discovery)
 //! describe_metrics();
 //! ```
+//!
+//! # Cardinality
+//!
+//! Request metrics are labeled with provider, model, task and instance ID, so each
+//! series is multiplied by the number of tasks times the number of instances. Each
+//! histogram (`llm_request_duration_seconds`, `llm_tokens_prompt`, `llm_tokens_completion`)
+//! additionally stores one series per bucket, making them the most expensive metrics to
+//! keep. Managers with many tasks or dynamically added instances may want to drop the
+//! `instance_id` label in their exporter or bucket fewer histograms.
 
 pub mod labels;
 mod recorder;
@@ -38,6 +47,10 @@ pub mod names {
     pub const TOKENS_PROMPT: &str = "llm_tokens_prompt_total";
     /// Total completion tokens generated
     pub const TOKENS_COMPLETION: &str = "llm_tokens_completion_total";
+    /// Prompt tokens per request
+    pub const TOKENS_PROMPT_PER_REQUEST: &str = "llm_tokens_prompt";
+    /// Completion tokens per request
+    pub const TOKENS_COMPLETION_PER_REQUEST: &str = "llm_tokens_completion";
     /// Total number of errors by type
     pub const ERRORS_TOTAL: &str = "llm_errors_total";
     /// Provider health status (1=healthy, 0=unhealthy)
@@ -46,6 +59,12 @@ pub mod names {
     pub const RETRIES_TOTAL: &str = "llm_retries_total";
     /// Total number of rate limit responses
     pub const RATE_LIMITS_TOTAL: &str = "llm_rate_limits_total";
+    /// Number of requests currently outstanding
+    pub const INFLIGHT_REQUESTS: &str = "llm_inflight_requests";
+    /// Total number of responses served from the cache
+    pub const CACHE_HITS_TOTAL: &str = "llm_cache_hits_total";
+    /// Estimated cost of responses in USD
+    pub const COST_USD_TOTAL: &str = "llm_cost_usd_total";
 }
 
 /// Describe all metrics with their units and descriptions.
@@ -73,6 +92,16 @@ pub fn describe_metrics() {
         Unit::Count,
         "Total completion tokens generated"
     );
+    describe_histogram!(
+        names::TOKENS_PROMPT_PER_REQUEST,
+        Unit::Count,
+        "Prompt tokens per request"
+    );
+    describe_histogram!(
+        names::TOKENS_COMPLETION_PER_REQUEST,
+        Unit::Count,
+        "Completion tokens per request"
+    );
     describe_counter!(
         names::ERRORS_TOTAL,
         Unit::Count,
@@ -93,4 +122,18 @@ pub fn describe_metrics() {
         Unit::Count,
         "Total number of rate limit responses"
     );
+    describe_gauge!(
+        names::INFLIGHT_REQUESTS,
+        Unit::Count,
+        "Number of requests currently outstanding"
+    );
+    describe_counter!(
+        names::CACHE_HITS_TOTAL,
+        Unit::Count,
+        "Total number of responses served from the cache"
+    );
+    describe_gauge!(
+        names::COST_USD_TOTAL,
+        "Estimated cost of responses in USD"
+    );
 }
@@ -8,90 +8,73 @@ use crate::providers::TokenUsage;
 use super::{labels, names};
 
 /// Record a successful LLM request
+///
+/// Requests, durations and token counts are labeled with provider, model, task
+/// and instance ID (see `labels::request_labels`). Token usage is recorded both as
+/// running totals and as per-request histograms.
 pub fn record_request_success(
     provider: &str,
     model: &str,
     task: Option<&str>,
+    instance_id: usize,
     duration: Duration,
     usage: Option<&TokenUsage>,
 ) {
-    let task_value = task.unwrap_or("default");
+    let request_labels = labels::request_labels(provider, model, task, instance_id);
 
     // Increment request counter
-    metrics::counter!(
-        names::REQUESTS_TOTAL,
-        labels::keys::PROVIDER => provider.to_string(),
-        labels::keys::MODEL => model.to_string(),
-        labels::keys::TASK => task_value.to_string()
-    )
-    .increment(1);
+    metrics::counter!(names::REQUESTS_TOTAL, request_labels.iter()).increment(1);
 
     // Record duration histogram
-    metrics::histogram!(
-        names::REQUEST_DURATION,
-        labels::keys::PROVIDER => provider.to_string(),
-        labels::keys::MODEL => model.to_string(),
-        labels::keys::TASK => task_value.to_string()
-    )
-    .record(duration.as_secs_f64());
+    metrics::histogram!(names::REQUEST_DURATION, request_labels.iter()).record(duration.as_secs_f64());
 
     // Record token usage if available
     if let Some(usage) = usage {
-        metrics::counter!(
-            names::TOKENS_PROMPT,
-            labels::keys::PROVIDER => provider.to_string(),
-            labels::keys::MODEL => model.to_string()
-        )
-        .increment(usage.prompt_tokens as u64);
+        metrics::counter!(names::TOKENS_PROMPT, request_labels.iter())
+            .increment(usage.prompt_tokens as u64);
 
-        metrics::counter!(
-            names::TOKENS_COMPLETION,
-            labels::keys::PROVIDER => provider.to_string(),
-            labels::keys::MODEL => model.to_string()
-        )
-        .increment(usage.completion_tokens as u64);
+        metrics::counter!(names::TOKENS_COMPLETION, request_labels.iter())
+            .increment(usage.completion_tokens as u64);
+
+        // Per-request distributions for capacity planning
+        metrics::histogram!(names::TOKENS_PROMPT_PER_REQUEST, request_labels.iter())
+            .record(usage.prompt_tokens as f64);
+
+        metrics::histogram!(names::TOKENS_COMPLETION_PER_REQUEST, request_labels.iter())
+            .record(usage.completion_tokens as f64);
     }
 }
 
 /// Record a failed LLM request
+///
+/// Uses the same labels as `record_request_success`, plus the error type on the
+/// error counter.
 pub fn record_request_failure(
     provider: &str,
     model: &str,
     task: Option<&str>,
+    instance_id: usize,
     error: &LlmError,
     duration: Duration,
 ) {
-    let task_value = task.unwrap_or("default");
+    let request_labels = labels::request_labels(provider, model, task, instance_id);
 
     // Increment request counter (failures still count as requests)
-    metrics::counter!(
-        names::REQUESTS_TOTAL,
-        labels::keys::PROVIDER => provider.to_string(),
-        labels::keys::MODEL => model.to_string(),
-        labels::keys::TASK => task_value.to_string()
-    )
-    .increment(1);
+    metrics::counter!(names::REQUESTS_TOTAL, request_labels.iter()).increment(1);
 
     // Record duration even for failures
-    metrics::histogram!(
-        names::REQUEST_DURATION,
-        labels::keys::PROVIDER => provider.to_string(),
-        labels::keys::MODEL => model.to_string(),
-        labels::keys::TASK => task_value.to_string()
-    )
-    .record(duration.as_secs_f64());
+    metrics::histogram!(names::REQUEST_DURATION, request_labels.iter()).record(duration.as_secs_f64());
 
     // Record error with type
-    metrics::counter!(
-        names::ERRORS_TOTAL,
-        labels::keys::PROVIDER => provider.to_string(),
-        labels::keys::MODEL => model.to_string(),
-        labels::keys::ERROR_TYPE => labels::error_type_label(error).to_string()
-    )
-    .increment(1);
+    let mut error_labels = request_labels;
+    error_labels.push(metrics::Label::new(
+        labels::keys::ERROR_TYPE,
+        labels::error_type_label(error),
+    ));
+    metrics::counter!(names::ERRORS_TOTAL, error_labels.iter()).increment(1);
 
     // Track rate limits specifically
-    if matches!(error, LlmError::RateLimit(_)) {
+    if matches!(error, LlmError::RateLimit { .. }) {
         metrics::counter!(
             names::RATE_LIMITS_TOTAL,
             labels::keys::PROVIDER => provider.to_string()
@@ -117,3 +100,35 @@ pub fn set_provider_health(provider: &str, healthy: bool) {
     )
     .set(if healthy { 1.0 } else { 0.0 });
 }
+
+/// Update the in-flight requests gauge
+pub fn set_inflight_requests(provider: &str, model: &str, count: usize) {
+    metrics::gauge!(
+        names::INFLIGHT_REQUESTS,
+        labels::keys::PROVIDER => provider.to_string(),
+        labels::keys::MODEL => model.to_string()
+    )
+    .set(count as f64);
+}
+
+/// Record a response served from the cache
+pub fn record_cache_hit(provider: &str, model: &str) {
+    metrics::counter!(
+        names::CACHE_HITS_TOTAL,
+        labels::keys::PROVIDER => provider.to_string(),
+        labels::keys::MODEL => model.to_string()
+    )
+    .increment(1);
+}
+
+/// Add the estimated cost of a response in USD
+///
+/// Emitted as a monotonically increasing gauge since counters only hold integers.
+pub fn record_cost(provider: &str, model: &str, usd: f64) {
+    metrics::gauge!(
+        names::COST_USD_TOTAL,
+        labels::keys::PROVIDER => provider.to_string(),
+        labels::keys::MODEL => model.to_string()
+    )
+    .increment(usd);
+}
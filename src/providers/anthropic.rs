@@ -1,40 +1,57 @@
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::providers::instances::{LlmInstance, BaseInstance};
-use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage};
-use crate::providers::streaming::AnthropicStreamEvent;
+use crate::providers::instances::{LlmInstance, BaseInstance, InstanceSettings, normalize_finish_reason};
+use crate::providers::types::{ContentPart, LlmRequest, LlmResponse, LlmStream, Message, Role, TokenUsage, ToolCall, ToolDefinition};
+use crate::providers::streaming::{parse_lines, AnthropicStreamParser};
+use crate::providers::tokens::context_window_for_model;
 use crate::errors::{LlmError, LlmResult};
 use crate::constants;
 
 use async_trait::async_trait;
 use reqwest::header;
 use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
 use std::collections::HashMap;
-use futures::StreamExt;
 
 /// Provider implementation for Anthropic's Claude API
 pub struct AnthropicInstance {
     base: BaseInstance,
+    beta: Option<header::HeaderValue>,
 }
 
 /// Request structure for the Anthropic Claude API
 #[derive(Serialize)]
 struct AnthropicRequest {
     model: String,
+    /// A plain string, or an array of text blocks when the system prompt is cached
     #[serde(skip_serializing_if = "Option::is_none")]
-    system: Option<String>,
+    system: Option<Value>,
     messages: Vec<AnthropicMessage>,
     max_tokens: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<AnthropicTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+}
+
+/// Tool definition in Anthropic's format
+#[derive(Serialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: Value,
 }
 
 /// Individual message structure for Anthropic's API
+///
+/// `content` is a plain string, or an array of content blocks for multimodal messages.
 #[derive(Serialize)]
 struct AnthropicMessage {
     role: String,
-    content: String,
+    content: Value,
 }
 
 /// Response structure from Anthropic's Claude API
@@ -43,14 +60,25 @@ struct AnthropicResponse {
     content: Vec<AnthropicContent>,
     model: String,
     usage: Option<AnthropicUsage>,
+    #[serde(default)]
+    stop_reason: Option<String>,
 }
 
 /// Content block from Anthropic's response
+///
+/// Text blocks carry `text`; `tool_use` blocks carry `id`, `name` and `input`.
 #[derive(Deserialize)]
 struct AnthropicContent {
+    #[serde(default)]
     text: String,
     #[serde(rename = "type")]
     content_type: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    input: Option<Value>,
 }
 
 /// Token usage information from Anthropic
@@ -58,6 +86,108 @@ struct AnthropicContent {
 struct AnthropicUsage {
     input_tokens: u32,
     output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: Option<u32>,
+    #[serde(default)]
+    cache_read_input_tokens: Option<u32>,
+}
+
+impl AnthropicResponse {
+    /// Convert Anthropic's response into the unified response format
+    fn into_llm_response(self) -> LlmResult<LlmResponse> {
+        if self.content.is_empty() {
+            return Err(LlmError::ApiError("No response from Anthropic".to_string()));
+        }
+
+        let usage = self.usage.map(|u| TokenUsage {
+            prompt_tokens: u.input_tokens,
+            completion_tokens: u.output_tokens,
+            total_tokens: u.input_tokens + u.output_tokens,
+            cache_creation_tokens: u.cache_creation_input_tokens,
+            cache_read_tokens: u.cache_read_input_tokens,
+        });
+
+        let text = self.content.iter()
+            .filter(|c| c.content_type == "text")
+            .map(|c| c.text.clone())
+            .collect::<Vec<String>>()
+            .join("");
+
+        let tool_calls = self.content.into_iter()
+            .filter(|c| c.content_type == "tool_use")
+            .map(|c| ToolCall {
+                id: c.id.unwrap_or_default(),
+                name: c.name.unwrap_or_default(),
+                arguments: c.input.unwrap_or_else(|| json!({})).to_string(),
+            })
+            .collect();
+
+        Ok(LlmResponse {
+            content: text,
+            model: self.model,
+            usage,
+            tool_calls,
+            finish_reason: self.stop_reason.as_deref().map(normalize_finish_reason),
+            ..Default::default()
+        })
+    }
+}
+
+/// Convert a message's content into Anthropic's format, using blocks only when parts are present
+/// or the message is cached
+///
+/// Cached messages get `cache_control` on their last block, which makes them a cache breakpoint.
+fn convert_content(message: &Message) -> Value {
+    let mut blocks: Vec<Value> = match &message.content_parts {
+        Some(parts) => parts.iter().map(convert_content_part).collect(),
+        None if message.cache => vec![json!({ "type": "text", "text": message.content })],
+        None => return json!(message.content),
+    };
+    if message.cache {
+        if let Some(last) = blocks.last_mut() {
+            last["cache_control"] = json!({ "type": "ephemeral" });
+        }
+    }
+    Value::Array(blocks)
+}
+
+/// Convert a single content part into an Anthropic content block
+fn convert_content_part(part: &ContentPart) -> Value {
+    match part {
+        ContentPart::Text(text) => json!({ "type": "text", "text": text }),
+        ContentPart::ImageUrl { url, .. } => json!({
+            "type": "image",
+            "source": { "type": "url", "url": url },
+        }),
+        ContentPart::ImageBase64 { media_type, data } => json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": media_type, "data": data },
+        }),
+    }
+}
+
+/// Convert unified tool definitions into Anthropic's format
+fn convert_tools(tools: &[ToolDefinition]) -> Vec<AnthropicTool> {
+    tools
+        .iter()
+        .map(|tool| AnthropicTool {
+            name: tool.name.clone(),
+            description: tool.description.clone(),
+            input_schema: tool.parameters.clone(),
+        })
+        .collect()
+}
+
+/// Convert a unified tool choice into Anthropic's format
+///
+/// "required" maps to Anthropic's "any"; any unrecognised value forces that tool by name.
+fn convert_tool_choice(choice: &str) -> Value {
+    match choice {
+        "auto" => json!({ "type": "auto" }),
+        "none" => json!({ "type": "none" }),
+        "required" | "any" => json!({ "type": "any" }),
+        name => json!({ "type": "tool", "name": name }),
+    }
 }
 
 impl AnthropicInstance {
@@ -68,9 +198,17 @@ impl AnthropicInstance {
     /// * `model` - Default model to use (e.g. "claude-3-opus-20240229")
     /// * `supported_tasks` - Map of tasks this provider supports
     /// * `enabled` - Whether this provider is enabled
-    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool) -> Self {
-        let base = BaseInstance::new("anthropic".to_string(), api_key, model, supported_tasks, enabled);
-        Self { base }
+    /// * `settings` - Extra HTTP settings such as custom headers and beta features
+    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, settings: &InstanceSettings) -> LlmResult<Self> {
+        let beta = match settings.anthropic_beta.as_deref() {
+            Some(features) if !features.is_empty() => Some(
+                header::HeaderValue::from_str(&features.join(","))
+                    .map_err(|e| LlmError::ConfigError(format!("Invalid anthropic-beta features: {}", e)))?,
+            ),
+            _ => None,
+        };
+        let base = BaseInstance::new("anthropic".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        Ok(Self { base, beta })
     }
 
     /// Build request headers for Anthropic API
@@ -89,21 +227,24 @@ impl AnthropicInstance {
             "anthropic-version",
             header::HeaderValue::from_static(constants::ANTHROPIC_API_VERSION),
         );
+        if let Some(beta) = &self.beta {
+            headers.insert("anthropic-beta", beta.clone());
+        }
         Ok(headers)
     }
 
     /// Prepare messages for Anthropic API format
-    fn prepare_messages(&self, request: &LlmRequest) -> Result<(Option<String>, Vec<AnthropicMessage>), LlmError> {
+    fn prepare_messages(&self, request: &LlmRequest) -> Result<(Option<Value>, Vec<AnthropicMessage>), LlmError> {
         let mut system_content = None;
         let mut regular_messages = Vec::new();
 
         for msg in &request.messages {
-            if msg.role == "system" {
-                system_content = Some(msg.content.clone());
+            if msg.role_kind() == Some(Role::System) {
+                system_content = Some(msg);
             } else {
                 regular_messages.push(AnthropicMessage {
-                    role: msg.role.clone(),
-                    content: msg.content.clone(),
+                    role: msg.canonical_role().to_string(),
+                    content: convert_content(msg),
                 });
             }
         }
@@ -112,7 +253,7 @@ impl AnthropicInstance {
         if regular_messages.is_empty() && system_content.is_some() {
             regular_messages.push(AnthropicMessage {
                 role: "user".to_string(),
-                content: format!("Using this context: {}", system_content.as_ref().unwrap()),
+                content: json!(format!("Using this context: {}", system_content.unwrap().content)),
             });
             system_content = None;
         }
@@ -121,7 +262,25 @@ impl AnthropicInstance {
             return Err(LlmError::ApiError("Anthropic requires at least one message".to_string()));
         }
 
-        Ok((system_content, regular_messages))
+        Ok((system_content.map(convert_content), regular_messages))
+    }
+
+    /// Build the Messages API body for a non-streaming request
+    ///
+    /// The API requires `max_tokens`, so without a request limit or instance default
+    /// `constants::DEFAULT_MAX_TOKENS` is sent.
+    fn build_request(&self, request: &LlmRequest) -> LlmResult<AnthropicRequest> {
+        let (system_content, regular_messages) = self.prepare_messages(request)?;
+        Ok(AnthropicRequest {
+            model: request.model.clone().unwrap_or_else(|| self.base.model().to_string()),
+            system: system_content,
+            messages: regular_messages,
+            max_tokens: self.base.max_tokens(request).unwrap_or(constants::DEFAULT_MAX_TOKENS),
+            temperature: request.temperature,
+            stream: None,
+            tools: request.tools.as_deref().map(convert_tools),
+            tool_choice: request.tool_choice.as_deref().map(convert_tool_choice),
+        })
     }
 }
 
@@ -134,17 +293,7 @@ impl LlmInstance for AnthropicInstance {
         }
 
         let headers = self.build_headers()?;
-        let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
-        let (system_content, regular_messages) = self.prepare_messages(request)?;
-
-        let anthropic_request = AnthropicRequest {
-            model,
-            system: system_content,
-            messages: regular_messages,
-            max_tokens: request.max_tokens.unwrap_or(constants::DEFAULT_MAX_TOKENS),
-            temperature: request.temperature,
-            stream: None,
-        };
+        let anthropic_request = self.build_request(request)?;
 
         let response = self.base.client()
             .post(constants::ANTHROPIC_API_ENDPOINT)
@@ -153,35 +302,22 @@ impl LlmInstance for AnthropicInstance {
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("Anthropic rate limit: {}", error_text), &headers));
+        }
+
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Anthropic API error: {}", error_text)));
+            return Err(LlmError::api("Anthropic", status, &error_text));
         }
 
         let anthropic_response: AnthropicResponse = response.json().await?;
-
-        if anthropic_response.content.is_empty() {
-            return Err(LlmError::ApiError("No response from Anthropic".to_string()));
-        }
-
-        let usage = anthropic_response.usage.map(|u| TokenUsage {
-            prompt_tokens: u.input_tokens,
-            completion_tokens: u.output_tokens,
-            total_tokens: u.input_tokens + u.output_tokens,
-        });
-
-        let text = anthropic_response.content.iter()
-            .filter(|c| c.content_type == "text")
-            .map(|c| c.text.clone())
-            .collect::<Vec<String>>()
-            .join("");
-
-        Ok(LlmResponse {
-            content: text,
-            model: anthropic_response.model,
-            usage,
-        })
+        anthropic_response.into_llm_response()
     }
 
     /// Generates a streaming completion using Anthropic's Claude API
@@ -191,16 +327,11 @@ impl LlmInstance for AnthropicInstance {
         }
 
         let headers = self.build_headers()?;
-        let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
-        let (system_content, regular_messages) = self.prepare_messages(request)?;
-
         let anthropic_request = AnthropicRequest {
-            model,
-            system: system_content,
-            messages: regular_messages,
-            max_tokens: request.max_tokens.unwrap_or(constants::DEFAULT_MAX_TOKENS),
-            temperature: request.temperature,
             stream: Some(true),
+            tools: None,
+            tool_choice: None,
+            ..self.build_request(request)?
         };
 
         let response = self.base.client()
@@ -210,54 +341,22 @@ impl LlmInstance for AnthropicInstance {
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("Anthropic rate limit: {}", error_text), &headers));
+        }
+
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Anthropic API error: {}", error_text)));
+            return Err(LlmError::api("Anthropic", status, &error_text));
         }
 
-        // Create a stream that processes the SSE response
-        let byte_stream = response.bytes_stream();
-
-        let chunk_stream = byte_stream
-            .map(|result| {
-                result.map_err(|e| LlmError::RequestError(e))
-            })
-            .flat_map(|result| {
-                match result {
-                    Ok(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-                        let chunks: Vec<Result<StreamChunk, LlmError>> = text
-                            .lines()
-                            .filter_map(|line| {
-                                let line = line.trim();
-                                if line.starts_with("data: ") {
-                                    let data = &line[6..];
-                                    if data == "[DONE]" {
-                                        return None;
-                                    }
-                                    match serde_json::from_str::<AnthropicStreamEvent>(data) {
-                                        Ok(event) => event.to_stream_chunk().map(Ok),
-                                        Err(e) => {
-                                            // Skip parse errors for non-content events
-                                            log::debug!("Failed to parse Anthropic streaming event: {}", e);
-                                            None
-                                        }
-                                    }
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-                        futures::stream::iter(chunks)
-                    }
-                    Err(e) => {
-                        futures::stream::iter(vec![Err(e)])
-                    }
-                }
-            });
-
-        Ok(Box::pin(chunk_stream))
+        let mut parser = AnthropicStreamParser::default();
+        Ok(parse_lines(response.bytes_stream(), move |line| parser.parse_line(line)))
     }
 
     /// Returns whether this provider supports native streaming
@@ -265,6 +364,11 @@ impl LlmInstance for AnthropicInstance {
         true
     }
 
+    /// Looks the model up in the table of known context windows
+    fn context_window(&self) -> Option<u32> {
+        context_window_for_model(self.base.model())
+    }
+
     /// Returns provider name
     fn get_name(&self) -> &str {
         self.base.name()
@@ -285,3 +389,175 @@ impl LlmInstance for AnthropicInstance {
         self.base.is_enabled()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_map_to_anthropic_roles() {
+        let instance = AnthropicInstance::new("key".to_string(), "claude-3-5-sonnet".to_string(), HashMap::new(), true, &InstanceSettings::default()).unwrap();
+        let request = LlmRequest {
+            messages: vec![
+                Message::system("Be brief."),
+                Message::user("Hi"),
+                Message { role: "model".to_string(), content: "Hello!".to_string(), ..Default::default() },
+                Message { role: "USER".to_string(), content: "Thanks".to_string(), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+
+        let (system, messages) = instance.prepare_messages(&request).unwrap();
+        assert_eq!(system, Some(json!("Be brief.")));
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+    }
+
+    #[test]
+    fn test_beta_features_sent_in_one_header() {
+        let settings = InstanceSettings {
+            anthropic_beta: Some(vec!["prompt-caching-2024-07-31".to_string(), "context-1m-2025-08-07".to_string()]),
+            ..Default::default()
+        };
+        let instance = AnthropicInstance::new("key".to_string(), "claude-sonnet-4".to_string(), HashMap::new(), true, &settings).unwrap();
+        let headers = instance.build_headers().unwrap();
+        assert_eq!(headers["anthropic-beta"], "prompt-caching-2024-07-31,context-1m-2025-08-07");
+        assert_eq!(headers["anthropic-version"], constants::ANTHROPIC_API_VERSION);
+
+        let instance = AnthropicInstance::new("key".to_string(), "claude-sonnet-4".to_string(), HashMap::new(), true, &InstanceSettings::default()).unwrap();
+        assert!(!instance.build_headers().unwrap().contains_key("anthropic-beta"));
+    }
+
+    #[test]
+    fn test_default_max_tokens_sent_when_request_has_none() {
+        let settings = InstanceSettings { default_max_tokens: Some(4096), ..Default::default() };
+        let instance = AnthropicInstance::new("key".to_string(), "claude-sonnet-4".to_string(), HashMap::new(), true, &settings).unwrap();
+        let mut request = LlmRequest { messages: vec![Message::user("Hi")], ..Default::default() };
+
+        let body = serde_json::to_value(instance.build_request(&request).unwrap()).unwrap();
+        assert_eq!(body["max_tokens"], 4096);
+
+        request.max_tokens = Some(100);
+        let body = serde_json::to_value(instance.build_request(&request).unwrap()).unwrap();
+        assert_eq!(body["max_tokens"], 100);
+
+        let instance = AnthropicInstance::new("key".to_string(), "claude-sonnet-4".to_string(), HashMap::new(), true, &InstanceSettings::default()).unwrap();
+        request.max_tokens = None;
+        let body = serde_json::to_value(instance.build_request(&request).unwrap()).unwrap();
+        assert_eq!(body["max_tokens"], constants::DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_tool_use_response_populates_tool_calls() {
+        let body = r#"{
+            "model": "claude-3-5-sonnet",
+            "content": [
+                {"type": "text", "text": "Checking the weather."},
+                {"type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": {"city": "Paris"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 12, "output_tokens": 8}
+        }"#;
+
+        let parsed: AnthropicResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response().unwrap();
+
+        assert_eq!(response.content, "Checking the weather.");
+        assert_eq!(response.tool_calls.len(), 1);
+        assert_eq!(response.tool_calls[0].id, "toolu_01");
+        assert_eq!(response.tool_calls[0].name, "get_weather");
+        assert_eq!(response.tool_calls[0].arguments, r#"{"city":"Paris"}"#);
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn test_max_tokens_stop_reason_is_normalized() {
+        let body = r#"{
+            "model": "claude-3-5-sonnet",
+            "content": [{"type": "text", "text": "Once upon a"}],
+            "stop_reason": "max_tokens"
+        }"#;
+
+        let parsed: AnthropicResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response().unwrap();
+
+        assert_eq!(response.finish_reason.as_deref(), Some("length"));
+    }
+
+    #[test]
+    fn test_cached_messages_serialize_with_cache_control() {
+        let instance = AnthropicInstance::new("key".to_string(), "claude-3-5-sonnet".to_string(), HashMap::new(), true, &InstanceSettings::default()).unwrap();
+        let request = LlmRequest {
+            messages: vec![
+                Message::system("A long style guide.").cached(),
+                Message::user("Reference document").cached(),
+                Message::user("Summarize it"),
+            ],
+            ..Default::default()
+        };
+
+        let (system, messages) = instance.prepare_messages(&request).unwrap();
+        assert_eq!(system, Some(json!([
+            {"type": "text", "text": "A long style guide.", "cache_control": {"type": "ephemeral"}}
+        ])));
+        let messages = serde_json::to_value(messages).unwrap();
+        assert_eq!(messages[0]["content"], json!([
+            {"type": "text", "text": "Reference document", "cache_control": {"type": "ephemeral"}}
+        ]));
+        assert_eq!(messages[1]["content"], "Summarize it");
+
+        // With content parts only the last block is marked
+        let message = Message {
+            content_parts: Some(vec![
+                ContentPart::Text("Describe this".to_string()),
+                ContentPart::ImageBase64 { media_type: "image/png".to_string(), data: "aGk=".to_string() },
+            ]),
+            ..Message::user("Describe this").cached()
+        };
+        let content = convert_content(&message);
+        assert!(content[0].get("cache_control").is_none());
+        assert_eq!(content[1]["cache_control"], json!({"type": "ephemeral"}));
+    }
+
+    #[test]
+    fn test_cache_usage_is_reported() {
+        let body = r#"{
+            "model": "claude-3-5-sonnet",
+            "content": [{"type": "text", "text": "Done."}],
+            "usage": {"input_tokens": 12, "output_tokens": 3, "cache_creation_input_tokens": 2048, "cache_read_input_tokens": 0}
+        }"#;
+
+        let parsed: AnthropicResponse = serde_json::from_str(body).unwrap();
+        let usage = parsed.into_llm_response().unwrap().usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.cache_creation_tokens, Some(2048));
+        assert_eq!(usage.cache_read_tokens, Some(0));
+    }
+
+    #[test]
+    fn test_image_content_blocks() {
+        let message = Message {
+            role: "user".to_string(),
+            content: "Describe this".to_string(),
+            content_parts: Some(vec![
+                ContentPart::Text("Describe this".to_string()),
+                ContentPart::ImageBase64 { media_type: "image/jpeg".to_string(), data: "aGk=".to_string() },
+            ]),
+            ..Default::default()
+        };
+
+        let content = convert_content(&message);
+        assert_eq!(content[0], json!({"type": "text", "text": "Describe this"}));
+        assert_eq!(content[1]["type"], "image");
+        assert_eq!(content[1]["source"], json!({"type": "base64", "media_type": "image/jpeg", "data": "aGk="}));
+
+        let text_only = Message { content_parts: None, ..message };
+        assert_eq!(convert_content(&text_only), json!("Describe this"));
+    }
+
+    #[test]
+    fn test_tool_choice_conversion() {
+        assert_eq!(convert_tool_choice("required"), json!({"type": "any"}));
+        assert_eq!(convert_tool_choice("get_weather"), json!({"type": "tool", "name": "get_weather"}));
+    }
+}
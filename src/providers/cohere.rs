@@ -1,15 +1,16 @@
 use std::collections::HashMap;
 
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::providers::instances::{LlmInstance, BaseInstance};
-use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message};
+use crate::providers::instances::{LlmInstance, EmbeddingInstance, BaseInstance, InstanceSettings, ensure_text_only, normalize_finish_reason};
+use crate::providers::types::{EmbeddingResponse, LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message};
 use crate::errors::{LlmError, LlmResult};
 use crate::constants;
 
 use async_trait::async_trait;
 use reqwest::header;
 use serde::{Serialize, Deserialize};
-use futures::StreamExt;
+use crate::providers::streaming::{parse_lines, parse_sse_line};
+use crate::providers::tokens::context_window_for_model;
 
 /// Provider implementation for Cohere's API (v2)
 ///
@@ -36,6 +37,8 @@ struct CohereRequest {
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    documents: Option<Vec<serde_json::Value>>,
     stream: bool,
 }
 
@@ -45,6 +48,8 @@ struct CohereResponse {
     message: CohereResponseMessage,
     #[serde(default)]
     usage: Option<CohereUsage>,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 /// Response message from Cohere
@@ -52,6 +57,9 @@ struct CohereResponse {
 struct CohereResponseMessage {
     role: String,
     content: Vec<CohereContentBlock>,
+    /// Spans of the answer grounded in the request's documents
+    #[serde(default)]
+    citations: Vec<serde_json::Value>,
 }
 
 /// Content block in Cohere response
@@ -129,6 +137,39 @@ struct CohereContentDeltaContent {
 struct CohereMessageEndDelta {
     #[serde(default)]
     usage: Option<CohereUsage>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+/// Request structure for Cohere's v2 embed API
+#[derive(Serialize)]
+struct CohereEmbedRequest<'a> {
+    model: &'a str,
+    texts: &'a [String],
+    input_type: &'static str,
+    embedding_types: Vec<&'static str>,
+}
+
+/// Response structure from Cohere's v2 embed API
+#[derive(Deserialize, Debug)]
+struct CohereEmbedResponse {
+    embeddings: CohereEmbeddings,
+    #[serde(default)]
+    meta: Option<CohereEmbedMeta>,
+}
+
+/// Embeddings grouped by type in Cohere's response
+#[derive(Deserialize, Debug)]
+struct CohereEmbeddings {
+    #[serde(default)]
+    float: Vec<Vec<f32>>,
+}
+
+/// Metadata from Cohere's embed response, carrying billed units
+#[derive(Deserialize, Debug)]
+struct CohereEmbedMeta {
+    #[serde(default)]
+    billed_units: Option<CohereBilledUnits>,
 }
 
 impl CohereInstance {
@@ -139,14 +180,16 @@ impl CohereInstance {
     /// * `model` - Default model to use (e.g., "command-r-plus", "command-r")
     /// * `supported_tasks` - Map of tasks this provider supports
     /// * `enabled` - Whether this provider is enabled
+    /// * `settings` - Extra HTTP settings such as custom headers
     pub fn new(
         api_key: String,
         model: String,
         supported_tasks: HashMap<String, TaskDefinition>,
         enabled: bool,
-    ) -> Self {
-        let base = BaseInstance::new("cohere".to_string(), api_key, model, supported_tasks, enabled);
-        Self { base }
+        settings: &InstanceSettings,
+    ) -> LlmResult<Self> {
+        let base = BaseInstance::new("cohere".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        Ok(Self { base })
     }
 
     /// Convert internal Message format to Cohere's message format
@@ -154,12 +197,24 @@ impl CohereInstance {
         messages
             .iter()
             .map(|m| CohereMessage {
-                role: m.role.clone(),
+                role: m.canonical_role().to_string(),
                 content: m.content.clone(),
             })
             .collect()
     }
 
+    /// Build the chat request body, passing the request's documents through for grounded answers
+    fn build_request(&self, request: &LlmRequest, stream: bool) -> CohereRequest {
+        CohereRequest {
+            model: request.model.clone().unwrap_or_else(|| self.base.model().to_string()),
+            messages: Self::convert_messages(&request.messages),
+            max_tokens: self.base.max_tokens(request),
+            temperature: request.temperature,
+            documents: request.documents.clone(),
+            stream,
+        }
+    }
+
     /// Extract text content from Cohere's response content blocks
     fn extract_content(content_blocks: &[CohereContentBlock]) -> String {
         content_blocks
@@ -183,6 +238,7 @@ impl LlmInstance for CohereInstance {
         if !self.base.is_enabled() {
             return Err(LlmError::ProviderDisabled("Cohere".to_string()));
         }
+        ensure_text_only(request, "Cohere")?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -199,15 +255,8 @@ impl LlmInstance for CohereInstance {
             header::HeaderValue::from_static("application/json"),
         );
 
-        let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
-
-        let cohere_request = CohereRequest {
-            model: model.clone(),
-            messages: Self::convert_messages(&request.messages),
-            max_tokens: request.max_tokens,
-            temperature: request.temperature,
-            stream: false,
-        };
+        let cohere_request = self.build_request(request, false);
+        let model = cohere_request.model.clone();
 
         let response = self
             .base
@@ -222,11 +271,12 @@ impl LlmInstance for CohereInstance {
 
         // Check for rate limiting
         if response_status.as_u16() == 429 {
+            let headers = response.headers().clone();
             let error_text = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Rate limit exceeded".to_string());
-            return Err(LlmError::RateLimit(format!("Cohere rate limit: {}", error_text)));
+            return Err(LlmError::rate_limit(format!("Cohere rate limit: {}", error_text), &headers));
         }
 
         if !response_status.is_success() {
@@ -234,7 +284,7 @@ impl LlmInstance for CohereInstance {
                 .text()
                 .await
                 .unwrap_or_else(|_| format!("Unknown error. Status: {}", response_status));
-            return Err(LlmError::ApiError(format!("Cohere API error: {}", error_text)));
+            return Err(LlmError::api("Cohere", response_status, &error_text));
         }
 
         let response_text = response.text().await?;
@@ -255,6 +305,14 @@ impl LlmInstance for CohereInstance {
         // Extract text content from response
         let content = Self::extract_content(&cohere_response.message.content);
 
+        let mut metadata = HashMap::new();
+        if !cohere_response.message.citations.is_empty() {
+            metadata.insert(
+                "citations".to_string(),
+                serde_json::Value::Array(cohere_response.message.citations),
+            );
+        }
+
         // Map token usage - Cohere v2 uses different structures
         let usage = cohere_response.usage.and_then(|u| {
             // Try tokens first, then billed_units
@@ -265,6 +323,7 @@ impl LlmInstance for CohereInstance {
                     prompt_tokens: input,
                     completion_tokens: output,
                     total_tokens: input + output,
+                    ..Default::default()
                 })
             } else if let Some(billed) = u.billed_units {
                 let input = billed.input_tokens.unwrap_or(0);
@@ -273,6 +332,7 @@ impl LlmInstance for CohereInstance {
                     prompt_tokens: input,
                     completion_tokens: output,
                     total_tokens: input + output,
+                    ..Default::default()
                 })
             } else {
                 None
@@ -283,6 +343,9 @@ impl LlmInstance for CohereInstance {
             content,
             model,
             usage,
+            finish_reason: cohere_response.finish_reason.as_deref().map(normalize_finish_reason),
+            metadata,
+            ..Default::default()
         })
     }
 
@@ -290,6 +353,7 @@ impl LlmInstance for CohereInstance {
         if !self.base.is_enabled() {
             return Err(LlmError::ProviderDisabled("Cohere".to_string()));
         }
+        ensure_text_only(request, "Cohere")?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -306,15 +370,7 @@ impl LlmInstance for CohereInstance {
             header::HeaderValue::from_static("application/json"),
         );
 
-        let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
-
-        let cohere_request = CohereRequest {
-            model: model.clone(),
-            messages: Self::convert_messages(&request.messages),
-            max_tokens: request.max_tokens,
-            temperature: request.temperature,
-            stream: true,
-        };
+        let cohere_request = self.build_request(request, true);
 
         let response = self
             .base
@@ -328,11 +384,12 @@ impl LlmInstance for CohereInstance {
         let response_status = response.status();
 
         if response_status.as_u16() == 429 {
+            let headers = response.headers().clone();
             let error_text = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Rate limit exceeded".to_string());
-            return Err(LlmError::RateLimit(format!("Cohere rate limit: {}", error_text)));
+            return Err(LlmError::rate_limit(format!("Cohere rate limit: {}", error_text), &headers));
         }
 
         if !response_status.is_success() {
@@ -340,97 +397,86 @@ impl LlmInstance for CohereInstance {
                 .text()
                 .await
                 .unwrap_or_else(|_| format!("Unknown error. Status: {}", response_status));
-            return Err(LlmError::ApiError(format!("Cohere API error: {}", error_text)));
+            return Err(LlmError::api("Cohere", response_status, &error_text));
         }
 
-        let byte_stream = response.bytes_stream();
-
         // Cohere uses SSE with JSON events
-        let chunk_stream = byte_stream
-            .map(|result| result.map_err(|e| LlmError::RequestError(e)))
-            .flat_map(|result| {
-                match result {
-                    Ok(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-                        let chunks: Vec<Result<StreamChunk, LlmError>> = text
-                            .lines()
-                            .filter_map(|line| {
-                                let line = line.trim();
-                                // Cohere SSE format: data: {...}
-                                if line.starts_with("data: ") {
-                                    let data = &line[6..];
-                                    match serde_json::from_str::<CohereStreamEvent>(data) {
-                                        Ok(event) => {
-                                            match event {
-                                                CohereStreamEvent::ContentDelta { delta } => {
-                                                    if let Some(delta) = delta {
-                                                        if let Some(message) = delta.message {
-                                                            if let Some(content) = message.content {
-                                                                if let Some(text) = content.text {
-                                                                    return Some(Ok(StreamChunk {
-                                                                        content: text,
-                                                                        model: None,
-                                                                        is_final: false,
-                                                                        usage: None,
-                                                                    }));
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                    None
-                                                }
-                                                CohereStreamEvent::MessageEnd { delta } => {
-                                                    let usage = delta.and_then(|d| d.usage).and_then(|u| {
-                                                        if let Some(tokens) = u.tokens {
-                                                            let input = tokens.input_tokens.unwrap_or(0);
-                                                            let output = tokens.output_tokens.unwrap_or(0);
-                                                            Some(TokenUsage {
-                                                                prompt_tokens: input,
-                                                                completion_tokens: output,
-                                                                total_tokens: input + output,
-                                                            })
-                                                        } else if let Some(billed) = u.billed_units {
-                                                            let input = billed.input_tokens.unwrap_or(0);
-                                                            let output = billed.output_tokens.unwrap_or(0);
-                                                            Some(TokenUsage {
-                                                                prompt_tokens: input,
-                                                                completion_tokens: output,
-                                                                total_tokens: input + output,
-                                                            })
-                                                        } else {
-                                                            None
-                                                        }
-                                                    });
-                                                    Some(Ok(StreamChunk {
-                                                        content: String::new(),
-                                                        model: None,
-                                                        is_final: true,
-                                                        usage,
-                                                    }))
-                                                }
-                                                _ => None, // Skip other event types
-                                            }
+        Ok(parse_lines(response.bytes_stream(), |line| {
+            // Cohere SSE format: data: {...}
+            let data = parse_sse_line(line)?;
+            match serde_json::from_str::<CohereStreamEvent>(data) {
+                Ok(event) => {
+                    match event {
+                        CohereStreamEvent::ContentDelta { delta } => {
+                            if let Some(delta) = delta {
+                                if let Some(message) = delta.message {
+                                    if let Some(content) = message.content {
+                                        if let Some(text) = content.text {
+                                            return Some(Ok(StreamChunk {
+                                                content: text,
+                                                model: None,
+                                                is_final: false,
+                                                usage: None,
+                                                finish_reason: None,
+                                            }));
                                         }
-                                        Err(_) => None, // Skip unparseable events
                                     }
+                                }
+                            }
+                            None
+                        }
+                        CohereStreamEvent::MessageEnd { delta } => {
+                            let (finish_reason, usage) = match delta {
+                                Some(delta) => (delta.finish_reason, delta.usage),
+                                None => (None, None),
+                            };
+                            let usage = usage.and_then(|u| {
+                                if let Some(tokens) = u.tokens {
+                                    let input = tokens.input_tokens.unwrap_or(0);
+                                    let output = tokens.output_tokens.unwrap_or(0);
+                                    Some(TokenUsage {
+                                        prompt_tokens: input,
+                                        completion_tokens: output,
+                                        total_tokens: input + output,
+                                        ..Default::default()
+                                    })
+                                } else if let Some(billed) = u.billed_units {
+                                    let input = billed.input_tokens.unwrap_or(0);
+                                    let output = billed.output_tokens.unwrap_or(0);
+                                    Some(TokenUsage {
+                                        prompt_tokens: input,
+                                        completion_tokens: output,
+                                        total_tokens: input + output,
+                                        ..Default::default()
+                                    })
                                 } else {
                                     None
                                 }
-                            })
-                            .collect();
-                        futures::stream::iter(chunks)
+                            });
+                            Some(Ok(StreamChunk {
+                                content: String::new(),
+                                model: None,
+                                is_final: true,
+                                usage,
+                                finish_reason: finish_reason.as_deref().map(normalize_finish_reason),
+                            }))
+                        }
+                        _ => None, // Skip other event types
                     }
-                    Err(e) => futures::stream::iter(vec![Err(e)])
                 }
-            });
-
-        Ok(Box::pin(chunk_stream))
+                Err(_) => None, // Skip unparseable events
+            }
+        }))
     }
 
     fn supports_streaming(&self) -> bool {
         true
     }
 
+    fn context_window(&self) -> Option<u32> {
+        context_window_for_model(self.base.model())
+    }
+
     fn get_name(&self) -> &str {
         self.base.name()
     }
@@ -446,4 +492,145 @@ impl LlmInstance for CohereInstance {
     fn is_enabled(&self) -> bool {
         self.base.is_enabled()
     }
+
+    fn as_embedding(&self) -> Option<&(dyn EmbeddingInstance + Send + Sync)> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl EmbeddingInstance for CohereInstance {
+    /// Embeds the inputs using Cohere's v2 embed API with the configured model
+    async fn embed_with_usage(&self, inputs: &[String]) -> LlmResult<EmbeddingResponse> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled("Cohere".to_string()));
+        }
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key()))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+        );
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        let embed_request = CohereEmbedRequest {
+            model: self.base.model(),
+            texts: inputs,
+            input_type: "search_document",
+            embedding_types: vec!["float"],
+        };
+
+        let response = self
+            .base
+            .client()
+            .post(constants::COHERE_EMBED_ENDPOINT)
+            .headers(headers)
+            .json(&embed_request)
+            .send()
+            .await?;
+
+        let response_status = response.status();
+
+        // Check for rate limiting
+        if response_status.as_u16() == 429 {
+            let headers = response.headers().clone();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("Cohere rate limit: {}", error_text), &headers));
+        }
+
+        if !response_status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| format!("Unknown error. Status: {}", response_status));
+            return Err(LlmError::api("Cohere", response_status, &error_text));
+        }
+
+        let response_text = response.text().await?;
+        let embed_response: CohereEmbedResponse = serde_json::from_str(&response_text)
+            .map_err(|e| {
+                LlmError::ApiError(format!(
+                    "Failed to parse Cohere embed response: {}",
+                    e
+                ))
+            })?;
+
+        // Embedding calls are billed on input tokens only
+        let usage = embed_response
+            .meta
+            .and_then(|m| m.billed_units)
+            .map(|billed| {
+                let input = billed.input_tokens.unwrap_or(0);
+                TokenUsage {
+                    prompt_tokens: input,
+                    completion_tokens: 0,
+                    total_tokens: input,
+                    ..Default::default()
+                }
+            });
+
+        Ok(EmbeddingResponse {
+            embeddings: embed_response.embeddings.float,
+            model: self.base.model().to_string(),
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn instance() -> CohereInstance {
+        CohereInstance::new("key".to_string(), "command-r-plus".to_string(), HashMap::new(), true, &InstanceSettings::default()).unwrap()
+    }
+
+    #[test]
+    fn test_documents_serialized_into_request_body() {
+        let request = LlmRequest {
+            messages: vec![Message::user("When was the bridge built?")],
+            documents: Some(vec![json!({ "id": "doc-1", "data": { "text": "The bridge opened in 1932." } })]),
+            ..Default::default()
+        };
+
+        let body = serde_json::to_value(instance().build_request(&request, false)).unwrap();
+        assert_eq!(body["documents"][0]["id"], "doc-1");
+        assert_eq!(body["documents"][0]["data"]["text"], "The bridge opened in 1932.");
+    }
+
+    #[test]
+    fn test_documents_omitted_when_absent() {
+        let request = LlmRequest { messages: vec![Message::user("Hi")], ..Default::default() };
+
+        let body = serde_json::to_value(instance().build_request(&request, true)).unwrap();
+        assert!(body.get("documents").is_none());
+        assert_eq!(body["stream"], true);
+    }
+
+    #[test]
+    fn test_response_citations_parsed() {
+        let response: CohereResponse = serde_json::from_value(json!({
+            "message": {
+                "role": "assistant",
+                "content": [{ "type": "text", "text": "It opened in 1932." }],
+                "citations": [{ "start": 11, "end": 18, "text": "in 1932", "sources": [{ "type": "document", "id": "doc-1" }] }]
+            },
+            "finish_reason": "COMPLETE"
+        })).unwrap();
+
+        assert_eq!(response.message.citations.len(), 1);
+        assert_eq!(response.message.citations[0]["sources"][0]["id"], "doc-1");
+    }
 }
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::instances::{InstanceSettings, openai_compatible_url};
+use crate::providers::openai_compat::{OpenAICompatibleProvider, CompatConfig, AuthScheme, delegate_llm_instance};
+use crate::errors::LlmResult;
+use crate::constants;
+
+/// Provider implementation for Fireworks AI's API
+///
+/// Fireworks serves open models with fast inference.
+/// API endpoint: https://api.fireworks.ai/inference/v1/chat/completions
+/// Uses OpenAI-compatible API format with Bearer token authentication.
+///
+/// Model names are namespaced paths (e.g. "accounts/fireworks/models/llama-v3p1-70b-instruct")
+/// and are sent as given.
+pub struct FireworksInstance(OpenAICompatibleProvider);
+
+impl FireworksInstance {
+    /// Creates a new Fireworks provider instance
+    pub fn new(
+        api_key: String,
+        model: String,
+        supported_tasks: HashMap<String, TaskDefinition>,
+        enabled: bool,
+        endpoint_url: Option<String>,
+        settings: &InstanceSettings,
+    ) -> LlmResult<Self> {
+        let config = CompatConfig {
+            name: "fireworks",
+            display_name: "Fireworks",
+            chat_url: openai_compatible_url(endpoint_url.as_deref(), constants::FIREWORKS_API_ENDPOINT, "/chat/completions"),
+            models_url: Some(openai_compatible_url(endpoint_url.as_deref(), constants::FIREWORKS_MODELS_ENDPOINT, "/models")),
+            auth: AuthScheme::Bearer,
+            streaming: true,
+            seed: true,
+            logprobs: true,
+            known_models: true,
+        };
+        OpenAICompatibleProvider::new(config, api_key, model, supported_tasks, enabled, settings).map(Self)
+    }
+}
+
+delegate_llm_instance!(FireworksInstance);
@@ -0,0 +1,418 @@
+//! Gemini request and response mapping shared by the Google and Vertex AI providers
+//!
+//! Both APIs take the same `generateContent` body and return the same candidates;
+//! they differ only in URL and authentication.
+
+use crate::providers::instances::normalize_finish_reason;
+use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message, ResponseFormat, Role};
+use crate::errors::{LlmError, LlmResult};
+
+use serde::{Serialize, Deserialize};
+use log::debug;
+use crate::providers::streaming::{parse_lines, parse_sse_line};
+
+/// Request structure for Google's Gemini API
+#[derive(Serialize)]
+pub(crate) struct GoogleGenerateContentRequest {
+    contents: Vec<GoogleContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "generationConfig")]
+    generation_config: Option<GoogleGenerationConfig>,
+}
+
+/// Content structure for Google's Gemini API messages
+#[derive(Serialize, Deserialize)]
+struct GoogleContent {
+    // Final streamed candidates may omit the role or parts
+    #[serde(default)]
+    role: String,
+    #[serde(default)]
+    parts: Vec<GooglePart>,
+}
+
+/// Individual content part for Google's Gemini API
+#[derive(Serialize, Deserialize)]
+struct GooglePart {
+    text: String,
+}
+
+/// Generation configuration for Google's Gemini API
+#[derive(Serialize, Default)] 
+struct GoogleGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    // #[serde(skip_serializing_if = "Option::is_none")]
+    // top_k: Option<u32>,
+    // #[serde(skip_serializing_if = "Option::is_none")]
+    // top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "maxOutputTokens")]
+    max_output_tokens: Option<u32>,
+    // #[serde(skip_serializing_if = "Option::is_none")]
+    // stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "responseMimeType")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "responseSchema")]
+    response_schema: Option<serde_json::Value>,
+}
+
+impl GoogleGenerationConfig {
+    /// Build the generation config for a request, or `None` if nothing needs setting
+    ///
+    /// `max_tokens` is the request's limit after the instance's default is applied.
+    fn from_request(request: &LlmRequest, max_tokens: Option<u32>) -> Option<Self> {
+        let (response_mime_type, response_schema) = match &request.response_format {
+            Some(ResponseFormat::JsonObject) => (Some("application/json".to_string()), None),
+            Some(ResponseFormat::JsonSchema { schema }) => {
+                (Some("application/json".to_string()), Some(schema.clone()))
+            }
+            Some(ResponseFormat::Text) | None => (None, None),
+        };
+
+        let config = Self {
+            temperature: request.temperature,
+            max_output_tokens: max_tokens,
+            response_mime_type,
+            response_schema,
+        };
+
+        Some(config).filter(|gc| {
+            gc.temperature.is_some() || gc.max_output_tokens.is_some() || gc.response_mime_type.is_some()
+        })
+    }
+}
+
+/// Response structure from Google's Gemini API
+#[derive(Deserialize)]
+struct GoogleGenerateContentResponse {
+    candidates: Vec<GoogleCandidate>,
+    #[serde(rename = "usageMetadata")]
+    #[serde(default)]
+    usage_metadata: Option<GoogleUsageMetadata>,
+}
+
+impl GoogleGenerateContentResponse {
+    /// Convert Google's response into the unified response format
+    fn into_llm_response(self, model: &str) -> LlmResult<LlmResponse> {
+        let Some(candidate) = self.candidates.into_iter().next() else {
+            return Err(LlmError::ApiError("No candidates returned from Google. Content may have been blocked.".to_string()));
+        };
+
+        let combined_content = candidate.content.parts.iter()
+            .map(|part| part.text.clone())
+            .collect::<Vec<String>>()
+            .join("");
+
+        let usage = match self.usage_metadata {
+            Some(metadata) => Some(metadata.to_token_usage()),
+            // Older responses only carry a per-candidate total
+            None if candidate.token_count > 0 => Some(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: candidate.token_count,
+                ..Default::default()
+            }),
+            None => None,
+        };
+
+        debug!("Google usage: {:?}", usage);
+
+        Ok(LlmResponse {
+            content: combined_content,
+            model: model.to_string(),
+            usage,
+            finish_reason: candidate.finish_reason.as_deref().map(normalize_finish_reason),
+            ..Default::default()
+        })
+    }
+}
+
+/// Token counts reported by Google for a request
+#[derive(Deserialize)]
+struct GoogleUsageMetadata {
+    #[serde(rename = "promptTokenCount")]
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(rename = "candidatesTokenCount")]
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(rename = "totalTokenCount")]
+    #[serde(default)]
+    total_token_count: u32,
+}
+
+impl GoogleUsageMetadata {
+    /// Convert to the unified usage format
+    fn to_token_usage(&self) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_token_count,
+            completion_tokens: self.candidates_token_count,
+            total_tokens: self.total_token_count,
+            ..Default::default()
+        }
+    }
+}
+
+/// Individual candidate from Google's Gemini API response
+#[derive(Deserialize)]
+struct GoogleCandidate {
+    content: GoogleContent,
+    #[serde(rename = "tokenCount")]
+    #[serde(default)]
+    token_count: u32, // Note: Google provides total token count here
+    #[serde(rename = "finishReason")]
+    #[serde(default)]
+    finish_reason: Option<String>,
+    // safety_ratings: Vec<SafetyRating>, // We don't use this currently
+}
+
+/// Streaming response structure from Google's Gemini API
+#[derive(Deserialize)]
+struct GoogleStreamChunk {
+    candidates: Option<Vec<GoogleStreamCandidate>>,
+    #[serde(rename = "usageMetadata")]
+    #[serde(default)]
+    usage_metadata: Option<GoogleUsageMetadata>,
+}
+
+/// Streaming candidate from Google's response
+#[derive(Deserialize)]
+struct GoogleStreamCandidate {
+    content: Option<GoogleContent>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
+}
+
+impl GoogleStreamChunk {
+    /// Convert to a StreamChunk
+    ///
+    /// The chunk carrying `finishReason` is final and reports the request's token usage,
+    /// even when it has no text.
+    fn to_stream_chunk(&self) -> Option<StreamChunk> {
+        let candidate = self.candidates.as_ref()?.first()?;
+        let text = candidate.content.as_ref()
+            .map(|content| content.parts.iter().map(|p| p.text.as_str()).collect::<String>())
+            .unwrap_or_default();
+
+        let is_final = candidate.finish_reason.is_some();
+        if !is_final && candidate.content.is_none() {
+            return None;
+        }
+
+        // Usage is cumulative, so only report the final count
+        let usage = self.usage_metadata.as_ref()
+            .filter(|_| is_final)
+            .map(GoogleUsageMetadata::to_token_usage);
+
+        Some(StreamChunk {
+            content: text,
+            model: None,
+            is_final,
+            usage,
+            finish_reason: candidate.finish_reason.as_deref().map(normalize_finish_reason),
+        })
+    }
+}
+
+/// Gemini's name for a conversation role; the assistant is "model"
+fn gemini_role(role: Role) -> &'static str {
+    match role {
+        Role::Assistant => "model",
+        role => role.as_str(),
+    }
+}
+
+/// Maps standard message format to Google's expected format
+///
+/// This function handles several Google-specific requirements:
+/// - Converts "assistant" role to "model" role
+/// - Prepends system messages to the first user message
+/// - Validates that the first message is from the user
+///
+/// # Parameters
+/// * `messages` - Array of messages in our standard format
+///
+/// # Returns
+/// * `LlmResult<Vec<GoogleContent>>` - Mapped contents or an error
+fn map_messages_to_contents(messages: &[Message]) -> LlmResult<Vec<GoogleContent>> {
+    let mut contents = Vec::new();
+    let mut system_prompt: Option<String> = None;
+    let mut first_user_message_index: Option<usize> = None;
+    for (_, msg) in messages.iter().enumerate() {
+         match msg.role_kind() {
+             Some(Role::System) => {
+                 if system_prompt.is_some() {
+                     return Err(LlmError::ApiError("Multiple system messages are not supported by Google provider mapping.".to_string()));
+                 }
+                 system_prompt = Some(msg.content.clone());
+             }
+             Some(role) => {
+                 if role == Role::User && first_user_message_index.is_none() {
+                    first_user_message_index = Some(contents.len()); 
+                 }
+                 contents.push(GoogleContent {
+                     role: gemini_role(role).to_string(),
+                     parts: vec![GooglePart { text: msg.content.clone() }],
+                 });
+             }
+             None => {
+                 log::warn!("Ignoring message with unknown role: {}", msg.role);
+             }
+         }
+    }
+    
+    if let Some(sys_prompt) = &system_prompt {
+        if let Some(user_idx) = first_user_message_index {
+            if let Some(user_content) = contents.get_mut(user_idx) {
+                if let Some(part) = user_content.parts.get_mut(0) {
+                    part.text = format!("{}\n\n{}", sys_prompt, part.text);
+                }
+            } else {
+                return Err(LlmError::ApiError("System message provided but no user message found.".to_string()));
+            }
+        } else {
+            return Err(LlmError::ApiError("System message provided but no user message found.".to_string()));
+        }
+    }
+    
+    if contents.is_empty() {
+        return Err(LlmError::ApiError("No valid messages found for Google provider.".to_string()));
+    }
+    if contents[0].role != "user" {
+         return Err(LlmError::ApiError(format!("Google chat must start with a 'user' role message, found '{}'.", contents[0].role)));
+    }
+    Ok(contents)
+}
+
+/// Build a `generateContent` body from a unified request, with `max_tokens` as the output limit
+pub(crate) fn build_request(request: &LlmRequest, max_tokens: Option<u32>) -> LlmResult<GoogleGenerateContentRequest> {
+    Ok(GoogleGenerateContentRequest {
+        contents: map_messages_to_contents(&request.messages)?,
+        generation_config: GoogleGenerationConfig::from_request(request, max_tokens),
+    })
+}
+
+/// Parse a successful `generateContent` response
+///
+/// # Parameters
+/// * `response` - HTTP response with a success status
+/// * `model` - Model name reported in the unified response
+pub(crate) async fn parse_response(response: reqwest::Response, model: &str) -> LlmResult<LlmResponse> {
+    let google_response: GoogleGenerateContentResponse = response.json().await
+        .map_err(|e| LlmError::ApiError(format!("Failed to parse Google JSON response: {}", e)))?;
+    google_response.into_llm_response(model)
+}
+
+/// Turn a successful `streamGenerateContent?alt=sse` response into a stream of chunks
+pub(crate) fn parse_stream(response: reqwest::Response) -> LlmStream {
+    // Google SSE format: data: {...}
+    parse_lines(response.bytes_stream(), |line| {
+        match serde_json::from_str::<GoogleStreamChunk>(parse_sse_line(line)?) {
+            Ok(chunk) => chunk.to_stream_chunk().map(Ok),
+            Err(e) => {
+                // Skip events that aren't content chunks
+                debug!("Failed to parse Google streaming chunk: {}", e);
+                None
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_generation_config_json_schema() {
+        let schema = json!({"type": "object", "properties": {"answer": {"type": "string"}}});
+        let request = LlmRequest {
+            response_format: Some(ResponseFormat::JsonSchema { schema: schema.clone() }),
+            ..Default::default()
+        };
+
+        let config = GoogleGenerationConfig::from_request(&request, None).unwrap();
+        let value = serde_json::to_value(config).unwrap();
+        assert_eq!(value["responseMimeType"], "application/json");
+        assert_eq!(value["responseSchema"], schema);
+    }
+
+    #[test]
+    fn test_roles_map_to_gemini_roles() {
+        let messages = vec![
+            Message::system("Be brief."),
+            Message::user("Hi"),
+            Message::assistant("Hello!"),
+            Message { role: "Model".to_string(), content: "Again".to_string(), ..Default::default() },
+            Message { role: "tool".to_string(), content: "ignored".to_string(), ..Default::default() },
+        ];
+
+        let contents = map_messages_to_contents(&messages).unwrap();
+        let roles: Vec<&str> = contents.iter().map(|c| c.role.as_str()).collect();
+        assert_eq!(roles, ["user", "model", "model"]);
+        assert_eq!(contents[0].parts[0].text, "Be brief.\n\nHi");
+    }
+
+    #[test]
+    fn test_generation_config_omitted_when_empty() {
+        assert!(GoogleGenerationConfig::from_request(&LlmRequest::default(), None).is_none());
+    }
+
+    #[test]
+    fn test_usage_metadata_splits_prompt_and_completion() {
+        let body = r#"{
+            "candidates": [{
+                "content": {"parts": [{"text": "Hello"}, {"text": " there"}], "role": "model"},
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 3, "totalTokenCount": 15},
+            "modelVersion": "gemini-2.0-flash"
+        }"#;
+
+        let parsed: GoogleGenerateContentResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response("gemini-2.0-flash").unwrap();
+
+        assert_eq!(response.content, "Hello there");
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(response.usage, Some(TokenUsage {
+            prompt_tokens: 12,
+            completion_tokens: 3,
+            total_tokens: 15,
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_legacy_token_count_used_without_usage_metadata() {
+        let body = r#"{"candidates": [{"content": {"parts": [{"text": "Hi"}], "role": "model"}, "tokenCount": 7}]}"#;
+
+        let parsed: GoogleGenerateContentResponse = serde_json::from_str(body).unwrap();
+        let usage = parsed.into_llm_response("gemini-pro").unwrap().usage.unwrap();
+
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (0, 0, 7));
+    }
+
+    #[test]
+    fn test_final_stream_chunk_carries_usage() {
+        let content = r#"{"candidates": [{"content": {"parts": [{"text": "Hel"}], "role": "model"}}], "usageMetadata": {"promptTokenCount": 12, "totalTokenCount": 12}}"#;
+        let chunk = serde_json::from_str::<GoogleStreamChunk>(content).unwrap().to_stream_chunk().unwrap();
+        assert_eq!(chunk.content, "Hel");
+        assert!(!chunk.is_final);
+        assert!(chunk.usage.is_none());
+
+        let last = r#"{"candidates": [{"content": {"role": "model"}, "finishReason": "MAX_TOKENS"}], "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 5, "totalTokenCount": 17}}"#;
+        let chunk = serde_json::from_str::<GoogleStreamChunk>(last).unwrap().to_stream_chunk().unwrap();
+        assert!(chunk.is_final);
+        assert_eq!(chunk.content, "");
+        assert_eq!(chunk.finish_reason.as_deref(), Some("length"));
+        assert_eq!(chunk.usage, Some(TokenUsage {
+            prompt_tokens: 12,
+            completion_tokens: 5,
+            total_tokens: 17,
+            ..Default::default()
+        }));
+    }
+}
@@ -1,22 +1,29 @@
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, ProviderType, StreamChunk};
+use crate::providers::types::{EmbeddingResponse, LlmRequest, LlmResponse, LlmStream, Message, ProviderType, StreamChunk};
 use crate::providers::anthropic::AnthropicInstance;
 use crate::providers::openai::OpenAIInstance;
 use crate::providers::ollama::OllamaInstance;
 use crate::providers::google::GoogleInstance;
+use crate::providers::vertex::VertexAIInstance;
 use crate::providers::mistral::MistralInstance;
 use crate::providers::lmstudio::LMStudioInstance;
 use crate::providers::groq::GroqInstance;
+use crate::providers::replicate::ReplicateInstance;
+use crate::providers::ai21::Ai21Instance;
+use crate::providers::fireworks::FireworksInstance;
+use crate::providers::xai::XaiInstance;
 use crate::providers::cohere::CohereInstance;
 use crate::providers::togetherai::TogetherAIInstance;
 use crate::providers::perplexity::PerplexityInstance;
-use crate::errors::{LlmResult};
+use crate::errors::{LlmError, LlmResult};
+use crate::constants;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use std::time::Duration;
 use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use futures::stream;
 
 /// Common interface for all LLM instances
@@ -40,6 +47,7 @@ pub trait LlmInstance {
             model: Some(response.model),
             is_final: true,
             usage: response.usage,
+            finish_reason: response.finish_reason,
         };
         Ok(Box::pin(stream::once(async move { Ok(chunk) })))
     }
@@ -49,6 +57,20 @@ pub trait LlmInstance {
         false // Default: no native streaming support
     }
 
+    /// Check if this instance honours `LlmRequest::response_format`
+    fn supports_structured_output(&self) -> bool {
+        false // Default: response format is ignored and plain text is returned
+    }
+
+    /// Maximum prompt plus output tokens the model accepts, if known
+    ///
+    /// Default implementation returns `None`. The hosted providers look their model up
+    /// in `tokens::context_window_for_model`; a `context_window` set on the provider's
+    /// config takes precedence over either.
+    fn context_window(&self) -> Option<u32> {
+        None
+    }
+
     /// Get the name of this instance
     fn get_name(&self) -> &str;
     /// Get the currently configured model name
@@ -57,23 +79,193 @@ pub trait LlmInstance {
     fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition>;
     /// Check if this instance is enabled
     fn is_enabled(&self) -> bool;
+
+    /// Probe whether this instance can currently serve requests
+    ///
+    /// Used by the manager's background health checks. Default implementation sends a
+    /// one-token request; providers with a cheaper probe (e.g. a models endpoint) can
+    /// override it.
+    async fn health_check(&self) -> LlmResult<()> {
+        let request = LlmRequest {
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: "ping".to_string(),
+                ..Default::default()
+            }],
+            max_tokens: Some(1),
+            ..Default::default()
+        };
+        self.generate(&request).await.map(|_| ())
+    }
+
+    /// Get this instance as an embedding provider, if it supports embeddings
+    ///
+    /// Default implementation returns `None`, which keeps chat-only providers
+    /// out of embedding selection.
+    fn as_embedding(&self) -> Option<&(dyn EmbeddingInstance + Send + Sync)> {
+        None
+    }
+}
+
+/// Interface for instances that can produce embedding vectors
+#[async_trait]
+pub trait EmbeddingInstance {
+    /// Embed the given inputs, returning the vectors along with token usage
+    async fn embed_with_usage(&self, inputs: &[String]) -> LlmResult<EmbeddingResponse>;
+
+    /// Embed the given inputs, returning one vector per input
+    async fn embed(&self, inputs: &[String]) -> LlmResult<Vec<Vec<f32>>> {
+        Ok(self.embed_with_usage(inputs).await?.embeddings)
+    }
+}
+
+/// Reject requests containing images for providers without vision support
+///
+/// # Parameters
+/// * `request` - The request to check
+/// * `provider` - Provider display name used in the error message
+pub(crate) fn ensure_text_only(request: &LlmRequest, provider: &str) -> LlmResult<()> {
+    if request.has_images() {
+        return Err(LlmError::ApiError(format!(
+            "{} does not support image input in FlyLLM; use an OpenAI or Anthropic instance for vision requests",
+            provider
+        )));
+    }
+    Ok(())
+}
+
+/// Optional HTTP settings applied to an instance's client
+#[derive(Debug, Clone, Default)]
+pub struct InstanceSettings {
+    /// Extra headers sent with every request (e.g. `X-Org-Id` for a proxy)
+    pub custom_headers: HashMap<String, String>,
+    /// Proxy URL all requests are routed through. When unset, reqwest falls back
+    /// to the `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` environment variables.
+    pub proxy: Option<String>,
+    /// Overall timeout of each request, including reading the response (defaults to 120s)
+    pub timeout: Option<Duration>,
+    /// Timeout for establishing a connection (defaults to none beyond `timeout`)
+    pub connect_timeout: Option<Duration>,
+    /// Client to share with other instances instead of opening a separate connection pool.
+    /// Ignored when any of the settings above are set, since they need a dedicated client.
+    pub shared_client: Option<Arc<Client>>,
+    /// Header that carries the raw API key instead of `Authorization: Bearer`
+    /// (OpenAI-compatible providers only, e.g. `api-key`)
+    pub auth_header: Option<String>,
+    /// Path of the model list under the endpoint, used for health checks
+    /// (OpenAI-compatible providers only, defaults to `/models`)
+    pub models_path: Option<String>,
+    /// Google Cloud project that requests are billed to (Vertex AI only, required)
+    pub project_id: Option<String>,
+    /// Google Cloud region serving the model (Vertex AI only, defaults to `us-central1`)
+    pub location: Option<String>,
+    /// Beta features sent in the `anthropic-beta` header (Anthropic only,
+    /// e.g. `prompt-caching-2024-07-31`)
+    pub anthropic_beta: Option<Vec<String>>,
+    /// Output token limit used when a request doesn't set `max_tokens`. Without it the
+    /// limit is left to the provider, except for Anthropic, which requires one and
+    /// falls back to `constants::DEFAULT_MAX_TOKENS`.
+    pub default_max_tokens: Option<u32>,
+    /// `User-Agent` sent with every request (defaults to `constants::DEFAULT_USER_AGENT`,
+    /// `flyllm/<version>`). A `User-Agent` in `custom_headers` takes precedence.
+    pub user_agent: Option<String>,
+}
+
+impl InstanceSettings {
+    /// Whether these settings need a client of their own rather than a shared one
+    fn needs_dedicated_client(&self) -> bool {
+        !self.custom_headers.is_empty()
+            || self.proxy.is_some()
+            || self.timeout.is_some()
+            || self.connect_timeout.is_some()
+            || self.user_agent.is_some()
+    }
+}
+
+/// Build a proxy routing all traffic through `url`, still honouring `NO_PROXY`
+pub(crate) fn build_proxy(url: &str) -> LlmResult<reqwest::Proxy> {
+    reqwest::Proxy::all(url)
+        .map(|proxy| proxy.no_proxy(reqwest::NoProxy::from_env()))
+        .map_err(|e| LlmError::ConfigError(format!("Invalid proxy URL '{}': {}", url, e)))
+}
+
+/// Send a GET request used as a health probe, mapping failures like any other call
+///
+/// # Parameters
+/// * `client` - The instance's HTTP client
+/// * `url` - Endpoint to probe (typically a model listing)
+/// * `headers` - Authentication headers for the provider
+/// * `provider` - Provider name used in error messages
+pub(crate) async fn probe_endpoint(client: &Client, url: &str, headers: HeaderMap, provider: &str) -> LlmResult<()> {
+    let response = client.get(url).headers(headers).send().await?;
+    let status = response.status();
+
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let headers = response.headers().clone();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(LlmError::rate_limit(format!("{} rate limit: {}", provider, error_text), &headers));
+    }
+    if !status.is_success() {
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(LlmError::api(provider, status, &error_text));
+    }
+    Ok(())
+}
+
+/// Resolve the URL of an OpenAI-compatible endpoint
+///
+/// A custom base such as `https://proxy.example.com/v1` gets `path` appended; a base
+/// that already ends in `/chat/completions` is treated as the chat URL and replaced.
+///
+/// # Parameters
+/// * `custom_base` - Optional base URL override
+/// * `default_url` - Full URL used when no override is given
+/// * `path` - Path of the endpoint relative to the API base (e.g. `/chat/completions`)
+pub(crate) fn openai_compatible_url(custom_base: Option<&str>, default_url: &str, path: &str) -> String {
+    match custom_base {
+        Some(base) => {
+            let base = base.trim_end_matches('/');
+            let base = base.strip_suffix("/chat/completions").unwrap_or(base);
+            format!("{}{}", base, path)
+        }
+        None => default_url.to_string(),
+    }
+}
+
+/// Map a provider's stop reason onto the common `finish_reason` values
+///
+/// Natural stops become `"stop"`, token limits `"length"`, tool use `"tool_calls"` and
+/// safety blocks `"content_filter"`. Anything else is passed through, lowercased.
+pub(crate) fn normalize_finish_reason(reason: &str) -> String {
+    let reason = reason.to_ascii_lowercase();
+    let normalized = match reason.as_str() {
+        "stop" | "end_turn" | "stop_sequence" | "complete" => "stop",
+        "length" | "max_tokens" | "model_length" => "length",
+        "tool_calls" | "tool_use" | "tool_call" | "function_call" => "tool_calls",
+        "content_filter" | "safety" | "recitation" | "blocklist" | "prohibited_content" | "spii"
+        | "refusal" => "content_filter",
+        _ => return reason,
+    };
+    normalized.to_string()
 }
 
 /// Base instance implementation with common functionality
 ///
 /// Handles common properties and functionality shared across all instances:
-/// - HTTP client with timeout
+/// - HTTP client with timeout (possibly shared with other instances)
 /// - API key storage
 /// - Model selection
 /// - Task support
 /// - Enable/disable status
 pub struct BaseInstance {
     name: String,
-    client: Client,
+    client: Arc<Client>,
     api_key: String,
     model: String,
     supported_tasks: HashMap<String, TaskDefinition>,
     enabled: bool,
+    default_max_tokens: Option<u32>,
 }
 
 impl BaseInstance {
@@ -85,13 +277,51 @@ impl BaseInstance {
     /// * `model` - Default model identifier to use
     /// * `supported_tasks` - Map of tasks this instance supports
     /// * `enabled` - Whether this instance is enabled
-    pub fn new(name: String, api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .expect("Failed to create HTTP client");
+    /// * `settings` - Extra HTTP settings such as custom headers and proxy
+    pub fn new(name: String, api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, settings: &InstanceSettings) -> LlmResult<Self> {
+        let client = match &settings.shared_client {
+            Some(shared) if !settings.needs_dedicated_client() => shared.clone(),
+            _ => Arc::new(Self::build_client(&name, settings)?),
+        };
+
+        Ok(Self {
+            name,
+            client,
+            api_key,
+            model,
+            supported_tasks,
+            enabled,
+            default_max_tokens: settings.default_max_tokens,
+        })
+    }
+
+    /// Build a dedicated HTTP client for an instance
+    fn build_client(name: &str, settings: &InstanceSettings) -> LlmResult<Client> {
+        let mut default_headers = HeaderMap::new();
+        for (key, value) in &settings.custom_headers {
+            let header_name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| LlmError::ConfigError(format!("Invalid custom header name '{}' for {}: {}", key, name, e)))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|e| LlmError::ConfigError(format!("Invalid value for custom header '{}' for {}: {}", key, name, e)))?;
+            default_headers.insert(header_name, header_value);
+        }
 
-        Self { name, client, api_key, model, supported_tasks, enabled }
+        let timeout = settings.timeout.unwrap_or(Duration::from_secs(constants::DEFAULT_REQUEST_TIMEOUT_SECS));
+        // Custom headers are applied after the user agent so they can replace it
+        let user_agent = settings.user_agent.as_deref().unwrap_or(constants::DEFAULT_USER_AGENT);
+        let mut client_builder = Client::builder()
+            .timeout(timeout)
+            .user_agent(user_agent)
+            .default_headers(default_headers);
+        if let Some(connect_timeout) = settings.connect_timeout {
+            client_builder = client_builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy_url) = &settings.proxy {
+            client_builder = client_builder.proxy(build_proxy(proxy_url)?);
+        }
+        client_builder
+            .build()
+            .map_err(|e| LlmError::ConfigError(format!("Failed to create HTTP client for {}: {}", name, e)))
     }
 
     /// Get the HTTP client instance
@@ -99,6 +329,11 @@ impl BaseInstance {
         &self.client
     }
 
+    /// Get the HTTP client as a shareable handle, e.g. to check whether it is shared
+    pub fn http_client(&self) -> &Arc<Client> {
+        &self.client
+    }
+
     /// Get the API key
     pub fn api_key(&self) -> &str {
         &self.api_key
@@ -109,6 +344,11 @@ impl BaseInstance {
         &self.model
     }
 
+    /// Output token limit for a request: its `max_tokens`, or else the instance's `default_max_tokens`
+    pub fn max_tokens(&self, request: &LlmRequest) -> Option<u32> {
+        request.max_tokens.or(self.default_max_tokens)
+    }
+
     /// Check if this instance is enabled
     pub fn is_enabled(&self) -> bool {
         self.enabled
@@ -133,24 +373,34 @@ impl BaseInstance {
 /// * `model` - Default model identifier
 /// * `supported_tasks` - List of tasks this instance supports
 /// * `enabled` - Whether this instance should be enabled
+/// * `endpoint_url` - Optional base URL override (Ollama, LM Studio, OpenAI-compatible, Vertex AI and Replicate providers)
+/// * `settings` - Extra HTTP settings such as custom headers and proxy
 ///
 /// # Returns
-/// * Arc-wrapped trait object implementing Llminstance
-pub fn create_instance(instance_type: ProviderType, api_key: String, model: String, supported_tasks: Vec<TaskDefinition>, enabled: bool, endpoint_url: Option<String>) -> Arc<dyn LlmInstance + Send + Sync> {
+/// * Arc-wrapped trait object implementing Llminstance, or a `ConfigError` if its HTTP client could not be built
+pub fn create_instance(instance_type: ProviderType, api_key: String, model: String, supported_tasks: Vec<TaskDefinition>, enabled: bool, endpoint_url: Option<String>, settings: InstanceSettings) -> LlmResult<Arc<dyn LlmInstance + Send + Sync>> {
     let supported_tasks: HashMap<String, TaskDefinition> = supported_tasks
         .into_iter()  
         .map(|task| (task.name.clone(), task)) 
         .collect();
-    match instance_type {
-        ProviderType::Anthropic => Arc::new(AnthropicInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::OpenAI => Arc::new(OpenAIInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::Mistral => Arc::new(MistralInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::Google => Arc::new(GoogleInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::Ollama => Arc::new(OllamaInstance::new(api_key, model, supported_tasks, enabled, endpoint_url)),
-        ProviderType::LMStudio => Arc::new(LMStudioInstance::new(api_key, model, supported_tasks, enabled, endpoint_url)),
-        ProviderType::Groq => Arc::new(GroqInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::Cohere => Arc::new(CohereInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::TogetherAI => Arc::new(TogetherAIInstance::new(api_key, model, supported_tasks, enabled)),
-        ProviderType::Perplexity => Arc::new(PerplexityInstance::new(api_key, model, supported_tasks, enabled)),
-    }
-}
\ No newline at end of file
+    let settings = &settings;
+    let instance: Arc<dyn LlmInstance + Send + Sync> = match instance_type {
+        ProviderType::Anthropic => Arc::new(AnthropicInstance::new(api_key, model, supported_tasks, enabled, settings)?),
+        ProviderType::OpenAI => Arc::new(OpenAIInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Mistral => Arc::new(MistralInstance::new(api_key, model, supported_tasks, enabled, settings)?),
+        ProviderType::Google => Arc::new(GoogleInstance::new(api_key, model, supported_tasks, enabled, settings)?),
+        ProviderType::Ollama => Arc::new(OllamaInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::LMStudio => Arc::new(LMStudioInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Groq => Arc::new(GroqInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Cohere => Arc::new(CohereInstance::new(api_key, model, supported_tasks, enabled, settings)?),
+        ProviderType::TogetherAI => Arc::new(TogetherAIInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Perplexity => Arc::new(PerplexityInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::OpenAICompatible => Arc::new(OpenAIInstance::compatible(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::VertexAI => Arc::new(VertexAIInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Xai => Arc::new(XaiInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Replicate => Arc::new(ReplicateInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Ai21 => Arc::new(Ai21Instance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+        ProviderType::Fireworks => Arc::new(FireworksInstance::new(api_key, model, supported_tasks, enabled, endpoint_url, settings)?),
+    };
+    Ok(instance)
+}
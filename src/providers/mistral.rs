@@ -1,7 +1,8 @@
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::providers::instances::{LlmInstance, BaseInstance};
-use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message};
-use crate::providers::streaming::OpenAIStreamChunk;
+use crate::providers::instances::{LlmInstance, EmbeddingInstance, BaseInstance, InstanceSettings, ensure_text_only, normalize_finish_reason};
+use crate::providers::types::{EmbeddingResponse, LlmRequest, LlmResponse, LlmStream, TokenUsage, Message, Role};
+use crate::providers::streaming::{parse_lines, parse_openai_sse_line};
+use crate::providers::tokens::context_window_for_model;
 use crate::errors::{LlmError, LlmResult};
 use crate::constants;
 
@@ -9,7 +10,6 @@ use std::collections::HashMap;
 use async_trait::async_trait;
 use reqwest::header;
 use serde::{Serialize, Deserialize};
-use futures::StreamExt;
 
 /// Provider implementation for Mistral AI's API
 pub struct MistralInstance {
@@ -27,6 +27,17 @@ struct MistralRequest {
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    // Mistral names the sampling seed `random_seed`
+    #[serde(rename = "random_seed", skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
 }
 
 /// Response structure from Mistral AI's chat completion API
@@ -56,6 +67,35 @@ struct MistralUsage {
     total_tokens: u32,
 }
 
+/// Request structure for Mistral AI's embeddings API
+#[derive(Serialize)]
+struct MistralEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+/// Response structure from Mistral AI's embeddings API
+#[derive(Deserialize, Debug)]
+struct MistralEmbeddingResponse {
+    model: String,
+    data: Vec<MistralEmbeddingData>,
+    usage: Option<MistralEmbeddingUsage>,
+}
+
+/// Individual embedding from Mistral's response
+#[derive(Deserialize, Debug)]
+struct MistralEmbeddingData {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+/// Token usage information from Mistral's embeddings API
+#[derive(Deserialize, Debug)]
+struct MistralEmbeddingUsage {
+    prompt_tokens: u32,
+    total_tokens: u32,
+}
+
 impl MistralInstance {
     /// Creates a new Mistral provider instance
     ///
@@ -64,9 +104,10 @@ impl MistralInstance {
     /// * `model` - Default model to use (e.g. "mistral-large")
     /// * `supported_tasks` - Map of tasks this provider supports
     /// * `enabled` - Whether this provider is enabled
-    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool) -> Self {
-        let base = BaseInstance::new("mistral".to_string(), api_key, model, supported_tasks, enabled);
-        Self { base }
+    /// * `settings` - Extra HTTP settings such as custom headers
+    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, settings: &InstanceSettings) -> LlmResult<Self> {
+        let base = BaseInstance::new("mistral".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        Ok(Self { base })
     }
 }
 
@@ -83,6 +124,7 @@ impl LlmInstance for MistralInstance {
         if !self.base.is_enabled() {
             return Err(LlmError::ProviderDisabled("Mistral".to_string()));
         }
+        ensure_text_only(request, "Mistral")?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -108,14 +150,17 @@ impl LlmInstance for MistralInstance {
         let mistral_request = MistralRequest {
             model,
             messages: request.messages.iter().map(|m| Message {
-                 role: match m.role.as_str() {
-                    "system" | "user" | "assistant" => m.role.clone(),
-                    _ => "user".to_string(),
-                 },
-                 content: m.content.clone()
+                 role: m.role_kind().unwrap_or(Role::User).as_str().to_string(),
+                 content: m.content.clone(),
+                 ..Default::default()
             }).collect(),
             temperature: request.temperature,
-            max_tokens: request.max_tokens,
+            seed: request.seed,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.clone(),
+            max_tokens: self.base.max_tokens(request),
             stream: None,
         };
 
@@ -126,14 +171,18 @@ impl LlmInstance for MistralInstance {
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("Mistral rate limit: {}", error_text), &headers));
+        }
+
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error reading response body".to_string());
-            return Err(LlmError::ApiError(format!(
-                "Mistral API error ({}): {}",
-                status, error_text
-            )));
+            return Err(LlmError::api("Mistral", status, &error_text));
         }
 
         // Debug: Log raw response body for inspection if needed
@@ -165,12 +214,15 @@ impl LlmInstance for MistralInstance {
             prompt_tokens: u.prompt_tokens,
             completion_tokens: u.completion_tokens,
             total_tokens: u.total_tokens,
+            ..Default::default()
         });
 
         Ok(LlmResponse {
             content: choice.message.content.clone(),
             model: mistral_response.model,
             usage,
+            finish_reason: choice.finish_reason.as_deref().map(normalize_finish_reason),
+            ..Default::default()
         })
     }
 
@@ -178,6 +230,7 @@ impl LlmInstance for MistralInstance {
         if !self.base.is_enabled() {
             return Err(LlmError::ProviderDisabled("Mistral".to_string()));
         }
+        ensure_text_only(request, "Mistral")?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -203,14 +256,17 @@ impl LlmInstance for MistralInstance {
         let mistral_request = MistralRequest {
             model,
             messages: request.messages.iter().map(|m| Message {
-                role: match m.role.as_str() {
-                    "system" | "user" | "assistant" => m.role.clone(),
-                    _ => "user".to_string(),
-                },
-                content: m.content.clone()
+                role: m.role_kind().unwrap_or(Role::User).as_str().to_string(),
+                content: m.content.clone(),
+                ..Default::default()
             }).collect(),
             temperature: request.temperature,
-            max_tokens: request.max_tokens,
+            seed: request.seed,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.clone(),
+            max_tokens: self.base.max_tokens(request),
             stream: Some(true),
         };
 
@@ -224,58 +280,30 @@ impl LlmInstance for MistralInstance {
         let response_status = response.status();
 
         if response_status.as_u16() == 429 {
+            let headers = response.headers().clone();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Rate limit exceeded".to_string());
-            return Err(LlmError::RateLimit(format!("Mistral rate limit: {}", error_text)));
+            return Err(LlmError::rate_limit(format!("Mistral rate limit: {}", error_text), &headers));
         }
 
         if !response_status.is_success() {
             let error_text = response.text().await
                 .unwrap_or_else(|_| format!("Unknown error. Status: {}", response_status));
-            return Err(LlmError::ApiError(format!("Mistral API error: {}", error_text)));
+            return Err(LlmError::api("Mistral", response_status, &error_text));
         }
 
-        let byte_stream = response.bytes_stream();
-
-        let chunk_stream = byte_stream
-            .map(|result| result.map_err(|e| LlmError::RequestError(e)))
-            .flat_map(|result| {
-                match result {
-                    Ok(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-                        let chunks: Vec<Result<StreamChunk, LlmError>> = text
-                            .lines()
-                            .filter_map(|line| {
-                                let line = line.trim();
-                                if line.starts_with("data: ") {
-                                    let data = &line[6..];
-                                    if data == "[DONE]" {
-                                        return None;
-                                    }
-                                    match serde_json::from_str::<OpenAIStreamChunk>(data) {
-                                        Ok(chunk) => chunk.to_stream_chunk().map(Ok),
-                                        Err(e) => Some(Err(LlmError::ParseError(
-                                            format!("Failed to parse streaming chunk: {}", e)
-                                        ))),
-                                    }
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-                        futures::stream::iter(chunks)
-                    }
-                    Err(e) => futures::stream::iter(vec![Err(e)])
-                }
-            });
-
-        Ok(Box::pin(chunk_stream))
+        Ok(parse_lines(response.bytes_stream(), parse_openai_sse_line))
     }
 
     fn supports_streaming(&self) -> bool {
         true
     }
 
+    /// Looks the model up in the table of known context windows
+    fn context_window(&self) -> Option<u32> {
+        context_window_for_model(self.base.model())
+    }
+
     /// Returns provider name
     fn get_name(&self) -> &str {
         self.base.name()
@@ -295,4 +323,85 @@ impl LlmInstance for MistralInstance {
     fn is_enabled(&self) -> bool {
         self.base.is_enabled()
     }
+
+    /// Returns this provider as an embedding provider
+    fn as_embedding(&self) -> Option<&(dyn EmbeddingInstance + Send + Sync)> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl EmbeddingInstance for MistralInstance {
+    /// Embeds the inputs using Mistral AI's embeddings API with the configured model
+    async fn embed_with_usage(&self, inputs: &[String]) -> LlmResult<EmbeddingResponse> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled("Mistral".to_string()));
+        }
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key()))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+        );
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        let embedding_request = MistralEmbeddingRequest {
+            model: self.base.model(),
+            input: inputs,
+        };
+
+        let response = self.base.client()
+            .post(constants::MISTRAL_EMBEDDINGS_ENDPOINT)
+            .headers(headers)
+            .json(&embedding_request)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("Mistral rate limit: {}", error_text), &headers));
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Unknown error reading response body".to_string());
+            return Err(LlmError::api("Mistral", status, &error_text));
+        }
+
+        let response_body = response.text().await
+            .map_err(|e| LlmError::ApiError(format!("Failed to read Mistral response body: {}", e)))?;
+
+        let mut embedding_response: MistralEmbeddingResponse = serde_json::from_str(&response_body)
+            .map_err(|e| {
+                LlmError::ApiError(format!(
+                    "Failed to parse Mistral embeddings response: {}",
+                    e
+                ))
+            })?;
+        embedding_response.data.sort_by_key(|d| d.index);
+
+        let usage = embedding_response.usage.map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: 0,
+            total_tokens: u.total_tokens,
+            ..Default::default()
+        });
+
+        Ok(EmbeddingResponse {
+            embeddings: embedding_response.data.into_iter().map(|d| d.embedding).collect(),
+            model: embedding_response.model,
+            usage,
+        })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use crate::errors::{LlmError, LlmResult};
+use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::instances::LlmInstance;
+use crate::providers::types::{LlmRequest, LlmResponse, TokenUsage};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Offline instance that answers with a canned response, for tests
+///
+/// Queued errors are returned by the first calls, in order; once they run out every
+/// call succeeds. Only available with the `test-util` feature.
+///
+/// ```ignore
+/// let flaky = MockInstance::new("mock-model")
+///     .with_response("hello")
+///     .with_errors(vec![LlmError::ApiError("boom".to_string())]);
+/// let id = manager.add_instance_dynamic(Arc::new(flaky)).await;
+/// ```
+pub struct MockInstance {
+    name: String,
+    model: String,
+    response: String,
+    usage: Option<TokenUsage>,
+    latency: Option<Duration>,
+    errors: Mutex<VecDeque<LlmError>>,
+    calls: AtomicUsize,
+    supported_tasks: HashMap<String, TaskDefinition>,
+    enabled: bool,
+}
+
+impl MockInstance {
+    /// Create a mock instance that answers "mock response" immediately
+    ///
+    /// # Parameters
+    /// * `model` - Model name reported by the instance and its responses
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            name: "mock".to_string(),
+            model: model.into(),
+            response: "mock response".to_string(),
+            usage: None,
+            latency: None,
+            errors: Mutex::new(VecDeque::new()),
+            calls: AtomicUsize::new(0),
+            supported_tasks: HashMap::new(),
+            enabled: true,
+        }
+    }
+
+    /// Report a different provider name (default "mock")
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the content of successful responses
+    pub fn with_response(mut self, content: impl Into<String>) -> Self {
+        self.response = content.into();
+        self
+    }
+
+    /// Set the token usage reported with successful responses
+    pub fn with_usage(mut self, usage: TokenUsage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Wait this long before answering each call, successful or not
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Queue errors returned by the next calls, one per call
+    pub fn with_errors(self, errors: Vec<LlmError>) -> Self {
+        self.errors.lock().unwrap().extend(errors);
+        self
+    }
+
+    /// Add a task this instance supports
+    pub fn supports(mut self, task: TaskDefinition) -> Self {
+        self.supported_tasks.insert(task.name.clone(), task);
+        self
+    }
+
+    /// Number of times `generate` has been called
+    pub fn call_count(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl LlmInstance for MockInstance {
+    async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if let Some(latency) = self.latency {
+            tokio::time::sleep(latency).await;
+        }
+
+        // Take the error before matching so the lock isn't held across the return
+        let queued = self.errors.lock().unwrap().pop_front();
+        if let Some(error) = queued {
+            return Err(error);
+        }
+
+        Ok(LlmResponse {
+            content: self.response.clone(),
+            model: self.model.clone(),
+            usage: self.usage.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Always healthy, so background probes don't consume queued errors
+    async fn health_check(&self) -> LlmResult<()> {
+        Ok(())
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        &self.supported_tasks
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
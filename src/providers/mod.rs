@@ -5,12 +5,19 @@
 /// - OpenAI (GPT models)
 /// - Mistral AI
 /// - Google (Gemini models)
+/// - Vertex AI (Gemini models on Google Cloud)
 /// - Ollama (local)
 /// - LM Studio (local, OpenAI-compatible)
 /// - Groq (ultra-fast inference)
+/// - xAI (Grok models)
 /// - Cohere (enterprise LLMs)
 /// - Together AI (open-source models)
 /// - Perplexity (search-augmented)
+/// - Replicate (hosted open models)
+/// - AI21 (Jamba models)
+/// - Fireworks AI (open models)
+/// - Any OpenAI-compatible server (served by `OpenAIInstance`)
+/// - An offline mock for tests (`test-util` feature)
 ///
 /// Each provider implements a common interface for generating text
 /// completions through their respective APIs.
@@ -20,24 +27,41 @@ pub mod openai;
 pub mod types;
 pub mod instances;
 pub mod google;
+pub mod vertex;
+mod gemini;
 pub mod mistral;
 pub mod ollama;
+mod openai_compat;
 pub mod lmstudio;
 pub mod groq;
+pub mod xai;
 pub mod cohere;
 pub mod togetherai;
 pub mod perplexity;
+pub mod replicate;
+pub mod ai21;
+pub mod fireworks;
 pub mod model_discovery;
 pub mod streaming;
+pub mod tokens;
+#[cfg(feature = "test-util")]
+pub mod mock;
 
 pub use model_discovery::ModelDiscovery;
-pub use types::{ProviderType, LlmRequest, LlmResponse, Message, TokenUsage, ModelInfo, StreamChunk, LlmStream};
-pub use streaming::{OpenAIStreamChunk, AnthropicStreamEvent};
-pub use instances::{LlmInstance, create_instance};
+pub use types::{ProviderType, LlmRequest, LlmResponse, Message, TokenUsage, ModelInfo, ProviderCapabilities, StreamChunk, LlmStream, EmbeddingResponse, ToolCall, ToolDefinition, ResponseFormat, ContentPart, COMMON_PARAMS, validate_messages};
+pub use streaming::{OpenAIStreamChunk, AnthropicStreamEvent, collect_response};
+pub use instances::{LlmInstance, EmbeddingInstance, InstanceSettings, create_instance};
 pub use anthropic::AnthropicInstance;
 pub use openai::OpenAIInstance;
+pub use vertex::{VertexAIInstance, TokenProvider};
 pub use lmstudio::LMStudioInstance;
 pub use groq::GroqInstance;
+pub use xai::XaiInstance;
 pub use cohere::CohereInstance;
 pub use togetherai::TogetherAIInstance;
-pub use perplexity::PerplexityInstance;
\ No newline at end of file
+pub use perplexity::PerplexityInstance;
+pub use replicate::ReplicateInstance;
+pub use ai21::Ai21Instance;
+pub use fireworks::FireworksInstance;
+#[cfg(feature = "test-util")]
+pub use mock::MockInstance;
\ No newline at end of file
@@ -1,21 +1,39 @@
 use crate::providers::types::{ModelInfo, ProviderType};
 use crate::errors::{LlmError, LlmResult};
 use crate::constants;
+use futures::future::join_all;
 use reqwest::{Client, header};
 use serde::Deserialize;
-use std::time::Duration;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 /// Helper module for listing available models from providers
 /// without requiring a fully initialized provider instance
 pub struct ModelDiscovery;
 
+/// Cache key: provider, endpoint and a hash of the API key (the key itself isn't stored)
+type DiscoveryCacheKey = (ProviderType, Option<String>, u64);
+
+/// Successful `list_models` results and when they were fetched
+type DiscoveryCache = Mutex<HashMap<DiscoveryCacheKey, (Instant, Vec<ModelInfo>)>>;
+
+/// Process-wide discovery cache
+fn discovery_cache() -> &'static DiscoveryCache {
+    static CACHE: OnceLock<DiscoveryCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
 impl ModelDiscovery {
     /// Create a standardized HTTP client for model discovery
-    fn create_client() -> Client {
+    fn create_client() -> LlmResult<Client> {
         Client::builder()
             .timeout(Duration::from_secs(30))
+            .user_agent(constants::DEFAULT_USER_AGENT)
             .build()
-            .expect("Failed to create HTTP client")
+            .map_err(|e| LlmError::ConfigError(format!("Failed to create HTTP client: {}", e)))
     }
 
     /// List available models from Anthropic
@@ -26,7 +44,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_anthropic_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
         
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -50,7 +68,7 @@ impl ModelDiscovery {
             let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| format!("Unknown error reading error response body, status: {}", status));
-            return Err(LlmError::ApiError(format!("Anthropic API error ({}): {}", status, error_text)));
+            return Err(LlmError::api("Anthropic", status, &error_text));
         }
         
         let response_bytes = response.bytes().await?;
@@ -94,7 +112,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_openai_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -111,9 +129,10 @@ impl ModelDiscovery {
             .await?;
             
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("OpenAI API error: {}", error_text)));
+            return Err(LlmError::api("OpenAI", status, &error_text));
         }
         
         #[derive(Deserialize)]
@@ -147,7 +166,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_mistral_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -164,9 +183,10 @@ impl ModelDiscovery {
             .await?;
             
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Mistral API error: {}", error_text)));
+            return Err(LlmError::api("Mistral", status, &error_text));
         }
         
         #[derive(Deserialize)]
@@ -199,7 +219,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_google_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let models_endpoint = format!(
             "{}/v1beta/models?key={}",
@@ -212,9 +232,10 @@ impl ModelDiscovery {
             .await?;
             
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Google API error: {}", error_text)));
+            return Err(LlmError::api("Google", status, &error_text));
         }
         
         #[derive(Deserialize)]
@@ -234,7 +255,7 @@ impl ModelDiscovery {
         let models = google_response.models.into_iter()
             .map(|m| {
                 let name = m.display_name.unwrap_or_else(|| {
-                    m.name.split('/').last().unwrap_or(&m.name).to_string()
+                    m.name.split('/').next_back().unwrap_or(&m.name).to_string()
                 });
                 
                 ModelInfo {
@@ -255,7 +276,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_ollama_models(base_url: Option<&str>) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         // Use provided base URL or default to localhost
         let base_url = base_url.unwrap_or("http://localhost:11434");
@@ -266,9 +287,10 @@ impl ModelDiscovery {
             .await?;
             
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Ollama API error: {}", error_text)));
+            return Err(LlmError::api("Ollama", status, &error_text));
         }
         
         #[derive(Deserialize)]
@@ -301,7 +323,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_lmstudio_models(base_url: Option<&str>) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let base_url = base_url.unwrap_or("http://localhost:1234");
         let models_endpoint = format!("{}/v1/models", base_url.trim_end_matches('/'));
@@ -311,9 +333,10 @@ impl ModelDiscovery {
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("LM Studio API error: {}", error_text)));
+            return Err(LlmError::api("LM Studio", status, &error_text));
         }
 
         #[derive(Deserialize)]
@@ -346,7 +369,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_groq_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -363,9 +386,10 @@ impl ModelDiscovery {
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Groq API error: {}", error_text)));
+            return Err(LlmError::api("Groq", status, &error_text));
         }
 
         #[derive(Deserialize)]
@@ -390,6 +414,166 @@ impl ModelDiscovery {
         Ok(models)
     }
 
+    /// List available models from xAI
+    ///
+    /// # Parameters
+    /// * `api_key` - xAI API key
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing model names
+    pub async fn list_xai_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
+        let client = Self::create_client()?;
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+        );
+
+        let response = client.get(constants::XAI_MODELS_ENDPOINT)
+            .headers(headers)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::api("xAI", status, &error_text));
+        }
+
+        #[derive(Deserialize)]
+        struct XaiModelsResponse {
+            data: Vec<XaiModelInfo>,
+        }
+
+        #[derive(Deserialize)]
+        struct XaiModelInfo {
+            id: String,
+        }
+
+        let xai_response: XaiModelsResponse = response.json().await?;
+
+        let models = xai_response.data.into_iter()
+            .map(|m| ModelInfo {
+                name: m.id,
+                provider: ProviderType::Xai,
+            })
+            .collect();
+
+        Ok(models)
+    }
+
+    /// List available models from AI21
+    ///
+    /// # Parameters
+    /// * `api_key` - AI21 API key
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing model names
+    pub async fn list_ai21_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
+        let client = Self::create_client()?;
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+        );
+
+        let response = client.get(constants::AI21_MODELS_ENDPOINT)
+            .headers(headers)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::api("AI21", status, &error_text));
+        }
+
+        #[derive(Deserialize)]
+        struct Ai21ModelsResponse {
+            data: Vec<Ai21ModelInfo>,
+        }
+
+        #[derive(Deserialize)]
+        struct Ai21ModelInfo {
+            id: String,
+        }
+
+        let ai21_response: Ai21ModelsResponse = response.json().await?;
+
+        let models = ai21_response.data.into_iter()
+            .map(|m| ModelInfo {
+                name: m.id,
+                provider: ProviderType::Ai21,
+            })
+            .collect();
+
+        Ok(models)
+    }
+
+    /// List available models from Fireworks AI
+    ///
+    /// Model names are returned as Fireworks spells them (e.g.
+    /// "accounts/fireworks/models/llama-v3p1-70b-instruct"), ready to use as an instance's model.
+    ///
+    /// # Parameters
+    /// * `api_key` - Fireworks API key
+    /// * `base_url` - Optional API base URL replacing `https://api.fireworks.ai/inference/v1`
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing model names
+    pub async fn list_fireworks_models(api_key: &str, base_url: Option<&str>) -> LlmResult<Vec<ModelInfo>> {
+        let client = Self::create_client()?;
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+        );
+
+        let base_url = base_url.unwrap_or(constants::FIREWORKS_API_BASE);
+        let models_endpoint = format!("{}/models", base_url.trim_end_matches('/'));
+
+        let response = client.get(&models_endpoint)
+            .headers(headers)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::api("Fireworks", status, &error_text));
+        }
+
+        #[derive(Deserialize)]
+        struct FireworksModelsResponse {
+            data: Vec<FireworksModelInfo>,
+        }
+
+        #[derive(Deserialize)]
+        struct FireworksModelInfo {
+            id: String,
+        }
+
+        let fireworks_response: FireworksModelsResponse = response.json().await?;
+
+        let models = fireworks_response.data.into_iter()
+            .map(|m| ModelInfo {
+                name: m.id,
+                provider: ProviderType::Fireworks,
+            })
+            .collect();
+
+        Ok(models)
+    }
+
     /// List available models from Cohere
     ///
     /// # Parameters
@@ -398,7 +582,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_cohere_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -415,9 +599,10 @@ impl ModelDiscovery {
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Cohere API error: {}", error_text)));
+            return Err(LlmError::api("Cohere", status, &error_text));
         }
 
         #[derive(Deserialize)]
@@ -450,7 +635,7 @@ impl ModelDiscovery {
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
     pub async fn list_togetherai_models(api_key: &str) -> LlmResult<Vec<ModelInfo>> {
-        let client = Self::create_client();
+        let client = Self::create_client()?;
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -467,9 +652,10 @@ impl ModelDiscovery {
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("Together AI API error: {}", error_text)));
+            return Err(LlmError::api("Together AI", status, &error_text));
         }
 
         #[derive(Deserialize)]
@@ -524,12 +710,122 @@ impl ModelDiscovery {
         Ok(models)
     }
 
+    /// List available models from Vertex AI
+    ///
+    /// Note: listing publisher models needs a project and OAuth2 token, returns known Gemini models
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing known model names
+    pub async fn list_vertexai_models() -> LlmResult<Vec<ModelInfo>> {
+        let known_models = vec![
+            "gemini-2.0-flash",
+            "gemini-2.0-flash-lite",
+            "gemini-1.5-pro",
+            "gemini-1.5-flash",
+        ];
+
+        let models = known_models.into_iter()
+            .map(|name| ModelInfo {
+                name: name.to_string(),
+                provider: ProviderType::VertexAI,
+            })
+            .collect();
+
+        Ok(models)
+    }
+
+    /// List available models from Replicate
+    ///
+    /// Note: Replicate hosts thousands of community models, returns well-known language models
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing known model names
+    pub async fn list_replicate_models() -> LlmResult<Vec<ModelInfo>> {
+        let known_models = vec![
+            "meta/meta-llama-3.1-405b-instruct",
+            "meta/meta-llama-3-70b-instruct",
+            "meta/meta-llama-3-8b-instruct",
+            "mistralai/mixtral-8x7b-instruct-v0.1",
+        ];
+
+        let models = known_models.into_iter()
+            .map(|name| ModelInfo {
+                name: name.to_string(),
+                provider: ProviderType::Replicate,
+            })
+            .collect();
+
+        Ok(models)
+    }
+
+    /// List available models from an OpenAI-compatible server
+    ///
+    /// Queries `{base_url}/models` with a Bearer key (omitted when empty). Servers using
+    /// another auth header or models path can be checked with `LlmInstance::health_check`.
+    ///
+    /// # Parameters
+    /// * `api_key` - API key, or an empty string for servers without authentication
+    /// * `base_url` - API base URL of the server (required)
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing model names
+    pub async fn list_openai_compatible_models(api_key: &str, base_url: Option<&str>) -> LlmResult<Vec<ModelInfo>> {
+        let base_url = base_url.ok_or_else(|| LlmError::ConfigError(
+            "Listing OpenAI-compatible models needs the server's base URL".to_string()
+        ))?;
+        let client = Self::create_client()?;
+
+        let mut headers = header::HeaderMap::new();
+        if !api_key.is_empty() {
+            headers.insert(
+                header::AUTHORIZATION,
+                header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+                    .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+            );
+        }
+
+        let models_endpoint = format!("{}/models", base_url.trim_end_matches('/'));
+
+        let response = client.get(&models_endpoint)
+            .headers(headers)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::api("OpenAI-compatible", status, &error_text));
+        }
+
+        #[derive(Deserialize)]
+        struct CompatibleModelsResponse {
+            data: Vec<CompatibleModelInfo>,
+        }
+
+        #[derive(Deserialize)]
+        struct CompatibleModelInfo {
+            id: String,
+        }
+
+        let compatible_response: CompatibleModelsResponse = response.json().await?;
+
+        let models = compatible_response.data.into_iter()
+            .map(|m| ModelInfo {
+                name: m.id,
+                provider: ProviderType::OpenAICompatible,
+            })
+            .collect();
+
+        Ok(models)
+    }
+
     /// List all models from a specific provider
     ///
     /// # Parameters
     /// * `provider_type` - Type of provider to query
     /// * `api_key` - API key for authentication
-    /// * `base_url` - Optional base URL (used for Ollama and LM Studio)
+    /// * `base_url` - Optional base URL (used for Ollama, LM Studio, Fireworks and OpenAI-compatible servers)
     ///
     /// # Returns
     /// * Vector of ModelInfo structs containing model names
@@ -546,9 +842,83 @@ impl ModelDiscovery {
             ProviderType::Ollama => Self::list_ollama_models(base_url).await,
             ProviderType::LMStudio => Self::list_lmstudio_models(base_url).await,
             ProviderType::Groq => Self::list_groq_models(api_key).await,
+            ProviderType::Xai => Self::list_xai_models(api_key).await,
             ProviderType::Cohere => Self::list_cohere_models(api_key).await,
             ProviderType::TogetherAI => Self::list_togetherai_models(api_key).await,
             ProviderType::Perplexity => Self::list_perplexity_models().await,
+            ProviderType::OpenAICompatible => Self::list_openai_compatible_models(api_key, base_url).await,
+            ProviderType::VertexAI => Self::list_vertexai_models().await,
+            ProviderType::Replicate => Self::list_replicate_models().await,
+            ProviderType::Ai21 => Self::list_ai21_models(api_key).await,
+            ProviderType::Fireworks => Self::list_fireworks_models(api_key, base_url).await,
+        }
+    }
+
+    /// List models from several providers concurrently
+    ///
+    /// A failing provider doesn't affect the others; its error is returned alongside
+    /// the successful results.
+    ///
+    /// # Parameters
+    /// * `creds` - Provider type, API key and optional base URL for each provider to query
+    ///
+    /// # Returns
+    /// * Per-provider results, in the same order as `creds`
+    pub async fn list_all_models(
+        creds: &[(ProviderType, String, Option<String>)],
+    ) -> Vec<(ProviderType, LlmResult<Vec<ModelInfo>>)> {
+        let futures = creds.iter().map(|(provider_type, api_key, base_url)| async move {
+            let result = Self::list_models(*provider_type, api_key, base_url.as_deref()).await;
+            (*provider_type, result)
+        });
+        join_all(futures).await
+    }
+
+    /// List all models from a specific provider, reusing a recent result if there is one
+    ///
+    /// Results are cached per provider, endpoint and API key for the whole process.
+    /// Errors are never cached, so the next call after a failure queries the provider again.
+    ///
+    /// # Parameters
+    /// * `provider_type` - Type of provider to query
+    /// * `api_key` - API key for authentication
+    /// * `base_url` - Optional base URL (used for Ollama, LM Studio, Fireworks and OpenAI-compatible servers)
+    /// * `ttl` - How long a cached result stays valid
+    ///
+    /// # Returns
+    /// * Vector of ModelInfo structs containing model names
+    pub async fn list_models_cached(
+        provider_type: ProviderType,
+        api_key: &str,
+        base_url: Option<&str>,
+        ttl: Duration,
+    ) -> LlmResult<Vec<ModelInfo>> {
+        let mut hasher = DefaultHasher::new();
+        api_key.hash(&mut hasher);
+        let key = (provider_type, base_url.map(str::to_string), hasher.finish());
+
+        {
+            let cache = discovery_cache().lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((fetched_at, models)) = cache.get(&key) {
+                if fetched_at.elapsed() < ttl {
+                    return Ok(models.clone());
+                }
+            }
         }
+
+        let models = Self::list_models(provider_type, api_key, base_url).await?;
+        discovery_cache()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (Instant::now(), models.clone()));
+        Ok(models)
+    }
+
+    /// Drop all cached `list_models_cached` results
+    pub fn clear_cache() {
+        discovery_cache()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
     }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::providers::instances::{LlmInstance, BaseInstance};
+use crate::providers::instances::{LlmInstance, BaseInstance, InstanceSettings, ensure_text_only, probe_endpoint, normalize_finish_reason};
 use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message};
 use crate::errors::{LlmError, LlmResult};
 use crate::constants;
@@ -8,13 +8,15 @@ use async_trait::async_trait;
 use reqwest::header;
 use serde::{Serialize, Deserialize};
 use url::Url;
-use futures::StreamExt;
+use crate::providers::streaming::parse_lines;
 
 /// Provider implementation for Ollama (local LLMs)
 pub struct OllamaInstance {
     base: BaseInstance,
     // Specific URL for this provider instance
     endpoint_url: String,
+    // Model list endpoint, used for health checks
+    tags_url: String,
 }
 
 /// Request structure for Ollama's chat API
@@ -27,12 +29,59 @@ struct OllamaRequest {
     options: Option<OllamaOptions>,
 }
 
+/// Model options sent with an Ollama request
+///
+/// Besides `temperature` and `max_tokens` (sent as `num_predict`), these request
+/// parameters are passed through under the same name:
+/// * `num_ctx` (or `context_length`) - Context window size in tokens
+/// * `top_k` - Sample from the K most likely tokens
+/// * `repeat_penalty` - Penalty applied to repeated tokens
+///
+/// `LlmRequest::seed` is sent as `seed`. Other parameters, or values of the wrong type, are ignored.
 #[derive(Serialize, Default)]
 struct OllamaOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     num_predict: Option<u32>, // Corresponds to max_tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+impl OllamaOptions {
+    /// Map request fields and parameters to Ollama options, or None if none are set
+    ///
+    /// `max_tokens` is the request's limit after the instance's default is applied.
+    fn from_request(request: &LlmRequest, max_tokens: Option<u32>) -> Option<Self> {
+        let param_u32 = |key: &str| {
+            request.params.get(key)
+                .and_then(|v| v.as_u64())
+                .and_then(|v| u32::try_from(v).ok())
+        };
+
+        let options = Self {
+            temperature: request.temperature,
+            num_predict: max_tokens,
+            num_ctx: param_u32("num_ctx").or_else(|| param_u32("context_length")),
+            top_k: param_u32("top_k"),
+            repeat_penalty: request.params.get("repeat_penalty").and_then(|v| v.as_f64()).map(|v| v as f32),
+            seed: request.seed,
+        };
+
+        let is_empty = options.temperature.is_none()
+            && options.num_predict.is_none()
+            && options.num_ctx.is_none()
+            && options.top_k.is_none()
+            && options.repeat_penalty.is_none()
+            && options.seed.is_none();
+        if is_empty { None } else { Some(options) }
+    }
 }
 
 /// Response structure from Ollama's chat API (non-streaming)
@@ -46,6 +95,8 @@ struct OllamaResponse {
     prompt_eval_count: u32,
     #[serde(default)] // Use default (0) if not present
     eval_count: u32, // Corresponds roughly to completion tokens
+    #[serde(default)]
+    done_reason: Option<String>,
 }
 
 /// Streaming response structure from Ollama's chat API
@@ -60,6 +111,8 @@ struct OllamaStreamResponse {
     prompt_eval_count: Option<u32>,
     #[serde(default)]
     eval_count: Option<u32>,
+    #[serde(default)]
+    done_reason: Option<String>,
 }
 
 impl OllamaInstance {
@@ -71,7 +124,8 @@ impl OllamaInstance {
     /// * `supported_tasks` - Map of tasks this provider supports
     /// * `enabled` - Whether this provider is enabled
     /// * `endpoint_url` - Optional base endpoint URL override. If None, uses the default from constants.
-    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, endpoint_url: Option<String>) -> Self {
+    /// * `settings` - Extra HTTP settings such as custom headers
+    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, endpoint_url: Option<String>, settings: &InstanceSettings) -> LlmResult<Self> {
         // Determine the endpoint: use provided one or default
         let base_endpoint = endpoint_url.unwrap_or_else(|| constants::OLLAMA_API_ENDPOINT.to_string());
 
@@ -98,12 +152,32 @@ impl OllamaInstance {
         };
 
         // Create BaseProvider with the actual API key (even if empty/unused)
-        let base = BaseInstance::new("ollama".to_string(), api_key, model, supported_tasks, enabled);
+        let base = BaseInstance::new("ollama".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+
+        let tags_url = format!("{}/api/tags", final_endpoint.trim_end_matches("/api/chat"));
 
-        Self {
+        Ok(Self {
             base,
             endpoint_url: final_endpoint,
+            tags_url,
+        })
+    }
+
+    /// Build request headers, adding Authorization only when an API key is set
+    fn build_headers(&self) -> Result<header::HeaderMap, LlmError> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        // Add Authorization header if an API key is actually provided and non-empty
+        if !self.base.api_key().is_empty() {
+            let value = header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key()))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format for Ollama: {}", e)))?;
+            headers.insert(header::AUTHORIZATION, value);
         }
+        Ok(headers)
     }
 }
 
@@ -120,37 +194,17 @@ impl LlmInstance for OllamaInstance {
         if !self.base.is_enabled() {
             return Err(LlmError::ProviderDisabled("Ollama".to_string()));
         }
+        ensure_text_only(request, "Ollama")?;
 
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("application/json"),
-        );
-
-        // Add Authorization header if an API key is actually provided and non-empty
-        if !self.base.api_key().is_empty() {
-            match header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key())) {
-               Ok(val) => { headers.insert(header::AUTHORIZATION, val); },
-               Err(e) => return Err(LlmError::ConfigError(format!("Invalid API key format for Ollama: {}", e))),
-           }
-        }
+        let headers = self.build_headers()?;
 
         let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
 
-        // Map common parameters to Ollama options
-        let mut options = OllamaOptions::default();
-        if request.temperature.is_some() {
-            options.temperature = request.temperature;
-        }
-        if request.max_tokens.is_some() {
-            options.num_predict = request.max_tokens;
-        }
-
         let ollama_request = OllamaRequest {
             model,
             messages: request.messages.clone(),
             stream: false, 
-            options: if options.temperature.is_some() || options.num_predict.is_some() { Some(options) } else { None },
+            options: OllamaOptions::from_request(request, self.base.max_tokens(request)),
         };
 
         let response = self.base.client()
@@ -164,7 +218,7 @@ impl LlmInstance for OllamaInstance {
         if !response_status.is_success() {
             let error_text = response.text().await
                 .unwrap_or_else(|_| format!("Unknown error. Status: {}", response_status));
-            return Err(LlmError::ApiError(format!("Ollama API error: {}", error_text)));
+            return Err(LlmError::api("Ollama", response_status, &error_text));
         }
 
         let response_text = response.text().await?;
@@ -183,6 +237,7 @@ impl LlmInstance for OllamaInstance {
              prompt_tokens: ollama_response.prompt_eval_count,
              completion_tokens: ollama_response.eval_count,
              total_tokens: ollama_response.prompt_eval_count + ollama_response.eval_count,
+             ..Default::default()
          });
 
 
@@ -190,6 +245,8 @@ impl LlmInstance for OllamaInstance {
             content: ollama_response.message.content.clone(),
             model: ollama_response.model,
             usage,
+            finish_reason: ollama_response.done_reason.as_deref().map(normalize_finish_reason),
+            ..Default::default()
         })
     }
 
@@ -197,37 +254,17 @@ impl LlmInstance for OllamaInstance {
         if !self.base.is_enabled() {
             return Err(LlmError::ProviderDisabled("Ollama".to_string()));
         }
+        ensure_text_only(request, "Ollama")?;
 
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("application/json"),
-        );
-
-        // Add Authorization header if an API key is actually provided and non-empty
-        if !self.base.api_key().is_empty() {
-            match header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key())) {
-                Ok(val) => { headers.insert(header::AUTHORIZATION, val); },
-                Err(e) => return Err(LlmError::ConfigError(format!("Invalid API key format for Ollama: {}", e))),
-            }
-        }
+        let headers = self.build_headers()?;
 
         let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
 
-        // Map common parameters to Ollama options
-        let mut options = OllamaOptions::default();
-        if request.temperature.is_some() {
-            options.temperature = request.temperature;
-        }
-        if request.max_tokens.is_some() {
-            options.num_predict = request.max_tokens;
-        }
-
         let ollama_request = OllamaRequest {
             model,
             messages: request.messages.clone(),
             stream: true, // Enable streaming
-            options: if options.temperature.is_some() || options.num_predict.is_some() { Some(options) } else { None },
+            options: OllamaOptions::from_request(request, self.base.max_tokens(request)),
         };
 
         let response = self.base.client()
@@ -241,69 +278,59 @@ impl LlmInstance for OllamaInstance {
         if !response_status.is_success() {
             let error_text = response.text().await
                 .unwrap_or_else(|_| format!("Unknown error. Status: {}", response_status));
-            return Err(LlmError::ApiError(format!("Ollama API error: {}", error_text)));
+            return Err(LlmError::api("Ollama", response_status, &error_text));
         }
 
-        let byte_stream = response.bytes_stream();
-
         // Ollama uses NDJSON - each line is a complete JSON object
-        let chunk_stream = byte_stream
-            .map(|result| result.map_err(|e| LlmError::RequestError(e)))
-            .flat_map(|result| {
-                match result {
-                    Ok(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-                        let chunks: Vec<Result<StreamChunk, LlmError>> = text
-                            .lines()
-                            .filter_map(|line| {
-                                let line = line.trim();
-                                if line.is_empty() {
-                                    return None;
-                                }
-                                match serde_json::from_str::<OllamaStreamResponse>(line) {
-                                    Ok(response) => {
-                                        let content = response.message
-                                            .map(|m| m.content)
-                                            .unwrap_or_default();
-
-                                        let usage = if response.done {
-                                            let prompt = response.prompt_eval_count.unwrap_or(0);
-                                            let completion = response.eval_count.unwrap_or(0);
-                                            Some(TokenUsage {
-                                                prompt_tokens: prompt,
-                                                completion_tokens: completion,
-                                                total_tokens: prompt + completion,
-                                            })
-                                        } else {
-                                            None
-                                        };
-
-                                        Some(Ok(StreamChunk {
-                                            content,
-                                            model: response.model,
-                                            is_final: response.done,
-                                            usage,
-                                        }))
-                                    }
-                                    Err(e) => Some(Err(LlmError::ParseError(
-                                        format!("Failed to parse Ollama streaming response: {}", e)
-                                    ))),
-                                }
-                            })
-                            .collect();
-                        futures::stream::iter(chunks)
-                    }
-                    Err(e) => futures::stream::iter(vec![Err(e)])
+        Ok(parse_lines(response.bytes_stream(), |line| {
+            let line = line.trim();
+            if line.is_empty() {
+                return None;
+            }
+            match serde_json::from_str::<OllamaStreamResponse>(line) {
+                Ok(response) => {
+                    let content = response.message
+                        .map(|m| m.content)
+                        .unwrap_or_default();
+
+                    let usage = if response.done {
+                        let prompt = response.prompt_eval_count.unwrap_or(0);
+                        let completion = response.eval_count.unwrap_or(0);
+                        Some(TokenUsage {
+                            prompt_tokens: prompt,
+                            completion_tokens: completion,
+                            total_tokens: prompt + completion,
+                            ..Default::default()
+                        })
+                    } else {
+                        None
+                    };
+
+                    Some(Ok(StreamChunk {
+                        content,
+                        model: response.model,
+                        is_final: response.done,
+                        usage,
+                        finish_reason: response.done_reason.as_deref().map(normalize_finish_reason),
+                    }))
                 }
-            });
-
-        Ok(Box::pin(chunk_stream))
+                Err(e) => Some(Err(LlmError::ParseError(
+                    format!("Failed to parse Ollama streaming response: {}", e)
+                ))),
+            }
+        }))
     }
 
     fn supports_streaming(&self) -> bool {
         true
     }
 
+    /// Checks the server is reachable by listing local models, without loading one
+    async fn health_check(&self) -> LlmResult<()> {
+        let headers = self.build_headers()?;
+        probe_endpoint(self.base.client(), &self.tags_url, headers, "Ollama").await
+    }
+
     /// Returns provider name
     fn get_name(&self) -> &str {
         self.base.name()
@@ -1,20 +1,32 @@
 use std::collections::HashMap;
 
 use crate::load_balancer::tasks::TaskDefinition;
-use crate::providers::instances::{LlmInstance, BaseInstance};
-use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message};
-use crate::providers::streaming::OpenAIStreamChunk;
+use crate::providers::instances::{LlmInstance, EmbeddingInstance, BaseInstance, InstanceSettings, openai_compatible_url, probe_endpoint, normalize_finish_reason};
+use crate::providers::types::{EmbeddingResponse, LlmRequest, LlmResponse, LlmStream, TokenUsage, Message, ContentPart, ToolCall, ToolDefinition, ResponseFormat};
+use crate::providers::streaming::{parse_lines, parse_openai_sse_line};
+use crate::providers::tokens::context_window_for_model;
 use crate::errors::{LlmError, LlmResult};
 use crate::constants;
 
 use async_trait::async_trait;
 use reqwest::header;
 use serde::{Serialize, Deserialize};
-use futures::StreamExt;
+use serde_json::{json, Value};
 
 /// Provider implementation for OpenAI's API (GPT models)
+///
+/// Also serves `ProviderType::OpenAICompatible` endpoints (see `OpenAIInstance::compatible`).
 pub struct OpenAIInstance {
     base: BaseInstance,
+    chat_url: String,
+    embeddings_url: String,
+    models_url: String,
+    // Provider name used in error messages
+    label: &'static str,
+    // Header carrying the raw API key instead of `Authorization: Bearer`
+    auth_header: Option<header::HeaderName>,
+    // Whether requests may go out without credentials when the key is empty
+    key_optional: bool,
 }
 
 /// Request structure for OpenAI's chat completion API
@@ -22,7 +34,7 @@ pub struct OpenAIInstance {
 #[derive(Serialize)]
 struct OpenAIRequest {
     model: String,
-    messages: Vec<Message>,
+    messages: Vec<OpenAIMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -31,6 +43,37 @@ struct OpenAIRequest {
     stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<OpenAITool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+/// Outgoing message in OpenAI's format
+///
+/// `content` is a plain string, or an array of parts for multimodal messages.
+#[derive(Serialize)]
+struct OpenAIMessage {
+    role: String,
+    content: Value,
 }
 
 #[derive(Serialize)]
@@ -38,6 +81,22 @@ struct StreamOptions {
     include_usage: bool,
 }
 
+/// Tool definition in OpenAI's format
+#[derive(Serialize)]
+struct OpenAITool {
+    #[serde(rename = "type")]
+    tool_type: &'static str,
+    function: OpenAIFunction,
+}
+
+/// Function description inside an OpenAI tool definition
+#[derive(Serialize)]
+struct OpenAIFunction {
+    name: String,
+    description: String,
+    parameters: Value,
+}
+
 /// Response structure from OpenAI's chat completion API
 #[derive(Deserialize)]
 struct OpenAIResponse {
@@ -49,7 +108,35 @@ struct OpenAIResponse {
 /// Individual choice from OpenAI's response
 #[derive(Deserialize)]
 struct OpenAIChoice {
-    message: Message,
+    message: OpenAIResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    /// Present when `logprobs` was requested, null otherwise
+    #[serde(default)]
+    logprobs: Option<Value>,
+}
+
+/// Message returned by OpenAI; content is null when the model only calls tools
+#[derive(Deserialize)]
+struct OpenAIResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<OpenAIToolCall>,
+}
+
+/// Tool call from OpenAI's response
+#[derive(Deserialize)]
+struct OpenAIToolCall {
+    id: String,
+    function: OpenAIFunctionCall,
+}
+
+/// Function name and JSON-encoded arguments of an OpenAI tool call
+#[derive(Deserialize)]
+struct OpenAIFunctionCall {
+    name: String,
+    arguments: String,
 }
 
 /// Token usage information from OpenAI
@@ -60,6 +147,152 @@ struct OpenAIUsage {
     total_tokens: u32,
 }
 
+/// Request structure for OpenAI's embeddings API
+#[derive(Serialize)]
+struct OpenAIEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+/// Response structure from OpenAI's embeddings API
+#[derive(Deserialize)]
+struct OpenAIEmbeddingResponse {
+    data: Vec<OpenAIEmbeddingData>,
+    model: String,
+    usage: Option<OpenAIEmbeddingUsage>,
+}
+
+/// Individual embedding from OpenAI's response
+#[derive(Deserialize)]
+struct OpenAIEmbeddingData {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+/// Token usage information from OpenAI's embeddings API
+#[derive(Deserialize)]
+struct OpenAIEmbeddingUsage {
+    prompt_tokens: u32,
+    total_tokens: u32,
+}
+
+impl OpenAIResponse {
+    /// Convert OpenAI's response into the unified response format
+    ///
+    /// Tool calls, the finish reason and logprobs come from the first choice.
+    fn into_llm_response(self) -> LlmResult<LlmResponse> {
+        let mut choices = self.choices.into_iter();
+        let Some(choice) = choices.next() else {
+            return Err(LlmError::ApiError("No response from OpenAI".to_string()));
+        };
+        let content = choice.message.content.unwrap_or_default();
+        let others: Vec<String> = choices
+            .map(|choice| choice.message.content.unwrap_or_default())
+            .collect();
+        let choices = if others.is_empty() {
+            Vec::new()
+        } else {
+            std::iter::once(content.clone()).chain(others).collect()
+        };
+
+        let usage = self.usage.map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            total_tokens: u.total_tokens,
+            ..Default::default()
+        });
+
+        let tool_calls = choice.message.tool_calls
+            .into_iter()
+            .map(|call| ToolCall {
+                id: call.id,
+                name: call.function.name,
+                arguments: call.function.arguments,
+            })
+            .collect();
+
+        Ok(LlmResponse {
+            content,
+            model: self.model,
+            usage,
+            tool_calls,
+            finish_reason: choice.finish_reason.as_deref().map(normalize_finish_reason),
+            choices,
+            logprobs: choice.logprobs,
+            ..Default::default()
+        })
+    }
+}
+
+/// Convert unified messages into OpenAI's format, using the array form only when parts are present
+fn convert_messages(messages: &[Message]) -> Vec<OpenAIMessage> {
+    messages
+        .iter()
+        .map(|message| {
+            let content = match &message.content_parts {
+                Some(parts) => Value::Array(parts.iter().map(convert_content_part).collect()),
+                None => json!(message.content),
+            };
+            OpenAIMessage { role: message.canonical_role().to_string(), content }
+        })
+        .collect()
+}
+
+/// Convert a single content part into OpenAI's format
+fn convert_content_part(part: &ContentPart) -> Value {
+    match part {
+        ContentPart::Text(text) => json!({ "type": "text", "text": text }),
+        ContentPart::ImageUrl { url, detail } => {
+            let mut image_url = json!({ "url": url });
+            if let Some(detail) = detail {
+                image_url["detail"] = json!(detail);
+            }
+            json!({ "type": "image_url", "image_url": image_url })
+        }
+        ContentPart::ImageBase64 { media_type, data } => json!({
+            "type": "image_url",
+            "image_url": { "url": format!("data:{};base64,{}", media_type, data) },
+        }),
+    }
+}
+
+/// Convert unified tool definitions into OpenAI's format
+fn convert_tools(tools: &[ToolDefinition]) -> Vec<OpenAITool> {
+    tools
+        .iter()
+        .map(|tool| OpenAITool {
+            tool_type: "function",
+            function: OpenAIFunction {
+                name: tool.name.clone(),
+                description: tool.description.clone(),
+                parameters: tool.parameters.clone(),
+            },
+        })
+        .collect()
+}
+
+/// Convert a unified tool choice into OpenAI's format
+///
+/// "auto", "none" and "required" pass through; anything else forces that tool by name.
+fn convert_tool_choice(choice: &str) -> Value {
+    match choice {
+        "auto" | "none" | "required" => json!(choice),
+        name => json!({ "type": "function", "function": { "name": name } }),
+    }
+}
+
+/// Convert a unified response format into OpenAI's `response_format`
+fn convert_response_format(format: &ResponseFormat) -> Value {
+    match format {
+        ResponseFormat::Text => json!({ "type": "text" }),
+        ResponseFormat::JsonObject => json!({ "type": "json_object" }),
+        ResponseFormat::JsonSchema { schema } => json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema },
+        }),
+    }
+}
+
 impl OpenAIInstance {
     /// Creates a new OpenAI provider instance
     ///
@@ -68,19 +301,62 @@ impl OpenAIInstance {
     /// * `model` - Default model to use (e.g. "gpt-4-turbo")
     /// * `supported_tasks` - Map of tasks this provider supports
     /// * `enabled` - Whether this provider is enabled
-    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool) -> Self {
-        let base = BaseInstance::new("openai".to_string(), api_key, model, supported_tasks, enabled);
-        Self { base }
+    /// * `endpoint_url` - Optional API base URL override (e.g. a proxy serving `/chat/completions` and `/embeddings`)
+    /// * `settings` - Extra HTTP settings such as custom headers
+    pub fn new(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, endpoint_url: Option<String>, settings: &InstanceSettings) -> LlmResult<Self> {
+        let base = BaseInstance::new("openai".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        let chat_url = openai_compatible_url(endpoint_url.as_deref(), constants::OPENAI_API_ENDPOINT, "/chat/completions");
+        let embeddings_url = openai_compatible_url(endpoint_url.as_deref(), constants::OPENAI_EMBEDDINGS_ENDPOINT, "/embeddings");
+        let models_url = openai_compatible_url(endpoint_url.as_deref(), constants::OPENAI_MODELS_ENDPOINT, "/models");
+        Ok(Self { base, chat_url, embeddings_url, models_url, label: "OpenAI", auth_header: None, key_optional: false })
+    }
+
+    /// Creates an instance for any server speaking OpenAI's chat completions API
+    ///
+    /// The key is sent as `Authorization: Bearer <key>`, or as-is in
+    /// `settings.auth_header` when set (e.g. `api-key`), and omitted when empty so local
+    /// servers such as vLLM work without one. Health checks list models at
+    /// `settings.models_path` (default `/models`) under the endpoint.
+    ///
+    /// # Parameters
+    /// * `api_key` - API key, or an empty string for servers without authentication
+    /// * `model` - Default model to use
+    /// * `supported_tasks` - Map of tasks this provider supports
+    /// * `enabled` - Whether this provider is enabled
+    /// * `endpoint_url` - Required API base URL (e.g. `https://api.deepinfra.com/v1/openai`)
+    /// * `settings` - Extra HTTP settings, including the auth header and models path
+    pub fn compatible(api_key: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, endpoint_url: Option<String>, settings: &InstanceSettings) -> LlmResult<Self> {
+        let endpoint_url = endpoint_url.ok_or_else(|| LlmError::ConfigError(
+            "OpenAI-compatible providers need a custom endpoint (e.g. https://api.deepinfra.com/v1/openai)".to_string()
+        ))?;
+        let auth_header = settings.auth_header.as_deref()
+            .map(|name| header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| LlmError::ConfigError(format!("Invalid auth header name '{}': {}", name, e))))
+            .transpose()?;
+
+        let base = BaseInstance::new("openai_compatible".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        let chat_url = openai_compatible_url(Some(&endpoint_url), "", "/chat/completions");
+        let embeddings_url = openai_compatible_url(Some(&endpoint_url), "", "/embeddings");
+        let models_path = settings.models_path.as_deref().unwrap_or("/models");
+        let models_url = openai_compatible_url(Some(&endpoint_url), "", &format!("/{}", models_path.trim_start_matches('/')));
+        Ok(Self { base, chat_url, embeddings_url, models_url, label: "OpenAI-compatible", auth_header, key_optional: true })
     }
 
     /// Build request headers for OpenAI API
     fn build_headers(&self) -> Result<header::HeaderMap, LlmError> {
         let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key()))
-                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
-        );
+        let api_key = self.base.api_key();
+        if !(self.key_optional && api_key.is_empty()) {
+            let (name, value) = match &self.auth_header {
+                Some(name) => (name.clone(), api_key.to_string()),
+                None => (header::AUTHORIZATION, format!("Bearer {}", api_key)),
+            };
+            headers.insert(
+                name,
+                header::HeaderValue::from_str(&value)
+                    .map_err(|e| LlmError::ConfigError(format!("Invalid API key format: {}", e)))?,
+            );
+        }
         headers.insert(
             header::CONTENT_TYPE,
             header::HeaderValue::from_static("application/json"),
@@ -100,7 +376,7 @@ impl LlmInstance for OpenAIInstance {
     /// * `LlmResult<LlmResponse>` - The response from the model or an error
     async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
         if !self.base.is_enabled() {
-            return Err(LlmError::ProviderDisabled("OpenAI".to_string()));
+            return Err(LlmError::ProviderDisabled(self.label.to_string()));
         }
 
         let headers = self.build_headers()?;
@@ -108,49 +384,53 @@ impl LlmInstance for OpenAIInstance {
 
         let openai_request = OpenAIRequest {
             model,
-            messages: request.messages.clone(),
-            max_tokens: request.max_tokens,
+            messages: convert_messages(&request.messages),
+            max_tokens: self.base.max_tokens(request),
             temperature: request.temperature,
+            seed: request.seed,
+            n: request.n,
+            logprobs: request.logprobs,
+            top_logprobs: request.top_logprobs,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.clone(),
             stream: None,
             stream_options: None,
+            tools: request.tools.as_deref().map(convert_tools),
+            tool_choice: request.tool_choice.as_deref().map(convert_tool_choice),
+            response_format: request.response_format.as_ref().map(convert_response_format),
         };
 
         let response = self.base.client()
-            .post(constants::OPENAI_API_ENDPOINT)
+            .post(&self.chat_url)
             .headers(headers)
             .json(&openai_request)
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("{} rate limit: {}", self.label, error_text), &headers));
+        }
+
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("OpenAI API error: {}", error_text)));
+            return Err(LlmError::api(self.label, status, &error_text));
         }
 
         let openai_response: OpenAIResponse = response.json().await?;
-
-        if openai_response.choices.is_empty() {
-            return Err(LlmError::ApiError("No response from OpenAI".to_string()));
-        }
-
-        let usage = openai_response.usage.map(|u| TokenUsage {
-            prompt_tokens: u.prompt_tokens,
-            completion_tokens: u.completion_tokens,
-            total_tokens: u.total_tokens,
-        });
-
-        Ok(LlmResponse {
-            content: openai_response.choices[0].message.content.clone(),
-            model: openai_response.model,
-            usage,
-        })
+        openai_response.into_llm_response()
     }
 
     /// Generates a streaming completion using OpenAI's API
     async fn generate_stream(&self, request: &LlmRequest) -> LlmResult<LlmStream> {
         if !self.base.is_enabled() {
-            return Err(LlmError::ProviderDisabled("OpenAI".to_string()));
+            return Err(LlmError::ProviderDisabled(self.label.to_string()));
         }
 
         let headers = self.build_headers()?;
@@ -158,66 +438,47 @@ impl LlmInstance for OpenAIInstance {
 
         let openai_request = OpenAIRequest {
             model,
-            messages: request.messages.clone(),
-            max_tokens: request.max_tokens,
+            messages: convert_messages(&request.messages),
+            max_tokens: self.base.max_tokens(request),
             temperature: request.temperature,
+            seed: request.seed,
+            // Streams carry a single completion
+            n: None,
+            logprobs: None,
+            top_logprobs: None,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.clone(),
             stream: Some(true),
             stream_options: Some(StreamOptions { include_usage: true }),
+            tools: None,
+            tool_choice: None,
+            response_format: request.response_format.as_ref().map(convert_response_format),
         };
 
         let response = self.base.client()
-            .post(constants::OPENAI_API_ENDPOINT)
+            .post(&self.chat_url)
             .headers(headers)
             .json(&openai_request)
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("{} rate limit: {}", self.label, error_text), &headers));
+        }
+
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LlmError::ApiError(format!("OpenAI API error: {}", error_text)));
+            return Err(LlmError::api(self.label, status, &error_text));
         }
 
-        // Create a stream that processes the SSE response
-        let byte_stream = response.bytes_stream();
-
-        let chunk_stream = byte_stream
-            .map(|result| {
-                result.map_err(|e| LlmError::RequestError(e))
-            })
-            .flat_map(|result| {
-                match result {
-                    Ok(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-                        let chunks: Vec<Result<StreamChunk, LlmError>> = text
-                            .lines()
-                            .filter_map(|line| {
-                                let line = line.trim();
-                                if line.starts_with("data: ") {
-                                    let data = &line[6..];
-                                    if data == "[DONE]" {
-                                        return None;
-                                    }
-                                    match serde_json::from_str::<OpenAIStreamChunk>(data) {
-                                        Ok(chunk) => chunk.to_stream_chunk().map(Ok),
-                                        Err(e) => Some(Err(LlmError::ParseError(
-                                            format!("Failed to parse streaming chunk: {}", e)
-                                        ))),
-                                    }
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-                        futures::stream::iter(chunks)
-                    }
-                    Err(e) => {
-                        futures::stream::iter(vec![Err(e)])
-                    }
-                }
-            });
-
-        Ok(Box::pin(chunk_stream))
+        Ok(parse_lines(response.bytes_stream(), parse_openai_sse_line))
     }
 
     /// Returns whether this provider supports native streaming
@@ -225,6 +486,22 @@ impl LlmInstance for OpenAIInstance {
         true
     }
 
+    /// Returns whether this provider supports structured output
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
+    /// Checks the API key and endpoint by listing models, which costs no tokens
+    async fn health_check(&self) -> LlmResult<()> {
+        let headers = self.build_headers()?;
+        probe_endpoint(self.base.client(), &self.models_url, headers, self.label).await
+    }
+
+    /// Looks the model up in the table of known context windows
+    fn context_window(&self) -> Option<u32> {
+        context_window_for_model(self.base.model())
+    }
+
     /// Returns provider name
     fn get_name(&self) -> &str {
         self.base.name()
@@ -244,4 +521,259 @@ impl LlmInstance for OpenAIInstance {
     fn is_enabled(&self) -> bool {
         self.base.is_enabled()
     }
+
+    /// Returns this provider as an embedding provider
+    fn as_embedding(&self) -> Option<&(dyn EmbeddingInstance + Send + Sync)> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl EmbeddingInstance for OpenAIInstance {
+    /// Embeds the inputs using OpenAI's embeddings API with the configured model
+    async fn embed_with_usage(&self, inputs: &[String]) -> LlmResult<EmbeddingResponse> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled(self.label.to_string()));
+        }
+
+        let headers = self.build_headers()?;
+        let embedding_request = OpenAIEmbeddingRequest {
+            model: self.base.model(),
+            input: inputs,
+        };
+
+        let response = self.base.client()
+            .post(&self.embeddings_url)
+            .headers(headers)
+            .json(&embedding_request)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("{} rate limit: {}", self.label, error_text), &headers));
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::api(self.label, status, &error_text));
+        }
+
+        let mut embedding_response: OpenAIEmbeddingResponse = response.json().await?;
+        embedding_response.data.sort_by_key(|d| d.index);
+
+        let usage = embedding_response.usage.map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: 0,
+            total_tokens: u.total_tokens,
+            ..Default::default()
+        });
+
+        Ok(EmbeddingResponse {
+            embeddings: embedding_response.data.into_iter().map(|d| d.embedding).collect(),
+            model: embedding_response.model,
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_call_response_populates_tool_calls() {
+        let body = r#"{
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {
+                            "name": "get_weather",
+                            "arguments": "{\"city\":\"Paris\"}"
+                        }
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 20, "completion_tokens": 10, "total_tokens": 30}
+        }"#;
+
+        let parsed: OpenAIResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response().unwrap();
+
+        assert_eq!(response.content, "");
+        assert_eq!(response.model, "gpt-4o");
+        assert_eq!(response.tool_calls, vec![ToolCall {
+            id: "call_abc".to_string(),
+            name: "get_weather".to_string(),
+            arguments: r#"{"city":"Paris"}"#.to_string(),
+        }]);
+        assert_eq!(response.usage.unwrap().total_tokens, 30);
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn test_text_response_has_no_tool_calls() {
+        let body = r#"{
+            "model": "gpt-4o",
+            "choices": [{"message": {"role": "assistant", "content": "Hello"}}]
+        }"#;
+
+        let parsed: OpenAIResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response().unwrap();
+
+        assert_eq!(response.content, "Hello");
+        assert!(response.tool_calls.is_empty());
+        assert_eq!(response.finish_reason, None);
+    }
+
+    #[test]
+    fn test_truncated_response_reports_length() {
+        let body = r#"{
+            "model": "gpt-4o",
+            "choices": [{"message": {"role": "assistant", "content": "Once upon a"}, "finish_reason": "length"}]
+        }"#;
+
+        let parsed: OpenAIResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response().unwrap();
+
+        assert_eq!(response.content, "Once upon a");
+        assert_eq!(response.finish_reason.as_deref(), Some("length"));
+    }
+
+    #[test]
+    fn test_multiple_choices_are_all_returned() {
+        let body = r#"{
+            "model": "gpt-4o",
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "Red"}, "finish_reason": "stop"},
+                {"index": 1, "message": {"role": "assistant", "content": "Blue"}, "finish_reason": "stop"}
+            ],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+        }"#;
+
+        let parsed: OpenAIResponse = serde_json::from_str(body).unwrap();
+        let response = parsed.into_llm_response().unwrap();
+
+        assert_eq!(response.content, "Red");
+        assert_eq!(response.choices, vec!["Red".to_string(), "Blue".to_string()]);
+        assert_eq!(response.usage.unwrap().completion_tokens, 2);
+    }
+
+    #[test]
+    fn test_logprobs_are_kept_raw() {
+        let body = r#"{
+            "model": "gpt-4o",
+            "choices": [{
+                "message": {"role": "assistant", "content": "Yes"},
+                "finish_reason": "stop",
+                "logprobs": {
+                    "content": [{
+                        "token": "Yes",
+                        "logprob": -0.01,
+                        "bytes": [89, 101, 115],
+                        "top_logprobs": [
+                            {"token": "Yes", "logprob": -0.01, "bytes": [89, 101, 115]},
+                            {"token": "No", "logprob": -4.6, "bytes": [78, 111]}
+                        ]
+                    }]
+                }
+            }]
+        }"#;
+
+        let parsed: OpenAIResponse = serde_json::from_str(body).unwrap();
+        let logprobs = parsed.into_llm_response().unwrap().logprobs.unwrap();
+
+        assert_eq!(logprobs["content"][0]["token"], "Yes");
+        assert_eq!(logprobs["content"][0]["top_logprobs"][1]["token"], "No");
+        assert_eq!(logprobs["content"][0]["top_logprobs"][1]["logprob"], -4.6);
+    }
+
+    #[test]
+    fn test_null_logprobs_are_none() {
+        let body = r#"{
+            "model": "gpt-4o",
+            "choices": [{"message": {"role": "assistant", "content": "Hi"}, "logprobs": null}]
+        }"#;
+
+        let parsed: OpenAIResponse = serde_json::from_str(body).unwrap();
+        assert!(parsed.into_llm_response().unwrap().logprobs.is_none());
+    }
+
+    #[test]
+    fn test_stream_chunk_reports_finish_reason() {
+        let data = r#"{"model": "gpt-4o", "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}"#;
+        let chunk: crate::providers::streaming::OpenAIStreamChunk = serde_json::from_str(data).unwrap();
+        let chunk = chunk.to_stream_chunk().unwrap();
+
+        assert!(chunk.is_final);
+        assert_eq!(chunk.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn test_tool_serialization() {
+        let tools = vec![ToolDefinition::new(
+            "get_weather",
+            "Get the weather for a city",
+            json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+        )];
+
+        let value = serde_json::to_value(convert_tools(&tools)).unwrap();
+        assert_eq!(value[0]["type"], "function");
+        assert_eq!(value[0]["function"]["name"], "get_weather");
+        assert_eq!(value[0]["function"]["parameters"]["type"], "object");
+
+        assert_eq!(convert_tool_choice("auto"), json!("auto"));
+        assert_eq!(convert_tool_choice("get_weather")["function"]["name"], "get_weather");
+    }
+
+    #[test]
+    fn test_message_serialization() {
+        let text_only = Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        };
+        let with_image = Message {
+            role: "user".to_string(),
+            content: "What is this?".to_string(),
+            content_parts: Some(vec![
+                ContentPart::Text("What is this?".to_string()),
+                ContentPart::ImageUrl { url: "https://example.com/cat.png".to_string(), detail: Some("low".to_string()) },
+                ContentPart::ImageBase64 { media_type: "image/png".to_string(), data: "aGk=".to_string() },
+            ]),
+            ..Default::default()
+        };
+
+        let value = serde_json::to_value(convert_messages(&[text_only, with_image])).unwrap();
+        assert_eq!(value[0]["content"], "Hello");
+
+        let parts = &value[1]["content"];
+        assert_eq!(parts[0], json!({"type": "text", "text": "What is this?"}));
+        assert_eq!(parts[1]["image_url"]["url"], "https://example.com/cat.png");
+        assert_eq!(parts[1]["image_url"]["detail"], "low");
+        assert_eq!(parts[2]["image_url"]["url"], "data:image/png;base64,aGk=");
+    }
+
+    #[test]
+    fn test_response_format_serialization() {
+        assert_eq!(convert_response_format(&ResponseFormat::JsonObject), json!({"type": "json_object"}));
+
+        let schema = json!({"type": "object", "properties": {"answer": {"type": "string"}}});
+        let value = convert_response_format(&ResponseFormat::JsonSchema { schema: schema.clone() });
+        assert_eq!(value["type"], "json_schema");
+        assert_eq!(value["json_schema"]["schema"], schema);
+    }
 }
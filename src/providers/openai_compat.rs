@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::instances::{LlmInstance, BaseInstance, InstanceSettings, ensure_text_only, probe_endpoint, normalize_finish_reason};
+use crate::providers::types::{LlmRequest, LlmResponse, LlmStream, StreamChunk, TokenUsage, Message};
+use crate::providers::streaming::{parse_lines, parse_openai_sse_line};
+use crate::providers::tokens::context_window_for_model;
+use crate::errors::{LlmError, LlmResult};
+
+use async_trait::async_trait;
+use reqwest::header;
+use serde::{Serialize, Deserialize};
+
+/// How a provider sends its API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AuthScheme {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// `Authorization: Bearer <key>`, left out when the key is empty (local servers)
+    OptionalBearer,
+}
+
+/// What sets one OpenAI-compatible provider apart from another
+pub(crate) struct CompatConfig {
+    /// Instance name returned by `get_name` (e.g. "togetherai")
+    pub name: &'static str,
+    /// Name used in error messages (e.g. "Together AI")
+    pub display_name: &'static str,
+    /// Full chat completions URL
+    pub chat_url: String,
+    /// Models URL probed by health checks; `None` probes with a one-token request
+    pub models_url: Option<String>,
+    pub auth: AuthScheme,
+    /// Whether the API streams over SSE; otherwise streaming falls back to one chunk
+    pub streaming: bool,
+    /// Whether `LlmRequest::seed` is sent
+    pub seed: bool,
+    /// Whether `LlmRequest::logprobs` and `top_logprobs` are sent; some APIs reject them
+    pub logprobs: bool,
+    /// Whether `context_window` looks the model up in the table of known models;
+    /// local servers pick their own window
+    pub known_models: bool,
+}
+
+/// Shared implementation for providers speaking OpenAI's chat completions API
+///
+/// Groq, Together AI, Perplexity, LM Studio, xAI, AI21 and Fireworks wrap this and only supply a
+/// `CompatConfig`; `delegate_llm_instance!` forwards their `LlmInstance` impl here.
+pub(crate) struct OpenAICompatibleProvider {
+    base: BaseInstance,
+    config: CompatConfig,
+}
+
+/// Request body for the chat completions API
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: String,
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u32>,
+    stream: bool,
+}
+
+/// Response body from the chat completions API
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+    /// Left out by some providers (AI21), in which case the requested model is reported
+    #[serde(default)]
+    model: Option<String>,
+    usage: Option<ChatUsage>,
+    /// URLs of the sources the answer draws on (Perplexity)
+    #[serde(default)]
+    citations: Vec<String>,
+}
+
+/// Individual choice in a response
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: Message,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    /// Present when `logprobs` was requested, null otherwise
+    #[serde(default)]
+    logprobs: Option<serde_json::Value>,
+}
+
+/// Token usage information
+#[derive(Deserialize)]
+struct ChatUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+impl OpenAICompatibleProvider {
+    /// Creates a provider instance from its config
+    pub(crate) fn new(
+        config: CompatConfig,
+        api_key: String,
+        model: String,
+        supported_tasks: HashMap<String, TaskDefinition>,
+        enabled: bool,
+        settings: &InstanceSettings,
+    ) -> LlmResult<Self> {
+        let base = BaseInstance::new(config.name.to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        Ok(Self { base, config })
+    }
+
+    fn build_headers(&self) -> LlmResult<header::HeaderMap> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        let api_key = self.base.api_key();
+        if self.config.auth == AuthScheme::Bearer || !api_key.is_empty() {
+            let value = header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format for {}: {}", self.config.display_name, e)))?;
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
+
+    /// Send a chat completions request, mapping error statuses to errors
+    async fn send(&self, request: &LlmRequest, stream: bool) -> LlmResult<reqwest::Response> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled(self.config.display_name.to_string()));
+        }
+        ensure_text_only(request, self.config.display_name)?;
+
+        let body = ChatRequest {
+            model: request.model.clone().unwrap_or_else(|| self.base.model().to_string()),
+            messages: &request.messages,
+            max_tokens: self.base.max_tokens(request),
+            temperature: request.temperature,
+            seed: if self.config.seed { request.seed } else { None },
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            logprobs: if self.config.logprobs { request.logprobs } else { None },
+            top_logprobs: if self.config.logprobs { request.top_logprobs } else { None },
+            stream,
+        };
+
+        let response = self.base.client()
+            .post(&self.config.chat_url)
+            .headers(self.build_headers()?)
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return Err(LlmError::rate_limit(format!("{} rate limit: {}", self.config.display_name, error_text), &headers));
+        }
+        if !status.is_success() {
+            let error_text = response.text().await
+                .unwrap_or_else(|_| format!("Unknown error. Status: {}", status));
+            return Err(LlmError::api(self.config.display_name, status, &error_text));
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl LlmInstance for OpenAICompatibleProvider {
+    async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+        let response: ChatResponse = self.send(request, false).await?.json().await?;
+
+        let Some(choice) = response.choices.into_iter().next() else {
+            return Err(LlmError::ApiError(format!("No response from {}", self.config.display_name)));
+        };
+
+        Ok(LlmResponse {
+            content: choice.message.content,
+            model: response.model
+                .unwrap_or_else(|| request.model.clone().unwrap_or_else(|| self.base.model().to_string())),
+            usage: response.usage.map(|u| TokenUsage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+                ..Default::default()
+            }),
+            citations: response.citations,
+            finish_reason: choice.finish_reason.as_deref().map(normalize_finish_reason),
+            logprobs: choice.logprobs,
+            ..Default::default()
+        })
+    }
+
+    async fn generate_stream(&self, request: &LlmRequest) -> LlmResult<LlmStream> {
+        if !self.config.streaming {
+            let response = self.generate(request).await?;
+            let chunk = StreamChunk {
+                content: response.content,
+                model: Some(response.model),
+                is_final: true,
+                usage: response.usage,
+                finish_reason: response.finish_reason,
+            };
+            return Ok(Box::pin(futures::stream::once(async move { Ok(chunk) })));
+        }
+
+        Ok(parse_lines(self.send(request, true).await?.bytes_stream(), parse_openai_sse_line))
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.config.streaming
+    }
+
+    /// Lists models when the provider has a models URL, which costs no tokens;
+    /// otherwise sends a one-token request
+    async fn health_check(&self) -> LlmResult<()> {
+        match &self.config.models_url {
+            Some(url) => probe_endpoint(self.base.client(), url, self.build_headers()?, self.config.display_name).await,
+            None => {
+                let request = LlmRequest {
+                    messages: vec![Message {
+                        role: "user".to_string(),
+                        content: "ping".to_string(),
+                        ..Default::default()
+                    }],
+                    max_tokens: Some(1),
+                    ..Default::default()
+                };
+                self.generate(&request).await.map(|_| ())
+            }
+        }
+    }
+
+    fn context_window(&self) -> Option<u32> {
+        if self.config.known_models {
+            context_window_for_model(self.base.model())
+        } else {
+            None
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        self.base.name()
+    }
+
+    fn get_model(&self) -> &str {
+        self.base.model()
+    }
+
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        self.base.supported_tasks()
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.base.is_enabled()
+    }
+}
+
+/// Implement `LlmInstance` for a newtype around `OpenAICompatibleProvider` by forwarding to it
+macro_rules! delegate_llm_instance {
+    ($instance:ty) => {
+        #[async_trait::async_trait]
+        impl crate::providers::instances::LlmInstance for $instance {
+            async fn generate(&self, request: &crate::providers::types::LlmRequest) -> crate::errors::LlmResult<crate::providers::types::LlmResponse> {
+                self.0.generate(request).await
+            }
+
+            async fn generate_stream(&self, request: &crate::providers::types::LlmRequest) -> crate::errors::LlmResult<crate::providers::types::LlmStream> {
+                self.0.generate_stream(request).await
+            }
+
+            fn supports_streaming(&self) -> bool {
+                self.0.supports_streaming()
+            }
+
+            async fn health_check(&self) -> crate::errors::LlmResult<()> {
+                self.0.health_check().await
+            }
+
+            fn context_window(&self) -> Option<u32> {
+                self.0.context_window()
+            }
+
+            fn get_name(&self) -> &str {
+                self.0.get_name()
+            }
+
+            fn get_model(&self) -> &str {
+                self.0.get_model()
+            }
+
+            fn get_supported_tasks(&self) -> &std::collections::HashMap<String, crate::load_balancer::tasks::TaskDefinition> {
+                self.0.get_supported_tasks()
+            }
+
+            fn is_enabled(&self) -> bool {
+                self.0.is_enabled()
+            }
+        }
+    };
+}
+
+pub(crate) use delegate_llm_instance;
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::instances::{LlmInstance, BaseInstance, InstanceSettings, ensure_text_only, probe_endpoint};
+use crate::providers::types::{LlmRequest, LlmResponse, Message, Role, TokenUsage};
+use crate::errors::{LlmError, LlmResult};
+use crate::constants;
+
+use async_trait::async_trait;
+use reqwest::header;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+/// Provider implementation for open models hosted on Replicate
+///
+/// Replicate runs models as predictions rather than chat completions: a prediction is
+/// created with the prompt as input, then fetched until it finishes. Requests ask the
+/// API to hold the response open (`Prefer: wait`) so short generations need no polling.
+/// API endpoint: https://api.replicate.com/v1/predictions
+///
+/// Models are named `owner/name` (e.g. "meta/meta-llama-3-8b-instruct"), optionally
+/// pinned to a version with `owner/name:version`.
+pub struct ReplicateInstance {
+    base: BaseInstance,
+    api_base: String,
+}
+
+/// Request body creating a prediction
+#[derive(Serialize)]
+struct PredictionRequest {
+    /// Version ID; left out when running the latest version of an official model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    input: PredictionInput,
+}
+
+/// Input of a prediction, following the schema of Replicate's language models
+#[derive(Serialize)]
+struct PredictionInput {
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+/// A prediction as returned when it is created or fetched
+#[derive(Deserialize)]
+struct Prediction {
+    id: String,
+    #[serde(default)]
+    model: Option<String>,
+    /// One of "starting", "processing", "succeeded", "failed" or "canceled"
+    status: String,
+    /// Language models return a list of tokens, some models a single string
+    #[serde(default)]
+    output: Value,
+    #[serde(default)]
+    error: Option<Value>,
+    #[serde(default)]
+    urls: Option<PredictionUrls>,
+    #[serde(default)]
+    metrics: Option<PredictionMetrics>,
+}
+
+/// Links for following up on a prediction
+#[derive(Deserialize)]
+struct PredictionUrls {
+    get: Option<String>,
+    cancel: Option<String>,
+}
+
+/// Metrics of a finished prediction; token counts are only reported by some models
+#[derive(Deserialize)]
+struct PredictionMetrics {
+    input_token_count: Option<u32>,
+    output_token_count: Option<u32>,
+}
+
+impl Prediction {
+    /// Whether the prediction has stopped running, successfully or not
+    fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "succeeded" | "failed" | "canceled")
+    }
+
+    /// Concatenate the output, which is streamed token by token into a list
+    fn content(&self) -> String {
+        match &self.output {
+            Value::Null => String::new(),
+            Value::String(text) => text.clone(),
+            Value::Array(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                })
+                .collect(),
+            other => other.to_string(),
+        }
+    }
+
+    /// Token usage, when the model reported both counts
+    fn usage(&self) -> Option<TokenUsage> {
+        let metrics = self.metrics.as_ref()?;
+        let (prompt_tokens, completion_tokens) = (metrics.input_token_count?, metrics.output_token_count?);
+        Some(TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            ..Default::default()
+        })
+    }
+}
+
+impl ReplicateInstance {
+    /// Creates a new Replicate provider instance
+    ///
+    /// # Parameters
+    /// * `api_key` - Replicate API token (required)
+    /// * `model` - Default model to use (e.g. "meta/meta-llama-3-8b-instruct")
+    /// * `supported_tasks` - Map of tasks this provider supports
+    /// * `enabled` - Whether this provider is enabled
+    /// * `endpoint_url` - Optional API base URL replacing `https://api.replicate.com/v1`
+    /// * `settings` - Extra HTTP settings such as custom headers
+    pub fn new(
+        api_key: String,
+        model: String,
+        supported_tasks: HashMap<String, TaskDefinition>,
+        enabled: bool,
+        endpoint_url: Option<String>,
+        settings: &InstanceSettings,
+    ) -> LlmResult<Self> {
+        let api_base = endpoint_url
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| constants::REPLICATE_API_BASE.to_string());
+        let base = BaseInstance::new("replicate".to_string(), api_key, model, supported_tasks, enabled, settings)?;
+        Ok(Self { base, api_base })
+    }
+
+    /// URL creating a prediction for `model` and the version to send with it
+    ///
+    /// `owner/name:version` and bare version IDs go to `/predictions`; `owner/name` runs
+    /// the latest version through the model's own predictions endpoint.
+    fn prediction_target(&self, model: &str) -> (String, Option<String>) {
+        match model.split_once(':') {
+            Some((_, version)) => (format!("{}/predictions", self.api_base), Some(version.to_string())),
+            None if model.contains('/') => (format!("{}/models/{}/predictions", self.api_base, model), None),
+            None => (format!("{}/predictions", self.api_base), Some(model.to_string())),
+        }
+    }
+
+    fn build_headers(&self) -> LlmResult<header::HeaderMap> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", self.base.api_key()))
+                .map_err(|e| LlmError::ConfigError(format!("Invalid API key format for Replicate: {}", e)))?,
+        );
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        Ok(headers)
+    }
+
+    /// Flatten chat messages into a prompt and system prompt
+    ///
+    /// A lone user message is sent as-is; longer conversations become a
+    /// "User: ... / Assistant: ..." transcript ending with an open assistant turn.
+    fn build_prompt(messages: &[Message]) -> (String, Option<String>) {
+        let system: Vec<&str> = messages
+            .iter()
+            .filter(|m| m.role_kind() == Some(Role::System))
+            .map(|m| m.content.as_str())
+            .collect();
+        let turns: Vec<&Message> = messages
+            .iter()
+            .filter(|m| m.role_kind() != Some(Role::System))
+            .collect();
+
+        let prompt = match turns.as_slice() {
+            [only] if only.role_kind() == Some(Role::User) => only.content.clone(),
+            turns => {
+                let mut transcript: Vec<String> = turns
+                    .iter()
+                    .map(|m| {
+                        let speaker = if m.role_kind() == Some(Role::Assistant) { "Assistant" } else { "User" };
+                        format!("{}: {}", speaker, m.content)
+                    })
+                    .collect();
+                transcript.push("Assistant:".to_string());
+                transcript.join("\n\n")
+            }
+        };
+
+        let system_prompt = (!system.is_empty()).then(|| system.join("\n\n"));
+        (prompt, system_prompt)
+    }
+
+    /// Map a failed HTTP response to an error
+    async fn error_from_response(response: reqwest::Response) -> LlmError {
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return LlmError::rate_limit(format!("Replicate rate limit: {}", error_text), &headers);
+        }
+
+        let error_text = response.text().await
+            .unwrap_or_else(|_| format!("Unknown error. Status: {}", status));
+        LlmError::api("Replicate", status, &error_text)
+    }
+
+    /// Fetch a prediction until it finishes, giving up after `REPLICATE_MAX_WAIT_SECS`
+    async fn wait_for(&self, mut prediction: Prediction) -> LlmResult<Prediction> {
+        let started = Instant::now();
+        while !prediction.is_finished() {
+            let urls = prediction.urls.as_ref();
+            if started.elapsed() >= Duration::from_secs(constants::REPLICATE_MAX_WAIT_SECS) {
+                // Best effort: stop paying for a prediction nobody is waiting on
+                if let Some(cancel_url) = urls.and_then(|u| u.cancel.as_deref()) {
+                    let _ = self.base.client().post(cancel_url).headers(self.build_headers()?).send().await;
+                }
+                return Err(LlmError::ApiError(format!(
+                    "Replicate prediction {} still {} after {}s",
+                    prediction.id, prediction.status, constants::REPLICATE_MAX_WAIT_SECS
+                )));
+            }
+
+            let get_url = urls
+                .and_then(|u| u.get.clone())
+                .unwrap_or_else(|| format!("{}/predictions/{}", self.api_base, prediction.id));
+            tokio::time::sleep(Duration::from_millis(constants::REPLICATE_POLL_INTERVAL_MS)).await;
+
+            let response = self.base.client().get(&get_url).headers(self.build_headers()?).send().await?;
+            if !response.status().is_success() {
+                return Err(Self::error_from_response(response).await);
+            }
+            prediction = response.json().await?;
+        }
+        Ok(prediction)
+    }
+}
+
+#[async_trait]
+impl LlmInstance for ReplicateInstance {
+    /// Generates a completion by running a prediction to completion
+    ///
+    /// # Parameters
+    /// * `request` - The LLM request containing messages and parameters
+    ///
+    /// # Returns
+    /// * `LlmResult<LlmResponse>` - The response from the model or an error
+    async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled("Replicate".to_string()));
+        }
+        ensure_text_only(request, "Replicate")?;
+
+        let model = request.model.clone().unwrap_or_else(|| self.base.model().to_string());
+        let (url, version) = self.prediction_target(&model);
+        let (prompt, system_prompt) = Self::build_prompt(&request.messages);
+        let body = PredictionRequest {
+            version,
+            input: PredictionInput {
+                prompt,
+                system_prompt,
+                max_tokens: self.base.max_tokens(request),
+                temperature: request.temperature,
+                seed: request.seed,
+            },
+        };
+
+        let response = self.base.client()
+            .post(&url)
+            .headers(self.build_headers()?)
+            .header("Prefer", "wait")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        let prediction = self.wait_for(response.json().await?).await?;
+        if prediction.status != "succeeded" {
+            let reason = match &prediction.error {
+                Some(Value::String(message)) => message.clone(),
+                Some(error) if !error.is_null() => error.to_string(),
+                _ => "no error given".to_string(),
+            };
+            return Err(LlmError::ApiError(format!(
+                "Replicate prediction {} {}: {}",
+                prediction.id, prediction.status, reason
+            )));
+        }
+
+        Ok(LlmResponse {
+            content: prediction.content(),
+            usage: prediction.usage(),
+            model: prediction.model.unwrap_or(model),
+            ..Default::default()
+        })
+    }
+
+    /// Checks the API token against the account endpoint, which costs nothing
+    async fn health_check(&self) -> LlmResult<()> {
+        let url = format!("{}/account", self.api_base);
+        probe_endpoint(self.base.client(), &url, self.build_headers()?, "Replicate").await
+    }
+
+    /// Returns provider name
+    fn get_name(&self) -> &str {
+        self.base.name()
+    }
+
+    /// Returns current model name
+    fn get_model(&self) -> &str {
+        self.base.model()
+    }
+
+    /// Returns supported tasks for this provider
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        self.base.supported_tasks()
+    }
+
+    /// Returns whether this provider is enabled
+    fn is_enabled(&self) -> bool {
+        self.base.is_enabled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance() -> ReplicateInstance {
+        ReplicateInstance::new("r8_key".to_string(), "meta/meta-llama-3-8b-instruct".to_string(), HashMap::new(), true, None, &InstanceSettings::default()).unwrap()
+    }
+
+    #[test]
+    fn test_prediction_target_by_model_form() {
+        let instance = instance();
+        assert_eq!(
+            instance.prediction_target("meta/meta-llama-3-8b-instruct"),
+            ("https://api.replicate.com/v1/models/meta/meta-llama-3-8b-instruct/predictions".to_string(), None)
+        );
+        assert_eq!(
+            instance.prediction_target("owner/model:5c7d5dc6"),
+            ("https://api.replicate.com/v1/predictions".to_string(), Some("5c7d5dc6".to_string()))
+        );
+        assert_eq!(
+            instance.prediction_target("5c7d5dc6"),
+            ("https://api.replicate.com/v1/predictions".to_string(), Some("5c7d5dc6".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_single_user_message_is_the_prompt() {
+        let (prompt, system) = ReplicateInstance::build_prompt(&[Message::system("Be brief."), Message::user("Hi")]);
+        assert_eq!(prompt, "Hi");
+        assert_eq!(system.as_deref(), Some("Be brief."));
+    }
+
+    #[test]
+    fn test_conversation_becomes_transcript() {
+        let messages = [Message::user("Hi"), Message::assistant("Hello!"), Message::user("Tell me a joke")];
+        let (prompt, system) = ReplicateInstance::build_prompt(&messages);
+        assert_eq!(prompt, "User: Hi\n\nAssistant: Hello!\n\nUser: Tell me a joke\n\nAssistant:");
+        assert!(system.is_none());
+    }
+
+    #[test]
+    fn test_output_tokens_are_concatenated() {
+        let prediction: Prediction = serde_json::from_str(
+            r#"{"id": "p1", "status": "succeeded", "output": ["Hel", "lo", "!"], "metrics": {"predict_time": 0.4}}"#,
+        ).unwrap();
+        assert_eq!(prediction.content(), "Hello!");
+        assert!(prediction.usage().is_none());
+
+        let prediction: Prediction = serde_json::from_str(r#"{"id": "p2", "status": "succeeded", "output": "Hello!"}"#).unwrap();
+        assert_eq!(prediction.content(), "Hello!");
+    }
+}
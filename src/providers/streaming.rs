@@ -3,15 +3,273 @@
 //! This module provides utilities for parsing Server-Sent Events (SSE) streams
 //! from various LLM providers.
 
-use crate::providers::types::{StreamChunk, TokenUsage};
+use crate::errors::{LlmError, LlmResult};
+use crate::providers::instances::normalize_finish_reason;
+use crate::providers::tokens::count_tokens;
+use crate::providers::types::{LlmResponse, LlmStream, StreamChunk, TokenUsage};
+use futures::{Stream, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+/// Stream wrapper that ends as soon as a cancellation token fires
+///
+/// The inner stream is dropped on cancellation, which aborts the provider's HTTP request.
+pub(crate) struct CancellableStream {
+    inner: Option<LlmStream>,
+    cancelled: Pin<Box<WaitForCancellationFutureOwned>>,
+}
+
+impl CancellableStream {
+    pub(crate) fn new(inner: LlmStream, token: CancellationToken) -> Self {
+        Self {
+            inner: Some(inner),
+            cancelled: Box::pin(token.cancelled_owned()),
+        }
+    }
+}
+
+impl Stream for CancellableStream {
+    type Item = Result<StreamChunk, LlmError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.inner.is_none() {
+            return Poll::Ready(None);
+        }
+        if this.cancelled.as_mut().poll(cx).is_ready() {
+            this.inner = None;
+            return Poll::Ready(None);
+        }
+
+        let inner = this.inner.as_mut().expect("checked above");
+        match inner.as_mut().poll_next(cx) {
+            Poll::Ready(None) => {
+                this.inner = None;
+                Poll::Ready(None)
+            }
+            other => other,
+        }
+    }
+}
+
+/// Stream wrapper that ends at the first stop sequence, whatever the provider does
+///
+/// Content that could be the start of a stop sequence is held back until the next chunk
+/// shows whether it is. On a match, the content is cut before the stop sequence, the
+/// chunk is marked final with finish reason `"stop"` and the inner stream is dropped.
+/// The provider's usage would only arrive at the end of its stream, so the final chunk
+/// carries an estimate instead when one was asked for with `with_usage_estimate`.
+pub(crate) struct StopSequenceStream {
+    inner: Option<LlmStream>,
+    stops: Vec<String>,
+    pending: String,
+    // Estimated prompt tokens and model, plus the content emitted so far, for estimating usage
+    estimate: Option<(u32, String)>,
+    emitted: String,
+}
+
+impl StopSequenceStream {
+    pub(crate) fn new(inner: LlmStream, stops: Vec<String>) -> Self {
+        Self {
+            inner: Some(inner),
+            stops: stops.into_iter().filter(|stop| !stop.is_empty()).collect(),
+            pending: String::new(),
+            estimate: None,
+            emitted: String::new(),
+        }
+    }
+
+    /// Estimate the usage of a stream cut short by a stop sequence
+    ///
+    /// # Parameters
+    /// * `prompt_tokens` - Estimated tokens of the prompt
+    /// * `model` - Model the completion tokens are estimated for
+    pub(crate) fn with_usage_estimate(mut self, prompt_tokens: u32, model: impl Into<String>) -> Self {
+        self.estimate = Some((prompt_tokens, model.into()));
+        self
+    }
+
+    /// Estimated usage of the content emitted so far, if an estimate was asked for
+    fn estimated_usage(&self) -> Option<TokenUsage> {
+        let (prompt_tokens, model) = self.estimate.as_ref()?;
+        let completion_tokens = count_tokens(&self.emitted, model);
+        Some(TokenUsage {
+            prompt_tokens: *prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            ..Default::default()
+        })
+    }
+
+    /// Byte offset of the earliest stop sequence in the held-back content
+    fn find_stop(&self) -> Option<usize> {
+        self.stops.iter().filter_map(|stop| self.pending.find(stop.as_str())).min()
+    }
+
+    /// Byte offset up to which held-back content can't be part of a stop sequence
+    fn safe_len(&self) -> usize {
+        let longest = self.stops.iter().map(String::len).max().unwrap_or(0);
+        let mut cut = self.pending.len().saturating_sub(longest.saturating_sub(1));
+        while !self.pending.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        cut
+    }
+}
+
+impl Stream for StopSequenceStream {
+    type Item = Result<StreamChunk, LlmError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(inner) = this.inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+
+        match inner.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(mut chunk))) => {
+                this.pending.push_str(&chunk.content);
+                if let Some(position) = this.find_stop() {
+                    this.pending.truncate(position);
+                    chunk.content = std::mem::take(&mut this.pending);
+                    chunk.is_final = true;
+                    chunk.finish_reason = Some("stop".to_string());
+                    this.inner = None;
+                    this.emitted.push_str(&chunk.content);
+                    if chunk.usage.is_none() {
+                        chunk.usage = this.estimated_usage();
+                    }
+                    return Poll::Ready(Some(Ok(chunk)));
+                }
+
+                let emit = if chunk.is_final { this.pending.len() } else { this.safe_len() };
+                let held_back = this.pending.split_off(emit);
+                chunk.content = std::mem::replace(&mut this.pending, held_back);
+                this.emitted.push_str(&chunk.content);
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(None) => {
+                this.inner = None;
+                if this.pending.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Ok(StreamChunk::content(std::mem::take(&mut this.pending)))))
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Consume a stream and assemble its chunks into one response
+///
+/// Chunk contents are concatenated in order; the model, usage and finish reason are
+/// taken from the last chunk that reports them. The first error ends collection and
+/// is returned. To show tokens live as well, inspect the stream before collecting:
+///
+/// ```ignore
+/// let stream = manager.generate_stream(request).await?;
+/// let response = collect_response(Box::pin(stream.inspect(|chunk| {
+///     if let Ok(chunk) = chunk {
+///         print!("{}", chunk.content);
+///     }
+/// }))).await?;
+/// ```
+pub async fn collect_response(mut stream: LlmStream) -> LlmResult<LlmResponse> {
+    let mut response = LlmResponse::default();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        response.content.push_str(&chunk.content);
+        if let Some(model) = chunk.model {
+            response.model = model;
+        }
+        if chunk.usage.is_some() {
+            response.usage = chunk.usage;
+        }
+        if chunk.finish_reason.is_some() {
+            response.finish_reason = chunk.finish_reason;
+        }
+    }
+    Ok(response)
+}
 
 /// Parse a single SSE line and extract the data field
 pub fn parse_sse_line(line: &str) -> Option<&str> {
-    let line = line.trim();
-    if line.starts_with("data: ") {
-        Some(&line[6..])
-    } else {
-        None
+    line.trim().strip_prefix("data: ")
+}
+
+/// Buffer that splits bytes arriving in arbitrary pieces into complete lines
+///
+/// Network chunks can end mid-line, or even mid-character; the unfinished tail is kept
+/// as raw bytes until the rest of the line arrives. Only whole lines are decoded, so a
+/// multi-byte UTF-8 character split between chunks (e.g. an emoji) decodes intact
+/// instead of turning into U+FFFD replacement characters.
+#[derive(Debug, Default)]
+pub(crate) struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Add received bytes, returning the lines they complete (without line endings)
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let Some(last_newline) = self.pending.iter().rposition(|b| *b == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.pending.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        String::from_utf8_lossy(&complete).lines().map(str::to_string).collect()
+    }
+
+    /// Take the unterminated last line, if any, once the body has ended
+    pub(crate) fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.pending);
+        Some(String::from_utf8_lossy(&rest).into_owned())
+    }
+}
+
+/// Turn a streamed response body into chunks, handing each complete line to `parse_line`
+///
+/// Lines split across network chunks are reassembled first, so `parse_line` never sees
+/// half an SSE event or NDJSON object. Returning `None` skips a line.
+pub(crate) fn parse_lines<S, B, F>(bytes: S, mut parse_line: F) -> LlmStream
+where
+    S: Stream<Item = reqwest::Result<B>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    F: FnMut(&str) -> Option<LlmResult<StreamChunk>> + Send + 'static,
+{
+    let mut buffer = LineBuffer::default();
+    let chunk_stream = bytes
+        .map(Some)
+        // A final `None` flushes a last line that has no trailing newline
+        .chain(futures::stream::once(async { None }))
+        .flat_map(move |item| {
+            let chunks: Vec<LlmResult<StreamChunk>> = match item {
+                Some(Ok(bytes)) => buffer.push(bytes.as_ref()).iter().filter_map(|line| parse_line(line)).collect(),
+                Some(Err(e)) => vec![Err(LlmError::RequestError(e))],
+                None => buffer.finish().iter().filter_map(|line| parse_line(line)).collect(),
+            };
+            futures::stream::iter(chunks)
+        });
+    Box::pin(chunk_stream)
+}
+
+/// Parse one line of an OpenAI-style SSE stream (`data: {...}` lines ending with `data: [DONE]`)
+pub(crate) fn parse_openai_sse_line(line: &str) -> Option<LlmResult<StreamChunk>> {
+    let data = parse_sse_line(line)?;
+    if data == "[DONE]" {
+        return None;
+    }
+    match serde_json::from_str::<OpenAIStreamChunk>(data) {
+        Ok(chunk) => chunk.to_stream_chunk().map(Ok),
+        Err(e) => Some(Err(LlmError::ParseError(
+            format!("Failed to parse streaming chunk: {}", e)
+        ))),
     }
 }
 
@@ -76,26 +334,35 @@ pub struct OpenAIStreamUsage {
 
 impl OpenAIStreamChunk {
     /// Convert to a StreamChunk
+    ///
+    /// With `stream_options.include_usage`, OpenAI sends usage in one more chunk after the
+    /// finish reason, with no choices; it becomes a final chunk carrying only the usage.
     pub fn to_stream_chunk(&self) -> Option<StreamChunk> {
-        if self.choices.is_empty() {
-            return None;
-        }
-
-        let choice = &self.choices[0];
-        let content = choice.delta.content.clone().unwrap_or_default();
-        let is_final = choice.finish_reason.is_some();
-
         let usage = self.usage.as_ref().map(|u| TokenUsage {
             prompt_tokens: u.prompt_tokens,
             completion_tokens: u.completion_tokens,
             total_tokens: u.total_tokens,
+            ..Default::default()
         });
 
+        let Some(choice) = self.choices.first() else {
+            return usage.map(|usage| StreamChunk {
+                content: String::new(),
+                model: self.model.clone(),
+                is_final: true,
+                usage: Some(usage),
+                finish_reason: None,
+            });
+        };
+        let content = choice.delta.content.clone().unwrap_or_default();
+        let is_final = choice.finish_reason.is_some();
+
         Some(StreamChunk {
             content,
             model: self.model.clone(),
             is_final,
             usage,
+            finish_reason: choice.finish_reason.as_deref().map(normalize_finish_reason),
         })
     }
 }
@@ -157,6 +424,10 @@ pub struct AnthropicUsage {
     pub input_tokens: Option<u32>,
     #[serde(default)]
     pub output_tokens: Option<u32>,
+    #[serde(default)]
+    pub cache_creation_input_tokens: Option<u32>,
+    #[serde(default)]
+    pub cache_read_input_tokens: Option<u32>,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -166,6 +437,75 @@ pub struct AnthropicError {
     pub message: String,
 }
 
+/// Parser for Anthropic SSE streams
+///
+/// `message_start` reports the input and cache tokens while the final `message_delta`
+/// may only report output tokens, so the start's usage is kept and merged into the
+/// final chunk.
+#[derive(Debug, Default)]
+pub(crate) struct AnthropicStreamParser {
+    start_usage: Option<TokenUsage>,
+}
+
+impl AnthropicStreamParser {
+    /// Parse one SSE line; events without content or usage are skipped
+    pub(crate) fn parse_line(&mut self, line: &str) -> Option<LlmResult<StreamChunk>> {
+        let data = parse_sse_line(line)?;
+        if data == "[DONE]" {
+            return None;
+        }
+        let event = match serde_json::from_str::<AnthropicStreamEvent>(data) {
+            Ok(event) => event,
+            Err(e) => {
+                // Skip parse errors for non-content events
+                log::debug!("Failed to parse Anthropic streaming event: {}", e);
+                return None;
+            }
+        };
+
+        if let AnthropicStreamEvent::MessageStart { message } = &event {
+            self.start_usage = message.usage.as_ref().map(AnthropicUsage::to_token_usage);
+        }
+        let mut chunk = event.to_stream_chunk()?;
+        if chunk.is_final {
+            chunk.usage = match (self.start_usage.take(), chunk.usage) {
+                (Some(start), Some(end)) => Some(Self::merge_usage(start, end)),
+                (start, end) => end.or(start),
+            };
+        }
+        Some(Ok(chunk))
+    }
+
+    /// Fill in the usage at the end of the stream from the usage at its start
+    ///
+    /// Counts the end reports win, except input tokens it leaves at zero.
+    fn merge_usage(start: TokenUsage, end: TokenUsage) -> TokenUsage {
+        let prompt_tokens = if end.prompt_tokens > 0 { end.prompt_tokens } else { start.prompt_tokens };
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens: end.completion_tokens,
+            total_tokens: prompt_tokens + end.completion_tokens,
+            cache_creation_tokens: end.cache_creation_tokens.or(start.cache_creation_tokens),
+            cache_read_tokens: end.cache_read_tokens.or(start.cache_read_tokens),
+        }
+    }
+}
+
+impl AnthropicUsage {
+    /// Convert to TokenUsage, counting missing fields as zero
+    fn to_token_usage(&self) -> TokenUsage {
+        let input_tokens = self.input_tokens.unwrap_or(0);
+        let output_tokens = self.output_tokens.unwrap_or(0);
+        TokenUsage {
+            prompt_tokens: input_tokens,
+            completion_tokens: output_tokens,
+            total_tokens: input_tokens + output_tokens,
+            cache_creation_tokens: self.cache_creation_input_tokens,
+            cache_read_tokens: self.cache_read_input_tokens,
+        }
+    }
+}
+
 impl AnthropicStreamEvent {
     /// Convert to a StreamChunk if applicable
     pub fn to_stream_chunk(&self) -> Option<StreamChunk> {
@@ -180,16 +520,13 @@ impl AnthropicStreamEvent {
             AnthropicStreamEvent::MessageDelta { delta, usage } => {
                 let is_final = delta.stop_reason.is_some();
                 if is_final {
-                    let token_usage = usage.as_ref().map(|u| TokenUsage {
-                        prompt_tokens: u.input_tokens.unwrap_or(0),
-                        completion_tokens: u.output_tokens.unwrap_or(0),
-                        total_tokens: u.input_tokens.unwrap_or(0) + u.output_tokens.unwrap_or(0),
-                    });
+                    let token_usage = usage.as_ref().map(AnthropicUsage::to_token_usage);
                     Some(StreamChunk {
                         content: String::new(),
                         model: None,
                         is_final: true,
                         usage: token_usage,
+                        finish_reason: delta.stop_reason.as_deref().map(normalize_finish_reason),
                     })
                 } else {
                     None
@@ -202,9 +539,165 @@ impl AnthropicStreamEvent {
                     model: Some(message.model.clone()),
                     is_final: false,
                     usage: None,
+                    finish_reason: None,
                 })
             }
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer_joins_lines_split_across_chunks() {
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(b"data: {\"a\"").is_empty());
+        assert_eq!(buffer.push(b":1}\r\n\r\ndata: {\"b\":2}\ndata: [DO"), ["data: {\"a\":1}", "", "data: {\"b\":2}"]);
+        assert_eq!(buffer.push(b"NE]\n"), ["data: [DONE]"]);
+        assert_eq!(buffer.finish(), None);
+
+        // A multi-byte character split between chunks survives intact
+        let bytes = "data: caf\u{e9}\n".as_bytes();
+        assert!(buffer.push(&bytes[..10]).is_empty());
+        assert_eq!(buffer.push(&bytes[10..]), ["data: caf\u{e9}"]);
+    }
+
+    #[tokio::test]
+    async fn test_parse_lines_handles_event_split_mid_line() {
+        let event = r#"data: {"model":"gpt-4o","choices":[{"index":0,"delta":{"content":"Hello"},"finish_reason":null}]}"#;
+        let last = r#"data: {"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#;
+        let body = format!("{}\n\n{}\n\ndata: [DONE]", event, last);
+        let (first, second) = body.split_at(40);
+        let pieces: Vec<reqwest::Result<Vec<u8>>> = vec![Ok(first.as_bytes().to_vec()), Ok(second.as_bytes().to_vec())];
+
+        let chunks: Vec<StreamChunk> = parse_lines(futures::stream::iter(pieces), parse_openai_sse_line)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].content, "Hello");
+        assert_eq!(chunks[0].model.as_deref(), Some("gpt-4o"));
+        assert!(chunks[1].is_final);
+        assert_eq!(chunks[1].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_lines_keeps_emoji_split_across_chunks() {
+        let body = "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi \u{1F980}!\"},\"finish_reason\":null}]}\n\n";
+        // Split inside the 4-byte crab emoji
+        let split = body.find('\u{1F980}').unwrap() + 2;
+        let pieces: Vec<reqwest::Result<bytes::Bytes>> = vec![
+            Ok(bytes::Bytes::copy_from_slice(&body.as_bytes()[..split])),
+            Ok(bytes::Bytes::copy_from_slice(&body.as_bytes()[split..])),
+        ];
+
+        let chunks: Vec<StreamChunk> = parse_lines(futures::stream::iter(pieces), parse_openai_sse_line)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, "Hi \u{1F980}!");
+        assert!(!chunks[0].content.contains('\u{FFFD}'));
+    }
+
+    #[tokio::test]
+    async fn test_parse_lines_flushes_unterminated_last_line() {
+        let pieces: Vec<reqwest::Result<&'static [u8]>> = vec![Ok(b"{\"n\":1}\n{\"n\""), Ok(b":2}")];
+        let lines: Vec<String> = parse_lines(futures::stream::iter(pieces), |line| Some(Ok(StreamChunk::content(line))))
+            .map(|chunk| chunk.unwrap().content)
+            .collect()
+            .await;
+        assert_eq!(lines, ["{\"n\":1}", "{\"n\":2}"]);
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_stream_keeps_input_tokens_from_message_start() {
+        use crate::load_balancer::pricing::Pricing;
+
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude-3-5-sonnet-latest\",",
+            "\"usage\":{\"input_tokens\":25,\"output_tokens\":1,\"cache_read_input_tokens\":100}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":12}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+        let pieces: Vec<reqwest::Result<&'static [u8]>> = vec![Ok(body.as_bytes())];
+        let mut parser = AnthropicStreamParser::default();
+        let response = collect_response(parse_lines(futures::stream::iter(pieces), move |line| parser.parse_line(line)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "Hello");
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (25, 12, 37));
+        assert_eq!(usage.cache_read_tokens, Some(100));
+        // 25 input tokens at $3/1M + 12 output tokens at $15/1M
+        let cost = Pricing::new(0.003, 0.015).cost(&usage);
+        assert!((cost - 0.000255).abs() < 1e-12, "{}", cost);
+    }
+
+    #[tokio::test]
+    async fn test_stop_sequence_truncates_overrunning_stream() {
+        let chunks = vec![
+            Ok(StreamChunk::content("1. Apples\n2. Pe")),
+            Ok(StreamChunk::content("ars\n#")),
+            Ok(StreamChunk::content("## 3. Plums\n")),
+            Ok(StreamChunk::content("4. Figs")),
+            Ok(StreamChunk::final_chunk("gpt-4o", None)),
+        ];
+        let inner: LlmStream = Box::pin(futures::stream::iter(chunks));
+        let stream = StopSequenceStream::new(inner, vec!["###".to_string(), "".to_string()]);
+
+        let emitted: Vec<StreamChunk> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        let content: String = emitted.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(content, "1. Apples\n2. Pears\n");
+        // The stop sequence ends the stream before the provider's own final chunk
+        assert_eq!(emitted.len(), 3);
+        let last = emitted.last().unwrap();
+        assert!(last.is_final);
+        assert_eq!(last.finish_reason.as_deref(), Some("stop"));
+        assert!(emitted[..2].iter().all(|chunk| !chunk.is_final));
+        assert!(last.usage.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stop_sequence_ends_endless_stream_with_estimated_usage() {
+        // A provider that never stops on its own
+        let chunks = futures::stream::iter(vec![Ok(StreamChunk::content("Done. END"))])
+            .chain(futures::stream::repeat_with(|| Ok(StreamChunk::content(" and more"))));
+        let inner: LlmStream = Box::pin(chunks);
+        let mut stream = StopSequenceStream::new(inner, vec!["END".to_string()]).with_usage_estimate(8, "gpt-4o");
+
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert_eq!(chunk.content, "Done. ");
+        assert!(chunk.is_final);
+        assert_eq!(chunk.finish_reason.as_deref(), Some("stop"));
+        let usage = chunk.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 8);
+        assert_eq!(usage.completion_tokens, count_tokens("Done. ", "gpt-4o"));
+        assert_eq!(usage.total_tokens, 8 + usage.completion_tokens);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stop_sequence_releases_held_back_content_without_match() {
+        let chunks = vec![
+            Ok(StreamChunk::content("Total: 4")),
+            Ok(StreamChunk::content("2 caf\u{e9}s")),
+        ];
+        let inner: LlmStream = Box::pin(futures::stream::iter(chunks));
+        let stream = StopSequenceStream::new(inner, vec!["END".to_string()]);
+
+        let emitted: Vec<StreamChunk> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        let content: String = emitted.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(content, "Total: 42 caf\u{e9}s");
+        assert!(emitted.iter().all(|chunk| chunk.finish_reason.is_none()));
+    }
+}
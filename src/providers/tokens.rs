@@ -0,0 +1,363 @@
+//! Token count estimates for pre-flight checks
+//!
+//! These are heuristics, not tokenizers: they are meant for guarding context windows
+//! and sizing requests, where being roughly right is enough.
+
+use crate::providers::types::{LlmRequest, Message, Role};
+
+/// Average number of characters per token for English text with common BPE tokenizers
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimate the number of tokens a model's tokenizer produces for a piece of text
+///
+/// Word characters count as a fraction of a token depending on the model family
+/// (about 4 characters per token for GPT, Gemini and Llama 3, 3.5 for Claude, Mistral
+/// and older Llama models). Whitespace is free since tokenizers merge it into the next
+/// word, while punctuation, symbols and CJK characters count as a token each, so code
+/// estimates higher than prose. The estimate never decreases as text is appended.
+///
+/// # Parameters
+/// * `text` - Text to estimate
+/// * `model` - Model identifier, e.g. `gpt-4o` or `claude-3-5-sonnet-20241022`; unknown
+///   models use 4 characters per token
+pub fn estimate_tokens(text: &str, model: &str) -> u32 {
+    let word_char_weight = 1.0 / chars_per_token(model);
+    let total: f64 = text
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() {
+                0.0
+            } else if !c.is_alphanumeric() || (c as u32) >= CJK_START {
+                1.0
+            } else {
+                word_char_weight
+            }
+        })
+        .sum();
+    // Round within a small tolerance so float error doesn't add a token
+    let tokens = (total - 1e-9).ceil().max(0.0);
+    if tokens >= u32::MAX as f64 {
+        u32::MAX
+    } else {
+        tokens as u32
+    }
+}
+
+/// Count the tokens of a piece of text exactly with the model's tiktoken encoding
+///
+/// Only OpenAI models have a known encoding; this returns `None` for every other model.
+///
+/// # Parameters
+/// * `text` - Text to count
+/// * `model` - Model identifier, e.g. `gpt-4o`
+#[cfg(feature = "tokenizer")]
+pub fn count_tokens_exact(text: &str, model: &str) -> Option<usize> {
+    let tokenizer = tiktoken_rs::tokenizer::get_tokenizer(model)?;
+    let bpe = tiktoken_rs::bpe_for_tokenizer(tokenizer).ok()?;
+    Some(bpe.encode_ordinary(text).len())
+}
+
+/// Best available token count: exact when the `tokenizer` feature knows the model's
+/// encoding, the heuristic estimate otherwise
+pub(crate) fn count_tokens(text: &str, model: &str) -> u32 {
+    #[cfg(feature = "tokenizer")]
+    if let Some(count) = count_tokens_exact(text, model) {
+        return u32::try_from(count).unwrap_or(u32::MAX);
+    }
+    estimate_tokens(text, model)
+}
+
+/// Drop the oldest messages of a conversation until it fits a token budget
+///
+/// Messages are counted by content with the best available count (see
+/// `estimate_tokens`). The latest user message is always kept, as are system messages
+/// when `keep_system` is set; every other message can be dropped, oldest first. If the
+/// kept messages alone exceed the budget they are returned as they are, so callers
+/// should still check the result against the provider's limit.
+///
+/// # Parameters
+/// * `messages` - Conversation in chronological order
+/// * `max_tokens` - Token budget for the returned messages
+/// * `model` - Model the messages are sent to, for tokenizer selection
+/// * `keep_system` - Never drop messages with the `system` role
+///
+/// # Returns
+/// * The remaining messages in their original order, and how many were dropped
+pub fn trim_messages(messages: Vec<Message>, max_tokens: u32, model: &str, keep_system: bool) -> (Vec<Message>, usize) {
+    let counts: Vec<u32> = messages.iter().map(|m| count_tokens(&m.content, model)).collect();
+    let mut total = counts.iter().fold(0u32, |sum, c| sum.saturating_add(*c));
+    let latest_user = messages.iter().rposition(|m| m.role_kind() == Some(Role::User));
+
+    let mut dropped = vec![false; messages.len()];
+    for (index, message) in messages.iter().enumerate() {
+        if total <= max_tokens {
+            break;
+        }
+        if Some(index) == latest_user || (keep_system && message.role_kind() == Some(Role::System)) {
+            continue;
+        }
+        dropped[index] = true;
+        total -= counts[index];
+    }
+
+    let dropped_count = dropped.iter().filter(|d| **d).count();
+    let kept = messages
+        .into_iter()
+        .zip(dropped)
+        .filter_map(|(message, dropped)| (!dropped).then_some(message))
+        .collect();
+    (kept, dropped_count)
+}
+
+/// Context windows of well-known hosted models, keyed on model name prefix
+///
+/// The longest matching prefix wins, so dated and suffixed variants
+/// (`gpt-4o-2024-08-06`, `claude-3-5-haiku-latest`) resolve to their family.
+const KNOWN_CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    // OpenAI
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o1-mini", 128_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    // Anthropic
+    ("claude-3", 200_000),
+    ("claude-opus-4", 200_000),
+    ("claude-sonnet-4", 200_000),
+    ("claude-haiku-4", 200_000),
+    // Google
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-1.5-flash", 1_048_576),
+    ("gemini-2.0-flash", 1_048_576),
+    ("gemini-2.5", 1_048_576),
+    // Mistral
+    ("mistral-large", 131_072),
+    ("mistral-medium", 131_072),
+    ("mistral-small", 32_768),
+    ("open-mistral-nemo", 131_072),
+    ("codestral", 256_000),
+    // Meta models as served by Groq and Together AI
+    ("llama3-", 8_192),
+    ("llama-3.1", 131_072),
+    ("llama-3.3", 131_072),
+    ("meta-llama-3.1", 131_072),
+    ("mixtral-8x7b", 32_768),
+    // Cohere
+    ("command-r", 128_000),
+    ("command-a", 256_000),
+    // xAI
+    ("grok-2", 131_072),
+    ("grok-3", 131_072),
+    ("grok-4", 256_000),
+    // AI21
+    ("jamba", 256_000),
+    // Perplexity
+    ("sonar", 127_072),
+    ("sonar-pro", 200_000),
+];
+
+/// Look up the context window of a well-known hosted model
+///
+/// Matching ignores case and any organization prefix (`meta-llama/...`). Returns
+/// `None` for models not in the table, including local models whose window depends
+/// on how the server was started.
+///
+/// # Parameters
+/// * `model` - Model identifier, e.g. `gpt-4o` or `claude-3-5-sonnet-20241022`
+pub fn context_window_for_model(model: &str) -> Option<u32> {
+    let model = model.to_ascii_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    KNOWN_CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, tokens)| *tokens)
+}
+
+/// First code point of the CJK ranges, whose characters are roughly a token each
+const CJK_START: u32 = 0x2E80;
+
+/// Average number of characters per token for a model family's tokenizer
+fn chars_per_token(model: &str) -> f64 {
+    let model = model.to_ascii_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    if model.starts_with("claude")
+        || model.starts_with("mistral")
+        || model.starts_with("mixtral")
+        || model.starts_with("codestral")
+        || model.starts_with("llama2")
+        || model.starts_with("llama-2")
+    {
+        3.5
+    } else {
+        CHARS_PER_TOKEN as f64
+    }
+}
+
+/// Approximate the number of tokens in a piece of text (about 4 characters per token)
+pub fn approximate_tokens(text: &str) -> u32 {
+    let chars = text.chars().count();
+    u32::try_from(chars.div_ceil(CHARS_PER_TOKEN)).unwrap_or(u32::MAX)
+}
+
+/// Approximate the number of prompt tokens of a list of messages
+pub fn approximate_message_tokens(messages: &[Message]) -> u32 {
+    messages
+        .iter()
+        .map(|m| approximate_tokens(&m.content))
+        .fold(0u32, u32::saturating_add)
+}
+
+/// Approximate the tokens a request needs: its prompt plus any reserved output tokens
+pub fn approximate_request_tokens(request: &LlmRequest) -> u32 {
+    approximate_message_tokens(&request.messages).saturating_add(request.max_tokens.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_is_monotonic() {
+        let text = "fn main() {\n    println!(\"Hello, world!\");\n}\n The quick brown fox 日本語";
+        for model in ["gpt-4o", "claude-3-5-sonnet-20241022", "mistral-large-latest", "unknown"] {
+            let mut previous = 0;
+            for (i, _) in text.char_indices() {
+                let estimate = estimate_tokens(&text[..i], model);
+                assert!(estimate >= previous, "estimate dropped at byte {} for {}", i, model);
+                previous = estimate;
+            }
+        }
+        assert_eq!(estimate_tokens("", "gpt-4o"), 0);
+    }
+
+    #[test]
+    fn test_estimate_roughly_matches_known_counts() {
+        // (text, token count from OpenAI's cl100k_base tokenizer)
+        let known = [
+            ("The quick brown fox jumps over the lazy dog.", 10),
+            ("fn add(a: i32, b: i32) -> i32 { a + b }", 20),
+        ];
+        for (text, actual) in known {
+            let estimate = estimate_tokens(text, "gpt-4") as f64;
+            let error = (estimate - actual as f64).abs() / actual as f64;
+            assert!(error <= 0.35, "estimated {} tokens for {} (actual {})", estimate, text, actual);
+        }
+    }
+
+    #[test]
+    fn test_estimate_depends_on_model_family() {
+        let text = "a".repeat(700);
+        assert_eq!(estimate_tokens(&text, "gpt-4o"), 175);
+        assert_eq!(estimate_tokens(&text, "claude-3-haiku-20240307"), 200);
+        assert_eq!(estimate_tokens(&text, "mistralai/Mixtral-8x7B-Instruct-v0.1"), 200);
+    }
+
+    #[test]
+    fn test_estimate_counts_code_and_cjk_higher_than_prose() {
+        let prose = estimate_tokens("abcdefghijklmnop", "gpt-4o");
+        let code = estimate_tokens("a.b(c);d[e]{f};g", "gpt-4o");
+        assert!(code > prose);
+        assert_eq!(estimate_tokens("日本語", "gpt-4o"), 3);
+    }
+
+    #[cfg(feature = "tokenizer")]
+    #[test]
+    fn test_exact_counts_for_openai_models() {
+        assert_eq!(count_tokens_exact("The quick brown fox jumps over the lazy dog.", "gpt-4"), Some(10));
+        assert_eq!(count_tokens_exact("fn add(a: i32, b: i32) -> i32 { a + b }", "gpt-4"), Some(20));
+        assert_eq!(count_tokens_exact("日本語", "gpt-3.5-turbo"), Some(4));
+        assert_eq!(count_tokens_exact("Hello world", "gpt-4o"), Some(2));
+        assert_eq!(count_tokens_exact("", "gpt-4o"), Some(0));
+    }
+
+    #[cfg(feature = "tokenizer")]
+    #[test]
+    fn test_exact_count_unknown_for_other_models() {
+        assert_eq!(count_tokens_exact("Hello world", "claude-3-5-sonnet-20241022"), None);
+        assert_eq!(count_tokens_exact("Hello world", "llama3"), None);
+        // Falls back to the heuristic
+        assert_eq!(count_tokens("abcdefgh", "llama3"), estimate_tokens("abcdefgh", "llama3"));
+    }
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            message("system", "You are a helpful assistant."),
+            message("user", &"first question ".repeat(20)),
+            message("assistant", &"first answer ".repeat(20)),
+            message("user", &"second question ".repeat(20)),
+            message("assistant", &"second answer ".repeat(20)),
+            message("user", "latest question"),
+        ]
+    }
+
+    #[test]
+    fn test_trim_keeps_fitting_conversation() {
+        let (kept, dropped) = trim_messages(conversation(), 10_000, "gpt-4o", true);
+        assert_eq!(dropped, 0);
+        assert_eq!(kept.len(), 6);
+    }
+
+    #[test]
+    fn test_trim_drops_oldest_and_keeps_system_and_latest_user() {
+        let messages = conversation();
+        let budget = ["You are a helpful assistant.", "latest question"]
+            .iter()
+            .map(|text| count_tokens(text, "gpt-4o"))
+            .sum::<u32>()
+            + count_tokens(&messages[4].content, "gpt-4o");
+
+        let (kept, dropped) = trim_messages(messages, budget, "gpt-4o", true);
+        assert_eq!(dropped, 3);
+        let roles: Vec<&str> = kept.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "assistant", "user"]);
+        assert!(kept[1].content.starts_with("second answer"));
+        assert_eq!(kept[2].content, "latest question");
+    }
+
+    #[test]
+    fn test_trim_never_drops_protected_messages() {
+        let (kept, dropped) = trim_messages(conversation(), 0, "gpt-4o", true);
+        assert_eq!(dropped, 4);
+        assert_eq!(kept[0].role, "system");
+        assert_eq!(kept[1].content, "latest question");
+
+        // Without keep_system the system prompt goes first
+        let (kept, dropped) = trim_messages(conversation(), 0, "gpt-4o", false);
+        assert_eq!(dropped, 5);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].content, "latest question");
+    }
+
+    #[test]
+    fn test_context_window_for_known_models() {
+        assert_eq!(context_window_for_model("gpt-4o-2024-08-06"), Some(128_000));
+        assert_eq!(context_window_for_model("gpt-4"), Some(8_192));
+        assert_eq!(context_window_for_model("o1-mini"), Some(128_000));
+        assert_eq!(context_window_for_model("claude-3-5-sonnet-20241022"), Some(200_000));
+        assert_eq!(context_window_for_model("gemini-1.5-pro"), Some(2_097_152));
+        assert_eq!(context_window_for_model("meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo"), Some(131_072));
+        assert_eq!(context_window_for_model("sonar-pro"), Some(200_000));
+    }
+
+    #[test]
+    fn test_context_window_unknown_models() {
+        assert_eq!(context_window_for_model("my-finetune"), None);
+        assert_eq!(context_window_for_model("llama3.2:3b"), None);
+        assert_eq!(context_window_for_model(""), None);
+    }
+}
@@ -1,7 +1,18 @@
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use std::pin::Pin;
 use futures::Stream;
-use crate::errors::LlmError;
+use crate::errors::{LlmError, LlmResult};
+
+/// Task and request parameters recognized for every provider
+///
+/// See `ProviderType::accepts_param` for provider-specific ones.
+pub const COMMON_PARAMS: &[&str] = &[
+    "max_tokens",
+    "temperature",
+    "seed",
+    "response_format",
+];
 
 /// Enum representing the different LLM providers supported
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
@@ -16,38 +27,349 @@ pub enum ProviderType {
     Cohere,
     TogetherAI,
     Perplexity,
+    /// Any server speaking OpenAI's chat completions API; needs a custom endpoint
+    OpenAICompatible,
+    /// Gemini models on Google Cloud Vertex AI; needs a project ID and an OAuth2 access token
+    VertexAI,
+    /// xAI's Grok models
+    Xai,
+    /// Open models hosted on Replicate, run as predictions
+    Replicate,
+    /// AI21's Jamba models
+    Ai21,
+    /// Open models served by Fireworks AI
+    Fireworks,
 }
 
 /// Unified request structure used across all providers
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LlmRequest {
     pub messages: Vec<Message>,
     pub model: Option<String>,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    /// Tools the model may call
+    #[serde(default)]
+    pub tools: Option<Vec<ToolDefinition>>,
+    /// How the model should pick tools: "auto", "none", "required", or a tool name
+    #[serde(default)]
+    pub tool_choice: Option<String>,
+    /// Requested output format; providers without structured output ignore it
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    /// Sampling seed for reproducible output; providers without seed support ignore it
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Number of completions to generate; providers without support return one
+    #[serde(default)]
+    pub n: Option<u32>,
+    /// Whether to return token log probabilities; providers without support ignore it
+    #[serde(default)]
+    pub logprobs: Option<bool>,
+    /// Number of most likely alternatives to return with each token's log probability
+    #[serde(default)]
+    pub top_logprobs: Option<u32>,
+    /// Nucleus sampling cutoff; only OpenAI-family providers and Mistral send it
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// Penalty for tokens by how often they appeared; only OpenAI-family providers and Mistral send it
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    /// Penalty for tokens that appeared at all; only OpenAI-family providers and Mistral send it
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    /// Sequences that end generation; only OpenAI-family providers and Mistral send them,
+    /// though the manager also enforces them on every stream
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    /// Documents to ground the answer in; only Cohere sends them for now, others ignore them
+    #[serde(default)]
+    pub documents: Option<Vec<serde_json::Value>>,
+    /// Merged task and request parameters, for provider-specific options such as Ollama's `num_ctx`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub params: HashMap<String, serde_json::Value>,
+}
+
+/// Output format requested from the model
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Plain text (the default)
+    Text,
+    /// Any valid JSON object
+    JsonObject,
+    /// JSON matching the given schema
+    JsonSchema { schema: serde_json::Value },
+}
+
+impl ResponseFormat {
+    /// Whether this format asks for structured (JSON) output
+    pub fn is_structured(&self) -> bool {
+        !matches!(self, ResponseFormat::Text)
+    }
+}
+
+/// Definition of a tool (function) the model may call
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// JSON schema describing the tool's parameters
+    pub parameters: serde_json::Value,
+}
+
+impl ToolDefinition {
+    /// Create a new tool definition
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: serde_json::Value) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters,
+        }
+    }
+}
+
+/// A tool call requested by the model
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// Arguments for the call, JSON-encoded
+    pub arguments: String,
+}
+
+impl LlmRequest {
+    /// Whether any message in this request contains image input
+    pub fn has_images(&self) -> bool {
+        self.messages.iter().any(|m| m.has_images())
+    }
 }
 
 /// Standard message format used across providers
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// Multimodal content; when set, vision-capable providers send these parts instead of `content`.
+    /// Never serialized directly, since `Message` doubles as the OpenAI-compatible wire format.
+    #[serde(default, skip_serializing)]
+    pub content_parts: Option<Vec<ContentPart>>,
+    /// Mark this message as a prompt-caching breakpoint, so the prompt up to and including
+    /// it is cached (Anthropic only; other providers ignore it). Never serialized directly.
+    #[serde(default, skip_serializing)]
+    pub cache: bool,
+}
+
+/// Canonical message role
+///
+/// `Message::role` stays a plain string, since it doubles as the wire format; providers
+/// parse it with `Message::role_kind` instead of matching strings themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    /// Parse a role name, case-insensitively, accepting Gemini's "model" for the assistant
+    pub(crate) fn parse(role: &str) -> Option<Role> {
+        match role.trim().to_ascii_lowercase().as_str() {
+            "system" => Some(Role::System),
+            "user" => Some(Role::User),
+            "assistant" | "model" => Some(Role::Assistant),
+            _ => None,
+        }
+    }
+
+    /// Canonical name, as used by OpenAI-style APIs
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+impl Message {
+    /// Create a message with the given role and text
+    fn with_role(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role: role.as_str().to_string(),
+            content: content.into(),
+            content_parts: None,
+            cache: false,
+        }
+    }
+
+    /// Create a system message
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::with_role(Role::System, content)
+    }
+
+    /// Create a user message
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::with_role(Role::User, content)
+    }
+
+    /// Create an assistant message
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::with_role(Role::Assistant, content)
+    }
+
+    /// Mark this message for prompt caching (see `Message::cache`)
+    pub fn cached(mut self) -> Self {
+        self.cache = true;
+        self
+    }
+
+    /// The parsed role, or `None` for roles outside system/user/assistant (e.g. "tool")
+    pub(crate) fn role_kind(&self) -> Option<Role> {
+        Role::parse(&self.role)
+    }
+
+    /// The canonical role name, with unrecognized roles passed through unchanged
+    pub(crate) fn canonical_role(&self) -> &str {
+        match self.role_kind() {
+            Some(role) => role.as_str(),
+            None => &self.role,
+        }
+    }
+
+    /// Whether this message contains image input
+    pub fn has_images(&self) -> bool {
+        self.content_parts
+            .as_ref()
+            .is_some_and(|parts| parts.iter().any(|p| !matches!(p, ContentPart::Text(_))))
+    }
+}
+
+/// Check that a conversation has a shape the provider accepts
+///
+/// Catches what providers otherwise reject with an opaque 400 or silently rewrite:
+/// - every provider needs at least one message
+/// - Anthropic, Google, Vertex AI, Mistral and Cohere only know system, user and assistant roles
+/// - Anthropic, Google, Vertex AI and Perplexity need the first non-system message to be from the user
+/// - Perplexity needs user and assistant messages to alternate
+///
+/// # Parameters
+/// * `messages` - Conversation to check
+/// * `provider` - Provider the conversation will be sent to
+///
+/// # Returns
+/// * `Ok(())`, or a `ConfigError` describing the first problem found
+pub fn validate_messages(messages: &[Message], provider: ProviderType) -> LlmResult<()> {
+    if messages.is_empty() {
+        return Err(LlmError::ConfigError(format!("Request for {} has no messages", provider)));
+    }
+
+    let known_roles_only = matches!(
+        provider,
+        ProviderType::Anthropic | ProviderType::Google | ProviderType::VertexAI | ProviderType::Mistral | ProviderType::Cohere
+    );
+    if known_roles_only {
+        if let Some(message) = messages.iter().find(|m| m.role_kind().is_none()) {
+            return Err(LlmError::ConfigError(format!(
+                "Unknown message role '{}' for {}: expected system, user or assistant",
+                message.role, provider
+            )));
+        }
+    }
+
+    let mut turns = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.role_kind() != Some(Role::System));
+
+    let user_first = matches!(
+        provider,
+        ProviderType::Anthropic | ProviderType::Google | ProviderType::VertexAI | ProviderType::Perplexity
+    );
+    if user_first {
+        // A lone system prompt is fine: Anthropic turns it into the user message
+        if let Some((_, first)) = turns.clone().next() {
+            if first.role_kind() != Some(Role::User) {
+                return Err(LlmError::ConfigError(format!(
+                    "Conversation for {} must start with a user message, not '{}'",
+                    provider, first.role
+                )));
+            }
+        }
+    }
+
+    if provider == ProviderType::Perplexity {
+        let mut previous = None;
+        for (index, message) in turns.by_ref() {
+            let role = message.role_kind();
+            if previous.is_some() && role == previous {
+                return Err(LlmError::ConfigError(format!(
+                    "Conversation must alternate user/assistant for {} (message {} repeats '{}')",
+                    provider, index, message.role
+                )));
+            }
+            previous = role;
+        }
+    }
+
+    Ok(())
+}
+
+/// A piece of multimodal message content
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentPart {
+    /// Plain text
+    Text(String),
+    /// Image fetched by the provider from a URL; `detail` is OpenAI's "low"/"high"/"auto"
+    ImageUrl { url: String, detail: Option<String> },
+    /// Inline base64-encoded image, e.g. media type "image/png"
+    ImageBase64 { media_type: String, data: String },
 }
 
 /// Unified response structure returned by all providers
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LlmResponse {
     pub content: String,
     pub model: String,
     pub usage: Option<TokenUsage>,
+    /// Tool calls requested by the model, empty if none
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Source URLs a search-augmented provider (e.g. Perplexity) cited, empty if none
+    #[serde(default)]
+    pub citations: Vec<String>,
+    /// Why generation stopped, normalized to `"stop"`, `"length"`, `"tool_calls"` or
+    /// `"content_filter"` (other provider values are passed through, lowercased)
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    /// Every completion when the provider returned several (see `LlmRequest::n`), the
+    /// first being `content`; empty for a single completion
+    #[serde(default)]
+    pub choices: Vec<String>,
+    /// Token log probabilities of `content` as the provider returned them, when requested
+    /// (see `LlmRequest::logprobs`)
+    #[serde(default)]
+    pub logprobs: Option<serde_json::Value>,
+    /// Provider-specific extras without a unified field, such as Cohere's `citations`
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 /// Token usage information returned by providers
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TokenUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Prompt tokens written to the provider's prompt cache, reported by Anthropic.
+    /// Not included in `prompt_tokens`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_tokens: Option<u32>,
+    /// Prompt tokens read from the provider's prompt cache, reported by Anthropic.
+    /// Not included in `prompt_tokens`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_tokens: Option<u32>,
 }
 
 impl Default for TokenUsage {
@@ -55,11 +377,58 @@ impl Default for TokenUsage {
         Self {
             prompt_tokens: 0,
             completion_tokens: 0,
-            total_tokens: 0
+            total_tokens: 0,
+            cache_creation_tokens: None,
+            cache_read_tokens: None,
         }
     }
 }
 
+impl TokenUsage {
+    /// Add another request's usage to this running total
+    ///
+    /// Cached token counts stay `None` until a request reports them.
+    pub fn accumulate(&mut self, other: &TokenUsage) {
+        fn sum(total: Option<u32>, other: Option<u32>) -> Option<u32> {
+            match (total, other) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            }
+        }
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.cache_creation_tokens = sum(self.cache_creation_tokens, other.cache_creation_tokens);
+        self.cache_read_tokens = sum(self.cache_read_tokens, other.cache_read_tokens);
+    }
+}
+
+/// Embedding vectors returned by a provider, along with the usage they incurred
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmbeddingResponse {
+    /// One vector per input, in the same order as the inputs
+    pub embeddings: Vec<Vec<f32>>,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+}
+
+/// Features a provider type supports in FlyLLM, from `ProviderType::capabilities`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderCapabilities {
+    /// Streams responses natively (`LlmInstance::supports_streaming`)
+    pub streaming: bool,
+    /// Sends `LlmRequest::tools` and returns tool calls
+    pub tools: bool,
+    /// Accepts image input
+    pub vision: bool,
+    /// Has an embeddings endpoint (`LlmInstance::as_embedding`)
+    pub embeddings: bool,
+    /// Honours `LlmRequest::response_format` (`LlmInstance::supports_structured_output`)
+    pub json_mode: bool,
+    /// Usually runs on the local machine and needs no API key
+    pub local: bool,
+}
+
 /// Information about an LLM model
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ModelInfo {
@@ -81,25 +450,134 @@ impl std::fmt::Display for ProviderType {
             ProviderType::Cohere => write!(f, "Cohere"),
             ProviderType::TogetherAI => write!(f, "TogetherAI"),
             ProviderType::Perplexity => write!(f, "Perplexity"),
+            ProviderType::OpenAICompatible => write!(f, "OpenAICompatible"),
+            ProviderType::VertexAI => write!(f, "VertexAI"),
+            ProviderType::Xai => write!(f, "xAI"),
+            ProviderType::Replicate => write!(f, "Replicate"),
+            ProviderType::Ai21 => write!(f, "AI21"),
+            ProviderType::Fireworks => write!(f, "Fireworks"),
         }
     }
 }
 
+impl ProviderType {
+    /// Every supported provider
+    pub const ALL: [ProviderType; 16] = [
+        ProviderType::Anthropic,
+        ProviderType::OpenAI,
+        ProviderType::Mistral,
+        ProviderType::Google,
+        ProviderType::Ollama,
+        ProviderType::LMStudio,
+        ProviderType::Groq,
+        ProviderType::Cohere,
+        ProviderType::TogetherAI,
+        ProviderType::Perplexity,
+        ProviderType::OpenAICompatible,
+        ProviderType::VertexAI,
+        ProviderType::Xai,
+        ProviderType::Replicate,
+        ProviderType::Ai21,
+        ProviderType::Fireworks,
+    ];
+
+    /// Every supported provider, for iterating and filtering on `capabilities`
+    pub fn all() -> &'static [ProviderType] {
+        &Self::ALL
+    }
+
+    /// What this provider supports in FlyLLM
+    ///
+    /// Instances report the same through `LlmInstance`; this answers without building one.
+    pub fn capabilities(&self) -> ProviderCapabilities {
+        // Only the OpenAI and Anthropic clients convert tools and image parts
+        let full_featured = matches!(self, ProviderType::Anthropic | ProviderType::OpenAI | ProviderType::OpenAICompatible);
+        ProviderCapabilities {
+            // Replicate predictions are fetched whole
+            streaming: *self != ProviderType::Replicate,
+            tools: full_featured,
+            vision: full_featured,
+            embeddings: self.supports_embeddings(),
+            json_mode: matches!(self, ProviderType::OpenAI | ProviderType::OpenAICompatible | ProviderType::Google | ProviderType::VertexAI),
+            local: matches!(self, ProviderType::Ollama | ProviderType::LMStudio),
+        }
+    }
+
+    /// Provider-specific parameters passed through from `LlmRequest::params`
+    fn extra_params(&self) -> &'static [&'static str] {
+        match self {
+            ProviderType::Ollama => &["num_ctx", "context_length", "top_k", "repeat_penalty"],
+            ProviderType::OpenAI | ProviderType::OpenAICompatible => {
+                &["n", "logprobs", "top_logprobs", "top_p", "frequency_penalty", "presence_penalty", "stop"]
+            }
+            // Sampling parameters sent as `LlmRequest` fields by OpenAI-compatible request bodies
+            ProviderType::Groq | ProviderType::TogetherAI | ProviderType::Xai | ProviderType::Fireworks => {
+                &["logprobs", "top_logprobs", "top_p", "frequency_penalty", "presence_penalty", "stop"]
+            }
+            ProviderType::Mistral
+            | ProviderType::Perplexity
+            | ProviderType::LMStudio
+            | ProviderType::Ai21 => &["top_p", "frequency_penalty", "presence_penalty", "stop"],
+            ProviderType::Cohere => &["documents"],
+            _ => &[],
+        }
+    }
+
+    /// Whether FlyLLM recognizes a task or request parameter for this provider
+    ///
+    /// Every provider shares `COMMON_PARAMS`; new parameters are registered here so the
+    /// manager's unknown-parameter check stays accurate.
+    pub fn accepts_param(&self, key: &str) -> bool {
+        COMMON_PARAMS.contains(&key) || self.extra_params().contains(&key)
+    }
+
+    /// Whether `model` looks like one of this provider's model names
+    ///
+    /// Only a prefix check for hosted providers with a known naming scheme; providers serving
+    /// arbitrary open models accept any name.
+    pub fn recognizes_model(&self, model: &str) -> bool {
+        let model = model.to_ascii_lowercase();
+        let prefixes: &[&str] = match self {
+            ProviderType::OpenAI => &["gpt-", "chatgpt-", "o1", "o3", "o4", "text-embedding-"],
+            ProviderType::Anthropic => &["claude-"],
+            ProviderType::Google | ProviderType::VertexAI => &["gemini-", "text-embedding-", "text-multilingual-"],
+            ProviderType::Mistral => &["mistral-", "open-mistral-", "open-mixtral-", "codestral-", "pixtral-", "ministral-", "magistral-", "devstral-"],
+            ProviderType::Cohere => &["command", "embed-", "c4ai-"],
+            ProviderType::Xai => &["grok-"],
+            ProviderType::Perplexity => &["sonar", "r1-"],
+            ProviderType::Ai21 => &["jamba-"],
+            ProviderType::Fireworks => &["accounts/"],
+            ProviderType::Replicate => return model.contains('/'),
+            _ => return true,
+        };
+        prefixes.iter().any(|prefix| model.starts_with(prefix))
+    }
+
+    /// Whether this provider exposes an embeddings endpoint supported by FlyLLM
+    pub fn supports_embeddings(&self) -> bool {
+        matches!(self, ProviderType::OpenAI | ProviderType::Cohere | ProviderType::Mistral | ProviderType::OpenAICompatible)
+    }
+}
+
+/// Case-insensitive parsing of provider names, the inverse of `Display`
+///
+/// Underscores and hyphens are ignored, so `openai_compatible` parses too.
+impl std::str::FromStr for ProviderType {
+    type Err = LlmError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let name: String = value.chars().filter(|c| *c != '_' && *c != '-').collect();
+        ProviderType::ALL
+            .into_iter()
+            .find(|provider| provider.to_string().eq_ignore_ascii_case(&name))
+            .ok_or_else(|| LlmError::ConfigError(format!("Unknown provider type '{}'", value)))
+    }
+}
+
+/// Panics on unknown names; use `str::parse` to handle them instead
 impl From<&str> for ProviderType {
     fn from(value: &str) -> Self {
-        match value.to_lowercase().as_str() {
-            "anthropic" => ProviderType::Anthropic,
-            "openai" => ProviderType::OpenAI,
-            "mistral" => ProviderType::Mistral,
-            "google" => ProviderType::Google,
-            "ollama" => ProviderType::Ollama,
-            "lmstudio" => ProviderType::LMStudio,
-            "groq" => ProviderType::Groq,
-            "cohere" => ProviderType::Cohere,
-            "togetherai" => ProviderType::TogetherAI,
-            "perplexity" => ProviderType::Perplexity,
-            _ => panic!("Unknown provider: {}", value),
-        }
+        value.parse().unwrap_or_else(|_| panic!("Unknown provider: {}", value))
     }
 }
 
@@ -114,6 +592,8 @@ pub struct StreamChunk {
     pub is_final: bool,
     /// Token usage information (typically only available in final chunk)
     pub usage: Option<TokenUsage>,
+    /// Why generation stopped, normalized like `LlmResponse::finish_reason` (final chunk only)
+    pub finish_reason: Option<String>,
 }
 
 impl StreamChunk {
@@ -124,6 +604,7 @@ impl StreamChunk {
             model: None,
             is_final: false,
             usage: None,
+            finish_reason: None,
         }
     }
 
@@ -134,6 +615,7 @@ impl StreamChunk {
             model: Some(model.into()),
             is_final: true,
             usage,
+            finish_reason: None,
         }
     }
 }
@@ -0,0 +1,270 @@
+use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::gemini;
+use crate::providers::instances::{LlmInstance, BaseInstance, InstanceSettings, ensure_text_only};
+use crate::providers::types::{LlmRequest, LlmResponse, LlmStream};
+use crate::providers::tokens::context_window_for_model;
+use crate::errors::{LlmError, LlmResult};
+use crate::constants;
+
+use async_trait::async_trait;
+use reqwest::header;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Callback returning a current OAuth2 access token, called before every request
+pub type TokenProvider = Arc<dyn Fn() -> LlmResult<String> + Send + Sync>;
+
+/// Provider implementation for Gemini models on Google Cloud Vertex AI
+///
+/// Unlike `GoogleInstance`, which uses an API key with the public Gemini API,
+/// requests go to `{location}-aiplatform.googleapis.com` under a project and
+/// authenticate with an OAuth2 bearer token.
+pub struct VertexAIInstance {
+    base: BaseInstance,
+    project_id: String,
+    location: String,
+    endpoint_url: Option<String>,
+    token_provider: Option<TokenProvider>,
+}
+
+impl VertexAIInstance {
+    /// Creates a new Vertex AI provider instance
+    ///
+    /// # Parameters
+    /// * `access_token` - OAuth2 access token (e.g. from `gcloud auth print-access-token`)
+    /// * `model` - Default model to use (e.g. "gemini-2.0-flash")
+    /// * `supported_tasks` - Map of tasks this provider supports
+    /// * `enabled` - Whether this provider is enabled
+    /// * `endpoint_url` - Optional base URL replacing `https://{location}-aiplatform.googleapis.com`
+    /// * `settings` - HTTP settings; `project_id` is required and `location` defaults to `us-central1`
+    pub fn new(access_token: String, model: String, supported_tasks: HashMap<String, TaskDefinition>, enabled: bool, endpoint_url: Option<String>, settings: &InstanceSettings) -> LlmResult<Self> {
+        let project_id = match settings.project_id.as_deref() {
+            Some(project_id) if !project_id.is_empty() => project_id.to_string(),
+            _ => return Err(LlmError::ConfigError(
+                "Vertex AI providers need a Google Cloud project ID".to_string(),
+            )),
+        };
+        let location = settings
+            .location
+            .clone()
+            .unwrap_or_else(|| constants::VERTEX_DEFAULT_LOCATION.to_string());
+
+        let base = BaseInstance::new("vertexai".to_string(), access_token, model, supported_tasks, enabled, settings)?;
+        Ok(Self { base, project_id, location, endpoint_url, token_provider: None })
+    }
+
+    /// Fetch the access token from `provider` before every request instead of using a fixed one
+    ///
+    /// Tokens from `gcloud` or a metadata server expire after about an hour; a provider that
+    /// caches and refreshes them keeps long-running managers authenticated.
+    pub fn with_token_provider(mut self, provider: TokenProvider) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    /// URL of a model method such as `generateContent`
+    fn method_url(&self, method: &str) -> String {
+        let base = match &self.endpoint_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            // The global location has no regional host prefix
+            None if self.location == "global" => "https://aiplatform.googleapis.com".to_string(),
+            None => format!("https://{}-aiplatform.googleapis.com", self.location),
+        };
+        format!(
+            "{}/v1/projects/{}/locations/{}/publishers/google/models/{}:{}",
+            base,
+            self.project_id,
+            self.location,
+            self.base.model(),
+            method
+        )
+    }
+
+    /// Build the JSON and bearer authentication headers for a request
+    fn build_headers(&self) -> LlmResult<header::HeaderMap> {
+        let token = match &self.token_provider {
+            Some(provider) => provider()?,
+            None => self.base.api_key().to_string(),
+        };
+        if token.is_empty() {
+            return Err(LlmError::ConfigError("Vertex AI needs an OAuth2 access token".to_string()));
+        }
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        let auth = header::HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|e| LlmError::ConfigError(format!("Invalid Vertex AI access token: {}", e)))?;
+        headers.insert(header::AUTHORIZATION, auth);
+        Ok(headers)
+    }
+
+    /// Map a failed response to an error
+    ///
+    /// A 401 usually means the access token expired. It stays an `Api` error, so the
+    /// manager fails over or retries, and a token provider is asked again next time.
+    async fn error_from_response(response: reqwest::Response) -> LlmError {
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers().clone();
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Rate limit exceeded".to_string());
+            return LlmError::rate_limit(format!("Vertex AI rate limit: {}", error_text), &headers);
+        }
+
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Failed to read error response body".to_string());
+        match LlmError::api("Vertex AI", status, &error_text) {
+            LlmError::Api { status: 401, code, message, provider } => LlmError::Api {
+                status: 401,
+                code,
+                message: format!("Access token rejected, it may have expired: {}", message),
+                provider,
+            },
+            error => error,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmInstance for VertexAIInstance {
+    /// Generates a completion using Vertex AI's `generateContent` method
+    ///
+    /// # Parameters
+    /// * `request` - The LLM request containing messages and parameters
+    ///
+    /// # Returns
+    /// * `LlmResult<LlmResponse>` - The response from the model or an error
+    async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled("Vertex AI".to_string()));
+        }
+        ensure_text_only(request, "Vertex AI")?;
+
+        let response = self.base.client()
+            .post(self.method_url("generateContent"))
+            .headers(self.build_headers()?)
+            .json(&gemini::build_request(request, self.base.max_tokens(request))?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        gemini::parse_response(response, self.base.model()).await
+    }
+
+    async fn generate_stream(&self, request: &LlmRequest) -> LlmResult<LlmStream> {
+        if !self.base.is_enabled() {
+            return Err(LlmError::ProviderDisabled("Vertex AI".to_string()));
+        }
+        ensure_text_only(request, "Vertex AI")?;
+
+        let response = self.base.client()
+            .post(format!("{}?alt=sse", self.method_url("streamGenerateContent")))
+            .headers(self.build_headers()?)
+            .json(&gemini::build_request(request, self.base.max_tokens(request))?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        Ok(gemini::parse_stream(response))
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
+    /// Looks the model up in the table of known context windows
+    fn context_window(&self) -> Option<u32> {
+        context_window_for_model(self.base.model())
+    }
+
+    /// Returns provider name
+    fn get_name(&self) -> &str {
+        self.base.name()
+    }
+
+    /// Returns current model name
+    fn get_model(&self) -> &str {
+        self.base.model()
+    }
+
+    /// Returns supported tasks for this provider
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        self.base.supported_tasks()
+    }
+
+    /// Returns whether this provider is enabled
+    fn is_enabled(&self) -> bool {
+        self.base.is_enabled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(location: Option<&str>) -> InstanceSettings {
+        InstanceSettings {
+            project_id: Some("my-project".to_string()),
+            location: location.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_method_url_uses_regional_host() {
+        let instance = VertexAIInstance::new("token".to_string(), "gemini-2.0-flash".to_string(), HashMap::new(), true, None, &settings(Some("europe-west4"))).unwrap();
+        assert_eq!(
+            instance.method_url("generateContent"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/my-project/locations/europe-west4/publishers/google/models/gemini-2.0-flash:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_method_url_global_location_and_default() {
+        let global = VertexAIInstance::new("token".to_string(), "gemini-2.0-flash".to_string(), HashMap::new(), true, None, &settings(Some("global"))).unwrap();
+        assert!(global.method_url("generateContent").starts_with("https://aiplatform.googleapis.com/v1/projects/my-project/locations/global/"));
+
+        let default = VertexAIInstance::new("token".to_string(), "gemini-2.0-flash".to_string(), HashMap::new(), true, None, &settings(None)).unwrap();
+        assert!(default.method_url("generateContent").contains("us-central1-aiplatform.googleapis.com"));
+    }
+
+    #[test]
+    fn test_project_id_required() {
+        let result = VertexAIInstance::new("token".to_string(), "gemini-2.0-flash".to_string(), HashMap::new(), true, None, &InstanceSettings::default());
+        assert!(matches!(result, Err(LlmError::ConfigError(msg)) if msg.contains("project ID")));
+    }
+
+    #[test]
+    fn test_token_provider_is_called_per_request() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let instance = VertexAIInstance::new(String::new(), "gemini-2.0-flash".to_string(), HashMap::new(), true, None, &settings(None))
+            .unwrap()
+            .with_token_provider(Arc::new(move || {
+                let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(format!("token-{}", n))
+            }));
+
+        assert_eq!(instance.build_headers().unwrap()[header::AUTHORIZATION], "Bearer token-0");
+        assert_eq!(instance.build_headers().unwrap()[header::AUTHORIZATION], "Bearer token-1");
+    }
+
+    #[test]
+    fn test_missing_token_is_config_error() {
+        let instance = VertexAIInstance::new(String::new(), "gemini-2.0-flash".to_string(), HashMap::new(), true, None, &settings(None)).unwrap();
+        assert!(matches!(instance.build_headers(), Err(LlmError::ConfigError(_))));
+    }
+}
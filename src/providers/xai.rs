@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use crate::load_balancer::tasks::TaskDefinition;
+use crate::providers::instances::{InstanceSettings, openai_compatible_url};
+use crate::providers::openai_compat::{OpenAICompatibleProvider, CompatConfig, AuthScheme, delegate_llm_instance};
+use crate::errors::LlmResult;
+use crate::constants;
+
+/// Provider implementation for xAI's API (Grok models)
+///
+/// API endpoint: https://api.x.ai/v1/chat/completions
+/// Uses OpenAI-compatible API format with Bearer token authentication.
+pub struct XaiInstance(OpenAICompatibleProvider);
+
+impl XaiInstance {
+    /// Creates a new xAI provider instance
+    pub fn new(
+        api_key: String,
+        model: String,
+        supported_tasks: HashMap<String, TaskDefinition>,
+        enabled: bool,
+        endpoint_url: Option<String>,
+        settings: &InstanceSettings,
+    ) -> LlmResult<Self> {
+        let config = CompatConfig {
+            name: "xai",
+            display_name: "xAI",
+            chat_url: openai_compatible_url(endpoint_url.as_deref(), constants::XAI_API_ENDPOINT, "/chat/completions"),
+            models_url: Some(openai_compatible_url(endpoint_url.as_deref(), constants::XAI_MODELS_ENDPOINT, "/models")),
+            auth: AuthScheme::Bearer,
+            streaming: true,
+            seed: true,
+            logprobs: true,
+            known_models: true,
+        };
+        OpenAICompatibleProvider::new(config, api_key, model, supported_tasks, enabled, settings).map(Self)
+    }
+}
+
+delegate_llm_instance!(XaiInstance);
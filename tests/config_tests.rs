@@ -297,7 +297,7 @@ api_key = "key"
 
 #[test]
 fn test_valid_strategies() {
-    for strategy in &["lru", "lowest_latency", "random"] {
+    for strategy in &["lru", "lowest_latency", "random", "p2c", "weighted", "priority"] {
         let toml = format!(r#"
 [settings]
 strategy = "{}"
@@ -313,11 +313,133 @@ api_key = "key"
     }
 }
 
+#[test]
+fn test_task_strategy() {
+    let toml = r#"
+[[tasks]]
+name = "code"
+strategy = "lowest_latency"
+
+[[tasks]]
+name = "chat"
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(config.tasks[0].strategy.as_deref(), Some("lowest_latency"));
+    assert_eq!(config.tasks[1].strategy, None);
+
+    let invalid = r#"
+[[tasks]]
+name = "code"
+strategy = "fastest"
+"#;
+    let err = parse_config(invalid).unwrap_err().to_string();
+    assert!(err.contains("Unknown strategy 'fastest' for task 'code'"), "{}", err);
+}
+
+#[test]
+fn test_provider_weight_and_priority() {
+    let toml = r#"
+[settings]
+strategy = "weighted"
+
+[[providers]]
+type = "openai"
+model = "gpt-4"
+api_key = "key"
+weight = 3.0
+priority = 1
+
+[[providers]]
+type = "ollama"
+model = "llama3"
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(config.providers[0].weight, Some(3.0));
+    assert_eq!(config.providers[0].priority, Some(1));
+    assert_eq!(config.providers[1].weight, None);
+    assert_eq!(config.providers[1].priority, None);
+}
+
+#[test]
+fn test_provider_context_window() {
+    let toml = r#"
+[settings]
+reject_oversized_prompts = true
+
+[[providers]]
+type = "ollama"
+model = "llama3"
+context_window = 8192
+
+[[providers]]
+type = "ollama"
+model = "mistral"
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert!(config.settings.reject_oversized_prompts);
+    assert_eq!(config.providers[0].context_window, Some(8192));
+    assert_eq!(config.providers[1].context_window, None);
+    assert!(!parse_config("").unwrap().settings.reject_oversized_prompts);
+}
+
+#[test]
+fn test_provider_timeouts() {
+    let toml = r#"
+[[providers]]
+type = "ollama"
+model = "llama3"
+timeout_ms = 300000
+connect_timeout_ms = 2000
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(config.providers[0].timeout_ms, Some(300000));
+    assert_eq!(config.providers[0].connect_timeout_ms, Some(2000));
+
+    let zero = toml.replace("connect_timeout_ms = 2000", "connect_timeout_ms = 0");
+    let err = parse_config(&zero).unwrap_err().to_string();
+    assert!(err.contains("Invalid timeout"));
+}
+
+#[test]
+fn test_default_max_tokens() {
+    let toml = r#"
+[[providers]]
+type = "anthropic"
+model = "claude-sonnet-4"
+api_key = "key"
+default_max_tokens = 4096
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(config.providers[0].default_max_tokens, Some(4096));
+
+    let zero = toml.replace("default_max_tokens = 4096", "default_max_tokens = 0");
+    let err = parse_config(&zero).unwrap_err().to_string();
+    assert!(err.contains("Invalid default_max_tokens"));
+}
+
+#[test]
+fn test_negative_weight_rejected() {
+    let toml = r#"
+[[providers]]
+type = "ollama"
+model = "llama3"
+weight = -1.0
+"#;
+
+    let err = parse_config(toml).unwrap_err().to_string();
+    assert!(err.contains("Invalid weight"));
+}
+
 #[test]
 fn test_all_valid_provider_types() {
     let providers = [
         "anthropic", "openai", "mistral", "google", "ollama",
-        "lmstudio", "groq", "cohere", "togetherai", "perplexity"
+        "lmstudio", "groq", "cohere", "togetherai", "perplexity", "xai", "ai21"
     ];
 
     for provider in providers {
@@ -333,6 +455,113 @@ api_key = "test-key"
     }
 }
 
+#[test]
+fn test_fireworks_model_must_be_full_path() {
+    for model in ["accounts/fireworks/models/llama-v3p1-70b-instruct", "accounts/acme/deployedModels/custom-1"] {
+        let toml = format!(r#"
+[[providers]]
+type = "fireworks"
+model = "{}"
+api_key = "fw-key"
+"#, model);
+        let config = parse_config(&toml).unwrap();
+        assert_eq!(config.providers[0].model, model);
+    }
+
+    for model in ["llama-v3p1-70b-instruct", "fireworks/models/llama", "accounts//models/llama"] {
+        let toml = format!(r#"
+[[providers]]
+type = "fireworks"
+model = "{}"
+api_key = "fw-key"
+"#, model);
+        let err = parse_config(&toml).unwrap_err().to_string();
+        assert!(err.contains("Fireworks models are full paths"), "'{}' should be rejected: {}", model, err);
+    }
+}
+
+#[test]
+fn test_replicate_model_must_name_owner_and_model() {
+    for model in ["meta/meta-llama-3-8b-instruct", "owner/model:5c7d5dc6", &"a".repeat(64)] {
+        let toml = format!(r#"
+[[providers]]
+type = "replicate"
+model = "{}"
+api_key = "r8_key"
+"#, model);
+        assert!(parse_config(&toml).is_ok(), "'{}' should be valid", model);
+    }
+
+    for model in ["llama3", "meta/", "meta/llama:", "a/b/c"] {
+        let toml = format!(r#"
+[[providers]]
+type = "replicate"
+model = "{}"
+api_key = "r8_key"
+"#, model);
+        let err = parse_config(&toml).unwrap_err().to_string();
+        assert!(err.contains("Replicate models are 'owner/name'"), "'{}' should be rejected: {}", model, err);
+    }
+}
+
+#[test]
+fn test_openai_compatible_provider() {
+    let toml = r#"
+[[providers]]
+type = "openai_compatible"
+model = "meta-llama/Meta-Llama-3-8B-Instruct"
+api_key = "key"
+endpoint = "https://api.deepinfra.com/v1/openai"
+auth_header = "api-key"
+models_path = "/v1/models"
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(config.providers[0].auth_header.as_deref(), Some("api-key"));
+    assert_eq!(config.providers[0].models_path.as_deref(), Some("/v1/models"));
+
+    let without_endpoint = toml.replace("endpoint = \"https://api.deepinfra.com/v1/openai\"\n", "");
+    let err = parse_config(&without_endpoint).unwrap_err().to_string();
+    assert!(err.contains("has no endpoint"), "{}", err);
+}
+
+#[test]
+fn test_vertexai_provider_requires_project_id() {
+    let toml = r#"
+[[providers]]
+type = "vertexai"
+model = "gemini-2.0-flash"
+api_key = "token"
+project_id = "my-project"
+location = "europe-west4"
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(config.providers[0].project_id.as_deref(), Some("my-project"));
+    assert_eq!(config.providers[0].location.as_deref(), Some("europe-west4"));
+
+    let without_project = toml.replace("project_id = \"my-project\"\n", "");
+    let err = parse_config(&without_project).unwrap_err().to_string();
+    assert!(err.contains("has no project_id"), "{}", err);
+}
+
+#[test]
+fn test_anthropic_beta_features() {
+    let toml = r#"
+[[providers]]
+type = "anthropic"
+model = "claude-sonnet-4-20250514"
+api_key = "key"
+anthropic_beta = ["prompt-caching-2024-07-31", "context-1m-2025-08-07"]
+"#;
+
+    let config = parse_config(toml).unwrap();
+    assert_eq!(
+        config.providers[0].anthropic_beta.as_deref(),
+        Some(&["prompt-caching-2024-07-31".to_string(), "context-1m-2025-08-07".to_string()][..])
+    );
+}
+
 // ============================================================================
 // File Loading Tests
 // ============================================================================
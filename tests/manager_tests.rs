@@ -1,6 +1,66 @@
 //! Integration tests for LlmManager.
 
-use flyllm::{LlmManager, ProviderType, TaskDefinition, GenerationRequest};
+use flyllm::{create_instance, InstanceSettings, LlmManager, ProviderType, TaskDefinition, GenerationRequest};
+use flyllm::{LlmInstance, LlmRequest, LlmResponse, LlmResult, UsageSnapshot};
+use flyllm::providers::TokenUsage;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Instance that answers locally and counts how often it was called
+struct CountingInstance {
+    name: &'static str,
+    calls: Arc<AtomicUsize>,
+    tasks: HashMap<String, TaskDefinition>,
+}
+
+impl CountingInstance {
+    fn new(tasks: Vec<TaskDefinition>) -> (Self, Arc<AtomicUsize>) {
+        Self::named("mock", tasks)
+    }
+
+    /// Like `new`, reporting `name` as its provider name
+    fn named(name: &'static str, tasks: Vec<TaskDefinition>) -> (Self, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tasks = tasks.into_iter().map(|t| (t.name.clone(), t)).collect();
+        (Self { name, calls: calls.clone(), tasks }, calls)
+    }
+}
+
+#[async_trait]
+impl LlmInstance for CountingInstance {
+    async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(LlmResponse {
+            content: format!("response #{}", call),
+            model: "mock-model".to_string(),
+            usage: Some(TokenUsage {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                total_tokens: 15,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_model(&self) -> &str {
+        "mock-model"
+    }
+
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        &self.tasks
+    }
+
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
 
 // ============================================================================
 // Builder Pattern Tests
@@ -65,6 +125,29 @@ async fn test_builder_with_custom_endpoint() {
     assert_eq!(manager.get_provider_count().await, 1);
 }
 
+#[tokio::test]
+async fn test_builder_rejects_invalid_proxy() {
+    let result = LlmManager::builder()
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key")
+        .proxy("not a url")
+        .build()
+        .await;
+
+    let err = result.err().unwrap().to_string();
+    assert!(err.contains("Invalid proxy URL"));
+}
+
+#[tokio::test]
+async fn test_builder_rejects_invalid_custom_header() {
+    let result = LlmManager::builder()
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key")
+        .custom_header("X-Org-Id", "line\nbreak")
+        .build()
+        .await;
+
+    assert!(matches!(result, Err(flyllm::LlmError::ConfigError(_))));
+}
+
 #[tokio::test]
 async fn test_builder_with_disabled_provider() {
     let manager = LlmManager::builder()
@@ -121,6 +204,45 @@ async fn test_builder_undefined_task_error() {
     }
 }
 
+#[tokio::test]
+async fn test_builder_require_all_tasks_served_rejects_unserved_task() {
+    let result = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .define_task(TaskDefinition::new("summary"))
+        .define_task(TaskDefinition::new("translate"))
+        .require_all_tasks_served(true)
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key")
+        .supports("chat")
+        .add_instance(ProviderType::Anthropic, "claude-3", "key")
+        .supports("translate")
+        .enabled(false)
+        .build()
+        .await;
+
+    match result {
+        Err(flyllm::LlmError::ConfigError(msg)) => {
+            assert!(msg.ends_with("summary, translate"), "{}", msg);
+        }
+        Err(e) => panic!("expected ConfigError, got {:?}", e),
+        Ok(_) => panic!("expected build to fail"),
+    }
+}
+
+#[tokio::test]
+async fn test_builder_require_all_tasks_served_accepts_served_tasks() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .define_task(TaskDefinition::new("summary"))
+        .require_all_tasks_served(true)
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key")
+        .supports_many(&["chat", "summary"])
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(manager.get_provider_count().await, 1);
+}
+
 // ============================================================================
 // TOML Configuration Tests
 // ============================================================================
@@ -165,6 +287,33 @@ tasks = ["chat"]
     assert_eq!(manager.get_provider_count().await, 2);
 }
 
+#[tokio::test]
+async fn test_from_config_str_require_all_tasks_served() {
+    let toml = r#"
+[settings]
+require_all_tasks_served = true
+
+[[tasks]]
+name = "chat"
+
+[[tasks]]
+name = "summary"
+
+[[providers]]
+type = "openai"
+model = "gpt-4"
+api_key = "key"
+tasks = ["chat"]
+"#;
+
+    let err = LlmManager::from_config_str(toml).await.err().unwrap();
+    assert!(matches!(&err, flyllm::LlmError::ConfigError(msg) if msg.contains("summary")), "{:?}", err);
+
+    let served = toml.replace(r#"tasks = ["chat"]"#, r#"tasks = ["chat", "summary"]"#);
+    let manager = LlmManager::from_config_str(&served).await.unwrap();
+    assert_eq!(manager.get_provider_count().await, 1);
+}
+
 #[tokio::test]
 async fn test_from_config_str_with_settings() {
     let toml = r#"
@@ -199,6 +348,130 @@ api_key = "key"
     assert!(result.is_err());
 }
 
+#[tokio::test]
+async fn test_from_config_str_verbose_report() {
+    let toml = r#"
+[[tasks]]
+name = "chat"
+
+[[tasks]]
+name = "unused"
+
+[[providers]]
+type = "openai"
+model = "gpt-4"
+api_key = "key"
+tasks = ["chat"]
+
+[[providers]]
+type = "ollama"
+model = "llama3"
+name = "local"
+enabled = false
+"#;
+
+    let (manager, report) = LlmManager::from_config_str_verbose(toml).await.unwrap();
+    assert_eq!(manager.get_provider_count().await, 2);
+    assert_eq!(report.tasks, vec!["chat", "unused"]);
+
+    assert_eq!(report.providers.len(), 2);
+    assert_eq!(report.providers[0].name, "openai/gpt-4");
+    assert_eq!(report.providers[0].tasks, vec!["chat"]);
+    assert_eq!(report.providers[1].name, "local");
+    assert!(!report.providers[1].enabled);
+
+    assert_eq!(report.warnings.len(), 3);
+    assert!(report.warnings.iter().any(|w| w.contains("'local' references no tasks")));
+    assert!(report.warnings.iter().any(|w| w.contains("'local' is disabled")));
+    assert!(report.warnings.iter().any(|w| w.contains("'unused' is not supported")));
+
+    // The report is meant to be printed as JSON
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["providers"][1]["provider_type"], "ollama");
+}
+
+#[tokio::test]
+async fn test_reload_adds_updates_and_removes_providers() {
+    let initial = r#"
+[[tasks]]
+name = "chat"
+
+[[providers]]
+type = "ollama"
+model = "llama3"
+name = "kept"
+tasks = ["chat"]
+
+[[providers]]
+type = "ollama"
+model = "mistral"
+"#;
+
+    let manager = LlmManager::from_config_str(initial).await.unwrap();
+    let instances = manager.list_instances().await;
+    assert_eq!(instances.len(), 2);
+    let kept_id = instances.iter().find(|(_, _, model, _)| model == "llama3").unwrap().0;
+
+    let usage = TokenUsage {
+        prompt_tokens: 100,
+        completion_tokens: 50,
+        total_tokens: 150,
+        ..Default::default()
+    };
+    manager.total_usage.lock().await.insert(kept_id, usage.clone());
+
+    let reloaded = r#"
+[[tasks]]
+name = "summary"
+
+[[providers]]
+type = "ollama"
+model = "llama3"
+name = "kept"
+tasks = ["summary"]
+enabled = false
+
+[[providers]]
+type = "ollama"
+model = "phi3"
+"#;
+
+    manager.reload_from_config_str(reloaded).await.unwrap();
+
+    let instances = manager.list_instances().await;
+    let models: Vec<&str> = instances.iter().map(|(_, _, model, _)| model.as_str()).collect();
+    assert_eq!(models, vec!["llama3", "phi3"]);
+
+    // The surviving instance keeps its ID and usage but picks up the new settings
+    assert_eq!(instances[0].0, kept_id);
+    assert!(!instances[0].3);
+    assert_eq!(manager.get_instance_usage(kept_id).await, Some(usage));
+    let task_map = manager.tasks_to_instances.lock().await;
+    assert_eq!(task_map.get("summary"), Some(&vec![kept_id]));
+    assert!(!task_map.contains_key("chat"));
+}
+
+#[tokio::test]
+async fn test_reload_rejects_invalid_config() {
+    let initial = r#"
+[[providers]]
+type = "ollama"
+model = "llama3"
+"#;
+
+    let manager = LlmManager::from_config_str(initial).await.unwrap();
+    let before = manager.list_instances().await;
+
+    let invalid = r#"
+[[providers]]
+type = "ollama"
+model = "phi3"
+tasks = ["undefined"]
+"#;
+    assert!(manager.reload_from_config_str(invalid).await.is_err());
+    assert_eq!(manager.list_instances().await, before);
+}
+
 // ============================================================================
 // Token Usage Tests
 // ============================================================================
@@ -237,49 +510,2714 @@ async fn test_instance_usage_none_initially() {
 }
 
 // ============================================================================
-// GenerationRequest Tests
+// In-Flight Tracking Tests
 // ============================================================================
 
-#[test]
-fn test_generation_request_basic() {
-    let request = GenerationRequest {
-        prompt: "Hello".to_string(),
-        task: None,
-        params: None,
-    };
+#[tokio::test]
+async fn test_inflight_counts_zero_initially() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("test"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key1")
+        .supports("test")
+        .add_instance(ProviderType::Anthropic, "claude-3", "key2")
+        .supports("test")
+        .build()
+        .await
+        .unwrap();
 
-    assert_eq!(request.prompt, "Hello");
-    assert!(request.task.is_none());
-    assert!(request.params.is_none());
+    let counts = manager.get_inflight_counts().await;
+    assert_eq!(counts.len(), 2);
+    assert!(counts.values().all(|count| *count == 0));
 }
 
-#[test]
-fn test_generation_request_with_task() {
-    let request = GenerationRequest {
-        prompt: "Summarize this".to_string(),
-        task: Some("summary".to_string()),
-        params: None,
-    };
+// ============================================================================
+// Runtime Enable/Disable Tests
+// ============================================================================
 
-    assert_eq!(request.task, Some("summary".to_string()));
+#[tokio::test]
+async fn test_list_instances() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key1")
+        .supports("chat")
+        .add_instance(ProviderType::Anthropic, "claude-3", "key2")
+        .supports("chat")
+        .enabled(false)
+        .build()
+        .await
+        .unwrap();
+
+    let instances = manager.list_instances().await;
+    assert_eq!(instances.len(), 2);
+    assert_eq!(instances[0], (0, "openai".to_string(), "gpt-4".to_string(), true));
+    assert_eq!(instances[1], (1, "anthropic".to_string(), "claude-3".to_string(), false));
 }
 
-#[test]
-fn test_generation_request_with_params() {
-    use std::collections::HashMap;
-    use serde_json::json;
+#[tokio::test]
+async fn test_set_instance_enabled_at_runtime() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key1")
+        .supports("chat")
+        .add_instance(ProviderType::Anthropic, "claude-3", "key2")
+        .supports("chat")
+        .build()
+        .await
+        .unwrap();
 
-    let mut params = HashMap::new();
-    params.insert("max_tokens".to_string(), json!(500));
-    params.insert("temperature".to_string(), json!(0.7));
+    assert!(manager.set_instance_enabled(1, false).await);
+    let instances = manager.list_instances().await;
+    assert!(instances[0].3);
+    assert!(!instances[1].3);
 
-    let request = GenerationRequest {
-        prompt: "Test".to_string(),
-        task: None,
-        params: Some(params),
+    assert!(manager.set_instance_enabled(1, true).await);
+    assert!(manager.list_instances().await[1].3);
+}
+
+#[tokio::test]
+async fn test_routing_table_maps_tasks_to_instances() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .define_task(TaskDefinition::new("summary"))
+        .define_task(TaskDefinition::new("translate"))
+        .add_instance(ProviderType::OpenAI, "gpt-4o", "key1")
+        .supports("chat")
+        .add_instance(ProviderType::Anthropic, "claude-3-5-haiku", "key2")
+        .supports_many(&["chat", "summary"])
+        .add_instance(ProviderType::Mistral, "mistral-small", "key3")
+        .supports("summary")
+        .build()
+        .await
+        .unwrap();
+
+    let table = manager.routing_table().await;
+    assert_eq!(table.len(), 3);
+    assert!(table["translate"].is_empty());
+    assert_eq!(
+        table["chat"],
+        vec![
+            (0, "openai".to_string(), "gpt-4o".to_string()),
+            (1, "anthropic".to_string(), "claude-3-5-haiku".to_string()),
+        ]
+    );
+    assert_eq!(
+        table["summary"],
+        vec![
+            (1, "anthropic".to_string(), "claude-3-5-haiku".to_string()),
+            (2, "mistral".to_string(), "mistral-small".to_string()),
+        ]
+    );
+    // A defined task that no instance supports is never routable
+    assert_eq!(manager.unrouted_tasks().await, vec!["translate".to_string()]);
+
+    manager.set_instance_enabled(1, false).await;
+    manager.set_instance_enabled(2, false).await;
+    assert_eq!(manager.unrouted_tasks().await, vec!["summary".to_string(), "translate".to_string()]);
+    // Disabled instances stay in the table
+    assert_eq!(manager.routing_table().await["summary"].len(), 2);
+
+    // Removing every instance of a task keeps reporting it
+    manager.set_instance_enabled(1, true).await;
+    manager.remove_instance(1).await;
+    manager.remove_instance(2).await;
+    assert!(manager.routing_table().await["summary"].is_empty());
+    assert_eq!(manager.unrouted_tasks().await, vec!["summary".to_string(), "translate".to_string()]);
+}
+
+#[tokio::test]
+async fn test_set_instance_enabled_unknown_id() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key")
+        .supports("chat")
+        .build()
+        .await
+        .unwrap();
+
+    assert!(!manager.set_instance_enabled(42, false).await);
+}
+
+#[tokio::test]
+async fn test_disabled_instances_skipped_by_selection() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key")
+        .supports("chat")
+        .max_retries(0)
+        .build()
+        .await
+        .unwrap();
+
+    manager.set_instance_enabled(0, false).await;
+
+    let responses = manager
+        .generate_sequentially(vec![GenerationRequest::new("Hello".to_string()).task("chat")])
+        .await;
+    assert!(!responses[0].success);
+    assert!(responses[0].error.as_ref().unwrap().contains("No enabled providers"));
+}
+
+// ============================================================================
+// Runtime Add/Remove Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_add_then_remove_instance() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key1")
+        .supports("chat")
+        .build()
+        .await
+        .unwrap();
+
+    let instance = create_instance(
+        ProviderType::Anthropic,
+        "key2".to_string(),
+        "claude-3".to_string(),
+        vec![TaskDefinition::new("chat"), TaskDefinition::new("summary")],
+        true,
+        None,
+        InstanceSettings::default(),
+    ).unwrap();
+    let id = manager.add_instance_dynamic(instance).await;
+    assert_eq!(id, 1);
+    assert_eq!(manager.get_provider_count().await, 2);
+    assert!(manager.get_instance_usage(id).await.is_some());
+
+    assert!(manager.remove_instance(id).await);
+    assert_eq!(manager.get_provider_count().await, 1);
+    assert!(manager.get_instance_usage(id).await.is_none());
+    assert_eq!(manager.list_instances().await[0].0, 0);
+
+    // The task only this instance supported is gone
+    let responses = manager
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string()).task("summary")])
+        .await;
+    assert!(!responses[0].success);
+    assert!(responses[0].error.as_ref().unwrap().contains("No providers available"));
+
+    // Removing twice fails
+    assert!(!manager.remove_instance(id).await);
+}
+
+#[tokio::test]
+async fn test_dynamic_ids_not_reused_after_removal() {
+    let manager = LlmManager::new();
+    let make = || {
+        create_instance(
+            ProviderType::OpenAI,
+            "key".to_string(),
+            "gpt-4".to_string(),
+            vec![TaskDefinition::new("chat")],
+            true,
+            None,
+            InstanceSettings::default(),
+        ).unwrap()
     };
 
-    assert!(request.params.is_some());
+    let first = manager.add_instance_dynamic(make()).await;
+    assert!(manager.remove_instance(first).await);
+    let second = manager.add_instance_dynamic(make()).await;
+    assert_ne!(first, second);
+}
+
+// ============================================================================
+// Embedding Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_embed_excludes_chat_only_providers() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("embed"))
+        .add_instance(ProviderType::Anthropic, "claude-3", "key")
+        .supports("embed")
+        .build()
+        .await
+        .unwrap();
+
+    let result = manager.embed(Some("embed"), vec!["hello".to_string()]).await;
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("No embedding-capable providers"));
+}
+
+#[tokio::test]
+async fn test_embed_unknown_task() {
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("embed"))
+        .add_instance(ProviderType::OpenAI, "text-embedding-3-small", "key")
+        .supports("embed")
+        .build()
+        .await
+        .unwrap();
+
+    let result = manager.embed(Some("missing"), vec!["hello".to_string()]).await;
+    assert!(result.unwrap_err().to_string().contains("No providers available for task"));
+}
+
+// ============================================================================
+// Response Cache Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_cache_serves_deterministic_requests() {
+    let manager = LlmManager::builder()
+        .enable_cache(16, std::time::Duration::from_secs(60))
+        .build()
+        .await
+        .unwrap();
+    let (instance, calls) = CountingInstance::new(vec![
+        TaskDefinition::new("extract").with_temperature(0.0),
+    ]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    let request = || GenerationRequest::new("Extract the date".to_string()).task("extract");
+    let first = manager.generate_sequentially(vec![request()]).await;
+    let second = manager.generate_sequentially(vec![request()]).await;
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(first[0].content, "response #1");
+    assert_eq!(second[0].content, "response #1");
+
+    manager.clear_cache().await;
+    let third = manager.generate_sequentially(vec![request()]).await;
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(third[0].content, "response #2");
+}
+
+#[tokio::test]
+async fn test_cache_skips_nonzero_temperature() {
+    let manager = LlmManager::builder()
+        .enable_cache(16, std::time::Duration::from_secs(60))
+        .build()
+        .await
+        .unwrap();
+    let (instance, calls) = CountingInstance::new(vec![
+        TaskDefinition::new("creative").with_temperature(0.9),
+    ]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    let request = || GenerationRequest::new("Write a poem".to_string()).task("creative");
+    manager.generate_sequentially(vec![request(), request()]).await;
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_cache_distinguishes_prompts() {
+    let manager = LlmManager::builder()
+        .enable_cache(16, std::time::Duration::from_secs(60))
+        .build()
+        .await
+        .unwrap();
+    let (instance, calls) = CountingInstance::new(vec![
+        TaskDefinition::new("extract").with_temperature(0.0),
+    ]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    manager
+        .generate_sequentially(vec![
+            GenerationRequest::new("First".to_string()).task("extract"),
+            GenerationRequest::new("Second".to_string()).task("extract"),
+        ])
+        .await;
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_response_cache_lru_eviction_and_ttl() {
+    use flyllm::load_balancer::cache::ResponseCache;
+    use std::time::Duration;
+
+    let response = |content: &str| LlmResponse {
+        content: content.to_string(),
+        ..Default::default()
+    };
+
+    let cache = ResponseCache::new(2, Duration::from_secs(60));
+    cache.insert(1, response("one")).await;
+    cache.insert(2, response("two")).await;
+    assert!(cache.get(1).await.is_some()); // 1 is now most recently used
+    cache.insert(3, response("three")).await;
+
+    assert!(cache.get(2).await.is_none());
+    assert_eq!(cache.get(1).await.unwrap().content, "one");
+    assert_eq!(cache.get(3).await.unwrap().content, "three");
+
+    let expiring = ResponseCache::new(2, Duration::from_millis(10));
+    expiring.insert(1, response("one")).await;
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert!(expiring.get(1).await.is_none());
+    assert!(expiring.is_empty().await);
+}
+
+// ============================================================================
+// Usage Persistence Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_take_usage_returns_totals_and_resets_them() {
+    let manager = LlmManager::new();
+    let (instance, _calls) = CountingInstance::new(vec![]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+    manager
+        .generate_sequentially(vec![
+            GenerationRequest::new("One".to_string()),
+            GenerationRequest::new("Two".to_string()),
+        ])
+        .await;
+
+    let taken = manager.take_usage().await;
+    assert_eq!(taken.len(), 1);
+    assert_eq!((taken[&id].prompt_tokens, taken[&id].completion_tokens, taken[&id].total_tokens), (20, 10, 30));
+    assert_eq!(manager.get_total_usage().await.total_tokens, 0);
+    assert_eq!(manager.get_instance_usage(id).await.map(|u| u.total_tokens), Some(0));
+
+    // The next window starts from zero
+    manager.generate(GenerationRequest::new("Three".to_string())).await.unwrap();
+    assert_eq!(manager.get_total_usage().await.total_tokens, 15);
+    manager.reset_usage().await;
+    assert_eq!(manager.get_total_usage().await.total_tokens, 0);
+}
+
+
+#[tokio::test]
+async fn test_usage_export_restore_round_trip() {
+    let manager = LlmManager::new();
+    let (instance, _calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+    manager
+        .generate_sequentially(vec![
+            GenerationRequest::new("One".to_string()).task("chat"),
+            GenerationRequest::new("Two".to_string()).task("chat"),
+        ])
+        .await;
+
+    let snapshot = manager.export_usage().await;
+    assert_eq!(snapshot.entries.len(), 1);
+    assert_eq!(snapshot.entries[0].provider, "mock");
+    assert_eq!(snapshot.entries[0].usage.total_tokens, 30);
+
+    // Persist and reload through JSON
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let restored_snapshot: UsageSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored_snapshot, snapshot);
+
+    let restarted = LlmManager::new();
+    let (instance, _calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    restarted.add_instance_dynamic(Arc::new(instance)).await;
+    restarted.import_usage(&restored_snapshot).await;
+
+    assert_eq!(restarted.get_total_usage().await.total_tokens, 30);
+    assert_eq!(restarted.export_usage().await, snapshot);
+}
+
+#[tokio::test]
+async fn test_restore_usage_drops_missing_and_zeroes_new_providers() {
+    let snapshot: UsageSnapshot = serde_json::from_value(serde_json::json!({
+        "entries": [
+            {"provider": "openai", "model": "gpt-4", "usage": {"prompt_tokens": 100, "completion_tokens": 50, "total_tokens": 150}},
+            {"provider": "cohere", "model": "command-r", "usage": {"prompt_tokens": 7, "completion_tokens": 3, "total_tokens": 10}}
+        ]
+    }))
+    .unwrap();
+
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .add_instance(ProviderType::OpenAI, "gpt-4", "key1")
+        .supports("chat")
+        .add_instance(ProviderType::Anthropic, "claude-3", "key2")
+        .supports("chat")
+        .restore_usage(snapshot)
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(manager.get_instance_usage(0).await.unwrap().total_tokens, 150);
+    assert_eq!(manager.get_instance_usage(1).await.unwrap().total_tokens, 0);
+    // The cohere entry had no matching instance and was dropped
+    assert_eq!(manager.get_total_usage().await.total_tokens, 150);
+}
+
+// ============================================================================
+// GenerationRequest Tests
+// ============================================================================
+
+#[test]
+fn test_generation_request_basic() {
+    let request = GenerationRequest {
+        prompt: "Hello".to_string(),
+        task: None,
+        params: None,
+        ..Default::default()
+    };
+
+    assert_eq!(request.prompt, "Hello");
+    assert!(request.task.is_none());
+    assert!(request.params.is_none());
+}
+
+#[test]
+fn test_generation_request_with_task() {
+    let request = GenerationRequest {
+        prompt: "Summarize this".to_string(),
+        task: Some("summary".to_string()),
+        params: None,
+        ..Default::default()
+    };
+
+    assert_eq!(request.task, Some("summary".to_string()));
+}
+
+#[test]
+fn test_generation_request_with_params() {
+    use std::collections::HashMap;
+    use serde_json::json;
+
+    let mut params = HashMap::new();
+    params.insert("max_tokens".to_string(), json!(500));
+    params.insert("temperature".to_string(), json!(0.7));
+
+    let request = GenerationRequest {
+        prompt: "Test".to_string(),
+        task: None,
+        params: Some(params),
+        ..Default::default()
+    };
+
+    assert!(request.params.is_some());
     let params = request.params.unwrap();
     assert_eq!(params.get("max_tokens"), Some(&json!(500)));
 }
+
+#[test]
+fn test_generation_request_with_tools() {
+    use flyllm::ToolDefinition;
+    use serde_json::json;
+
+    let tool = ToolDefinition::new(
+        "get_weather",
+        "Get the weather for a city",
+        json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+    );
+    let request = GenerationRequest::new("Weather in Paris?".to_string())
+        .tools(vec![tool.clone()])
+        .tool_choice("auto");
+
+    assert_eq!(request.tools, Some(vec![tool]));
+    assert_eq!(request.tool_choice.as_deref(), Some("auto"));
+}
+
+#[test]
+fn test_generation_request_image_url() {
+    use flyllm::ContentPart;
+
+    let request = GenerationRequest::new("What's in this picture?".to_string())
+        .image_url("https://example.com/cat.png");
+
+    assert_eq!(request.images, Some(vec![ContentPart::ImageUrl {
+        url: "https://example.com/cat.png".to_string(),
+        detail: None,
+    }]));
+}
+
+#[test]
+fn test_generation_request_json_mode() {
+    use serde_json::json;
+
+    let request = GenerationRequest::new("List three colors as JSON".to_string()).json_mode();
+    let params = request.params.unwrap();
+    assert_eq!(params.get("response_format"), Some(&json!({"type": "json_object"})));
+}
+
+// ============================================================================
+// Cost Estimation Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_cost_accumulates_per_instance() {
+    use flyllm::Pricing;
+
+    let mut manager = LlmManager::new();
+    manager.pricing.insert(
+        ("mock".to_string(), "mock-model".to_string()),
+        Pricing::new(1.0, 2.0),
+    );
+    let (instance, _calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+    assert_eq!(manager.get_instance_cost(id).await, Some(0.0));
+
+    manager
+        .generate_sequentially(vec![
+            GenerationRequest::new("One".to_string()).task("chat"),
+            GenerationRequest::new("Two".to_string()).task("chat"),
+        ])
+        .await;
+
+    // Each response: 10 prompt tokens at $1/1K + 5 completion tokens at $2/1K = $0.02
+    let expected = 0.04;
+    assert!((manager.get_instance_cost(id).await.unwrap() - expected).abs() < 1e-9);
+    assert!((manager.get_total_cost().await - expected).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_cost_skipped_without_pricing() {
+    let manager = LlmManager::new();
+    let (instance, _calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    manager
+        .generate_sequentially(vec![GenerationRequest::new("One".to_string()).task("chat")])
+        .await;
+
+    assert_eq!(manager.get_total_cost().await, 0.0);
+}
+
+#[tokio::test]
+async fn test_builder_pricing_overrides_defaults() {
+    use flyllm::Pricing;
+
+    let manager = LlmManager::builder()
+        .pricing(ProviderType::OpenAI, "gpt-4o", Pricing::new(0.5, 1.5))
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(manager.pricing_for("openai", "gpt-4o"), Some(Pricing::new(0.5, 1.5)));
+    // Dated snapshots fall back to the built-in table by longest prefix
+    assert_eq!(
+        manager.pricing_for("openai", "gpt-4o-mini-2024-07-18"),
+        Some(Pricing::new(0.00015, 0.0006))
+    );
+    assert_eq!(manager.pricing_for("openai", "unknown-model"), None);
+}
+
+#[tokio::test]
+async fn test_budget_blocks_requests_once_exceeded() {
+    use flyllm::{LlmError, Pricing};
+
+    let mut manager = LlmManager::builder()
+        .budget_usd(0.03)
+        .build()
+        .await
+        .unwrap();
+    manager.pricing.insert(
+        ("mock".to_string(), "mock-model".to_string()),
+        Pricing::new(1.0, 2.0),
+    );
+    let (instance, calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+    assert_eq!(manager.remaining_budget().await, Some(0.03));
+
+    // Each response costs $0.02, so the second one crosses the budget
+    let request = || GenerationRequest::new("Hi".to_string()).task("chat");
+    let responses = manager.generate_sequentially(vec![request(), request()]).await;
+    assert!(responses.iter().all(|r| r.success));
+    assert_eq!(manager.remaining_budget().await, Some(0.0));
+
+    let err = manager.generate_stream(request()).await.err().unwrap();
+    assert!(matches!(err, LlmError::BudgetExceeded(_)));
+    let blocked = manager.generate_sequentially(vec![request()]).await;
+    assert!(!blocked[0].success);
+    assert!(blocked[0].error.as_ref().unwrap().contains("Budget exceeded"));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // Raising the budget at runtime lets requests through again
+    manager.set_budget(Some(1.0)).await;
+    let resumed = manager.generate_sequentially(vec![request()]).await;
+    assert!(resumed[0].success);
+    manager.set_budget(None).await;
+    assert_eq!(manager.remaining_budget().await, None);
+}
+
+#[tokio::test]
+async fn test_removing_instance_keeps_its_spend() {
+    use flyllm::Pricing;
+
+    let mut manager = LlmManager::builder()
+        .budget_usd(1.0)
+        .build()
+        .await
+        .unwrap();
+    manager.pricing.insert(
+        ("mock".to_string(), "mock-model".to_string()),
+        Pricing::new(1.0, 2.0),
+    );
+    let (instance, _calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    manager
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string()).task("chat")])
+        .await;
+    let remaining = manager.remaining_budget().await.unwrap();
+    assert!((remaining - 0.98).abs() < 1e-9);
+
+    // Removing and re-adding the instance doesn't refund what it spent
+    assert!(manager.remove_instance(id).await);
+    assert_eq!(manager.get_instance_cost(id).await, None);
+    assert!((manager.get_total_cost().await - 0.02).abs() < 1e-9);
+    let (instance, _calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+    assert!(manager.remaining_budget().await.unwrap() <= remaining);
+}
+
+// ============================================================================
+// Retry Policy Tests
+// ============================================================================
+
+#[test]
+fn test_default_retry_policy_matches_previous_backoff() {
+    use flyllm::RetryPolicy;
+    use std::time::Duration;
+
+    let policy = RetryPolicy::default();
+    for attempt in 0..10 {
+        let expected = Duration::from_secs(2_u64.pow(attempt as u32).min(60));
+        assert_eq!(policy.delay_for(attempt), expected);
+    }
+    assert_eq!(policy.delay_for(usize::MAX), Duration::from_secs(60));
+}
+
+#[test]
+fn test_retry_policy_jitter_stays_within_bounds() {
+    use flyllm::RetryPolicy;
+    use std::time::Duration;
+
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(100),
+        multiplier: 3.0,
+        max_delay: Duration::from_millis(500),
+        jitter: true,
+        backoff_on_errors: true,
+    };
+
+    for _ in 0..100 {
+        let first = policy.delay_for(0);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+        let capped = policy.delay_for(5);
+        assert!(capped >= Duration::from_millis(250) && capped <= Duration::from_millis(500));
+    }
+}
+
+#[tokio::test]
+async fn test_rate_limit_waits_for_retry_after() {
+    use flyllm::LlmError;
+    use std::time::{Duration, Instant};
+
+    /// Rate-limits the first call with a short Retry-After, then succeeds
+    struct RateLimitedOnce {
+        calls: AtomicUsize,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for RateLimitedOnce {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(LlmError::RateLimit {
+                    message: "slow down".to_string(),
+                    retry_after: Some(Duration::from_millis(10)),
+                });
+            }
+            Ok(LlmResponse {
+                content: "ok".to_string(),
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "mock"
+        }
+
+        fn get_model(&self) -> &str {
+            "mock-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let manager = LlmManager::new();
+    manager
+        .add_instance_dynamic(Arc::new(RateLimitedOnce {
+            calls: AtomicUsize::new(0),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    // The default policy would wait a full second before the first retry
+    let start = Instant::now();
+    let responses = manager
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string())])
+        .await;
+    assert!(responses[0].success);
+    assert!(start.elapsed() < Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn test_model_loading_waits_and_retries_same_instance() {
+    use flyllm::LlmError;
+    use flyllm::load_balancer::strategies::LeastRecentlyUsedStrategy;
+    use std::time::{Duration, Instant};
+
+    /// Reports its model as loading for the first two calls, then succeeds
+    struct ColdStartInstance {
+        calls: Arc<AtomicUsize>,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for ColdStartInstance {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(LlmError::ModelLoading {
+                    message: "Model is currently loading".to_string(),
+                    retry_after: Some(Duration::from_millis(20)),
+                });
+            }
+            Ok(LlmResponse {
+                content: "ok".to_string(),
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "cold"
+        }
+
+        fn get_model(&self) -> &str {
+            "cold-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    // Least-recently-used would move on to the other instance if the cold one were failed over
+    let manager = LlmManager::new_with_strategy_and_retries(Box::new(LeastRecentlyUsedStrategy::new()), 3);
+    let cold_calls = Arc::new(AtomicUsize::new(0));
+    let cold_id = manager
+        .add_instance_dynamic(Arc::new(ColdStartInstance {
+            calls: cold_calls.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+    let (warm, warm_calls) = CountingInstance::new(vec![]);
+    manager.add_instance_dynamic(Arc::new(warm)).await;
+
+    let start = Instant::now();
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(response.instance_id, Some(cold_id));
+    assert_eq!(cold_calls.load(Ordering::SeqCst), 3);
+    assert_eq!(warm_calls.load(Ordering::SeqCst), 0);
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
+
+#[tokio::test]
+async fn test_max_total_retry_duration_caps_retries() {
+    use flyllm::LlmError;
+    use std::time::{Duration, Instant};
+
+    /// Always rate-limits with a short Retry-After
+    struct AlwaysRateLimited {
+        calls: Arc<AtomicUsize>,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for AlwaysRateLimited {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(LlmError::RateLimit {
+                message: "slow down".to_string(),
+                retry_after: Some(Duration::from_millis(10)),
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "mock"
+        }
+
+        fn get_model(&self) -> &str {
+            "mock-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let manager = LlmManager::builder()
+        .max_retries(1000)
+        .max_total_retry_duration(Duration::from_millis(100))
+        .build()
+        .await
+        .unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    manager
+        .add_instance_dynamic(Arc::new(AlwaysRateLimited {
+            calls: calls.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    let start = Instant::now();
+    let err = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap_err();
+    let elapsed = start.elapsed();
+
+    assert!(matches!(err, LlmError::RateLimit { .. }), "{:?}", err);
+    assert!(elapsed < Duration::from_millis(500), "took {:?}", elapsed);
+    let calls = calls.load(Ordering::SeqCst);
+    assert!((2..=11).contains(&calls), "made {} calls", calls);
+}
+
+#[tokio::test]
+async fn test_hooks_fire_per_retry_and_on_completion() {
+    use flyllm::{LlmError, RequestCompleteEvent, RetryEvent};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Rate-limits the first `failures` calls without waiting, then succeeds
+    struct RateLimitedFirst {
+        failures: usize,
+        calls: AtomicUsize,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for RateLimitedFirst {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(LlmError::RateLimit {
+                    message: "slow down".to_string(),
+                    retry_after: Some(Duration::ZERO),
+                });
+            }
+            Ok(LlmResponse {
+                content: "ok".to_string(),
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "mock"
+        }
+
+        fn get_model(&self) -> &str {
+            "mock-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let retries = Arc::new(Mutex::new(Vec::new()));
+    let completions = Arc::new(Mutex::new(Vec::new()));
+    let retry_log = retries.clone();
+    let completion_log = completions.clone();
+    let manager = LlmManager::builder()
+        .max_retries(3)
+        .on_retry(Arc::new(move |event: RetryEvent| {
+            assert!(matches!(event.error, LlmError::RateLimit { .. }));
+            retry_log.lock().unwrap().push((event.instance_id, event.attempt, event.task.map(str::to_string)));
+        }))
+        .on_request_complete(Arc::new(move |event: RequestCompleteEvent| {
+            completion_log.lock().unwrap().push((event.request_id, event.is_success(), event.attempts, event.instance_id));
+        }))
+        .build()
+        .await
+        .unwrap();
+    let id = manager
+        .add_instance_dynamic(Arc::new(RateLimitedFirst {
+            failures: 2,
+            calls: AtomicUsize::new(0),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(*retries.lock().unwrap(), vec![(Some(id), 1, None), (Some(id), 2, None)]);
+    assert_eq!(*completions.lock().unwrap(), vec![(response.request_id, true, 3, Some(id))]);
+
+    // A request that runs out of attempts reports its failure once, with no retry after the last attempt
+    retries.lock().unwrap().clear();
+    manager.remove_instance(id).await;
+    manager
+        .add_instance_dynamic(Arc::new(RateLimitedFirst {
+            failures: usize::MAX,
+            calls: AtomicUsize::new(0),
+            tasks: HashMap::new(),
+        }))
+        .await;
+    let response = manager.generate_sequentially(vec![GenerationRequest::new("Hi".to_string())]).await.remove(0);
+    assert!(!response.success);
+    assert_eq!(retries.lock().unwrap().len(), 3);
+    assert_eq!(completions.lock().unwrap()[1], (response.request_id, false, 4, None));
+}
+
+#[tokio::test]
+async fn test_retry_hook_has_no_instance_when_none_was_selected() {
+    use flyllm::RetryEvent;
+    use std::sync::Mutex;
+
+    let retries = Arc::new(Mutex::new(Vec::new()));
+    let retry_log = retries.clone();
+    let manager = LlmManager::builder()
+        .max_retries(2)
+        .on_retry(Arc::new(move |event: RetryEvent| {
+            retry_log.lock().unwrap().push(event.instance_id);
+        }))
+        .build()
+        .await
+        .unwrap();
+    let (instance, calls) = CountingInstance::new(vec![TaskDefinition::new("chat")]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+    assert_eq!(id, 0);
+    manager.set_instance_enabled(id, false).await;
+
+    // Selection fails before reaching an instance, so no retry is blamed on instance 0
+    let response = manager.generate_sequentially(vec![GenerationRequest::new("Hi".to_string())]).await.remove(0);
+    assert!(!response.success);
+    assert_eq!(*retries.lock().unwrap(), vec![None, None]);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_instance_max_retries_retries_same_instance() {
+    use flyllm::LlmError;
+    use flyllm::load_balancer::strategies::LeastRecentlyUsedStrategy;
+
+    /// Fails a fixed number of calls, then succeeds
+    struct FlakyInstance {
+        failures_left: AtomicUsize,
+        calls: Arc<AtomicUsize>,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for FlakyInstance {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let remaining = self.failures_left.load(Ordering::SeqCst);
+            if remaining > 0 {
+                self.failures_left.store(remaining - 1, Ordering::SeqCst);
+                return Err(LlmError::ApiError("flaky".to_string()));
+            }
+            Ok(LlmResponse {
+                content: "ok".to_string(),
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "flaky"
+        }
+
+        fn get_model(&self) -> &str {
+            "flaky-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let flaky_calls = Arc::new(AtomicUsize::new(0));
+    let manager = LlmManager::new_with_strategy_and_retries(Box::new(LeastRecentlyUsedStrategy::new()), 0);
+    let flaky_id = manager
+        .add_instance_dynamic(Arc::new(FlakyInstance {
+            failures_left: AtomicUsize::new(2),
+            calls: flaky_calls.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    // Without an override the manager gives up after the first failure
+    let responses = manager
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string())])
+        .await;
+    assert!(!responses[0].success);
+    assert_eq!(flaky_calls.load(Ordering::SeqCst), 1);
+
+    // One failure left: a single in-place retry is enough, even with max_retries = 0
+    assert!(manager.set_instance_max_retries(flaky_id, Some(1)).await);
+    let responses = manager
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string())])
+        .await;
+    assert!(responses[0].success);
+    assert_eq!(flaky_calls.load(Ordering::SeqCst), 3);
+
+    assert!(!manager.set_instance_max_retries(999, Some(1)).await);
+}
+
+#[tokio::test]
+async fn test_generate_with_instance_bypasses_strategy() {
+    use flyllm::{LlmError, Message};
+
+    let manager = LlmManager::new();
+    let (first, first_calls) = CountingInstance::named("first", vec![]);
+    let (second, second_calls) = CountingInstance::named("second", vec![]);
+    manager.add_instance_dynamic(Arc::new(first)).await;
+    let second_id = manager.add_instance_dynamic(Arc::new(second)).await;
+
+    let request = LlmRequest { messages: vec![Message::user("Hi")], ..Default::default() };
+    for call in 1..=2 {
+        let response = manager.generate_with_instance(second_id, request.clone()).await.unwrap();
+        assert_eq!(response.content, format!("response #{}", call));
+        assert_eq!(response.model, "mock-model");
+        assert_eq!(response.usage.unwrap().total_tokens, 15);
+    }
+    assert_eq!(first_calls.load(Ordering::SeqCst), 0);
+    assert_eq!(second_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(manager.get_instance_usage(second_id).await.unwrap().total_tokens, 30);
+
+    let err = manager.generate_with_instance(999, request.clone()).await.unwrap_err();
+    assert!(matches!(err, LlmError::ConfigError(_)), "{:?}", err);
+
+    manager.set_instance_enabled(second_id, false).await;
+    let err = manager.generate_with_instance(second_id, request).await.unwrap_err();
+    assert!(matches!(err, LlmError::ProviderDisabled(_)), "{:?}", err);
+    assert_eq!(second_calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_invalid_conversation_rejected_before_dispatch() {
+    use flyllm::{LlmError, Message};
+
+    let manager = LlmManager::new();
+    let (instance, calls) = CountingInstance::named("google", vec![]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    let empty = LlmRequest::default();
+    let err = manager.generate_with_instance(id, empty).await.unwrap_err();
+    assert!(matches!(&err, LlmError::ConfigError(message) if message.contains("no messages")), "{:?}", err);
+
+    let assistant_first = LlmRequest {
+        messages: vec![Message::assistant("Hello!"), Message::user("Hi")],
+        ..Default::default()
+    };
+    let err = manager.generate_with_instance(id, assistant_first).await.unwrap_err();
+    assert!(matches!(&err, LlmError::ConfigError(message) if message.contains("must start with a user message")), "{:?}", err);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_task_strategies_route_same_instances_differently() {
+    use flyllm::load_balancer::strategies::{PriorityStrategy, WeightedStrategy};
+
+    let manager = LlmManager::new();
+    let tasks = vec![TaskDefinition::new("code"), TaskDefinition::new("bulk-summary")];
+    let mut calls = Vec::new();
+    for (name, priority, weight) in [("a", 3, 0.0), ("b", 1, 0.0), ("c", 2, 1.0)] {
+        let (instance, counter) = CountingInstance::named(name, tasks.clone());
+        let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+        let mut trackers = manager.trackers.lock().await;
+        let tracker = trackers.get_mut(&id).unwrap();
+        tracker.priority = Some(priority);
+        tracker.weight = weight;
+        calls.push(counter);
+    }
+    manager.set_task_strategy("code", Some(Box::new(PriorityStrategy::new()))).await;
+    manager.set_task_strategy("bulk-summary", Some(Box::new(WeightedStrategy::new()))).await;
+
+    for task in ["code", "bulk-summary"] {
+        for _ in 0..3 {
+            manager.generate(GenerationRequest::builder("Hi").task(task).build()).await.unwrap();
+        }
+    }
+    let counts: Vec<usize> = calls.iter().map(|c| c.load(Ordering::SeqCst)).collect();
+    assert_eq!(counts, vec![0, 3, 3]);
+
+    // Without a task of its own, a request uses the manager's least-recently-used strategy
+    manager.generate(GenerationRequest::builder("Hi").build()).await.unwrap();
+    assert_eq!(calls[0].load(Ordering::SeqCst), 1);
+
+    manager.set_task_strategy("code", None).await;
+    assert!(manager.task_strategies.lock().await.get("code").is_none());
+}
+
+#[tokio::test]
+async fn test_builder_task_strategy_requires_defined_task() {
+    use flyllm::load_balancer::strategies::PriorityStrategy;
+
+    let result = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat"))
+        .task_strategy("code", Box::new(PriorityStrategy::new()))
+        .build()
+        .await;
+    assert!(result.is_err());
+
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("code"))
+        .task_strategy("code", Box::new(PriorityStrategy::new()))
+        .build()
+        .await
+        .unwrap();
+    assert!(manager.task_strategies.lock().await.contains_key("code"));
+}
+
+#[tokio::test]
+async fn test_compare_queries_every_targeted_instance() {
+    let manager = LlmManager::new();
+    let task = TaskDefinition::new("summary");
+    let mut calls = Vec::new();
+    let mut ids = Vec::new();
+    for name in ["a", "b", "c"] {
+        let tasks = if name == "c" { vec![] } else { vec![task.clone()] };
+        let (instance, counter) = CountingInstance::named(name, tasks);
+        ids.push(manager.add_instance_dynamic(Arc::new(instance)).await);
+        calls.push(counter);
+    }
+
+    // Every enabled instance by default
+    let results = manager.compare(GenerationRequest::new("Hi".to_string()), None).await;
+    assert_eq!(results.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(), ids);
+    assert!(results.iter().all(|(_, result, _)| result.as_ref().unwrap().content == "response #1"));
+    assert!(calls.iter().all(|counter| counter.load(Ordering::SeqCst) == 1));
+
+    // Only instances supporting the task, or exactly the ones asked for
+    let request = GenerationRequest::builder("Hi").task("summary").build();
+    let results = manager.compare(request, None).await;
+    assert_eq!(results.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(), ids[..2]);
+
+    let results = manager.compare(GenerationRequest::new("Hi".to_string()), Some(vec![ids[2], 999])).await;
+    assert_eq!(results[0].0, ids[2]);
+    assert_eq!(results[0].1.as_ref().unwrap().content, "response #2");
+    assert!(results[1].1.is_err());
+    assert_eq!(
+        calls.iter().map(|counter| counter.load(Ordering::SeqCst)).collect::<Vec<_>>(),
+        vec![2, 2, 2]
+    );
+}
+
+#[tokio::test]
+async fn test_seeded_random_selection_is_reproducible() {
+    use flyllm::load_balancer::strategies::RandomStrategy;
+
+    // Each manager's trackers hash in a different order; selection must not depend on it
+    async fn picks() -> Vec<usize> {
+        let manager = LlmManager::new_with_strategy_and_retries(Box::new(RandomStrategy::with_seed(42)), 0);
+        for _ in 0..8 {
+            let (instance, _) = CountingInstance::new(vec![]);
+            manager.add_instance_dynamic(Arc::new(instance)).await;
+        }
+        let mut picks = Vec::new();
+        for _ in 0..20 {
+            let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+            picks.push(response.instance_id.unwrap());
+        }
+        picks
+    }
+
+    let first = picks().await;
+    for _ in 0..3 {
+        assert_eq!(picks().await, first);
+    }
+}
+
+#[tokio::test]
+async fn test_session_key_routes_to_same_instance() {
+    use flyllm::load_balancer::strategies::{RandomStrategy, StickyStrategy};
+
+    let manager = LlmManager::new_with_strategy_and_retries(Box::new(StickyStrategy::new(RandomStrategy::new())), 0);
+    for _ in 0..4 {
+        let (instance, _) = CountingInstance::new(vec![]);
+        manager.add_instance_dynamic(Arc::new(instance)).await;
+    }
+
+    let ask = |key: String| {
+        let manager = &manager;
+        async move {
+            let request = GenerationRequest::builder("Hi").session_key(key).build();
+            manager.generate(request).await.unwrap().instance_id.unwrap()
+        }
+    };
+
+    let first = ask("conversation-a".to_string()).await;
+    for _ in 0..5 {
+        assert_eq!(ask("conversation-a".to_string()).await, first);
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    for i in 0..30 {
+        seen.insert(ask(format!("conversation-{}", i)).await);
+    }
+    assert!(seen.len() > 1);
+}
+
+#[tokio::test]
+async fn test_session_moves_together_when_its_instance_is_removed() {
+    use flyllm::load_balancer::strategies::{RandomStrategy, StickyStrategy};
+
+    let manager = LlmManager::new_with_strategy_and_retries(Box::new(StickyStrategy::new(RandomStrategy::new())), 0);
+    for _ in 0..8 {
+        let (instance, _) = CountingInstance::new(vec![]);
+        manager.add_instance_dynamic(Arc::new(instance)).await;
+    }
+
+    let ask = || async {
+        let request = GenerationRequest::builder("Hi").session_key("conversation-a").build();
+        manager.generate(request).await.unwrap().instance_id.unwrap()
+    };
+
+    let pinned = ask().await;
+    assert!(manager.remove_instance(pinned).await);
+
+    // Follow-ups land on one new instance instead of a random one each time
+    let moved = ask().await;
+    assert_ne!(moved, pinned);
+    for _ in 0..5 {
+        assert_eq!(ask().await, moved);
+    }
+}
+
+#[tokio::test]
+async fn test_request_pinned_to_instance_id() {
+    let manager = LlmManager::new();
+    let mut ids = Vec::new();
+    let mut calls = Vec::new();
+    for _ in 0..3 {
+        let (instance, counter) = CountingInstance::new(vec![]);
+        ids.push(manager.add_instance_dynamic(Arc::new(instance)).await);
+        calls.push(counter);
+    }
+
+    for _ in 0..4 {
+        let request = GenerationRequest::builder("Hi").instance_id(ids[1]).build();
+        let response = manager.generate(request).await.unwrap();
+        assert_eq!(response.instance_id, Some(ids[1]));
+    }
+    assert_eq!(calls[1].load(Ordering::SeqCst), 4);
+    assert_eq!(calls[0].load(Ordering::SeqCst) + calls[2].load(Ordering::SeqCst), 0);
+
+    // A disabled pinned instance is an error rather than a silent reroute
+    manager.set_instance_enabled(ids[1], false).await;
+    let err = manager
+        .generate(GenerationRequest::builder("Hi").instance_id(ids[1]).build())
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, flyllm::LlmError::ConfigError(msg) if msg.contains("Pinned instance")), "{:?}", err);
+    assert_eq!(calls[0].load(Ordering::SeqCst) + calls[2].load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_request_pinned_to_provider_type_intersects_task() {
+    let chat = TaskDefinition::new("chat");
+    let manager = LlmManager::new();
+    let (openai_chat, openai_chat_calls) = CountingInstance::named("openai", vec![chat.clone()]);
+    let openai_chat_id = manager.add_instance_dynamic(Arc::new(openai_chat)).await;
+    let (openai_other, openai_other_calls) = CountingInstance::named("openai", vec![]);
+    manager.add_instance_dynamic(Arc::new(openai_other)).await;
+    let (anthropic_chat, anthropic_calls) = CountingInstance::named("anthropic", vec![chat]);
+    manager.add_instance_dynamic(Arc::new(anthropic_chat)).await;
+
+    for _ in 0..3 {
+        let request = GenerationRequest::builder("Hi")
+            .task("chat")
+            .provider(ProviderType::OpenAI)
+            .build();
+        let response = manager.generate(request).await.unwrap();
+        assert_eq!(response.instance_id, Some(openai_chat_id));
+    }
+    assert_eq!(openai_chat_calls.load(Ordering::SeqCst), 3);
+    assert_eq!(openai_other_calls.load(Ordering::SeqCst), 0);
+    assert_eq!(anthropic_calls.load(Ordering::SeqCst), 0);
+
+    // No Google instance serves the task
+    let request = GenerationRequest::builder("Hi").task("chat").provider(ProviderType::Google).build();
+    let err = manager.generate(request).await.unwrap_err();
+    assert!(matches!(&err, flyllm::LlmError::ConfigError(msg) if msg.contains("provider Google")), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_priority_strategy_falls_back_in_order() {
+    use flyllm::LlmError;
+    use flyllm::load_balancer::strategies::PriorityStrategy;
+
+    /// Always fails with an API error
+    struct FailingInstance {
+        calls: Arc<AtomicUsize>,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for FailingInstance {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(LlmError::ApiError("down".to_string()))
+        }
+
+        fn get_name(&self) -> &str {
+            "failing"
+        }
+
+        fn get_model(&self) -> &str {
+            "failing-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let manager = LlmManager::new_with_strategy_and_retries(Box::new(PriorityStrategy::new()), 3);
+    let (third, third_calls) = CountingInstance::new(vec![]);
+    let third_id = manager.add_instance_dynamic(Arc::new(third)).await;
+    let (second, second_calls) = CountingInstance::new(vec![]);
+    let second_id = manager.add_instance_dynamic(Arc::new(second)).await;
+    let top_calls = Arc::new(AtomicUsize::new(0));
+    let top_id = manager
+        .add_instance_dynamic(Arc::new(FailingInstance {
+            calls: top_calls.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    {
+        let mut trackers = manager.trackers.lock().await;
+        trackers.get_mut(&top_id).unwrap().priority = Some(1);
+        trackers.get_mut(&second_id).unwrap().priority = Some(2);
+        trackers.get_mut(&third_id).unwrap().priority = Some(3);
+    }
+
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert!(response.success);
+    assert_eq!(response.instance_id, Some(second_id));
+    assert_eq!(response.attempts, 2);
+    assert_eq!(top_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(third_calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_oversized_prompt_is_rejected_before_sending() {
+    let manager = LlmManager::builder().reject_oversized_prompts(true).build().await.unwrap();
+    let (instance, calls) = CountingInstance::new(vec![]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+    manager.trackers.lock().await.get_mut(&id).unwrap().context_window = Some(100);
+
+    // 400 characters is about 100 tokens, plus 50 reserved for output
+    let request = GenerationRequest::builder("a".repeat(400)).max_tokens(50).build();
+    let err = manager.generate(request).await.unwrap_err();
+    match err {
+        flyllm::LlmError::ConfigError(msg) => {
+            assert!(msg.contains("estimated 150 tokens"), "{}", msg);
+            assert!(msg.contains("context window of 100 tokens"), "{}", msg);
+        }
+        other => panic!("expected ConfigError, got {:?}", other),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    // Requests that fit go through
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert!(response.success);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_oversized_prompt_uses_instance_that_fits() {
+    let manager = LlmManager::builder().reject_oversized_prompts(true).build().await.unwrap();
+    let (small, small_calls) = CountingInstance::new(vec![]);
+    let small_id = manager.add_instance_dynamic(Arc::new(small)).await;
+    let (unknown, unknown_calls) = CountingInstance::new(vec![]);
+    let unknown_id = manager.add_instance_dynamic(Arc::new(unknown)).await;
+    manager.trackers.lock().await.get_mut(&small_id).unwrap().context_window = Some(10);
+
+    for _ in 0..3 {
+        let response = manager.generate(GenerationRequest::new("a".repeat(400))).await.unwrap();
+        assert_eq!(response.instance_id, Some(unknown_id));
+    }
+    assert_eq!(small_calls.load(Ordering::SeqCst), 0);
+    assert_eq!(unknown_calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_oversized_prompt_is_rejected_before_streaming() {
+    let manager = LlmManager::builder().reject_oversized_prompts(true).build().await.unwrap();
+    let (small, small_calls) = CountingInstance::new(vec![]);
+    let small_id = manager.add_instance_dynamic(Arc::new(small)).await;
+    manager.trackers.lock().await.get_mut(&small_id).unwrap().context_window = Some(10);
+
+    let request = GenerationRequest::new("a".repeat(400));
+    match manager.generate_stream(request).await {
+        Err(flyllm::LlmError::ConfigError(msg)) => assert!(msg.contains("context window of 10 tokens"), "{}", msg),
+        Err(e) => panic!("expected ConfigError, got {:?}", e),
+        Ok(_) => panic!("expected ConfigError, got a stream"),
+    }
+    assert_eq!(small_calls.load(Ordering::SeqCst), 0);
+
+    // Streams go to an instance the prompt fits
+    let (unknown, unknown_calls) = CountingInstance::new(vec![]);
+    manager.add_instance_dynamic(Arc::new(unknown)).await;
+    for _ in 0..3 {
+        assert!(manager.generate_stream(GenerationRequest::new("a".repeat(400))).await.is_ok());
+    }
+    assert_eq!(small_calls.load(Ordering::SeqCst), 0);
+    assert_eq!(unknown_calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_oversized_prompt_allowed_when_guard_disabled() {
+    let manager = LlmManager::new();
+    let (instance, calls) = CountingInstance::new(vec![]);
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+    manager.trackers.lock().await.get_mut(&id).unwrap().context_window = Some(10);
+
+    let response = manager.generate(GenerationRequest::new("a".repeat(400))).await.unwrap();
+    assert!(response.success);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_oversized_prompt_uses_known_context_window() {
+    let manager = LlmManager::builder().reject_oversized_prompts(true).build().await.unwrap();
+    let instance = create_instance(ProviderType::OpenAI, "key".to_string(), "gpt-4".to_string(), vec![], true, None, InstanceSettings::default()).unwrap();
+    let id = manager.add_instance_dynamic(instance).await;
+    assert_eq!(manager.get_instance_stats().await[0].context_window, Some(8_192));
+
+    // Rejected before anything is sent
+    let err = manager.generate(GenerationRequest::new("word ".repeat(10_000))).await.unwrap_err();
+    assert!(matches!(&err, flyllm::LlmError::ConfigError(msg) if msg.contains("context window of 8192 tokens")), "{:?}", err);
+
+    // A configured window takes precedence
+    manager.trackers.lock().await.get_mut(&id).unwrap().context_window = Some(100);
+    assert_eq!(manager.get_instance_stats().await[0].context_window, Some(100));
+}
+
+#[test]
+fn test_approximate_tokens() {
+    use flyllm::providers::tokens::approximate_tokens;
+
+    assert_eq!(approximate_tokens(""), 0);
+    assert_eq!(approximate_tokens("abcd"), 1);
+    assert_eq!(approximate_tokens("abcde"), 2);
+}
+
+#[tokio::test]
+async fn test_from_config_str_instance_max_retries() {
+    let config = r#"
+[[providers]]
+type = "ollama"
+model = "llama2"
+max_retries = 3
+"#;
+
+    let manager = LlmManager::from_config_str(config).await.unwrap();
+    let trackers = manager.trackers.lock().await;
+    let tracker = trackers.values().next().unwrap();
+    assert_eq!(tracker.max_retries, Some(3));
+}
+
+// ============================================================================
+// Batch Concurrency Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_batch_generate_respects_max_concurrency() {
+    use std::time::Duration;
+
+    /// Echoes the prompt after a short delay, recording the peak number of concurrent calls
+    struct SlowEchoInstance {
+        active: AtomicUsize,
+        peak: Arc<AtomicUsize>,
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for SlowEchoInstance {
+        async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(LlmResponse {
+                content: request.messages.last().unwrap().content.clone(),
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "mock"
+        }
+
+        fn get_model(&self) -> &str {
+            "mock-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let peak = Arc::new(AtomicUsize::new(0));
+    let mut manager = LlmManager::new();
+    manager.max_concurrency = Some(3);
+    manager
+        .add_instance_dynamic(Arc::new(SlowEchoInstance {
+            active: AtomicUsize::new(0),
+            peak: peak.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    let requests = (0..12)
+        .map(|i| GenerationRequest::new(format!("prompt {}", i)))
+        .collect();
+    let responses = manager.batch_generate(requests).await;
+
+    assert_eq!(peak.load(Ordering::SeqCst), 3);
+    for (i, response) in responses.iter().enumerate() {
+        assert!(response.success);
+        assert_eq!(response.content, format!("prompt {}", i));
+    }
+}
+
+#[tokio::test]
+async fn test_stream_batch_yields_responses_as_they_complete() {
+    use flyllm::MockInstance;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    let manager = LlmManager::new();
+    let slow = MockInstance::new("slow-model")
+        .with_response("slow")
+        .with_latency(Duration::from_millis(200))
+        .supports(TaskDefinition::new("slow"));
+    let fast = MockInstance::new("fast-model")
+        .with_response("fast")
+        .supports(TaskDefinition::new("fast"));
+    manager.add_instance_dynamic(Arc::new(slow)).await;
+    manager.add_instance_dynamic(Arc::new(fast)).await;
+
+    let requests = vec![
+        GenerationRequest::new("a".to_string()).task("slow"),
+        GenerationRequest::new("b".to_string()).task("fast"),
+        GenerationRequest::new("c".to_string()).task("fast"),
+    ];
+    let results: Vec<_> = manager.stream_batch(requests).collect().await;
+
+    let mut indices: Vec<usize> = results.iter().map(|(index, _)| *index).collect();
+    assert_eq!(results.last().unwrap().0, 0);
+    assert_eq!(results.last().unwrap().1.content, "slow");
+    indices.sort();
+    assert_eq!(indices, [0, 1, 2]);
+    for (index, response) in &results {
+        assert!(response.success);
+        assert_eq!(response.content, if *index == 0 { "slow" } else { "fast" });
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_debug_writes_keep_every_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut manager = LlmManager::new();
+    manager.debug_folder = Some(dir.path().to_path_buf());
+    let (instance, _) = CountingInstance::new(vec![]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    // Spawned tasks run on separate worker threads, unlike batch_generate's futures
+    let manager = Arc::new(manager);
+    let handles: Vec<_> = (0..40)
+        .map(|i| {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                manager.generate(GenerationRequest::new(format!("prompt {}", i))).await
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    let content = std::fs::read_to_string(debug_instance_dir(dir.path()).join("debug.json")).unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(entries.len(), 40);
+}
+
+/// Find the instance folder of a manager's debug output
+fn debug_instance_dir(debug_folder: &std::path::Path) -> std::path::PathBuf {
+    // debug_folder/<timestamp>/<instance>/
+    let timestamp_dir = std::fs::read_dir(debug_folder).unwrap().next().unwrap().unwrap().path();
+    std::fs::read_dir(timestamp_dir).unwrap().next().unwrap().unwrap().path()
+}
+
+#[tokio::test]
+async fn test_debug_json_lines_appends_one_line_per_request() {
+    use flyllm::DebugFormat;
+
+    let dir = tempfile::tempdir().unwrap();
+    let manager = LlmManager::builder()
+        .debug_folder(dir.path())
+        .debug_format(DebugFormat::JsonLines)
+        .build()
+        .await
+        .unwrap();
+    let (instance, _) = CountingInstance::new(vec![]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    for i in 0..5 {
+        manager.generate(GenerationRequest::new(format!("prompt {}", i))).await.unwrap();
+    }
+
+    let content = std::fs::read_to_string(debug_instance_dir(dir.path()).join("debug.jsonl")).unwrap();
+    let lines: Vec<serde_json::Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[4]["input"]["prompt"], "prompt 4");
+}
+
+#[tokio::test]
+async fn test_request_id_appears_in_response_and_debug_entry() {
+    use flyllm::DebugFormat;
+
+    let dir = tempfile::tempdir().unwrap();
+    let manager = LlmManager::builder()
+        .debug_folder(dir.path())
+        .debug_format(DebugFormat::JsonLines)
+        .build()
+        .await
+        .unwrap();
+    let (instance, _) = CountingInstance::new(vec![]);
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    let first = manager.generate(GenerationRequest::new("first".to_string())).await.unwrap();
+    let batch = manager
+        .batch_generate(vec![GenerationRequest::new("second".to_string()), GenerationRequest::new("third".to_string())])
+        .await;
+
+    let mut ids = vec![first.request_id, batch[0].request_id, batch[1].request_id];
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 3);
+    assert!(ids.iter().all(|id| *id > 0));
+
+    let content = std::fs::read_to_string(debug_instance_dir(dir.path()).join("debug.jsonl")).unwrap();
+    let entries: Vec<serde_json::Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let first_entry = entries.iter().find(|entry| entry["input"]["prompt"] == "first").unwrap();
+    assert_eq!(first_entry["metadata"]["request_id"], first.request_id);
+    let third_entry = entries.iter().find(|entry| entry["input"]["prompt"] == "third").unwrap();
+    assert_eq!(third_entry["metadata"]["request_id"], batch[1].request_id);
+}
+
+#[tokio::test]
+async fn test_debug_files_rotate_when_full() {
+    use flyllm::DebugFormat;
+
+    for format in [DebugFormat::JsonArray, DebugFormat::JsonLines] {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = LlmManager::builder()
+            .debug_folder(dir.path())
+            .debug_format(format)
+            .max_debug_file_bytes(1)
+            .build()
+            .await
+            .unwrap();
+        let (instance, _) = CountingInstance::new(vec![]);
+        manager.add_instance_dynamic(Arc::new(instance)).await;
+
+        for i in 0..3 {
+            manager.generate(GenerationRequest::new(format!("prompt {}", i))).await.unwrap();
+        }
+
+        // Every entry overflows the 1-byte limit, so each lands in its own file
+        let instance_dir = debug_instance_dir(dir.path());
+        let ext = format.extension();
+        let mut names: Vec<String> = std::fs::read_dir(&instance_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![format!("debug.1.{}", ext), format!("debug.2.{}", ext), format!("debug.{}", ext)]
+        );
+        let last = std::fs::read_to_string(instance_dir.join(format!("debug.2.{}", ext))).unwrap();
+        assert!(last.contains("prompt 2"));
+    }
+}
+
+#[tokio::test]
+async fn test_debug_output_redacts_secrets() {
+    for redact in [true, false] {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = LlmManager::builder()
+            .debug_folder(dir.path())
+            .redact_secrets(redact)
+            .build()
+            .await
+            .unwrap();
+        let (instance, _) = CountingInstance::new(vec![]);
+        manager.add_instance_dynamic(Arc::new(instance)).await;
+
+        let request = GenerationRequest::builder("my key is sk-abcdefghijklmnopqrstuvwx")
+            .param("api_key", "not-a-pattern-but-a-key")
+            .param("headers", serde_json::json!({ "Authorization": "Bearer abc.def" }))
+            .build();
+        manager.generate(request).await.unwrap();
+
+        let content = std::fs::read_to_string(debug_instance_dir(dir.path()).join("debug.json")).unwrap();
+        for secret in ["sk-abcdefghijklmnopqrstuvwx", "not-a-pattern-but-a-key", "abc.def"] {
+            assert_eq!(content.contains(secret), !redact, "{} (redact = {})", secret, redact);
+        }
+        assert_eq!(content.contains("[REDACTED]"), redact);
+    }
+}
+
+#[test]
+fn test_redact_text_patterns() {
+    use flyllm::load_balancer::utils::redact_text;
+
+    let text = "Bearer abc123 failed at https://host/v1?key=AIzaSecretValue&alt=sse with gsk_0123456789abcdefXYZ";
+    let redacted = redact_text(text);
+    assert_eq!(
+        redacted,
+        "Bearer [REDACTED] failed at https://host/v1?key=[REDACTED]&alt=sse with [REDACTED]"
+    );
+    assert_eq!(redact_text("max_tokens exceeded"), "max_tokens exceeded");
+}
+
+#[tokio::test]
+async fn test_builder_max_concurrency() {
+    let manager = LlmManager::builder()
+        .max_concurrency(4)
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(manager.max_concurrency, Some(4));
+}
+
+#[tokio::test]
+async fn test_generate_single_request() {
+    let (instance, calls) = CountingInstance::new(Vec::new());
+    let manager = LlmManager::new();
+    manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    let response = manager
+        .generate(GenerationRequest::new("Hi".to_string()))
+        .await
+        .unwrap();
+    assert!(response.success);
+    assert_eq!(response.content, "response #1");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_generate_returns_err_without_providers() {
+    let manager = LlmManager::new();
+    let result = manager
+        .generate(GenerationRequest::new("Hi".to_string()))
+        .await;
+    assert!(matches!(result, Err(flyllm::LlmError::ConfigError(_))));
+}
+
+#[tokio::test]
+async fn test_response_reports_serving_instance() {
+    let (instance, _calls) = CountingInstance::new(Vec::new());
+    let manager = LlmManager::new();
+    let id = manager.add_instance_dynamic(Arc::new(instance)).await;
+
+    let response = manager
+        .generate(GenerationRequest::new("Hi".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.provider.as_deref(), Some("mock"));
+    assert_eq!(response.model.as_deref(), Some("mock-model"));
+    assert_eq!(response.instance_id, Some(id));
+    assert_eq!(response.attempts, 1);
+    assert_eq!(response.usage.unwrap().total_tokens, 15);
+
+    let empty = LlmManager::new();
+    let responses = empty
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string())])
+        .await;
+    assert!(!responses[0].success);
+    assert!(responses[0].instance_id.is_none());
+    assert!(responses[0].attempts >= 1);
+}
+
+#[tokio::test]
+async fn test_response_carries_citations() {
+    /// Answers with a fixed list of sources
+    struct CitingInstance {
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for CitingInstance {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            Ok(LlmResponse {
+                content: "answer".to_string(),
+                citations: vec!["https://example.com/a".to_string(), "https://example.com/b".to_string()],
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "citing"
+        }
+
+        fn get_model(&self) -> &str {
+            "citing-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let manager = LlmManager::new();
+    manager.add_instance_dynamic(Arc::new(CitingInstance { tasks: HashMap::new() })).await;
+
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(response.citations, vec!["https://example.com/a", "https://example.com/b"]);
+}
+
+#[tokio::test]
+async fn test_response_carries_every_choice() {
+    /// Returns as many completions as the request's `n` asks for
+    struct MultiChoiceInstance {
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for MultiChoiceInstance {
+        async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+            let choices: Vec<String> = match request.n {
+                Some(n) if n > 1 => (1..=n).map(|i| format!("choice {}", i)).collect(),
+                _ => Vec::new(),
+            };
+            Ok(LlmResponse {
+                content: "choice 1".to_string(),
+                choices,
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "multi"
+        }
+
+        fn get_model(&self) -> &str {
+            "multi-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let manager = LlmManager::new();
+    manager.add_instance_dynamic(Arc::new(MultiChoiceInstance { tasks: HashMap::new() })).await;
+
+    let response = manager.generate(GenerationRequest::new("Hi".to_string()).choices(3)).await.unwrap();
+    assert_eq!(response.content, "choice 1");
+    assert_eq!(response.choices, vec!["choice 1", "choice 2", "choice 3"]);
+
+    // A single completion still shows up in `choices`
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(response.choices, vec!["choice 1"]);
+}
+
+// ============================================================================
+// Cancellation Tests
+// ============================================================================
+
+/// Streams numbered chunks forever and blocks plain generation until it is dropped
+struct EndlessInstance {
+    tasks: HashMap<String, TaskDefinition>,
+}
+
+#[async_trait]
+impl LlmInstance for EndlessInstance {
+    async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+        futures::future::pending().await
+    }
+
+    async fn generate_stream(&self, _request: &LlmRequest) -> LlmResult<flyllm::LlmStream> {
+        use flyllm::providers::StreamChunk;
+        use std::time::Duration;
+
+        let chunks = futures::stream::unfold(0usize, |n| async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            Some((Ok(StreamChunk::content(n.to_string())), n + 1))
+        });
+        Ok(Box::pin(chunks))
+    }
+
+    fn get_name(&self) -> &str {
+        "mock"
+    }
+
+    fn get_model(&self) -> &str {
+        "mock-model"
+    }
+
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        &self.tasks
+    }
+
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_stream_ends_when_cancelled() {
+    use flyllm::CancellationToken;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    let manager = LlmManager::new();
+    manager
+        .add_instance_dynamic(Arc::new(EndlessInstance { tasks: HashMap::new() }))
+        .await;
+
+    let token = CancellationToken::new();
+    let mut stream = manager
+        .generate_stream_with_cancel(GenerationRequest::new("Hi".to_string()), token.clone())
+        .await
+        .unwrap();
+
+    for expected in 0..3 {
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert_eq!(chunk.content, expected.to_string());
+    }
+
+    token.cancel();
+    let next = tokio::time::timeout(Duration::from_secs(1), stream.next())
+        .await
+        .expect("stream did not end after cancellation");
+    assert!(next.is_none());
+    assert!(stream.next().await.is_none());
+
+    // A token that is already cancelled refuses to start the stream
+    let result = manager
+        .generate_stream_with_cancel(GenerationRequest::new("Hi".to_string()), token)
+        .await;
+    assert!(matches!(result, Err(flyllm::LlmError::Cancelled)));
+}
+
+#[tokio::test]
+async fn test_batch_cancel_drops_inflight_requests() {
+    use flyllm::CancellationToken;
+    use std::time::Duration;
+
+    let manager = Arc::new(LlmManager::new());
+    let id = manager
+        .add_instance_dynamic(Arc::new(EndlessInstance { tasks: HashMap::new() }))
+        .await;
+
+    let token = CancellationToken::new();
+    let batch = {
+        let manager = manager.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            let requests = (0..3)
+                .map(|i| GenerationRequest::new(format!("prompt {}", i)))
+                .collect();
+            manager.batch_generate_with_cancel(requests, token).await
+        })
+    };
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(manager.get_inflight_counts().await[&id], 3);
+
+    token.cancel();
+    let responses = tokio::time::timeout(Duration::from_secs(1), batch)
+        .await
+        .expect("batch did not finish after cancellation")
+        .unwrap();
+
+    assert_eq!(responses.len(), 3);
+    for response in &responses {
+        assert!(!response.success);
+        assert_eq!(response.error.as_deref(), Some("Request cancelled"));
+    }
+    assert_eq!(manager.get_inflight_counts().await[&id], 0);
+}
+
+// ============================================================================
+// Mock Instance Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_mock_failure_falls_back_to_next_instance() {
+    use flyllm::load_balancer::strategies::PriorityStrategy;
+    use flyllm::{LlmError, MockInstance};
+
+    let manager = LlmManager::new_with_strategy_and_retries(Box::new(PriorityStrategy::new()), 3);
+    let primary = Arc::new(
+        MockInstance::new("primary-model")
+            .with_response("primary")
+            .with_errors(vec![LlmError::ApiError("overloaded".to_string())]),
+    );
+    let fallback = Arc::new(MockInstance::new("fallback-model").with_response("fallback"));
+    let primary_id = manager.add_instance_dynamic(primary.clone()).await;
+    let fallback_id = manager.add_instance_dynamic(fallback.clone()).await;
+    {
+        let mut trackers = manager.trackers.lock().await;
+        trackers.get_mut(&primary_id).unwrap().priority = Some(1);
+        trackers.get_mut(&fallback_id).unwrap().priority = Some(2);
+    }
+
+    let response = manager.generate(GenerationRequest::builder("Hi").build()).await.unwrap();
+    assert_eq!(response.content, "fallback");
+    assert_eq!(response.instance_id, Some(fallback_id));
+    assert_eq!(response.model.as_deref(), Some("fallback-model"));
+    assert_eq!(response.attempts, 2);
+    assert_eq!(primary.call_count(), 1);
+
+    // Its queued error is spent, so the primary serves the next request
+    let response = manager.generate(GenerationRequest::builder("Hi").build()).await.unwrap();
+    assert_eq!(response.content, "primary");
+    assert_eq!(fallback.call_count(), 1);
+}
+
+#[tokio::test]
+async fn test_unknown_params_rejected_in_strict_mode() {
+    use flyllm::MockInstance;
+
+    let mut manager = LlmManager::builder().strict_params(true).max_retries(0).build().await.unwrap();
+    let openai = Arc::new(MockInstance::new("gpt-4o").with_name("openai"));
+    manager.add_instance_dynamic(openai.clone()).await;
+
+    let request = GenerationRequest::builder("Hi")
+        .param("temprature", 0.2)
+        .param("max_token", 10)
+        .param("top_p", 0.9)
+        .build();
+    let err = manager.generate(request).await.unwrap_err();
+    assert!(
+        matches!(&err, flyllm::LlmError::ConfigError(msg) if msg.contains("max_token, temprature") && !msg.contains("top_p")),
+        "{:?}",
+        err
+    );
+    assert_eq!(openai.call_count(), 0);
+
+    // Without strict mode the unknown keys only produce a warning
+    manager.strict_params = false;
+    let request = GenerationRequest::builder("Hi").param("temprature", 0.2).build();
+    assert!(manager.generate(request).await.is_ok());
+    assert_eq!(openai.call_count(), 1);
+}
+
+#[tokio::test]
+async fn test_sampling_params_rejected_for_providers_that_drop_them() {
+    use flyllm::MockInstance;
+
+    let manager = LlmManager::builder().strict_params(true).max_retries(0).build().await.unwrap();
+    let anthropic = Arc::new(MockInstance::new("claude-3-5-haiku").with_name("anthropic"));
+    manager.add_instance_dynamic(anthropic.clone()).await;
+
+    // Anthropic's request body has no `top_p` or `stop`, so strict mode refuses them
+    let request = GenerationRequest::builder("Hi").param("top_p", 0.9).param("stop", "END").build();
+    let err = manager.generate(request).await.unwrap_err();
+    assert!(
+        matches!(&err, flyllm::LlmError::ConfigError(msg) if msg.contains("stop, top_p")),
+        "{:?}",
+        err
+    );
+    assert_eq!(anthropic.call_count(), 0);
+}
+
+#[tokio::test]
+async fn test_strict_params_fall_back_to_provider_that_accepts_them() {
+    use flyllm::load_balancer::strategies::PriorityStrategy;
+    use flyllm::MockInstance;
+
+    let mut manager = LlmManager::new_with_strategy_and_retries(Box::new(PriorityStrategy::new()), 3);
+    manager.strict_params = true;
+    let openai = Arc::new(MockInstance::new("gpt-4o").with_name("openai"));
+    let ollama = Arc::new(MockInstance::new("llama3").with_name("ollama").with_response("local"));
+    let openai_id = manager.add_instance_dynamic(openai.clone()).await;
+    let ollama_id = manager.add_instance_dynamic(ollama.clone()).await;
+    {
+        let mut trackers = manager.trackers.lock().await;
+        trackers.get_mut(&openai_id).unwrap().priority = Some(1);
+        trackers.get_mut(&ollama_id).unwrap().priority = Some(2);
+    }
+
+    let request = GenerationRequest::builder("Hi").param("num_ctx", 8192).build();
+    let response = manager.generate(request).await.unwrap();
+    assert_eq!(response.content, "local");
+    assert_eq!(openai.call_count(), 0);
+}
+
+#[tokio::test]
+async fn test_default_params_are_overridden_by_task_then_request() {
+    use serde_json::json;
+
+    /// Echoes the temperature and max_tokens it was sent
+    struct EchoParamsInstance {
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for EchoParamsInstance {
+        async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+            Ok(LlmResponse {
+                content: format!("{:?} {:?}", request.temperature, request.max_tokens),
+                ..Default::default()
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "echo"
+        }
+
+        fn get_model(&self) -> &str {
+            "echo-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let task = TaskDefinition::new("summary").with_temperature(0.5);
+    let manager = LlmManager::builder()
+        .default_params(HashMap::from([
+            ("temperature".to_string(), json!(1.0)),
+            ("max_tokens".to_string(), json!(300)),
+        ]))
+        .build()
+        .await
+        .unwrap();
+    let tasks = HashMap::from([(task.name.clone(), task)]);
+    manager.add_instance_dynamic(Arc::new(EchoParamsInstance { tasks })).await;
+
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(response.content, "Some(1.0) Some(300)");
+
+    let response = manager.generate(GenerationRequest::builder("Hi").task("summary").build()).await.unwrap();
+    assert_eq!(response.content, "Some(0.5) Some(300)");
+
+    let request = GenerationRequest::builder("Hi").task("summary").param("temperature", 0.0).max_tokens(50).build();
+    let response = manager.generate(request).await.unwrap();
+    assert_eq!(response.content, "Some(0.0) Some(50)");
+}
+
+#[tokio::test]
+async fn test_generate_sequentially_with_mock_errors() {
+    use flyllm::{LlmError, MockInstance};
+
+    let manager = LlmManager::new_with_strategy_and_retries(
+        Box::new(flyllm::load_balancer::strategies::LeastRecentlyUsedStrategy::new()),
+        1,
+    );
+    let mock = Arc::new(
+        MockInstance::new("mock-model")
+            .with_response("done")
+            .with_latency(std::time::Duration::from_millis(5))
+            .with_errors(vec![LlmError::ApiError("boom".to_string())]),
+    );
+    manager.add_instance_dynamic(mock.clone()).await;
+
+    let responses = manager
+        .generate_sequentially(vec![
+            GenerationRequest::builder("first").build(),
+            GenerationRequest::builder("second").build(),
+        ])
+        .await;
+    assert_eq!(responses.len(), 2);
+    assert!(!responses[0].success);
+    assert!(responses[0].error.is_some());
+    assert!(responses[1].success);
+    assert_eq!(responses[1].content, "done");
+    assert_eq!(mock.call_count(), 2);
+}
+
+// ============================================================================
+// Streaming Usage Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_stream_records_usage_from_final_chunk() {
+    use flyllm::MockInstance;
+    use futures::StreamExt;
+
+    let manager = LlmManager::new();
+    let usage = TokenUsage {
+        prompt_tokens: 4,
+        completion_tokens: 6,
+        total_tokens: 10,
+        ..Default::default()
+    };
+    let id = manager
+        .add_instance_dynamic(Arc::new(MockInstance::new("mock-model").with_usage(usage.clone())))
+        .await;
+
+    let mut stream = manager.generate_stream(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    let chunk = stream.next().await.unwrap().unwrap();
+    assert!(chunk.is_final);
+
+    // Recorded by the time the final chunk is handed out
+    assert_eq!(manager.get_instance_usage(id).await, Some(usage));
+    let stats = manager.get_instance_stats().await;
+    assert_eq!(stats[0].request_count, 1);
+    assert_eq!(stats[0].success_count, 1);
+    assert_eq!(stats[0].in_flight, 0);
+    assert!(stream.next().await.is_none());
+    assert_eq!(manager.get_instance_stats().await[0].request_count, 1);
+}
+
+#[tokio::test]
+async fn test_stream_adds_cost_from_final_chunk() {
+    use flyllm::{MockInstance, Pricing};
+    use futures::StreamExt;
+
+    let mut manager = LlmManager::new();
+    manager.pricing.insert(
+        ("mock".to_string(), "mock-model".to_string()),
+        Pricing::new(1.0, 2.0),
+    );
+    let usage = TokenUsage {
+        prompt_tokens: 10,
+        completion_tokens: 5,
+        total_tokens: 15,
+        ..Default::default()
+    };
+    let id = manager
+        .add_instance_dynamic(Arc::new(MockInstance::new("mock-model").with_usage(usage)))
+        .await;
+
+    let mut stream = manager.generate_stream(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    while stream.next().await.is_some() {}
+
+    // 10 prompt tokens at $1/1K + 5 completion tokens at $2/1K
+    assert!((manager.get_total_cost().await - 0.02).abs() < 1e-9);
+    assert!((manager.get_instance_cost(id).await.unwrap() - 0.02).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_stream_refuses_features_it_cannot_carry() {
+    use flyllm::{MockInstance, ToolDefinition};
+    use serde_json::json;
+
+    let manager = LlmManager::new();
+    let mock = Arc::new(MockInstance::new("mock-model"));
+    manager.add_instance_dynamic(mock.clone()).await;
+
+    let tool = ToolDefinition::new("get_weather", "Get the weather", json!({"type": "object"}));
+    let requests = [
+        GenerationRequest::new("Hi".to_string()).tools(vec![tool]),
+        GenerationRequest::builder("Hi").param("n", 2).build(),
+        GenerationRequest::builder("Hi").param("logprobs", true).build(),
+    ];
+    for request in requests {
+        match manager.generate_stream(request).await {
+            Err(flyllm::LlmError::ConfigError(msg)) => assert!(msg.contains("can't be streamed"), "{}", msg),
+            Err(e) => panic!("expected ConfigError, got {:?}", e),
+            Ok(_) => panic!("expected ConfigError, got a stream"),
+        }
+    }
+    assert_eq!(mock.call_count(), 0);
+
+    // A single choice without log probabilities streams as usual
+    let request = GenerationRequest::builder("Hi").param("n", 1).param("logprobs", false).build();
+    assert!(manager.generate_stream(request).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_stream_dropped_early_still_counts_request() {
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    let manager = LlmManager::new();
+    let id = manager
+        .add_instance_dynamic(Arc::new(EndlessInstance { tasks: HashMap::new() }))
+        .await;
+
+    let mut stream = manager.generate_stream(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(manager.get_inflight_counts().await.get(&id), Some(&1));
+    drop(stream);
+
+    // The drop records in a spawned task
+    let mut stats = manager.get_instance_stats().await;
+    for _ in 0..50 {
+        if stats[0].request_count == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        stats = manager.get_instance_stats().await;
+    }
+    assert_eq!(stats[0].request_count, 1);
+    assert_eq!(stats[0].error_count, 0);
+    assert!(stats[0].last_latency_ms.is_some());
+    assert_eq!(stats[0].in_flight, 0);
+    assert_eq!(manager.get_instance_usage(id).await.map(|u| u.total_tokens), Some(0));
+}
+
+#[tokio::test]
+async fn test_stop_sequence_ends_endless_stream() {
+    use futures::StreamExt;
+
+    let manager = LlmManager::new();
+    let id = manager
+        .add_instance_dynamic(Arc::new(EndlessInstance { tasks: HashMap::new() }))
+        .await;
+
+    // The provider counts forever; the stop sequence ends the stream without waiting for it
+    let request = GenerationRequest::builder("Count").param("stop", "5").build();
+    let stream = manager.generate_stream(request).await.unwrap();
+    let chunks: Vec<_> = stream.map(|chunk| chunk.unwrap()).collect().await;
+    assert_eq!(chunks.iter().map(|chunk| chunk.content.as_str()).collect::<String>(), "01234");
+    assert_eq!(chunks.last().unwrap().finish_reason.as_deref(), Some("stop"));
+
+    // Recorded with estimated usage
+    assert_eq!(manager.get_instance_stats().await[0].request_count, 1);
+    let usage = manager.get_instance_usage(id).await.unwrap();
+    assert!(usage.prompt_tokens > 0 && usage.completion_tokens > 0);
+}
+
+// ============================================================================
+// Instance Stats Tests
+// ============================================================================
+
+/// Instance that fails while `failing` is set
+struct FlakyInstance {
+    failing: Arc<std::sync::atomic::AtomicBool>,
+    tasks: HashMap<String, TaskDefinition>,
+}
+
+#[async_trait]
+impl LlmInstance for FlakyInstance {
+    async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(flyllm::LlmError::ApiError("boom".to_string()));
+        }
+        Ok(LlmResponse {
+            content: "ok".to_string(),
+            model: "flaky-model".to_string(),
+            usage: Some(TokenUsage {
+                prompt_tokens: 3,
+                completion_tokens: 2,
+                total_tokens: 5,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn get_name(&self) -> &str {
+        "flaky"
+    }
+
+    fn get_model(&self) -> &str {
+        "flaky-model"
+    }
+
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        &self.tasks
+    }
+
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_instance_stats_track_successes_and_failures() {
+    let mut manager = LlmManager::new();
+    manager.max_retries = 1;
+    let failing = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let id = manager
+        .add_instance_dynamic(Arc::new(FlakyInstance {
+            failing: failing.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    let stats = manager.get_instance_stats().await;
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].request_count, 0);
+    assert_eq!(stats[0].last_latency_ms, None);
+
+    manager.generate(GenerationRequest::builder("Hi").build()).await.unwrap();
+    failing.store(true, Ordering::SeqCst);
+    assert!(manager.generate(GenerationRequest::builder("Hi").build()).await.is_err());
+
+    let stats = manager.get_instance_stats().await;
+    let instance = &stats[0];
+    assert_eq!(instance.instance_id, id);
+    assert_eq!(instance.provider, "flaky");
+    assert_eq!(instance.model, "flaky-model");
+    assert_eq!(instance.request_count, 2);
+    assert_eq!(instance.success_count, 1);
+    assert_eq!(instance.error_count, 1);
+    assert!(instance.last_latency_ms.is_some());
+    assert!(instance.avg_latency_ms >= 0.0);
+    assert_eq!(instance.usage.total_tokens, 5);
+
+    let json = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json[0]["success_count"], 1);
+}
+
+#[tokio::test]
+async fn test_intermittent_failures_disable_instance_until_reenabled() {
+    let mut manager = LlmManager::new();
+    manager.max_retries = 1;
+    manager.max_failures_before_disable = Some(3);
+    manager.failure_window = 5;
+    let failing = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let id = manager
+        .add_instance_dynamic(Arc::new(FlakyInstance {
+            failing: failing.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    // Failures are never consecutive, but three of the last five requests fail
+    for fail in [true, false, true, false] {
+        failing.store(fail, Ordering::SeqCst);
+        let _ = manager.generate(GenerationRequest::builder("Hi").build()).await;
+    }
+    assert!(manager.trackers.lock().await[&id].is_enabled());
+
+    failing.store(true, Ordering::SeqCst);
+    assert!(manager.generate(GenerationRequest::builder("Hi").build()).await.is_err());
+    assert!(!manager.trackers.lock().await[&id].is_enabled());
+
+    failing.store(false, Ordering::SeqCst);
+    assert!(manager.generate(GenerationRequest::builder("Hi").build()).await.is_err());
+
+    assert!(manager.reenable_instance(id).await);
+    assert!(!manager.reenable_instance(42).await);
+    assert_eq!(manager.trackers.lock().await[&id].recent_failures(), 0);
+    let response = manager.generate(GenerationRequest::builder("Hi").build()).await.unwrap();
+    assert_eq!(response.content, "ok");
+}
+
+// ============================================================================
+// Health Check Tests
+// ============================================================================
+
+/// Instance whose health can be toggled from the test
+struct ToggleInstance {
+    up: Arc<std::sync::atomic::AtomicBool>,
+    tasks: HashMap<String, TaskDefinition>,
+}
+
+#[async_trait]
+impl LlmInstance for ToggleInstance {
+    async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+        Ok(LlmResponse {
+            content: "ok".to_string(),
+            ..Default::default()
+        })
+    }
+
+    async fn health_check(&self) -> LlmResult<()> {
+        if self.up.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(flyllm::LlmError::ApiError("down".to_string()))
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        "toggle"
+    }
+
+    fn get_model(&self) -> &str {
+        "toggle-model"
+    }
+
+    fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+        &self.tasks
+    }
+
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_check_health_marks_and_recovers_instances() {
+    let manager = LlmManager::new();
+    let up = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let id = manager
+        .add_instance_dynamic(Arc::new(ToggleInstance {
+            up: up.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    let results = manager.check_all_health().await;
+    assert_eq!(results.len(), 1);
+    assert!(results[&id].is_err());
+    assert!(!manager.trackers.lock().await[&id].healthy);
+    let result = manager.generate(GenerationRequest::new("Hi".to_string())).await;
+    assert!(result.is_err(), "unhealthy instances should not be selected");
+
+    up.store(true, Ordering::SeqCst);
+    assert!(manager.check_all_health().await[&id].is_ok());
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(response.content, "ok");
+}
+
+#[tokio::test]
+async fn test_warm_up_reports_reachable_and_unauthorized_instances() {
+    /// Instance whose credentials the provider rejects
+    struct RejectedKeyInstance {
+        tasks: HashMap<String, TaskDefinition>,
+    }
+
+    #[async_trait]
+    impl LlmInstance for RejectedKeyInstance {
+        async fn generate(&self, _request: &LlmRequest) -> LlmResult<LlmResponse> {
+            unreachable!("warm-up only probes health")
+        }
+
+        async fn health_check(&self) -> LlmResult<()> {
+            Err(flyllm::LlmError::Api {
+                status: 401,
+                code: Some("invalid_api_key".to_string()),
+                message: "Incorrect API key provided".to_string(),
+                provider: "rejected".to_string(),
+            })
+        }
+
+        fn get_name(&self) -> &str {
+            "rejected"
+        }
+
+        fn get_model(&self) -> &str {
+            "rejected-model"
+        }
+
+        fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+            &self.tasks
+        }
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    let manager = LlmManager::new();
+    let ok_id = manager
+        .add_instance_dynamic(Arc::new(ToggleInstance {
+            up: Arc::new(std::sync::atomic::AtomicBool::new(true)),
+            tasks: HashMap::new(),
+        }))
+        .await;
+    let rejected_id = manager
+        .add_instance_dynamic(Arc::new(RejectedKeyInstance { tasks: HashMap::new() }))
+        .await;
+    let (disabled, _) = CountingInstance::new(vec![]);
+    let disabled_id = manager.add_instance_dynamic(Arc::new(disabled)).await;
+    manager.set_instance_enabled(disabled_id, false).await;
+
+    let results = manager.warm_up().await;
+    let ids: Vec<usize> = results.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, vec![ok_id, rejected_id]);
+    assert!(results[0].1.is_ok());
+    assert!(matches!(results[1].1, Err(flyllm::LlmError::Api { status: 401, .. })));
+    assert!(!manager.trackers.lock().await[&rejected_id].healthy);
+}
+
+#[tokio::test]
+async fn test_background_health_checks() {
+    let manager = LlmManager::builder()
+        .enable_health_checks(std::time::Duration::from_millis(20))
+        .build()
+        .await
+        .unwrap();
+    let up = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let id = manager
+        .add_instance_dynamic(Arc::new(ToggleInstance {
+            up: up.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(!manager.trackers.lock().await[&id].healthy);
+
+    up.store(true, Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(manager.trackers.lock().await[&id].healthy);
+}
+
+#[tokio::test]
+async fn test_shutdown_stops_background_health_checks() {
+    let manager = LlmManager::builder()
+        .enable_health_checks(std::time::Duration::from_millis(20))
+        .build()
+        .await
+        .unwrap();
+    let up = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let id = manager
+        .add_instance_dynamic(Arc::new(ToggleInstance {
+            up: up.clone(),
+            tasks: HashMap::new(),
+        }))
+        .await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(!manager.trackers.lock().await[&id].healthy);
+
+    manager.shutdown().await;
+    assert!(manager.is_shut_down());
+
+    // A running probe would mark the instance healthy again
+    up.store(true, Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(!manager.trackers.lock().await[&id].healthy);
+
+    // Requests don't depend on the background tasks
+    assert!(manager.check_all_health().await[&id].is_ok());
+    let response = manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap();
+    assert_eq!(response.content, "ok");
+}
@@ -43,6 +43,26 @@ mod metrics_tests {
         assert_eq!(provider_label(ProviderType::Groq), "groq");
     }
 
+    #[test]
+    fn test_provider_label_xai() {
+        assert_eq!(provider_label(ProviderType::Xai), "xai");
+    }
+
+    #[test]
+    fn test_provider_label_fireworks() {
+        assert_eq!(provider_label(ProviderType::Fireworks), "fireworks");
+    }
+
+    #[test]
+    fn test_provider_label_ai21() {
+        assert_eq!(provider_label(ProviderType::Ai21), "ai21");
+    }
+
+    #[test]
+    fn test_provider_label_replicate() {
+        assert_eq!(provider_label(ProviderType::Replicate), "replicate");
+    }
+
     #[test]
     fn test_provider_label_cohere() {
         assert_eq!(provider_label(ProviderType::Cohere), "cohere");
@@ -66,10 +86,16 @@ mod metrics_tests {
 
     #[test]
     fn test_error_type_label_rate_limit() {
-        let error = LlmError::RateLimit("test".to_string());
+        let error = LlmError::RateLimit { message: "test".to_string(), retry_after: None };
         assert_eq!(error_type_label(&error), "rate_limit");
     }
 
+    #[test]
+    fn test_error_type_label_model_loading() {
+        let error = LlmError::ModelLoading { message: "test".to_string(), retry_after: None };
+        assert_eq!(error_type_label(&error), "model_loading");
+    }
+
     #[test]
     fn test_error_type_label_parse_error() {
         let error = LlmError::ParseError("test".to_string());
@@ -93,9 +119,127 @@ mod metrics_tests {
         assert_eq!(keys::PROVIDER, "provider");
         assert_eq!(keys::MODEL, "model");
         assert_eq!(keys::TASK, "task");
+        assert_eq!(keys::INSTANCE_ID, "instance_id");
         assert_eq!(keys::ERROR_TYPE, "error_type");
     }
 
+    #[test]
+    fn test_request_labels_default_task() {
+        let labels = flyllm::metrics::labels::request_labels("openai", "gpt-4o", None, 3);
+        let pairs: Vec<(&str, &str)> = labels.iter().map(|l| (l.key(), l.value())).collect();
+        assert_eq!(
+            pairs,
+            vec![("provider", "openai"), ("model", "gpt-4o"), ("task", "none"), ("instance_id", "3")]
+        );
+    }
+
+    #[test]
+    fn test_request_metrics_carry_task_label() {
+        use async_trait::async_trait;
+        use flyllm::providers::TokenUsage;
+        use flyllm::{GenerationRequest, LlmInstance, LlmManager, LlmRequest, LlmResponse, LlmResult, TaskDefinition};
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        /// Answers every request locally, failing when the prompt says so
+        struct LocalInstance {
+            tasks: HashMap<String, TaskDefinition>,
+        }
+
+        #[async_trait]
+        impl LlmInstance for LocalInstance {
+            async fn generate(&self, request: &LlmRequest) -> LlmResult<LlmResponse> {
+                if request.messages.iter().any(|m| m.content == "fail") {
+                    return Err(LlmError::ApiError("failed".to_string()));
+                }
+                Ok(LlmResponse {
+                    content: "ok".to_string(),
+                    model: "local-model".to_string(),
+                    usage: Some(TokenUsage {
+                        prompt_tokens: 3,
+                        completion_tokens: 2,
+                        total_tokens: 5,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+            }
+
+            fn get_name(&self) -> &str {
+                "local"
+            }
+
+            fn get_model(&self) -> &str {
+                "local-model"
+            }
+
+            fn get_supported_tasks(&self) -> &HashMap<String, TaskDefinition> {
+                &self.tasks
+            }
+
+            fn is_enabled(&self) -> bool {
+                true
+            }
+        }
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        // A current-thread runtime keeps every future on this thread's local recorder
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let manager = LlmManager::new_with_strategy_and_retries(
+                    Box::new(flyllm::load_balancer::strategies::LeastRecentlyUsedStrategy::new()),
+                    1,
+                );
+                let task = TaskDefinition::new("summary");
+                manager
+                    .add_instance_dynamic(Arc::new(LocalInstance {
+                        tasks: [(task.name.clone(), task)].into(),
+                    }))
+                    .await;
+
+                manager
+                    .generate(GenerationRequest::builder("hi").task("summary").build())
+                    .await
+                    .unwrap();
+                let _ = manager.generate(GenerationRequest::new("fail".to_string())).await;
+            })
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let task_of = |name: &str, task: &str| {
+            snapshot.iter().any(|(key, _, _, _)| {
+                key.key().name() == name
+                    && key.key().labels().any(|l| l.key() == "task" && l.value() == task)
+                    && key.key().labels().any(|l| l.key() == "instance_id")
+            })
+        };
+
+        for name in ["llm_requests_total", "llm_request_duration_seconds", "llm_tokens_prompt_total", "llm_tokens_completion_total"] {
+            assert!(task_of(name, "summary"), "{} is missing the task label", name);
+        }
+        // Token histograms see exactly the mock's per-request usage
+        let histogram = |name: &str| {
+            snapshot.iter().find_map(|(key, _, _, value)| match value {
+                DebugValue::Histogram(values) if key.key().name() == name => {
+                    Some(values.iter().map(|v| v.into_inner()).collect::<Vec<f64>>())
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(histogram("llm_tokens_prompt"), Some(vec![3.0]));
+        assert_eq!(histogram("llm_tokens_completion"), Some(vec![2.0]));
+
+        assert!(task_of("llm_requests_total", "none"));
+        assert!(task_of("llm_errors_total", "none"));
+    }
+
     #[test]
     fn test_describe_metrics_does_not_panic() {
         // This should not panic even without a recorder installed
@@ -128,4 +272,46 @@ mod dashboard_tests {
         let config = DashboardServerConfig::new(addr);
         assert_eq!(config.bind_address, addr);
     }
+
+    #[tokio::test]
+    async fn test_stats_endpoint_reports_instances() {
+        use flyllm::{LlmManager, ProviderType};
+
+        // Reserve a free port for the server
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let manager = LlmManager::builder()
+            .add_instance(ProviderType::Ollama, "llama3", "")
+            .with_dashboard_server(DashboardServerConfig::new(addr))
+            .build()
+            .await
+            .unwrap();
+
+        let url = format!("http://{}/api/stats", addr);
+        let mut body = None;
+        for _ in 0..50 {
+            if let Ok(response) = reqwest::get(&url).await {
+                body = Some(response.json::<serde_json::Value>().await.unwrap());
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let body = body.expect("dashboard server did not start");
+
+        let instances = body["instances"].as_array().unwrap();
+        assert_eq!(instances.len(), 1);
+        let instance = &instances[0];
+        assert_eq!(instance["provider"], "ollama");
+        assert_eq!(instance["model"], "llama3");
+        assert_eq!(instance["enabled"], true);
+        assert_eq!(instance["healthy"], true);
+        assert_eq!(instance["request_count"], 0);
+        assert_eq!(instance["success_count"], 0);
+        assert_eq!(instance["error_count"], 0);
+        assert!(instance["last_latency_ms"].is_null());
+        assert!(instance["avg_latency_ms"].is_number());
+        assert!(instance["ema_latency_ms"].is_number());
+        assert_eq!(instance["usage"]["total_tokens"], 0);
+
+        assert_eq!(manager.get_instance_stats().await.len(), 1);
+    }
 }
@@ -1,6 +1,7 @@
 //! Tests for provider types and conversions.
 
-use flyllm::ProviderType;
+use flyllm::{create_instance, ContentPart, InstanceSettings, LlmRequest, ProviderType};
+use flyllm::providers::Message;
 
 // ============================================================================
 // ProviderType Conversion Tests
@@ -18,6 +19,10 @@ fn test_provider_type_from_str_lowercase() {
     assert_eq!(ProviderType::from("cohere"), ProviderType::Cohere);
     assert_eq!(ProviderType::from("togetherai"), ProviderType::TogetherAI);
     assert_eq!(ProviderType::from("perplexity"), ProviderType::Perplexity);
+    assert_eq!(ProviderType::from("xai"), ProviderType::Xai);
+    assert_eq!(ProviderType::from("replicate"), ProviderType::Replicate);
+    assert_eq!(ProviderType::from("ai21"), ProviderType::Ai21);
+    assert_eq!(ProviderType::from("fireworks"), ProviderType::Fireworks);
 }
 
 #[test]
@@ -40,6 +45,45 @@ fn test_provider_type_from_str_unknown() {
     let _ = ProviderType::from("unknown_provider");
 }
 
+#[test]
+fn test_provider_type_parse() {
+    assert_eq!("OpenAI".parse::<ProviderType>().unwrap(), ProviderType::OpenAI);
+    assert_eq!("togetherai".parse::<ProviderType>().unwrap(), ProviderType::TogetherAI);
+    assert!(matches!(
+        "unknown_provider".parse::<ProviderType>(),
+        Err(flyllm::LlmError::ConfigError(msg)) if msg.contains("unknown_provider")
+    ));
+}
+
+#[test]
+fn test_provider_type_display_round_trips() {
+    for provider in ProviderType::ALL {
+        let name = provider.to_string();
+        assert_eq!(name.parse::<ProviderType>().unwrap(), provider);
+        assert_eq!(name.to_uppercase().parse::<ProviderType>().unwrap(), provider);
+    }
+}
+
+#[test]
+fn test_provider_type_accepts_param() {
+    for provider in ProviderType::ALL {
+        assert!(provider.accepts_param("max_tokens"));
+        assert!(!provider.accepts_param("temprature"));
+    }
+    // Only providers whose request bodies carry them accept the sampling parameters
+    assert!(ProviderType::OpenAI.accepts_param("top_p"));
+    assert!(ProviderType::Groq.accepts_param("stop"));
+    assert!(ProviderType::Mistral.accepts_param("presence_penalty"));
+    assert!(!ProviderType::Anthropic.accepts_param("top_p"));
+    assert!(!ProviderType::Google.accepts_param("stop"));
+    assert!(ProviderType::Ollama.accepts_param("num_ctx"));
+    assert!(!ProviderType::OpenAI.accepts_param("num_ctx"));
+    assert!(ProviderType::OpenAI.accepts_param("n"));
+    assert!(!ProviderType::Anthropic.accepts_param("n"));
+    assert!(ProviderType::Fireworks.accepts_param("top_logprobs"));
+    assert!(!ProviderType::Perplexity.accepts_param("logprobs"));
+}
+
 #[test]
 fn test_provider_type_display() {
     assert_eq!(format!("{}", ProviderType::Anthropic), "Anthropic");
@@ -101,3 +145,1214 @@ fn test_all_providers_exist() {
     ];
     assert_eq!(providers.len(), 10);
 }
+
+// ============================================================================
+// Message Constructor Tests
+// ============================================================================
+
+#[test]
+fn test_message_constructors_set_roles() {
+    let messages = [
+        flyllm::Message::system("rules"),
+        flyllm::Message::user("question"),
+        flyllm::Message::assistant("answer"),
+    ];
+    let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["system", "user", "assistant"]);
+    assert_eq!(messages[1].content, "question");
+    assert!(messages[2].content_parts.is_none());
+}
+
+// ============================================================================
+// Embedding Capability Tests
+// ============================================================================
+
+#[test]
+fn test_provider_type_supports_embeddings() {
+    assert!(ProviderType::OpenAI.supports_embeddings());
+    assert!(ProviderType::Cohere.supports_embeddings());
+    assert!(ProviderType::Mistral.supports_embeddings());
+    assert!(!ProviderType::Anthropic.supports_embeddings());
+    assert!(!ProviderType::Groq.supports_embeddings());
+}
+
+#[test]
+fn test_instance_embedding_capability_matches_provider_type() {
+    let providers = vec![
+        ProviderType::Anthropic,
+        ProviderType::OpenAI,
+        ProviderType::Mistral,
+        ProviderType::Google,
+        ProviderType::Ollama,
+        ProviderType::LMStudio,
+        ProviderType::Groq,
+        ProviderType::Cohere,
+        ProviderType::TogetherAI,
+        ProviderType::Perplexity,
+    ];
+    for provider in providers {
+        let instance = create_instance(provider, "key".to_string(), "model".to_string(), vec![], true, None, InstanceSettings::default()).unwrap();
+        assert_eq!(
+            instance.as_embedding().is_some(),
+            provider.supports_embeddings(),
+            "capability mismatch for {}",
+            provider
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_capabilities_match_instances() {
+    let tool = flyllm::ToolDefinition {
+        name: "get_weather".to_string(),
+        description: "Get the weather".to_string(),
+        parameters: serde_json::json!({ "type": "object" }),
+    };
+    let tools_request = LlmRequest {
+        messages: vec![Message::user("Weather in Paris?")],
+        tools: Some(vec![tool]),
+        ..Default::default()
+    };
+    let image_request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "What is this?".to_string(),
+            content_parts: Some(vec![ContentPart::ImageUrl { url: "https://example.com/cat.png".to_string(), detail: None }]),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    // Providers without an endpoint override can't be pointed at a local server
+    let fixed_endpoint = [ProviderType::Anthropic, ProviderType::Mistral, ProviderType::Google, ProviderType::Cohere];
+
+    for provider in ProviderType::all() {
+        let capabilities = provider.capabilities();
+        let (base_url, requests) = spawn_recording_openai();
+        let instance = create_instance(*provider, "key".to_string(), "model".to_string(), vec![], true, Some(base_url), vertex_settings()).unwrap();
+
+        assert_eq!(instance.supports_streaming(), capabilities.streaming, "streaming mismatch for {}", provider);
+        assert_eq!(instance.supports_structured_output(), capabilities.json_mode, "json_mode mismatch for {}", provider);
+        assert_eq!(instance.as_embedding().is_some(), capabilities.embeddings, "embeddings mismatch for {}", provider);
+
+        // Text-only providers refuse images before sending anything
+        if !capabilities.vision {
+            let err = instance.generate(&image_request).await.unwrap_err();
+            assert!(err.to_string().contains("does not support image input"), "{} accepted an image: {}", provider, err);
+        }
+
+        if !fixed_endpoint.contains(provider) {
+            // The reply may not parse for every provider; only the request body matters
+            let _ = instance.generate(&tools_request).await;
+            let sent_tools = request_body(&requests.recv().unwrap()).get("tools").is_some();
+            assert_eq!(sent_tools, capabilities.tools, "tools mismatch for {}", provider);
+        }
+    }
+
+    let local: Vec<_> = ProviderType::all().iter().filter(|p| p.capabilities().local).collect();
+    assert_eq!(local, [&ProviderType::Ollama, &ProviderType::LMStudio]);
+}
+
+#[test]
+fn test_hosted_providers_know_model_context_windows() {
+    let known = [
+        (ProviderType::OpenAI, "gpt-4o-mini", Some(128_000)),
+        (ProviderType::Anthropic, "claude-3-5-sonnet-20241022", Some(200_000)),
+        (ProviderType::Google, "gemini-1.5-pro", Some(2_097_152)),
+        (ProviderType::Groq, "llama-3.1-8b-instant", Some(131_072)),
+        (ProviderType::Xai, "grok-3-mini", Some(131_072)),
+        (ProviderType::OpenAI, "my-finetune", None),
+        // Local servers choose their own window
+        (ProviderType::Ollama, "llama-3.1", None),
+        (ProviderType::LMStudio, "llama-3.1", None),
+    ];
+    for (provider, model, window) in known {
+        let instance = create_instance(provider, "key".to_string(), model.to_string(), vec![], true, None, InstanceSettings::default()).unwrap();
+        assert_eq!(instance.context_window(), window, "{} {}", provider, model);
+    }
+}
+
+// ============================================================================
+// Vision Input Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_image_input_rejected_by_non_vision_provider() {
+    let instance = create_instance(ProviderType::Groq, "key".to_string(), "llama3".to_string(), vec![], true, None, InstanceSettings::default()).unwrap();
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "What is this?".to_string(),
+            content_parts: Some(vec![
+                ContentPart::Text("What is this?".to_string()),
+                ContentPart::ImageUrl { url: "https://example.com/cat.png".to_string(), detail: None },
+            ]),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    assert!(request.has_images());
+    let error = instance.generate(&request).await.unwrap_err().to_string();
+    assert!(error.contains("does not support image input"));
+}
+
+// ============================================================================
+// Model Discovery Cache Tests
+// ============================================================================
+
+/// Serve Ollama's model list on a local port, failing the first request.
+/// Returns the base URL and a counter of requests received.
+fn spawn_flaky_ollama() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let (status, body) = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                ("500 Internal Server Error", "boom".to_string())
+            } else {
+                ("200 OK", r#"{"models":[{"name":"llama3"}]}"#.to_string())
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    (base_url, hits)
+}
+
+#[tokio::test]
+async fn test_list_models_cached_skips_errors_and_reuses_results() {
+    use flyllm::ModelDiscovery;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    let (base_url, hits) = spawn_flaky_ollama();
+    let ttl = Duration::from_secs(60);
+    let list = || ModelDiscovery::list_models_cached(ProviderType::Ollama, "", Some(&base_url), ttl);
+
+    // The failure isn't cached, so the second call reaches the server again
+    assert!(list().await.is_err());
+    let models = list().await.unwrap();
+    assert_eq!(models[0].name, "llama3");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // Served from the cache within the TTL
+    assert_eq!(list().await.unwrap().len(), 1);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    ModelDiscovery::clear_cache();
+    list().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_list_all_models_keeps_per_provider_results() {
+    use flyllm::ModelDiscovery;
+
+    let (base_url, _hits) = spawn_flaky_ollama();
+    let creds = vec![
+        (ProviderType::Ollama, String::new(), Some(base_url)),
+        (ProviderType::Perplexity, String::new(), None),
+    ];
+
+    let results = ModelDiscovery::list_all_models(&creds).await;
+
+    // The flaky server fails its first request without failing the batch
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, ProviderType::Ollama);
+    assert!(results[0].1.is_err());
+    assert_eq!(results[1].0, ProviderType::Perplexity);
+    assert!(!results[1].1.as_ref().unwrap().is_empty());
+}
+
+// ============================================================================
+// Custom Endpoint and Header Tests
+// ============================================================================
+
+/// Answer one request with an OpenAI-style completion and hand back the raw request text
+fn spawn_recording_openai() -> (String, std::sync::mpsc::Receiver<String>) {
+    spawn_recording_server(r#"{"choices":[{"message":{"content":"hi"}}],"model":"gpt-4o"}"#)
+}
+
+/// Answer one request with the given JSON body and hand back the raw request text
+fn spawn_recording_server(body: &'static str) -> (String, std::sync::mpsc::Receiver<String>) {
+    spawn_recording_server_with_status("200 OK", body)
+}
+
+/// Like `spawn_recording_server`, answering with the given HTTP status (e.g. "401 Unauthorized")
+fn spawn_recording_server_with_status(status: &'static str, body: &'static str) -> (String, std::sync::mpsc::Receiver<String>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        // Read until the full JSON body (or, for a GET, the headers) has arrived
+        loop {
+            let text = String::from_utf8_lossy(&request);
+            if text.ends_with('}') || (text.starts_with("GET ") && text.ends_with("\r\n\r\n")) {
+                break;
+            }
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes());
+        sender.send(String::from_utf8_lossy(&request).to_string()).unwrap();
+    });
+
+    (base_url, receiver)
+}
+
+/// Parse the JSON body out of a raw recorded HTTP request
+fn request_body(raw: &str) -> serde_json::Value {
+    let start = raw.find("\r\n\r\n").expect("request has no body") + 4;
+    serde_json::from_str(&raw[start..]).unwrap()
+}
+
+#[tokio::test]
+async fn test_openai_honors_custom_endpoint_and_headers() {
+    let (base_url, requests) = spawn_recording_openai();
+    let settings = InstanceSettings {
+        custom_headers: [("X-Org-Id".to_string(), "acme".to_string())].into(),
+        ..Default::default()
+    };
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        "gpt-4o".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        settings,
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        model: None,
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "hi");
+
+    let raw = requests.recv().unwrap().to_lowercase();
+    assert!(raw.starts_with("post /v1/chat/completions "));
+    assert!(raw.contains("x-org-id: acme"));
+}
+
+#[tokio::test]
+async fn test_default_user_agent_and_override() {
+    let expected = format!("user-agent: flyllm/{}\r\n", env!("CARGO_PKG_VERSION"));
+    let request = LlmRequest { messages: vec![Message::user("Hello")], ..Default::default() };
+
+    let (base_url, requests) = spawn_recording_openai();
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        "gpt-4o".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    instance.generate(&request).await.unwrap();
+    assert!(requests.recv().unwrap().to_lowercase().contains(&expected));
+
+    let (base_url, requests) = spawn_recording_openai();
+    let settings = InstanceSettings { user_agent: Some("acme-bot/2.1".to_string()), ..Default::default() };
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        "gpt-4o".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        settings,
+    ).unwrap();
+    instance.generate(&request).await.unwrap();
+    let raw = requests.recv().unwrap().to_lowercase();
+    assert!(raw.contains("user-agent: acme-bot/2.1\r\n"));
+    assert!(!raw.contains("flyllm/"));
+
+    // Model discovery identifies itself the same way
+    let (base_url, requests) = spawn_recording_server(r#"{"object":"list","data":[]}"#);
+    flyllm::ModelDiscovery::list_models(ProviderType::Fireworks, "ua-key", Some(&format!("{}/inference/v1", base_url)))
+        .await
+        .unwrap();
+    assert!(requests.recv().unwrap().to_lowercase().contains(&expected));
+}
+
+/// Replay a prediction that is created as "starting", then fetched twice until it succeeds
+///
+/// Requests are handed back in order; `{base}` in the bodies becomes the server's URL.
+fn spawn_replicate_lifecycle() -> (String, std::sync::mpsc::Receiver<String>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let urls = r#""urls": {"get": "{base}/v1/predictions/p1", "cancel": "{base}/v1/predictions/p1/cancel"}"#;
+    let bodies = [
+        ("201 Created", format!(r#"{{"id": "p1", "status": "starting", "output": null, {}}}"#, urls)),
+        ("200 OK", format!(r#"{{"id": "p1", "status": "processing", "output": ["Hello"], {}}}"#, urls)),
+        ("200 OK", format!(
+            r#"{{"id": "p1", "model": "meta/meta-llama-3-8b-instruct", "status": "succeeded", "output": ["Hello", ", ", "world"], "metrics": {{"input_token_count": 5, "output_token_count": 3}}, {}}}"#,
+            urls
+        )),
+    ];
+    let base = base_url.clone();
+
+    std::thread::spawn(move || {
+        for (status, body) in bodies {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let text = String::from_utf8_lossy(&request);
+                if text.ends_with('}') || (text.starts_with("GET ") && text.ends_with("\r\n\r\n")) {
+                    break;
+                }
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = body.replace("{base}", &base);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+            sender.send(String::from_utf8_lossy(&request).to_string()).unwrap();
+        }
+    });
+
+    (base_url, receiver)
+}
+
+#[tokio::test]
+async fn test_replicate_polls_prediction_until_it_succeeds() {
+    let (base_url, requests) = spawn_replicate_lifecycle();
+    let instance = create_instance(
+        ProviderType::Replicate,
+        "r8_key".to_string(),
+        "meta/meta-llama-3-8b-instruct".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    assert!(!instance.supports_streaming());
+
+    let request = LlmRequest {
+        messages: vec![Message::system("Be friendly."), Message::user("Say hello")],
+        max_tokens: Some(64),
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "Hello, world");
+    assert_eq!(response.model, "meta/meta-llama-3-8b-instruct");
+    let usage = response.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (5, 3, 8));
+
+    let create = requests.recv().unwrap();
+    assert!(create.starts_with("POST /v1/models/meta/meta-llama-3-8b-instruct/predictions "));
+    assert!(create.to_lowercase().contains("prefer: wait"));
+    assert!(create.to_lowercase().contains("authorization: bearer r8_key"));
+    let body = request_body(&create);
+    assert_eq!(body["input"]["prompt"], "Say hello");
+    assert_eq!(body["input"]["system_prompt"], "Be friendly.");
+    assert_eq!(body["input"]["max_tokens"], 64);
+    assert!(body.get("version").is_none());
+
+    for _ in 0..2 {
+        assert!(requests.recv().unwrap().starts_with("GET /v1/predictions/p1 "));
+    }
+}
+
+#[tokio::test]
+async fn test_replicate_failed_prediction_is_an_error() {
+    let (base_url, _requests) = spawn_recording_server_with_status(
+        "201 Created",
+        r#"{"id": "p2", "status": "failed", "output": null, "error": "CUDA out of memory"}"#,
+    );
+    let instance = create_instance(
+        ProviderType::Replicate,
+        "r8_key".to_string(),
+        "owner/model:5c7d5dc6".to_string(),
+        vec![],
+        true,
+        Some(base_url),
+        InstanceSettings::default(),
+    ).unwrap();
+
+    let request = LlmRequest { messages: vec![Message::user("Hi")], ..Default::default() };
+    let err = instance.generate(&request).await.unwrap_err().to_string();
+    assert!(err.contains("p2 failed: CUDA out of memory"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_fireworks_discovered_model_round_trips_verbatim() {
+    use flyllm::ModelDiscovery;
+
+    let model = "accounts/fireworks/models/llama-v3p1-70b-instruct";
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"object":"list","data":[{"id":"accounts/fireworks/models/llama-v3p1-70b-instruct","object":"model","owned_by":"fireworks"}]}"#,
+    );
+    let models = ModelDiscovery::list_models(ProviderType::Fireworks, "fw-key", Some(&format!("{}/inference/v1", base_url)))
+        .await
+        .unwrap();
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].name, model);
+    assert_eq!(models[0].provider, ProviderType::Fireworks);
+    let raw = requests.recv().unwrap();
+    assert!(raw.starts_with("GET /inference/v1/models "));
+    assert!(raw.to_lowercase().contains("authorization: bearer fw-key"));
+
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"choices":[{"message":{"role":"assistant","content":"hi"},"finish_reason":"stop"}],"model":"accounts/fireworks/models/llama-v3p1-70b-instruct","usage":{"prompt_tokens":5,"completion_tokens":1,"total_tokens":6}}"#,
+    );
+    let instance = create_instance(
+        ProviderType::Fireworks,
+        "fw-key".to_string(),
+        models[0].name.clone(),
+        vec![],
+        true,
+        Some(format!("{}/inference/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    assert_eq!(instance.get_name(), "fireworks");
+
+    let request = LlmRequest { messages: vec![Message::user("Hello")], ..Default::default() };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.model, model);
+    assert_eq!(response.usage.unwrap().total_tokens, 6);
+
+    let raw = requests.recv().unwrap();
+    assert!(raw.starts_with("POST /inference/v1/chat/completions "));
+    assert_eq!(request_body(&raw)["model"], model);
+}
+
+#[tokio::test]
+async fn test_ai21_parses_jamba_response_with_usage() {
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"id":"chat-3e3c6f0a","choices":[{"index":0,"message":{"role":"assistant","content":"Hello from Jamba","tool_calls":null},"logprobs":null,"finish_reason":"stop"}],"usage":{"prompt_tokens":12,"completion_tokens":4,"total_tokens":16},"meta":{"requestDurationMillis":210}}"#,
+    );
+    let instance = create_instance(
+        ProviderType::Ai21,
+        "ai21-key".to_string(),
+        "jamba-mini".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/studio/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    assert_eq!(instance.get_name(), "ai21");
+    assert_eq!(instance.context_window(), Some(256_000));
+
+    let request = LlmRequest { messages: vec![Message::user("Hello")], ..Default::default() };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "Hello from Jamba");
+    assert_eq!(response.model, "jamba-mini");
+    assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+    let usage = response.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (12, 4, 16));
+
+    let raw = requests.recv().unwrap();
+    assert!(raw.starts_with("POST /studio/v1/chat/completions "));
+    assert!(raw.to_lowercase().contains("authorization: bearer ai21-key"));
+    assert_eq!(request_body(&raw)["model"], "jamba-mini");
+}
+
+#[test]
+fn test_validate_messages_rejects_empty_conversation() {
+    use flyllm::providers::validate_messages;
+
+    for provider in ProviderType::all() {
+        let err = validate_messages(&[], *provider).unwrap_err().to_string();
+        assert!(err.contains("has no messages"), "{}: {}", provider, err);
+    }
+}
+
+#[test]
+fn test_validate_messages_checks_roles_per_provider() {
+    use flyllm::providers::validate_messages;
+
+    let assistant_first = [Message::system("Be brief."), Message::assistant("Hello!"), Message::user("Hi")];
+    let err = validate_messages(&assistant_first, ProviderType::Google).unwrap_err().to_string();
+    assert!(err.contains("Conversation for Google must start with a user message, not 'assistant'"), "{}", err);
+    assert!(validate_messages(&assistant_first, ProviderType::OpenAI).is_ok());
+
+    // Anthropic builds the user turn from a lone system prompt
+    assert!(validate_messages(&[Message::system("Summarize.")], ProviderType::Anthropic).is_ok());
+
+    let tool = Message { role: "tool".to_string(), content: "42".to_string(), ..Default::default() };
+    let err = validate_messages(&[Message::user("Hi"), tool.clone()], ProviderType::Mistral).unwrap_err().to_string();
+    assert!(err.contains("Unknown message role 'tool'"), "{}", err);
+    assert!(validate_messages(&[Message::user("Hi"), tool], ProviderType::OpenAI).is_ok());
+
+    let repeated = [Message::user("Hi"), Message::user("Anyone there?")];
+    let err = validate_messages(&repeated, ProviderType::Perplexity).unwrap_err().to_string();
+    assert!(err.contains("must alternate user/assistant for Perplexity"), "{}", err);
+    assert!(validate_messages(&repeated, ProviderType::Anthropic).is_ok());
+}
+
+#[tokio::test]
+async fn test_request_model_override_reaches_provider() {
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"choices":[{"message":{"role":"assistant","content":"hi"}}],"model":"gpt-4o"}"#,
+    );
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "openai-key".to_string(),
+        "gpt-4o-mini".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    let manager = flyllm::LlmManager::new();
+    manager.add_instance_dynamic(instance).await;
+
+    let response = manager
+        .generate(flyllm::GenerationRequest::builder("Hello").model("gpt-4o").build())
+        .await
+        .unwrap();
+    assert_eq!(response.model.as_deref(), Some("gpt-4o"));
+    assert_eq!(request_body(&requests.recv().unwrap())["model"], "gpt-4o");
+}
+
+#[test]
+fn test_recognizes_model_by_provider_family() {
+    assert!(ProviderType::OpenAI.recognizes_model("gpt-4o"));
+    assert!(!ProviderType::OpenAI.recognizes_model("claude-3-5-sonnet-latest"));
+    assert!(ProviderType::Anthropic.recognizes_model("claude-3-5-sonnet-latest"));
+    assert!(ProviderType::Fireworks.recognizes_model("accounts/fireworks/models/llama-v3p1-70b-instruct"));
+    assert!(!ProviderType::Fireworks.recognizes_model("llama-v3p1-70b-instruct"));
+    // Hosts of arbitrary open models accept anything
+    assert!(ProviderType::Ollama.recognizes_model("claude-3-5-sonnet-latest"));
+}
+
+#[tokio::test]
+async fn test_xai_sends_bearer_key_to_chat_completions() {
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"choices":[{"message":{"role":"assistant","content":"hi"}}],"model":"grok-3-mini"}"#,
+    );
+    let instance = create_instance(
+        ProviderType::Xai,
+        "xai-key".to_string(),
+        "grok-3-mini".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    assert_eq!(instance.get_name(), "xai");
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "hi");
+
+    let raw = requests.recv().unwrap();
+    assert!(raw.starts_with("POST /v1/chat/completions "));
+    assert!(raw.to_lowercase().contains("authorization: bearer xai-key"));
+    assert_eq!(request_body(&raw)["model"], "grok-3-mini");
+}
+
+#[test]
+fn test_openai_compatible_requires_endpoint() {
+    let result = create_instance(ProviderType::OpenAICompatible, "key".to_string(), "model".to_string(), vec![], true, None, InstanceSettings::default());
+    assert!(matches!(result, Err(flyllm::LlmError::ConfigError(msg)) if msg.contains("custom endpoint")));
+    assert_eq!("openai_compatible".parse::<ProviderType>().unwrap(), ProviderType::OpenAICompatible);
+}
+
+#[tokio::test]
+async fn test_openai_compatible_uses_endpoint_and_auth_header() {
+    let (base_url, requests) = spawn_recording_openai();
+    let settings = InstanceSettings {
+        auth_header: Some("api-key".to_string()),
+        ..Default::default()
+    };
+    let instance = create_instance(
+        ProviderType::OpenAICompatible,
+        "secret".to_string(),
+        "llama-3".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1/openai", base_url)),
+        settings,
+    ).unwrap();
+    assert_eq!(instance.get_name(), "openai_compatible");
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "hi");
+
+    let raw = requests.recv().unwrap();
+    let lower = raw.to_lowercase();
+    assert!(lower.starts_with("post /v1/openai/chat/completions "));
+    assert!(lower.contains("api-key: secret"));
+    assert!(!lower.contains("authorization:"));
+    assert_eq!(request_body(&raw)["model"], "llama-3");
+}
+
+fn vertex_settings() -> InstanceSettings {
+    InstanceSettings {
+        project_id: Some("my-project".to_string()),
+        location: Some("europe-west4".to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_vertexai_sends_gemini_request_with_bearer_token() {
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"candidates": [{"content": {"parts": [{"text": "hi"}], "role": "model"}, "finishReason": "STOP"}], "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 1, "totalTokenCount": 4}}"#,
+    );
+    let instance = create_instance(
+        ProviderType::VertexAI,
+        "ya29.token".to_string(),
+        "gemini-2.0-flash".to_string(),
+        vec![],
+        true,
+        Some(base_url),
+        vertex_settings(),
+    ).unwrap();
+    assert_eq!(instance.get_name(), "vertexai");
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        max_tokens: Some(16),
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "hi");
+    assert_eq!(response.usage.unwrap().total_tokens, 4);
+
+    let raw = requests.recv().unwrap();
+    let lower = raw.to_lowercase();
+    assert!(lower.starts_with(
+        "post /v1/projects/my-project/locations/europe-west4/publishers/google/models/gemini-2.0-flash:generatecontent "
+    ));
+    assert!(lower.contains("authorization: bearer ya29.token"));
+    let body = request_body(&raw);
+    assert_eq!(body["contents"][0]["parts"][0]["text"], "Hello");
+    assert_eq!(body["generationConfig"]["maxOutputTokens"], 16);
+}
+
+#[tokio::test]
+async fn test_vertexai_expired_token_is_auth_api_error() {
+    let (base_url, _requests) = spawn_recording_server_with_status(
+        "401 Unauthorized",
+        r#"{"error": {"code": 401, "message": "Request had invalid authentication credentials.", "status": "UNAUTHENTICATED"}}"#,
+    );
+    let instance = create_instance(
+        ProviderType::VertexAI,
+        "expired".to_string(),
+        "gemini-2.0-flash".to_string(),
+        vec![],
+        true,
+        Some(base_url),
+        vertex_settings(),
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let err = instance.generate(&request).await.unwrap_err();
+    match err {
+        flyllm::LlmError::Api { status, message, provider, .. } => {
+            assert_eq!(status, 401);
+            assert_eq!(provider, "Vertex AI");
+            assert!(message.contains("may have expired"), "{}", message);
+        }
+        other => panic!("expected an Api error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_vertexai_requires_project_id() {
+    let result = create_instance(ProviderType::VertexAI, "token".to_string(), "gemini-2.0-flash".to_string(), vec![], true, None, InstanceSettings::default());
+    assert!(matches!(result, Err(flyllm::LlmError::ConfigError(msg)) if msg.contains("project ID")));
+    assert_eq!("vertex_ai".parse::<ProviderType>().unwrap(), ProviderType::VertexAI);
+}
+
+#[tokio::test]
+async fn test_openai_sends_n_and_returns_every_choice() {
+    let request = LlmRequest {
+        messages: vec![Message::user("Pick a colour")],
+        n: Some(2),
+        ..Default::default()
+    };
+
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"model":"gpt-4o","choices":[{"message":{"role":"assistant","content":"Red"}},{"message":{"role":"assistant","content":"Blue"}}]}"#,
+    );
+    let openai = create_instance(ProviderType::OpenAI, "key".to_string(), "gpt-4o".to_string(), vec![], true, Some(base_url), InstanceSettings::default()).unwrap();
+    let response = openai.generate(&request).await.unwrap();
+
+    assert_eq!(request_body(&requests.recv().unwrap())["n"], 2);
+    assert_eq!(response.content, "Red");
+    assert_eq!(response.choices, ["Red", "Blue"]);
+}
+
+#[tokio::test]
+async fn test_logprobs_sent_only_to_supporting_providers() {
+    let request = LlmRequest {
+        messages: vec![Message::user("Is the sky blue?")],
+        logprobs: Some(true),
+        top_logprobs: Some(3),
+        ..Default::default()
+    };
+
+    let (base_url, requests) = spawn_recording_openai();
+    let openai = create_instance(ProviderType::OpenAI, "key".to_string(), "gpt-4o".to_string(), vec![], true, Some(format!("{}/v1", base_url)), InstanceSettings::default()).unwrap();
+    openai.generate(&request).await.unwrap();
+    let body = request_body(&requests.recv().unwrap());
+    assert_eq!(body["logprobs"], true);
+    assert_eq!(body["top_logprobs"], 3);
+
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"choices":[{"message":{"role":"assistant","content":"Yes"},"logprobs":{"content":[{"token":"Yes","logprob":-0.01}]}}],"model":"llama3"}"#,
+    );
+    let groq = create_instance(ProviderType::Groq, "key".to_string(), "llama3".to_string(), vec![], true, Some(base_url), InstanceSettings::default()).unwrap();
+    let response = groq.generate(&request).await.unwrap();
+    let body = request_body(&requests.recv().unwrap());
+    assert_eq!(body["logprobs"], true);
+    assert_eq!(body["top_logprobs"], 3);
+    assert_eq!(response.logprobs.unwrap()["content"][0]["token"], "Yes");
+
+    let (base_url, requests) = spawn_recording_server(r#"{"choices":[{"message":{"role":"assistant","content":"hi"}}],"model":"sonar"}"#);
+    let perplexity = create_instance(ProviderType::Perplexity, "key".to_string(), "sonar".to_string(), vec![], true, Some(base_url), InstanceSettings::default()).unwrap();
+    let response = perplexity.generate(&request).await.unwrap();
+    let body = request_body(&requests.recv().unwrap());
+    assert!(body.get("logprobs").is_none());
+    assert!(body.get("top_logprobs").is_none());
+    assert!(response.logprobs.is_none());
+}
+
+#[tokio::test]
+async fn test_sampling_params_sent_to_openai_family() {
+    let request = LlmRequest {
+        messages: vec![Message::user("Name a fruit")],
+        top_p: Some(0.5),
+        frequency_penalty: Some(0.25),
+        presence_penalty: Some(-0.5),
+        stop: Some(vec!["\n".to_string()]),
+        ..Default::default()
+    };
+
+    let (base_url, requests) = spawn_recording_openai();
+    let openai = create_instance(ProviderType::OpenAI, "key".to_string(), "gpt-4o".to_string(), vec![], true, Some(format!("{}/v1", base_url)), InstanceSettings::default()).unwrap();
+    openai.generate(&request).await.unwrap();
+    let body = request_body(&requests.recv().unwrap());
+    assert_eq!(body["top_p"], 0.5);
+    assert_eq!(body["frequency_penalty"], 0.25);
+    assert_eq!(body["presence_penalty"], -0.5);
+    assert_eq!(body["stop"], serde_json::json!(["\n"]));
+
+    let (base_url, requests) = spawn_recording_server(r#"{"choices":[{"message":{"role":"assistant","content":"hi"}}],"model":"llama3"}"#);
+    let groq = create_instance(ProviderType::Groq, "key".to_string(), "llama3".to_string(), vec![], true, Some(base_url), InstanceSettings::default()).unwrap();
+    groq.generate(&request).await.unwrap();
+    let body = request_body(&requests.recv().unwrap());
+    assert_eq!(body["top_p"], 0.5);
+    assert_eq!(body["stop"], serde_json::json!(["\n"]));
+}
+
+#[tokio::test]
+async fn test_manager_maps_sampling_params_to_request() {
+    use flyllm::{GenerationRequest, LlmManager, TaskDefinition};
+
+    let (base_url, requests) = spawn_recording_openai();
+    let manager = LlmManager::builder()
+        .define_task(TaskDefinition::new("chat").with_top_p(0.5).with_stop(vec!["END".to_string()]))
+        .add_instance(ProviderType::OpenAI, "gpt-4o", "key")
+        .custom_endpoint(format!("{}/v1", base_url))
+        .supports("chat")
+        .build()
+        .await
+        .unwrap();
+
+    let responses = manager
+        .generate_sequentially(vec![GenerationRequest::new("Hi".to_string()).task("chat")])
+        .await;
+    assert!(responses[0].success);
+    let body = request_body(&requests.recv().unwrap());
+    assert_eq!(body["top_p"], 0.5);
+    assert_eq!(body["stop"], serde_json::json!(["END"]));
+    assert!(body.get("frequency_penalty").is_none());
+}
+
+#[tokio::test]
+async fn test_manager_records_openai_stream_usage_chunk() {
+    use futures::StreamExt;
+
+    // With include_usage, usage arrives after the finish reason in a chunk with no choices
+    let (base_url, requests) = spawn_recording_server(concat!(
+        "data: {\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: {\"model\":\"gpt-4o\",\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":1,\"total_tokens\":10}}\n\n",
+        "data: [DONE]\n\n",
+    ));
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        "gpt-4o".to_string(),
+        vec![],
+        true,
+        Some(format!("{}/v1", base_url)),
+        InstanceSettings::default(),
+    ).unwrap();
+    let manager = flyllm::LlmManager::new();
+    let id = manager.add_instance_dynamic(instance).await;
+
+    let stream = manager.generate_stream(flyllm::GenerationRequest::new("Hello".to_string())).await.unwrap();
+    let chunks: Vec<_> = stream.map(|chunk| chunk.unwrap()).collect().await;
+    assert_eq!(chunks.iter().map(|chunk| chunk.content.as_str()).collect::<String>(), "Hi");
+    assert_eq!(chunks.last().unwrap().usage.as_ref().map(|u| u.total_tokens), Some(10));
+    assert_eq!(request_body(&requests.recv().unwrap())["stream_options"]["include_usage"], true);
+
+    let usage = manager.get_instance_usage(id).await.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens), (9, 1));
+    assert!(manager.get_total_cost().await > 0.0);
+}
+
+#[tokio::test]
+async fn test_seed_sent_only_to_supporting_providers() {
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        seed: Some(7),
+        ..Default::default()
+    };
+
+    let (base_url, requests) = spawn_recording_openai();
+    let openai = create_instance(ProviderType::OpenAI, "key".to_string(), "gpt-4o".to_string(), vec![], true, Some(format!("{}/v1", base_url)), InstanceSettings::default()).unwrap();
+    openai.generate(&request).await.unwrap();
+    assert_eq!(request_body(&requests.recv().unwrap())["seed"], 7);
+
+    let (base_url, requests) = spawn_recording_server(r#"{"choices":[{"message":{"role":"assistant","content":"hi"}}],"model":"sonar"}"#);
+    let perplexity = create_instance(ProviderType::Perplexity, "key".to_string(), "sonar".to_string(), vec![], true, Some(base_url), InstanceSettings::default()).unwrap();
+    perplexity.generate(&request).await.unwrap();
+    assert!(request_body(&requests.recv().unwrap()).get("seed").is_none());
+}
+
+#[tokio::test]
+async fn test_ollama_sends_options_from_params() {
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"model":"llama3","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"hi"},"done":true}"#,
+    );
+    let instance = create_instance(
+        ProviderType::Ollama,
+        String::new(),
+        "llama3".to_string(),
+        vec![],
+        true,
+        Some(base_url),
+        InstanceSettings::default(),
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        params: [
+            ("num_ctx".to_string(), serde_json::json!(8192)),
+            ("not_an_ollama_option".to_string(), serde_json::json!("ignored")),
+        ].into(),
+        seed: Some(42),
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "hi");
+
+    let body = request_body(&requests.recv().unwrap());
+    assert_eq!(body["options"]["num_ctx"], 8192);
+    assert_eq!(body["options"]["seed"], 42);
+    assert!(body["options"].get("not_an_ollama_option").is_none());
+    assert!(body.get("params").is_none());
+}
+
+#[test]
+fn test_instances_reuse_shared_client() {
+    use flyllm::providers::instances::BaseInstance;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    let shared = Arc::new(reqwest::Client::new());
+    let settings = InstanceSettings {
+        shared_client: Some(shared.clone()),
+        ..Default::default()
+    };
+
+    for i in 0..10 {
+        let base = BaseInstance::new("openai".to_string(), format!("key-{}", i), "gpt-4o".to_string(), HashMap::new(), true, &settings).unwrap();
+        assert!(Arc::ptr_eq(base.http_client(), &shared));
+    }
+    assert_eq!(Arc::strong_count(&shared), 2); // `shared` plus the copy in `settings`
+
+    // Settings that change the client get a dedicated one
+    let custom = InstanceSettings {
+        custom_headers: [("X-Org-Id".to_string(), "acme".to_string())].into(),
+        ..settings
+    };
+    let base = BaseInstance::new("openai".to_string(), "key".to_string(), "gpt-4o".to_string(), HashMap::new(), true, &custom).unwrap();
+    assert!(!Arc::ptr_eq(base.http_client(), &shared));
+}
+
+#[tokio::test]
+async fn test_connect_timeout_fails_fast() {
+    let settings = InstanceSettings {
+        connect_timeout: Some(std::time::Duration::from_millis(200)),
+        ..Default::default()
+    };
+    // Non-routable address: connecting hangs until the timeout fires
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        "gpt-4o".to_string(),
+        vec![],
+        true,
+        Some("http://10.255.255.1/v1".to_string()),
+        settings,
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let started = std::time::Instant::now();
+    let err = instance.generate(&request).await.unwrap_err();
+
+    // Request errors are retried on another instance by the manager
+    assert!(matches!(err, flyllm::LlmError::RequestError(_)), "unexpected error: {:?}", err);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_requests_are_routed_through_proxy() {
+    // The recording server plays the proxy; the upstream host never resolves
+    let (proxy_url, requests) = spawn_recording_openai();
+    let settings = InstanceSettings {
+        proxy: Some(proxy_url),
+        ..Default::default()
+    };
+    let instance = create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        "gpt-4o".to_string(),
+        vec![],
+        true,
+        Some("http://upstream.invalid/v1".to_string()),
+        settings,
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    instance.generate(&request).await.unwrap();
+
+    let raw = requests.recv().unwrap().to_lowercase();
+    assert!(raw.starts_with("post http://upstream.invalid/v1/chat/completions "));
+}
+
+#[tokio::test]
+async fn test_perplexity_returns_citations() {
+    let (base_url, _requests) = spawn_recording_server(
+        r#"{"choices":[{"message":{"role":"assistant","content":"Rust 1.0 shipped in 2015 [1]."}}],"model":"sonar","citations":["https://blog.rust-lang.org/2015/05/15/Rust-1.0.html"]}"#,
+    );
+    let instance = create_instance(
+        ProviderType::Perplexity,
+        "key".to_string(),
+        "sonar".to_string(),
+        vec![],
+        true,
+        Some(base_url),
+        InstanceSettings::default(),
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "When did Rust 1.0 ship?".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.citations, vec!["https://blog.rust-lang.org/2015/05/15/Rust-1.0.html"]);
+}
+
+#[tokio::test]
+async fn test_lmstudio_without_key_sends_no_auth_or_seed() {
+    let (base_url, requests) = spawn_recording_server(
+        r#"{"choices":[{"message":{"role":"assistant","content":"hi"}}],"model":"local-model"}"#,
+    );
+    let instance = create_instance(
+        ProviderType::LMStudio,
+        String::new(),
+        "local-model".to_string(),
+        vec![],
+        true,
+        Some(base_url),
+        InstanceSettings::default(),
+    ).unwrap();
+
+    let request = LlmRequest {
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        seed: Some(7),
+        ..Default::default()
+    };
+    let response = instance.generate(&request).await.unwrap();
+    assert_eq!(response.content, "hi");
+
+    let raw = requests.recv().unwrap();
+    assert!(raw.starts_with("POST /v1/chat/completions "));
+    assert!(!raw.to_lowercase().contains("authorization:"));
+    assert!(request_body(&raw).get("seed").is_none());
+}
+
+// ============================================================================
+// Stream Collection Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_collect_response_assembles_chunks() {
+    use flyllm::{collect_response, StreamChunk};
+    use flyllm::providers::TokenUsage;
+
+    let usage = TokenUsage {
+        prompt_tokens: 5,
+        completion_tokens: 3,
+        total_tokens: 8,
+        ..Default::default()
+    };
+    let mut last = StreamChunk::final_chunk("gpt-4o-2024-08-06", Some(usage.clone()));
+    last.finish_reason = Some("stop".to_string());
+    let mut first = StreamChunk::content("Hel");
+    first.model = Some("gpt-4o".to_string());
+    let chunks = vec![Ok(first), Ok(StreamChunk::content("lo")), Ok(StreamChunk::content("!")), Ok(last)];
+
+    let response = collect_response(Box::pin(futures::stream::iter(chunks))).await.unwrap();
+    assert_eq!(response.content, "Hello!");
+    assert_eq!(response.model, "gpt-4o-2024-08-06");
+    assert_eq!(response.usage, Some(usage));
+    assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+}
+
+#[tokio::test]
+async fn test_collect_response_returns_first_error() {
+    use flyllm::{collect_response, LlmError, StreamChunk};
+
+    let chunks = vec![
+        Ok(StreamChunk::content("partial")),
+        Err(LlmError::ParseError("bad chunk".to_string())),
+        Ok(StreamChunk::content("ignored")),
+    ];
+    let result = collect_response(Box::pin(futures::stream::iter(chunks))).await;
+    assert!(matches!(result, Err(LlmError::ParseError(msg)) if msg == "bad chunk"));
+}
+
+// ============================================================================
+// Health Check Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_health_check_uses_models_endpoint() {
+    for (provider, base_path, expected) in [
+        (ProviderType::OpenAI, "/v1", "get /v1/models "),
+        (ProviderType::Groq, "/openai/v1", "get /openai/v1/models "),
+        (ProviderType::Ollama, "", "get /api/tags "),
+    ] {
+        let (base_url, requests) = spawn_recording_openai();
+        let instance = create_instance(
+            provider,
+            "key".to_string(),
+            "model".to_string(),
+            vec![],
+            true,
+            Some(format!("{}{}", base_url, base_path)),
+            InstanceSettings::default(),
+        ).unwrap();
+
+        instance.health_check().await.unwrap();
+
+        let raw = requests.recv().unwrap().to_lowercase();
+        assert!(raw.starts_with(expected), "{}: unexpected request {}", provider, raw);
+    }
+}
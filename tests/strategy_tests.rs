@@ -1,12 +1,30 @@
 //! Tests for load balancing strategies.
 
-use flyllm::{LlmManager, ProviderType, TaskDefinition};
+use flyllm::{create_instance, InstanceSettings, LlmManager, ProviderType, TaskDefinition};
 use flyllm::load_balancer::strategies::{
     LoadBalancingStrategy,
     LeastRecentlyUsedStrategy,
     LowestLatencyStrategy,
-    RandomStrategy
+    PowerOfTwoStrategy,
+    PriorityStrategy,
+    RandomStrategy,
+    StickyStrategy,
+    WeightedStrategy,
 };
+use flyllm::load_balancer::tracker::InstanceTracker;
+use std::time::Duration;
+
+fn make_tracker(model: &str) -> InstanceTracker {
+    InstanceTracker::new(create_instance(
+        ProviderType::OpenAI,
+        "key".to_string(),
+        model.to_string(),
+        vec![],
+        true,
+        None,
+        InstanceSettings::default(),
+    ).unwrap())
+}
 
 // ============================================================================
 // Strategy Creation Tests
@@ -28,6 +46,250 @@ fn test_random_strategy_creation() {
     let _strategy = RandomStrategy::new();
 }
 
+#[test]
+fn test_seeded_random_strategy_is_reproducible() {
+    let owned: Vec<InstanceTracker> = (0..6).map(|i| make_tracker(&format!("model-{}", i))).collect();
+    let trackers: Vec<(usize, &InstanceTracker)> = owned.iter().enumerate().collect();
+    let picks = |seed: u64| {
+        let mut strategy = RandomStrategy::with_seed(seed);
+        (0..50).map(|_| strategy.select_instance(&trackers)).collect::<Vec<usize>>()
+    };
+
+    assert_eq!(picks(42), picks(42));
+    assert_ne!(picks(42), picks(7));
+}
+
+#[test]
+fn test_power_of_two_strategy_creation() {
+    let _strategy = PowerOfTwoStrategy::new();
+}
+
+// ============================================================================
+// Power of Two Choices Tests
+// ============================================================================
+
+#[test]
+fn test_power_of_two_idle_instances_both_chosen() {
+    let a = make_tracker("gpt-4");
+    let b = make_tracker("gpt-4o");
+    let trackers = vec![(0, &a), (1, &b)];
+    let mut strategy = PowerOfTwoStrategy::new();
+
+    let mut seen = [false, false];
+    for _ in 0..200 {
+        seen[strategy.select_instance(&trackers)] = true;
+    }
+
+    assert!(seen[0] && seen[1], "both idle instances should be selected at some point");
+}
+
+#[test]
+fn test_power_of_two_prefers_fewer_in_flight() {
+    let busy = make_tracker("gpt-4");
+    let idle = make_tracker("gpt-4o");
+    let _guards: Vec<_> = (0..3).map(|_| busy.start_request()).collect();
+    let trackers = vec![(0, &busy), (1, &idle)];
+    let mut strategy = PowerOfTwoStrategy::new();
+
+    // With only two candidates both are always sampled, so the idle one must win
+    for _ in 0..50 {
+        assert_eq!(strategy.select_instance(&trackers), 1);
+    }
+}
+
+#[test]
+fn test_priority_strategy_creation() {
+    let _strategy = PriorityStrategy::new();
+}
+
+// ============================================================================
+// Lowest Latency Tests
+// ============================================================================
+
+#[test]
+fn test_latency_ema_follows_recent_requests() {
+    let mut tracker = make_tracker("gpt-4");
+    for _ in 0..10 {
+        tracker.record_outcome(Duration::from_millis(100), true);
+    }
+    assert_eq!(tracker.ema_response_time(), Duration::from_millis(100));
+
+    // After a slowdown the EMA moves much closer to the new latency than a mean would
+    let series: Vec<u64> = (0..10).map(|_| 100).chain((0..5).map(|_| 500)).collect();
+    for ms in &series[10..] {
+        tracker.record_outcome(Duration::from_millis(*ms), true);
+    }
+    let cumulative_mean = series.iter().sum::<u64>() as f64 / series.len() as f64;
+    let ema_ms = tracker.ema_response_time().as_secs_f64() * 1000.0;
+    assert!(ema_ms > 400.0, "ema {}", ema_ms);
+    assert!(ema_ms > cumulative_mean + 100.0, "ema {} mean {}", ema_ms, cumulative_mean);
+
+    // Failures don't move it
+    tracker.record_outcome(Duration::from_secs(30), false);
+    assert_eq!(tracker.ema_response_time().as_secs_f64() * 1000.0, ema_ms);
+}
+
+#[test]
+fn test_lowest_latency_prefers_recently_faster_instance() {
+    let mut recovered = make_tracker("gpt-4");
+    let mut steady = make_tracker("gpt-4o");
+    recovered.record_outcome(Duration::from_secs(5), true);
+    for _ in 0..9 {
+        recovered.record_outcome(Duration::from_millis(100), true);
+        steady.record_outcome(Duration::from_millis(400), true);
+    }
+    // The single slow request still dominates the plain average
+    assert!(recovered.avg_response_time() > steady.avg_response_time());
+
+    let trackers = vec![(0, &recovered), (1, &steady)];
+    assert_eq!(LowestLatencyStrategy::new().select_instance(&trackers), 0);
+}
+
+#[test]
+fn test_latency_smoothing_factor() {
+    let mut tracker = make_tracker("gpt-4");
+    tracker.latency_smoothing = 1.0;
+    tracker.record_outcome(Duration::from_millis(100), true);
+    tracker.record_outcome(Duration::from_millis(300), true);
+    assert_eq!(tracker.ema_response_time(), Duration::from_millis(300));
+
+    tracker.latency_smoothing = 0.5;
+    tracker.record_outcome(Duration::from_millis(100), true);
+    assert_eq!(tracker.ema_response_time(), Duration::from_millis(200));
+}
+
+// ============================================================================
+// Priority Strategy Tests
+// ============================================================================
+
+#[test]
+fn test_priority_prefers_lowest_value_then_unset() {
+    let mut first = make_tracker("gpt-4o");
+    first.priority = Some(1);
+    let mut second = make_tracker("claude");
+    second.priority = Some(2);
+    let unset = make_tracker("mistral");
+    let mut strategy = PriorityStrategy::new();
+
+    let trackers = vec![(0, &unset), (1, &second), (2, &first)];
+    assert_eq!(strategy.select_instance(&trackers), 2);
+
+    // With the top instance gone the next one in the chain is used
+    let trackers = vec![(0, &unset), (1, &second)];
+    assert_eq!(strategy.select_instance(&trackers), 1);
+
+    let trackers = vec![(0, &unset)];
+    assert_eq!(strategy.select_instance(&trackers), 0);
+}
+
+#[test]
+fn test_priority_ties_break_by_instance_id() {
+    let a = make_tracker("gpt-4");
+    let b = make_tracker("gpt-4o");
+    let trackers = vec![(7, &a), (3, &b)];
+    let mut strategy = PriorityStrategy::new();
+
+    assert_eq!(strategy.select_instance(&trackers), 1);
+}
+
+// ============================================================================
+// Sticky Strategy Tests
+// ============================================================================
+
+#[test]
+fn test_sticky_same_session_keeps_instance() {
+    let owned: Vec<InstanceTracker> = (0..8).map(|i| make_tracker(&format!("model-{}", i))).collect();
+    let trackers: Vec<(usize, &InstanceTracker)> = owned.iter().enumerate().collect();
+    let mut strategy = StickyStrategy::new(RandomStrategy::new());
+
+    let first = strategy.select_instance_for_session(&trackers, Some("chat-1"));
+    for _ in 0..20 {
+        assert_eq!(strategy.select_instance_for_session(&trackers, Some("chat-1")), first);
+    }
+
+    // Different sessions spread over the instances
+    let chosen: std::collections::HashSet<usize> = (0..50)
+        .map(|i| strategy.select_instance_for_session(&trackers, Some(&format!("chat-{}", i))))
+        .collect();
+    assert!(chosen.len() > 1);
+}
+
+#[test]
+fn test_sticky_repins_to_inner_choice_when_pinned_unavailable() {
+    let mut first = make_tracker("gpt-4o");
+    first.priority = Some(1);
+    let mut second = make_tracker("claude");
+    second.priority = Some(2);
+    let third = make_tracker("mistral");
+    let mut strategy = StickyStrategy::new(PriorityStrategy::new());
+
+    let all = vec![(0, &first), (1, &second), (2, &third)];
+    let pinned = all[strategy.select_instance_for_session(&all, Some("chat"))].0;
+
+    // Without the pinned instance the priority strategy decides
+    let remaining: Vec<(usize, &InstanceTracker)> = all.iter().copied().filter(|(id, _)| *id != pinned).collect();
+    let expected = if pinned == 0 { 1 } else { 0 };
+    let index = strategy.select_instance_for_session(&remaining, Some("chat"));
+    assert_eq!(remaining[index].0, expected);
+
+    // The session stays on its new instance, even once the old one is back
+    assert_eq!(remaining[strategy.select_instance_for_session(&remaining, Some("chat"))].0, expected);
+    assert_eq!(all[strategy.select_instance_for_session(&all, Some("chat"))].0, expected);
+
+    // Requests without a session key always use the inner strategy
+    assert_eq!(strategy.select_instance_for_session(&all, None), 0);
+}
+
+// ============================================================================
+// Weighted Strategy Tests
+// ============================================================================
+
+#[test]
+fn test_weighted_follows_weights() {
+    let mut heavy = make_tracker("gpt-4");
+    heavy.weight = 3.0;
+    let light = make_tracker("gpt-4o");
+    let mut never = make_tracker("gpt-4o-mini");
+    never.weight = 0.0;
+    let trackers = vec![(0, &heavy), (1, &light), (2, &never)];
+    let mut strategy = WeightedStrategy::new();
+
+    let mut counts = [0usize; 3];
+    for _ in 0..4000 {
+        counts[strategy.select_instance(&trackers)] += 1;
+    }
+
+    assert_eq!(counts[2], 0, "zero-weight instance should never be selected");
+    let ratio = counts[0] as f64 / counts[1] as f64;
+    assert!(ratio > 2.4 && ratio < 3.7, "expected roughly 3:1, got {:?}", counts);
+}
+
+#[test]
+fn test_weighted_all_zero_falls_back_to_uniform() {
+    let mut a = make_tracker("gpt-4");
+    a.weight = 0.0;
+    let mut b = make_tracker("gpt-4o");
+    b.weight = 0.0;
+    let trackers = vec![(0, &a), (1, &b)];
+    let mut strategy = WeightedStrategy::new();
+
+    let mut seen = [false, false];
+    for _ in 0..200 {
+        seen[strategy.select_instance(&trackers)] = true;
+    }
+    assert!(seen[0] && seen[1]);
+}
+
+#[test]
+fn test_in_flight_guard_releases_on_drop() {
+    let tracker = make_tracker("gpt-4");
+    {
+        let _guard = tracker.start_request();
+        assert_eq!(tracker.in_flight_count(), 1);
+    }
+    assert_eq!(tracker.in_flight_count(), 0);
+}
+
 // ============================================================================
 // Builder Strategy Configuration Tests
 // ============================================================================
@@ -138,6 +400,26 @@ tasks = ["test"]
     assert_eq!(manager.get_provider_count().await, 1);
 }
 
+#[tokio::test]
+async fn test_config_p2c_strategy() {
+    let toml = r#"
+[settings]
+strategy = "p2c"
+
+[[tasks]]
+name = "test"
+
+[[providers]]
+type = "openai"
+model = "gpt-4"
+api_key = "key"
+tasks = ["test"]
+"#;
+
+    let manager = LlmManager::from_config_str(toml).await.unwrap();
+    assert_eq!(manager.get_provider_count().await, 1);
+}
+
 #[tokio::test]
 async fn test_config_default_strategy() {
     let toml = r#"
@@ -196,3 +478,34 @@ async fn test_strategy_with_disabled_provider() {
     // Both providers are added, but one is disabled
     assert_eq!(manager.get_provider_count().await, 2);
 }
+
+#[tokio::test]
+async fn test_config_weights_reach_trackers() {
+    let toml = r#"
+[settings]
+strategy = "weighted"
+
+[[providers]]
+type = "ollama"
+model = "llama3"
+weight = 2.5
+priority = 1
+
+[[providers]]
+type = "ollama"
+model = "phi3"
+"#;
+
+    let (manager, report) = LlmManager::from_config_str_verbose(toml).await.unwrap();
+    let trackers = manager.trackers.lock().await;
+    let mut weights: Vec<(f64, Option<u32>)> = trackers
+        .values()
+        .map(|t| (t.weight, t.priority))
+        .collect();
+    weights.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    assert_eq!(weights, vec![(1.0, None), (2.5, Some(1))]);
+
+    // Priorities mean nothing to the weighted strategy
+    assert!(report.warnings.iter().any(|w| w.contains("sets a priority")));
+    assert!(!report.warnings.iter().any(|w| w.contains("sets a weight")));
+}
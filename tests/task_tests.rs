@@ -1,6 +1,6 @@
 //! Tests for TaskDefinition and task routing.
 
-use flyllm::TaskDefinition;
+use flyllm::{ResponseFormat, TaskDefinition};
 use serde_json::json;
 
 // ============================================================================
@@ -89,6 +89,40 @@ fn test_task_definition_multiple_tasks() {
     assert!((temp - 0.1).abs() < 0.01);
 }
 
+#[test]
+fn test_task_definition_with_json_schema() {
+    let schema = json!({"type": "object", "properties": {"title": {"type": "string"}}});
+    let task = TaskDefinition::new("extract").with_json_schema(schema.clone());
+
+    let value = task.parameters.get("response_format").unwrap().clone();
+    let format: ResponseFormat = serde_json::from_value(value).unwrap();
+    assert_eq!(format, ResponseFormat::JsonSchema { schema });
+}
+
+#[test]
+fn test_task_definition_typed_sampling_params() {
+    let task = TaskDefinition::new("sample")
+        .with_top_p(0.5)
+        .with_frequency_penalty(0.25)
+        .with_presence_penalty(-1.5)
+        .with_seed(42);
+
+    // Values exactly representable in f32 compare equal to their JSON numbers
+    assert_eq!(task.parameters.get("top_p"), Some(&json!(0.5)));
+    assert_eq!(task.parameters.get("frequency_penalty"), Some(&json!(0.25)));
+    assert_eq!(task.parameters.get("presence_penalty"), Some(&json!(-1.5)));
+    assert_eq!(task.parameters.get("seed"), Some(&json!(42)));
+    assert!(task.parameters["seed"].is_u64());
+}
+
+#[test]
+fn test_task_definition_with_stop() {
+    let task = TaskDefinition::new("list")
+        .with_stop(vec!["\n\n".to_string(), "END".to_string()]);
+
+    assert_eq!(task.parameters.get("stop"), Some(&json!(["\n\n", "END"])));
+}
+
 // ============================================================================
 // Task Routing Configuration Tests
 // ============================================================================
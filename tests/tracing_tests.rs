@@ -0,0 +1,122 @@
+//! Tests for the `tracing` spans around manager requests.
+
+#![cfg(feature = "tracing")]
+
+use flyllm::{GenerationRequest, LlmError, LlmManager, MockInstance, TaskDefinition};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+type SpanFields = HashMap<String, String>;
+
+struct RecordedSpan {
+    id: Id,
+    name: &'static str,
+    fields: SpanFields,
+}
+
+/// Layer that keeps the name and fields of every span
+#[derive(Clone, Default)]
+struct SpanRecorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+}
+
+impl SpanRecorder {
+    /// Install the recorder for the current thread
+    fn install() -> (Self, tracing::subscriber::DefaultGuard) {
+        let recorder = Self::default();
+        let guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+        (recorder, guard)
+    }
+
+    /// Fields of every span with the given name, in creation order
+    fn spans_named(&self, name: &str) -> Vec<SpanFields> {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| span.name == name)
+            .map(|span| span.fields.clone())
+            .collect()
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut SpanFields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        let mut fields = SpanFields::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        self.spans.lock().unwrap().push(RecordedSpan { id: id.clone(), name: attrs.metadata().name(), fields });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        // Span IDs are reused once a span closes, so update the latest span with this ID
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(span) = spans.iter_mut().rev().find(|span| span.id == *id) {
+            values.record(&mut FieldVisitor(&mut span.fields));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_request_spans_carry_task_instance_and_latency() {
+    let (recorder, _guard) = SpanRecorder::install();
+
+    let manager = LlmManager::new();
+    let id = manager
+        .add_instance_dynamic(Arc::new(MockInstance::new("mock-model").supports(TaskDefinition::new("chat"))))
+        .await;
+    let response = manager.generate(GenerationRequest::new("Hi".to_string()).task("chat")).await.unwrap();
+    assert!(response.success);
+
+    let requests = recorder.spans_named("generate_response");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["request_id"], response.request_id.to_string());
+    assert_eq!(requests[0]["task"], "chat");
+    assert_eq!(requests[0]["attempts"], "1");
+    assert_eq!(requests[0]["instance_id"], id.to_string());
+    assert!(requests[0].contains_key("latency_ms"));
+
+    let selections = recorder.spans_named("instance_selection");
+    assert_eq!(selections.len(), 1);
+    assert_eq!(selections[0]["attempt"], "1");
+    assert_eq!(selections[0]["instance_id"], id.to_string());
+    assert_eq!(selections[0]["provider"], "mock");
+    assert!(selections[0].contains_key("latency_ms"));
+}
+
+#[tokio::test]
+async fn test_each_attempt_gets_a_selection_span() {
+    let (recorder, _guard) = SpanRecorder::install();
+
+    let manager = LlmManager::new();
+    let rate_limited = LlmError::RateLimit { message: "slow down".to_string(), retry_after: Some(Duration::ZERO) };
+    manager
+        .add_instance_dynamic(Arc::new(MockInstance::new("mock-model").with_errors(vec![rate_limited])))
+        .await;
+    assert!(manager.generate(GenerationRequest::new("Hi".to_string())).await.unwrap().success);
+
+    let attempts: Vec<String> = recorder
+        .spans_named("instance_selection")
+        .into_iter()
+        .map(|fields| fields["attempt"].clone())
+        .collect();
+    assert_eq!(attempts, ["1", "2"]);
+    assert_eq!(recorder.spans_named("generate_response")[0]["attempts"], "2");
+}